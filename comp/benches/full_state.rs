@@ -0,0 +1,77 @@
+//! Benchmarks for turning a [`CompSpec`] into a [`FullState`].  Jigsaw rebuilds (parts of) the
+//! [`FullState`] after every edit, so these should stay well within one frame even for peal-length
+//! compositions.
+//!
+//! Run with `cargo bench -p jigsaw_comp --features bench`.
+
+use bellframe::Stage;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use jigsaw_comp::{
+    full::{bench, FullState},
+    spec::{part_heads::PartHeads, CompSpec},
+};
+use jigsaw_utils::{
+    indexed_vec::{FragIdx, MethodIdx},
+    types::Position,
+};
+
+/// The number of leads of Yorkshire in each fragment of [`peal`]
+const LEADS_PER_FRAG: [usize; 4] = [6, 6, 6, 5];
+
+/// Generates a 7-part peal of Yorkshire Surprise Major (5152 changes), split over 4 fragments.
+/// This is only plain leads, so is very false, which is also the worst case for grouping false
+/// rows.
+fn peal() -> CompSpec {
+    let mut spec = CompSpec::empty(Stage::MAJOR);
+    let yorkshire = spec
+        .add_method(
+            "Yorkshire".to_owned(),
+            "Y".to_owned(),
+            "-38-14-58-16-12-38-14-78,12".to_owned(),
+            vec![0],
+        )
+        .unwrap();
+    for (i, &num_leads) in LEADS_PER_FRAG.iter().enumerate() {
+        let position = Position::new(i as f32 * 200.0, 0.0);
+        let frag_idx = spec.add_fragment(yorkshire, false, position).unwrap();
+        for _ in 1..num_leads {
+            spec.extend_fragment(frag_idx, yorkshire, false).unwrap();
+        }
+    }
+    // Cyclic part heads, rotating every bell except the treble
+    spec.set_part_heads(PartHeads::parse("18234567", Stage::MAJOR).unwrap());
+    spec
+}
+
+fn full_state(c: &mut Criterion) {
+    let spec = peal();
+    let mut group = c.benchmark_group("7-part peal");
+
+    group.bench_function("expand_fragments", |b| {
+        b.iter(|| bench::expand_fragments(&spec))
+    });
+    let expanded_frags = bench::expand_fragments(&spec);
+    group.bench_function("prove", |b| b.iter(|| bench::prove(&spec, &expanded_frags)));
+    group.bench_function("FullState::new", |b| b.iter(|| FullState::new(&spec)));
+
+    // Editing one fragment should only re-expand and re-prove that fragment
+    let mut edited_spec = spec.clone();
+    edited_spec
+        .extend_fragment(FragIdx::new(0), MethodIdx::new(0), false)
+        .unwrap();
+    group.bench_function("FullState::update (one fragment edited)", |b| {
+        b.iter_batched(
+            || FullState::new(&spec),
+            |mut full_state| {
+                full_state.update(&edited_spec);
+                full_state
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, full_state);
+criterion_main!(benches);
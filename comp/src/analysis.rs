@@ -0,0 +1,59 @@
+//! An extension point for custom analyses of a composition's [`Row`]s (e.g. measuring how close
+//! the rows are to tittums, or counting wraps of particular rows).  Unlike [`Music`](crate::Music),
+//! which is stored in the composition and only matches regexes, a [`MusicAnalyzer`] is arbitrary
+//! code registered by whichever program is running Jigsaw.
+
+use bellframe::Row;
+use jigsaw_utils::indexed_vec::PartIdx;
+
+use crate::full::FullState;
+
+/// A custom analysis of a composition's [`Row`]s.  Each analyser registered with the GUI is shown
+/// in its own panel, which lists the [`AnalysisLine`]s returned by [`MusicAnalyzer::analyse`].
+pub trait MusicAnalyzer: std::fmt::Debug {
+    /// The name of this analysis, used as the title of its panel
+    fn name(&self) -> String;
+
+    /// Analyses the proved [`Row`]s of a composition.  Each block contains consecutive [`Row`]s
+    /// (one part of one [`Fragment`](crate::full::Fragment)), so analyses which look at adjacent
+    /// [`Row`]s (like counting wraps) shouldn't look across the boundaries between blocks.
+    fn analyse(&self, blocks: &[Vec<&Row>]) -> Vec<AnalysisLine>;
+}
+
+/// One line of the result of a [`MusicAnalyzer`], displayed as `label: value`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisLine {
+    pub label: String,
+    pub value: String,
+}
+
+impl AnalysisLine {
+    pub fn new(label: impl Into<String>, value: impl ToString) -> Self {
+        Self {
+            label: label.into(),
+            value: value.to_string(),
+        }
+    }
+}
+
+impl FullState {
+    /// Splits the proved [`Row`]s of the composition into blocks of consecutive [`Row`]s, in the
+    /// form passed to [`MusicAnalyzer::analyse`].  There is one block for every part of every
+    /// [`Fragment`](crate::full::Fragment) which contains proved [`Row`]s.
+    pub fn proved_blocks(&self) -> Vec<Vec<&Row>> {
+        let mut blocks = Vec::new();
+        for part in (0..self.part_heads.len()).map(PartIdx::new) {
+            for frag in &self.fragments {
+                let block = frag
+                    .rows_in_part(part)
+                    .filter(|(_, data)| data.is_proved)
+                    .map(|(_, data)| data.row)
+                    .collect::<Vec<_>>();
+                if !block.is_empty() {
+                    blocks.push(block);
+                }
+            }
+        }
+        blocks
+    }
+}
@@ -15,22 +15,31 @@
 
 use std::rc::Rc;
 
-use bellframe::SameStageVec;
-use emath::Pos2;
-use jigsaw_utils::indexed_vec::{PartVec, RowVec};
+use bellframe::{Row, RowBuf, SameStageVec};
+use jigsaw_utils::{
+    indexed_vec::{PartIdx, RowIdx, RowVec},
+    types::Position,
+};
 
 use crate::spec::{self, part_heads::PartHeads};
 
 #[derive(Debug, Clone)]
 pub(crate) struct ExpandedFrag {
-    pub position: Pos2,
-    /// Each of these contains all the [`Row`]s of the source [`Fragment`] (including the leftover)
-    /// for each part in the expanded composition
-    pub rows_per_part: PartVec<SameStageVec>,
+    pub position: Position,
+    /// All the [`Row`]s of the source [`Fragment`] (including the leftover) in the first part of
+    /// the composition (whose part head is always rounds).  The [`Row`]s of the other parts are
+    /// generated from these as they're needed (see [`ExpandedFrag::rows_in_part_iter`]), so only
+    /// one part is ever stored.  These are shared with the
+    /// [`full::Fragment`](crate::full::Fragment) built from this, so don't have to be copied.
+    pub rows_in_one_part: Rc<SameStageVec>,
+    /// The part heads which transpose `rows_in_one_part` into the [`Row`]s of each part
+    pub part_heads: Rc<PartHeads>,
     /// Stores data for each [`Row`] which is independent of which part is being seen
     pub row_data: RowVec<RowData>,
     /// `false` if the source [`Fragment`] is muted
     pub is_proved: bool,
+    /// `true` if the source [`Fragment`] should be drawn with bluelines
+    pub show_bluelines: bool,
 }
 
 impl ExpandedFrag {
@@ -38,21 +47,20 @@ impl ExpandedFrag {
         rows_in_one_part: SameStageVec,
         row_data: RowVec<RowData>,
         is_proved: bool,
-        position: Pos2,
-        part_heads: &PartHeads,
+        show_bluelines: bool,
+        position: Position,
+        part_heads: &Rc<PartHeads>,
     ) -> Self {
         assert_eq!(rows_in_one_part.len(), row_data.len());
         assert_eq!(rows_in_one_part.stage(), part_heads.stage());
-        let rows_per_part = part_heads
-            .rows()
-            .iter()
-            .map(|part_head| rows_in_one_part.pre_multiplied(part_head).unwrap())
-            .collect();
+        assert!(part_heads.rows()[0].is_rounds());
         Self {
             position,
-            rows_per_part,
+            rows_in_one_part: Rc::new(rows_in_one_part),
+            part_heads: part_heads.clone(),
             row_data,
             is_proved,
+            show_bluelines,
         }
     }
 
@@ -64,6 +72,29 @@ impl ExpandedFrag {
             0 // No rows are proved
         }
     }
+
+    /// The number of parts which this [`ExpandedFrag`] is expanded into
+    pub(crate) fn num_parts(&self) -> usize {
+        self.part_heads.len()
+    }
+
+    /// Lazily generates the [`Row`]s of this [`ExpandedFrag`] (including the leftover) in one part
+    /// of the composition.  Each [`Row`] is transposed by the part head as it's yielded, so code
+    /// which reads every part (like proving and music matching) never has to store more than one
+    /// part at a time.
+    pub(crate) fn rows_in_part_iter(
+        &self,
+        part: PartIdx,
+    ) -> impl Iterator<Item = RowBuf> + Send + '_ {
+        let part_head: &Row = &self.part_heads.rows()[part.index()];
+        self.rows_in_one_part.iter().map(move |row| part_head * row)
+    }
+
+    /// Returns the [`Row`] at `row_idx` in a given part
+    pub(crate) fn row_in_part(&self, part: PartIdx, row_idx: RowIdx) -> RowBuf {
+        let part_head: &Row = &self.part_heads.rows()[part.index()];
+        part_head * &self.rows_in_one_part[row_idx.index()]
+    }
 }
 
 #[derive(Debug, Clone)]
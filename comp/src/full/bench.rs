@@ -0,0 +1,30 @@
+//! Entry points into the individual stages of building a [`FullState`](super::FullState), so that
+//! each stage can be benchmarked on its own.  These expose otherwise private parts of the
+//! pipeline, so are only compiled with the `bench` feature.
+
+use jigsaw_utils::indexed_vec::FragVec;
+
+use crate::{expanded_frag::ExpandedFrag, spec::CompSpec};
+
+use super::{buffers::Buffers, proving::Prover};
+
+/// The [`Fragment`](crate::spec::Fragment)s of a [`CompSpec`], expanded into their [`Row`]s
+///
+/// [`Row`]: bellframe::Row
+#[derive(Debug, Clone)]
+pub struct ExpandedFrags(FragVec<ExpandedFrag>);
+
+/// Expands every [`Fragment`](crate::spec::Fragment) of a [`CompSpec`] into its
+/// [`Row`](bellframe::Row)s, in every part
+pub fn expand_fragments(spec: &CompSpec) -> ExpandedFrags {
+    ExpandedFrags(spec.expand_fragments())
+}
+
+/// Proves a set of [`ExpandedFrags`] from scratch, returning the number of false
+/// [`Row`](bellframe::Row)s
+pub fn prove(spec: &CompSpec, expanded_frags: &ExpandedFrags) -> usize {
+    let prover = Prover::new(&expanded_frags.0, spec.proof_settings().clone());
+    let (falseness, _false_groups_per_frag) =
+        prover.falseness(&expanded_frags.0, &mut Buffers::default());
+    falseness.num_false_rows()
+}
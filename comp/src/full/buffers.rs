@@ -0,0 +1,61 @@
+//! Recycling of the large per-[`Row`] allocations made when building a [`FullState`].  Rebuilding
+//! the [`FullState`] from scratch happens whenever the part heads change (i.e. on most keystrokes
+//! in the part heads box), so reusing the buffers of an old [`FullState`] avoids reallocating
+//! several buffers for every [`Row`] in the composition.
+
+use jigsaw_utils::indexed_vec::RowVec;
+
+use super::{FullState, RowData};
+
+// Imports only used for doc comments
+#[allow(unused_imports)]
+use bellframe::Row;
+
+/// Buffers taken from a [`FullState`] which is no longer needed.  Buffers are handed out in no
+/// particular order, but are always cleared before being reused.
+#[derive(Debug, Default)]
+pub(super) struct Buffers {
+    /// Buffers used for music highlight counters
+    counters: Vec<Vec<u8>>,
+    /// Buffers used for the false group of each [`Row`]
+    false_groups: Vec<RowVec<Option<usize>>>,
+    /// Buffers used for the [`RowData`] of each [`Row`]
+    row_data: Vec<RowVec<RowData>>,
+}
+
+impl Buffers {
+    /// Takes ownership of the per-[`Row`] buffers of an unneeded [`FullState`], so that they can
+    /// be reused
+    pub(super) fn recycle(&mut self, state: FullState) {
+        for frag in state.fragments {
+            self.counters.extend(frag.music_highlights_per_part);
+            self.counters.extend(frag.anti_music_highlights_per_part);
+            self.counters.extend(frag.wrap_highlights_per_part);
+            self.false_groups.extend(frag.false_groups_per_part);
+            self.row_data.push(frag.row_data);
+        }
+    }
+
+    /// Returns a buffer of `len` music highlight counters, all set to zero
+    pub(super) fn counters(&mut self, len: usize) -> Vec<u8> {
+        let mut counters = self.counters.pop().unwrap_or_default();
+        counters.clear();
+        counters.resize(len, 0);
+        counters
+    }
+
+    /// Returns a buffer of `len` false groups, all set to `None`
+    pub(super) fn false_groups(&mut self, len: usize) -> RowVec<Option<usize>> {
+        let mut false_groups = self.false_groups.pop().unwrap_or_default();
+        false_groups.clear();
+        false_groups.resize(len, None);
+        false_groups
+    }
+
+    /// Returns an empty buffer of [`RowData`]
+    pub(super) fn row_data(&mut self) -> RowVec<RowData> {
+        let mut row_data = self.row_data.pop().unwrap_or_default();
+        row_data.clear();
+        row_data
+    }
+}
@@ -0,0 +1,467 @@
+//! Code to export a [`FullState`] to other formats, either as raw [`Row`]s which can be processed
+//! by other tools or formatted like a printed composition.
+
+use std::fmt::Write;
+
+use bellframe::{Bell, Row};
+use itertools::Itertools;
+use jigsaw_utils::{indexed_vec::PartIdx, types::RowLocation};
+
+use crate::Pace;
+
+use super::{FullState, MusicGroup, MusicGroupInner, RowDataForOnePart};
+
+/// The file formats which [`Row`]s can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One [`Row`] per line, with blank lines between fragments
+    PlainText,
+    /// Comma-separated values, with a header line naming the columns
+    Csv,
+    /// Only the proved [`Row`]s, one per line and finishing with the [`Row`] after the last proved
+    /// [`Row`] (usually rounds).  Ringing simulators (like Abel, Beltower and Mobel) can load
+    /// these as touches.
+    SimulatorRows,
+    /// The place notation of the proved [`Row`]s, with one lead per line labelled with its method
+    /// and calls.  This is for simulators which load touches as place notation.
+    PlaceNotation,
+}
+
+/// Settings which determine how a [`FullState`]'s [`Row`]s are exported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// The part to export, or `None` to export every part one after the other
+    pub part: Option<PartIdx>,
+    /// If `true`, the method and call annotations are exported alongside the [`Row`]s
+    pub annotations: bool,
+    /// If `true`, each [`Row`] is exported with whether or not it is proved and true
+    pub proof_flags: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::PlainText,
+            part: None,
+            annotations: true,
+            proof_flags: false,
+        }
+    }
+}
+
+impl FullState {
+    /// Writes every [`Row`] in the composition (in fragment order) to a string, using a given set
+    /// of [`ExportOptions`]
+    pub fn export_rows(&self, options: &ExportOptions) -> String {
+        let parts = match options.part {
+            Some(part) => vec![part],
+            None => (0..self.part_heads.len()).map(PartIdx::new).collect_vec(),
+        };
+        if options.format == ExportFormat::PlaceNotation {
+            return self.export_place_notation(&parts);
+        }
+
+        let mut out = String::new();
+        if options.format == ExportFormat::Csv {
+            let mut columns = vec!["part", "fragment", "row_index", "row"];
+            if options.annotations {
+                columns.extend(["method", "call"].iter());
+            }
+            if options.proof_flags {
+                columns.extend(["is_proved", "is_false"].iter());
+            }
+            out.push_str(&columns.join(","));
+            out.push('\n');
+        }
+
+        for &part in &parts {
+            if options.format == ExportFormat::PlainText && options.part.is_none() {
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+                writeln!(out, "Part {}:", part.index() + 1).unwrap();
+            }
+            for (frag_idx, frag) in self.fragments.iter_enumerated() {
+                if options.format == ExportFormat::PlainText && frag_idx.index() > 0 {
+                    out.push('\n');
+                }
+                for (row_idx, row_data) in frag.rows_in_part(part) {
+                    match options.format {
+                        ExportFormat::PlainText => write_text_row(&mut out, &row_data, options),
+                        ExportFormat::Csv => {
+                            let prefix = [part.index() + 1, frag_idx.index(), row_idx.index()];
+                            write_csv_row(&mut out, &prefix.iter().join(","), &row_data, options)
+                        }
+                        ExportFormat::SimulatorRows => {
+                            if row_data.is_proved {
+                                writeln!(out, "{}", row_data.row).unwrap();
+                            }
+                        }
+                        ExportFormat::PlaceNotation => unreachable!(),
+                    }
+                }
+            }
+        }
+        // Simulators need to know where the touch comes round
+        if options.format == ExportFormat::SimulatorRows {
+            if let Some(row) = parts.last().and_then(|&p| self.row_after_last_proved(p)) {
+                writeln!(out, "{}", row).unwrap();
+            }
+        }
+        out
+    }
+
+    /// Writes the place notation of every proved lead (as [`ExportFormat::PlaceNotation`]).  Each
+    /// lead is written on its own line, as `<method>: <place notation>` followed by the symbols of
+    /// any calls made in that lead.
+    fn export_place_notation(&self, parts: &[PartIdx]) -> String {
+        let mut out = String::new();
+        writeln!(out, "Stage: {}", self.stage.num_bells()).unwrap();
+        for &part in parts {
+            writeln!(out, "Part {}:", part.index() + 1).unwrap();
+            for frag in &self.fragments {
+                let rows = frag.rows_in_part(part).collect_vec();
+                let mut lead: Option<Lead> = None;
+                // Every proved row is followed by another row (at worst, the leftover row), so
+                // each proved row starts a change
+                for ((_, row_data), (_, next_row_data)) in rows.iter().tuple_windows() {
+                    if !row_data.is_proved {
+                        continue;
+                    }
+                    if row_data.is_lead_head || lead.is_none() {
+                        if let Some(lead) = lead.take() {
+                            lead.write(&mut out);
+                        }
+                        let method = row_data.method_annotation.as_ref();
+                        lead = Some(Lead {
+                            method: method.map_or_else(String::new, |m| m.name()),
+                            places: Vec::new(),
+                            calls: String::new(),
+                        });
+                    }
+                    let lead = lead.as_mut().unwrap();
+                    if let Some(call) = &row_data.call_annotation {
+                        lead.calls.push(call.symbol());
+                    }
+                    let places = (0..self.stage.num_bells())
+                        .filter(|&p| row_data.row[p] == next_row_data.row[p])
+                        .collect_vec();
+                    lead.places.push(places);
+                }
+                if let Some(lead) = lead {
+                    lead.write(&mut out);
+                }
+            }
+        }
+        out
+    }
+
+    /// Returns the locations of the proved [`Row`]s in the order in which they are rung, i.e. in
+    /// fragment order, with each part in turn.  If `part` is `Some`, only that part is rung.
+    pub fn ringing_order(&self, part: Option<PartIdx>) -> Vec<RowLocation> {
+        let parts = match part {
+            Some(part) => vec![part],
+            None => (0..self.part_heads.len()).map(PartIdx::new).collect_vec(),
+        };
+        let mut rows = Vec::new();
+        for part_index in parts {
+            for (frag_index, frag) in self.fragments.iter_enumerated() {
+                let proved_rows = frag
+                    .rows_in_part(part_index)
+                    .filter(|(_, data)| data.is_proved)
+                    .map(|(row_index, _)| RowLocation {
+                        frag_index,
+                        row_index,
+                        part_index,
+                    });
+                rows.extend(proved_rows);
+            }
+        }
+        rows
+    }
+
+    /// Writes the time (in seconds) at which each proved [`Row`] starts when rung at a given
+    /// [`Pace`], as CSV.  The [`Row`]s are listed in [`FullState::ringing_order`].  The bells in
+    /// each [`Row`] strike `blow_duration` seconds apart, so the time of every blow can be
+    /// calculated from this.
+    pub fn export_row_timings(&self, part: Option<PartIdx>, pace: &Pace) -> String {
+        let mut out = String::new();
+        let blow_duration = pace.blow_duration(self.stage);
+        writeln!(out, "part,fragment,row_index,row,time,blow_duration").unwrap();
+        for (idx, loc) in self.ringing_order(part).into_iter().enumerate() {
+            let row = self.fragments[loc.frag_index].row_in_part(loc.part_index, loc.row_index);
+            let time = pace.row_start(idx, self.stage) * blow_duration;
+            let prefix = [
+                loc.part_index.index() + 1,
+                loc.frag_index.index(),
+                loc.row_index.index(),
+            ];
+            let prefix = prefix.iter().join(",");
+            writeln!(out, "{},{},{:.4},{:.4}", prefix, row, time, blow_duration).unwrap();
+        }
+        out
+    }
+
+    /// Returns the [`Row`] which follows the last proved [`Row`] in a given part, or `None` if no
+    /// [`Row`]s are proved
+    fn row_after_last_proved(&self, part: PartIdx) -> Option<&Row> {
+        self.fragments.iter().rev().find_map(|frag| {
+            let (last_proved, _) = frag
+                .rows_in_part(part)
+                .filter(|(_, data)| data.is_proved)
+                .last()?;
+            Some(frag.row_in_part(part, last_proved + 1))
+        })
+    }
+}
+
+/// One lead of a composition, as written by [`ExportFormat::PlaceNotation`]
+struct Lead {
+    method: String,
+    /// The places made in each change of the lead (cross changes make no places)
+    places: Vec<Vec<usize>>,
+    /// The symbols of the calls made in this lead
+    calls: String,
+}
+
+impl Lead {
+    fn write(&self, out: &mut String) {
+        let mut place_notation = String::new();
+        for (idx, places) in self.places.iter().enumerate() {
+            if places.is_empty() {
+                place_notation.push('x');
+                continue;
+            }
+            // Adjacent changes which make places have to be separated by dots
+            if idx > 0 && !self.places[idx - 1].is_empty() {
+                place_notation.push('.');
+            }
+            for &p in places {
+                place_notation.push_str(&Bell::from_index(p).name());
+            }
+        }
+        write!(out, "{}: {}", self.method, place_notation).unwrap();
+        if !self.calls.is_empty() {
+            write!(out, " {}", self.calls).unwrap();
+        }
+        out.push('\n');
+    }
+}
+
+impl FullState {
+    /// Formats the composition like a printed composition, as a standalone HTML page which can be
+    /// printed (or saved as a PDF) from a web browser.  This contains the title and composer, the
+    /// methods and calls, the calling of every proved [`Fragment`](super::Fragment) (one course
+    /// per line) and summaries of the music, statistics and truth.
+    pub fn export_html(&self, title: &str, composer: &str) -> String {
+        let mut out = String::new();
+        writeln!(out, "<!DOCTYPE html>").unwrap();
+        writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">").unwrap();
+        writeln!(out, "<title>{}</title>", html_escape(title)).unwrap();
+        writeln!(out, "<style>{}</style>\n</head>\n<body>", HTML_STYLE).unwrap();
+
+        /* Title */
+        writeln!(out, "<h1>{}</h1>", html_escape(title)).unwrap();
+        if !composer.is_empty() {
+            writeln!(
+                out,
+                "<p class=\"composer\">Composed by {}</p>",
+                html_escape(composer)
+            )
+            .unwrap();
+        }
+        let num_parts = self.part_heads.len();
+        let mut length = format!("{} changes", self.stats.part_len * num_parts);
+        if num_parts > 1 {
+            let part_heads = self.part_heads.spec_string();
+            write!(
+                length,
+                " ({} parts: {})",
+                num_parts,
+                html_escape(&part_heads)
+            )
+            .unwrap();
+        }
+        writeln!(out, "<p>{}</p>", length).unwrap();
+
+        /* Methods & calls */
+        writeln!(out, "<h2>Methods</h2>\n<table>").unwrap();
+        let method_stats = self
+            .stats
+            .changes_per_method
+            .iter()
+            .zip_eq(&self.stats.leads_per_method)
+            .zip_eq(&self.stats.atw_per_method);
+        for (method, ((num_changes, num_leads), atw)) in self.methods.iter().zip_eq(method_stats) {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td><code>{}</code></td><td>{} changes</td>\
+                 <td>{} leads</td><td>{:.0}% ATW</td></tr>",
+                html_escape(&method.shorthand()),
+                html_escape(&method.title()),
+                html_escape(&method.place_notation()),
+                num_changes,
+                num_leads,
+                atw * 100.0
+            )
+            .unwrap();
+        }
+        writeln!(out, "</table>").unwrap();
+        if let Some(balance) = self.stats.spliced_balance() {
+            writeln!(
+                out,
+                "<p>{} changes of method.  {}.</p>",
+                self.stats.num_coms, balance
+            )
+            .unwrap();
+        }
+        if !self.calls.is_empty() {
+            writeln!(out, "<h2>Calls</h2>\n<table>").unwrap();
+            for (call, num_uses) in self.calls.iter().zip_eq(&self.stats.uses_per_call) {
+                writeln!(
+                    out,
+                    "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{} uses</td></tr>",
+                    html_escape(&call.symbol().to_string()),
+                    html_escape(&call.place_notation()),
+                    html_escape(&call.lead_location()),
+                    num_uses
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        }
+
+        /* Calling */
+        // Like a printed composition, the calling is only given for the first part
+        let first_part = PartIdx::new(0);
+        writeln!(out, "<h2>Calling</h2>").unwrap();
+        for frag in &self.fragments {
+            let is_proved = frag
+                .rows_in_part(first_part)
+                .any(|(_, data)| data.is_proved);
+            if !is_proved {
+                continue;
+            }
+            writeln!(out, "<table class=\"calling\">").unwrap();
+            for course in &frag.courses {
+                writeln!(
+                    out,
+                    "<tr><td><code>{}</code></td><td>{}</td></tr>",
+                    frag.row_in_part(first_part, course.end),
+                    html_escape(&course.calling)
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        }
+
+        /* Music & truth */
+        writeln!(out, "<h2>Music</h2>\n<ul>").unwrap();
+        for group in self.music.groups() {
+            write_html_music_group(&mut out, group);
+        }
+        writeln!(out, "</ul>").unwrap();
+        writeln!(out, "<p>Total score: {}</p>", self.music.total_score()).unwrap();
+        let truth = if self.falseness.is_true() {
+            "True".to_owned()
+        } else {
+            format!("False ({} rows)", self.falseness.num_false_rows())
+        };
+        writeln!(out, "<p>{}</p>", truth).unwrap();
+
+        writeln!(out, "</body>\n</html>").unwrap();
+        out
+    }
+}
+
+/// The CSS used by [`FullState::export_html`], chosen to look like a printed composition
+const HTML_STYLE: &str = "body { font-family: serif; max-width: 40em; margin: auto; } \
+                          h1 { text-align: center; } \
+                          .composer { text-align: center; font-style: italic; } \
+                          td { padding: 0 1em 0 0; } \
+                          .calling { margin-bottom: 1em; }";
+
+/// Writes a [`MusicGroup`] (and all of its sub-groups) as an HTML list item
+fn write_html_music_group(out: &mut String, group: &MusicGroup) {
+    write!(
+        out,
+        "<li>{} {} (score {})",
+        group.inner.count(),
+        html_escape(&group.name),
+        group.score
+    )
+    .unwrap();
+    if let MusicGroupInner::Group { sub_groups, .. } = &group.inner {
+        writeln!(out, "<ul>").unwrap();
+        for sub_group in sub_groups {
+            write_html_music_group(out, sub_group);
+        }
+        write!(out, "</ul>").unwrap();
+    }
+    writeln!(out, "</li>").unwrap();
+}
+
+/// Escapes the characters which can't appear directly in HTML text
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Writes a single [`Row`] as a line of plain text, followed by any annotations
+fn write_text_row(out: &mut String, row_data: &RowDataForOnePart, options: &ExportOptions) {
+    let mut line = row_data.row.to_string();
+    if options.annotations {
+        if let Some(call) = &row_data.call_annotation {
+            write!(line, " {}", call.symbol()).unwrap();
+        }
+        if let Some(method) = &row_data.method_annotation {
+            write!(line, " {}", method.name()).unwrap();
+        }
+    }
+    if options.proof_flags {
+        if !row_data.is_proved {
+            line.push_str(" (unproved)");
+        } else if row_data.is_false {
+            line.push_str(" (false)");
+        }
+    }
+    out.push_str(&line);
+    out.push('\n');
+}
+
+/// Writes a single [`Row`] as a line of CSV, where `prefix` contains the part, fragment and row
+/// indices
+fn write_csv_row(
+    out: &mut String,
+    prefix: &str,
+    row_data: &RowDataForOnePart,
+    options: &ExportOptions,
+) {
+    write!(out, "{},{}", prefix, row_data.row).unwrap();
+    if options.annotations {
+        let method = row_data
+            .method_annotation
+            .as_ref()
+            .map_or_else(String::new, |m| m.name());
+        let call = row_data
+            .call_annotation
+            .as_ref()
+            .map_or_else(String::new, |c| c.symbol().to_string());
+        write!(out, ",{},{}", csv_field(&method), csv_field(&call)).unwrap();
+    }
+    if options.proof_flags {
+        write!(out, ",{},{}", row_data.is_proved, row_data.is_false).unwrap();
+    }
+    out.push('\n');
+}
+
+/// Quotes a CSV field if it contains any characters which would break the CSV syntax
+fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
@@ -0,0 +1,374 @@
+//! Lead-level falseness.  For every pair of [`Method`]s, a [`FalsenessTable`] stores which lead
+//! heads make a plain lead of one [`Method`] share a [`Row`] with a plain lead of the other.  This
+//! way, false leads can be found by looking up lead heads rather than by comparing every [`Row`].
+//! The same table also gives the false course heads between every pair of [`Method`]s, which are
+//! used when choosing methods which can be spliced together.
+
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use bellframe::{Bell, Row, RowBuf};
+use itertools::Itertools;
+use jigsaw_utils::{
+    indexed_vec::{FragSlice, MethodIdx, MethodSlice, PartIdx},
+    types::RowLocation,
+};
+
+use crate::{expanded_frag::ExpandedFrag, spec::Method};
+
+use super::PlainLead;
+
+/// For every ordered pair of [`Method`]s `(a, b)`, the set of transpositions `t` such that a plain
+/// lead of `a` with lead head `h` is false against a plain lead of `b` with lead head `h * t`.
+/// These are the 'false lead heads' of `a` against `b`, relative to rounds.
+#[derive(Debug, Clone, Default)]
+pub(super) struct FalsenessTable {
+    false_lead_heads: HashMap<(MethodIdx, MethodIdx), HashSet<RowBuf>>,
+    /// For every ordered pair of [`Method`]s `(a, b)`, the course heads `c` such that a plain
+    /// course of `b` starting at `c` shares a [`Row`] with the plain course of `a`, in ascending
+    /// order.  Each course is represented by the course head with the tenor at home (or, if no
+    /// lead heads of `b` bring the tenor home, the smallest lead head in that course).
+    false_course_heads: HashMap<(MethodIdx, MethodIdx), Vec<RowBuf>>,
+}
+
+impl FalsenessTable {
+    pub(super) fn new(methods: &MethodSlice<Rc<Method>>) -> Self {
+        let mut false_lead_heads = HashMap::new();
+        let mut false_course_heads = HashMap::new();
+        for ((idx_a, method_a), (idx_b, method_b)) in methods
+            .iter_enumerated()
+            .cartesian_product(methods.iter_enumerated())
+        {
+            // A lead of `a` with lead head `h_a` contains `h_a * r_a` for every row `r_a` in a
+            // plain lead of `a` (and likewise for `b`).  These leads share a row if
+            // `h_a * r_a = h_b * r_b`, i.e. when `h_b = h_a * (r_a * r_b^-1)`.
+            let transpositions = method_a
+                .plain_lead()
+                .cartesian_product(method_b.plain_lead().map(Row::inv).collect_vec())
+                .filter_map(|(r_a, r_b_inv)| r_a.mul_result(&r_b_inv).ok())
+                .collect::<HashSet<_>>();
+            false_course_heads.insert(
+                (idx_a, idx_b),
+                course_heads(method_a, method_b, &transpositions, idx_a == idx_b),
+            );
+            false_lead_heads.insert((idx_a, idx_b), transpositions);
+        }
+        Self {
+            false_lead_heads,
+            false_course_heads,
+        }
+    }
+
+    /// The false course heads of `method_a` against `method_b`, in ascending order.  A plain
+    /// course of `method_b` starting at any of these course heads shares at least one [`Row`] with
+    /// the plain course of `method_a`.
+    pub(super) fn false_course_heads(
+        &self,
+        method_a: MethodIdx,
+        method_b: MethodIdx,
+    ) -> Option<&[RowBuf]> {
+        self.false_course_heads
+            .get(&(method_a, method_b))
+            .map(Vec::as_slice)
+    }
+
+    /// Finds every pair of complete plain leads in the composition which share at least one
+    /// [`Row`].  This compares whole [`Row`]s, so ignores the
+    /// [`ProofSettings`](crate::spec::proof::ProofSettings).
+    pub(super) fn false_leads(
+        &self,
+        expanded_frags: &FragSlice<ExpandedFrag>,
+        method_map: &HashMap<*const Method, MethodIdx>,
+    ) -> Vec<(PlainLead, PlainLead)> {
+        let leads = plain_leads(expanded_frags, method_map);
+        let mut pairs = Vec::new();
+        for (idx, (lead_a, head_a)) in leads.iter().enumerate() {
+            let head_a_inv = head_a.inv();
+            // Only check the later leads, so that each pair is only checked once
+            for (lead_b, head_b) in &leads[idx + 1..] {
+                // Unwrap is safe because all the lead heads share the composition's `Stage`
+                let transposition = head_a_inv.mul_result(head_b).unwrap();
+                if self.false_lead_heads[&(lead_a.method, lead_b.method)].contains(&transposition) {
+                    pairs.push((*lead_a, *lead_b));
+                }
+            }
+        }
+        pairs
+    }
+}
+
+/// Converts the false lead heads of `method_a` against `method_b` (relative to rounds) into the
+/// distinct false course heads of the plain course of `method_a` against courses of `method_b`.
+/// If `method_a` and `method_b` are the same [`Method`], then the plain course is trivially false
+/// against itself so rounds is excluded.
+fn course_heads(
+    method_a: &Method,
+    method_b: &Method,
+    false_lead_heads: &HashSet<RowBuf>,
+    is_same_method: bool,
+) -> Vec<RowBuf> {
+    let lead_heads_a = method_a.lead_head().closure_from_rounds();
+    let lead_heads_b = method_b.lead_head().closure_from_rounds();
+    let tenor = Bell::tenor(method_a.lead_head().stage());
+    let is_tenor_home = |row: &Row| row.place_of(tenor) == Some(tenor.index());
+
+    let mut course_heads = HashSet::new();
+    // A lead of `b` with lead head `h_a * t` is false against the lead of `a` with lead head
+    // `h_a`, and that lead of `b` belongs to the course of `b` with course head
+    // `h_a * t * h_b^-1` (for any lead head `h_b` of `b`).
+    // The unwraps are safe because all the methods share the composition's `Stage`
+    for (head_a, transposition) in lead_heads_a.iter().cartesian_product(false_lead_heads) {
+        let lead_head = head_a.mul_result(transposition).unwrap();
+        let course_head = lead_heads_b
+            .iter()
+            .map(|head_b| lead_head.mul_result(&head_b.inv()).unwrap())
+            .min_by(|r1, r2| {
+                // Prefer course heads with the tenor at home
+                is_tenor_home(r2)
+                    .cmp(&is_tenor_home(r1))
+                    .then_with(|| r1.cmp(r2))
+            })
+            .unwrap();
+        course_heads.insert(course_head);
+    }
+    if is_same_method {
+        course_heads.retain(|row| !row.is_rounds());
+    }
+    course_heads.into_iter().sorted().collect_vec()
+}
+
+/// Finds every complete plain lead in the proved [`Fragment`](super::Fragment)s, along with its
+/// lead head.  Leads which contain calls, or are cut short by the end of a
+/// [`Fragment`](super::Fragment), can't be looked up in a [`FalsenessTable`] so are ignored.
+fn plain_leads(
+    expanded_frags: &FragSlice<ExpandedFrag>,
+    method_map: &HashMap<*const Method, MethodIdx>,
+) -> Vec<(PlainLead, RowBuf)> {
+    let mut leads = Vec::new();
+    for (frag_index, frag) in expanded_frags.iter_enumerated() {
+        if !frag.is_proved {
+            continue;
+        }
+        for (row_index, row_data) in frag.row_data.iter_enumerated() {
+            let method = match &row_data.method_source {
+                Some((method, 0)) => method,
+                _ => continue, // Not the start of a lead
+            };
+            let lead_len = method.lead_len();
+            let lead_rows = match frag
+                .row_data
+                .raw
+                .get(row_index.index()..row_index.index() + lead_len)
+            {
+                Some(rows) => rows,
+                None => continue, // Lead is cut short by the end of the fragment
+            };
+            let is_plain_lead = lead_rows.iter().enumerate().all(|(sub_lead_idx, data)| {
+                data.call_source.is_none()
+                    && matches!(
+                        &data.method_source,
+                        Some((m, idx)) if Rc::ptr_eq(m, method) && *idx == sub_lead_idx
+                    )
+            });
+            if !is_plain_lead {
+                continue;
+            }
+            let method_idx = method_map[&(method.as_ref() as *const Method)];
+            for part_index in (0..frag.num_parts()).map(PartIdx::new) {
+                let lead = PlainLead {
+                    method: method_idx,
+                    head: RowLocation {
+                        frag_index,
+                        row_index,
+                        part_index,
+                    },
+                    len: lead_len,
+                };
+                leads.push((lead, frag.row_in_part(part_index, row_index)));
+            }
+        }
+    }
+    leads
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use bellframe::Stage;
+    use jigsaw_utils::{indexed_vec::FragIdx, types::Position};
+
+    use crate::{full::FullState, spec::CompSpec};
+
+    use super::*;
+
+    const CAMBRIDGE: &str = "-36-14-12-36-14-56,12";
+    const PRIMROSE: &str = "-36-14-12-36-14-56,16";
+
+    /// A Minor composition containing the given methods, but no fragments
+    fn spec_with_methods(place_notations: &[&str]) -> CompSpec {
+        let mut spec = CompSpec::empty(Stage::MINOR);
+        for (i, pn) in place_notations.iter().enumerate() {
+            spec.add_method(i.to_string(), i.to_string(), pn.to_string(), vec![0])
+                .unwrap();
+        }
+        spec
+    }
+
+    #[test]
+    fn plain_lead_is_false_against_itself() {
+        let spec = spec_with_methods(&[CAMBRIDGE, PRIMROSE]);
+        let table = FalsenessTable::new(spec.methods());
+        let rounds = RowBuf::rounds(Stage::MINOR);
+        let m0 = MethodIdx::new(0);
+        let m1 = MethodIdx::new(1);
+        assert!(table.false_lead_heads[&(m0, m0)].contains(&rounds));
+        // Cambridge and Primrose share every row except the lead end
+        assert!(table.false_lead_heads[&(m0, m1)].contains(&rounds));
+        // Falseness is symmetric, so `b` against `a` has the inverse transpositions
+        for t in &table.false_lead_heads[&(m0, m1)] {
+            assert!(table.false_lead_heads[&(m1, m0)].contains(&t.inv()));
+        }
+    }
+
+    #[test]
+    fn repeated_lead_is_false() {
+        let mut spec = spec_with_methods(&[CAMBRIDGE, PRIMROSE]);
+        // Two separate leads of Cambridge from rounds, and one of Primrose from rounds
+        for method_idx in [0, 0, 1] {
+            spec.add_fragment(MethodIdx::new(method_idx), false, Position::new(0.0, 0.0))
+                .unwrap();
+        }
+        let full_state = FullState::new(&spec);
+        let frags_in_false_leads = full_state
+            .false_leads
+            .iter()
+            .map(|(a, b)| (a.head.frag_index, b.head.frag_index))
+            .collect::<HashSet<_>>();
+        let expected = [(0, 1), (0, 2), (1, 2)]
+            .iter()
+            .map(|&(a, b)| (FragIdx::new(a), FragIdx::new(b)))
+            .collect::<HashSet<_>>();
+        assert_eq!(frags_in_false_leads, expected);
+    }
+
+    /// The rows of the plain course of `method` starting at `course_head`
+    fn course(method: &Method, course_head: &Row) -> BTreeSet<RowBuf> {
+        lead_heads(method, course_head)
+            .iter()
+            .flat_map(|head| {
+                method
+                    .plain_lead()
+                    .map(|r| head.mul_result(r).unwrap())
+                    .collect_vec()
+            })
+            .collect()
+    }
+
+    /// The lead heads of the plain course of `method` starting at `course_head`.  Two course heads
+    /// give the same course exactly when they give the same lead heads.
+    fn lead_heads(method: &Method, course_head: &Row) -> BTreeSet<RowBuf> {
+        method
+            .lead_head()
+            .closure_from_rounds()
+            .iter()
+            .map(|lead_head| course_head.mul_result(lead_head).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn false_course_heads_match_brute_force() {
+        let spec = spec_with_methods(&[CAMBRIDGE, PRIMROSE]);
+        let methods = spec.methods();
+        let table = FalsenessTable::new(methods);
+        let rounds = RowBuf::rounds(Stage::MINOR);
+        let all_rows = (0..6)
+            .permutations(6)
+            .map(|bells| RowBuf::from_vec(bells.into_iter().map(Bell::from_index).collect()))
+            .map(Result::unwrap)
+            .collect_vec();
+        for (idx_a, idx_b) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let (idx_a, idx_b) = (MethodIdx::new(idx_a), MethodIdx::new(idx_b));
+            let (method_a, method_b) = (&methods[idx_a], &methods[idx_b]);
+            let course_a = course(method_a, &rounds);
+            let plain_course_b = lead_heads(method_b, &rounds);
+            // Check every course of `b` against the plain course of `a`, skipping the plain
+            // course which is trivially false against itself
+            let false_courses = all_rows
+                .iter()
+                .filter(|c| !course(method_b, c).is_disjoint(&course_a))
+                .map(|c| lead_heads(method_b, c))
+                .filter(|courses_b| !(idx_a == idx_b && *courses_b == plain_course_b))
+                .collect::<BTreeSet<_>>();
+            let table_courses = table
+                .false_course_heads(idx_a, idx_b)
+                .unwrap()
+                .iter()
+                .map(|c| lead_heads(method_b, c))
+                .collect_vec();
+            // Every false course should be listed exactly once
+            assert_eq!(table_courses.len(), false_courses.len());
+            assert_eq!(
+                table_courses.into_iter().collect::<BTreeSet<_>>(),
+                false_courses
+            );
+        }
+    }
+
+    #[test]
+    fn table_is_only_rebuilt_when_methods_change() {
+        let mut spec = spec_with_methods(&[CAMBRIDGE]);
+        let mut full_state = FullState::new(&spec);
+        let table = full_state.source.falseness_table.clone();
+        // Changing the observation bell rebuilds the `FullState`, but not the falseness table
+        spec.set_observation_bell(Bell::from_index(4)).unwrap();
+        full_state.update(&spec);
+        assert!(Rc::ptr_eq(&full_state.source.falseness_table, &table));
+        // Adding a method requires a new falseness table
+        spec.add_method("P".to_owned(), "P".to_owned(), PRIMROSE.to_owned(), vec![0])
+            .unwrap();
+        full_state.update(&spec);
+        assert!(!Rc::ptr_eq(&full_state.source.falseness_table, &table));
+    }
+
+    #[test]
+    fn tenors_together_course_heads_on_minor() {
+        let spec = spec_with_methods(&[CAMBRIDGE, PRIMROSE]);
+        let full_state = FullState::new(&spec);
+        assert!(full_state.has_tenors());
+        let (m0, m1) = (MethodIdx::new(0), MethodIdx::new(1));
+        let all_fchs = full_state.false_course_heads(m0, m1, false);
+        let tenors_together_fchs = full_state.false_course_heads(m0, m1, true);
+        // On Minor, the 5th and 6th are the tenors
+        let expected = all_fchs
+            .iter()
+            .filter(|row| row[4] == Bell::from_index(4) && row[5] == Bell::from_index(5))
+            .collect_vec();
+        assert_eq!(tenors_together_fchs.iter().collect_vec(), expected);
+        assert!(tenors_together_fchs.len() < all_fchs.len());
+    }
+
+    #[test]
+    fn no_tenors_on_doubles() {
+        let mut spec = CompSpec::empty(Stage::DOUBLES);
+        spec.add_method(
+            "P".to_owned(),
+            "P".to_owned(),
+            "5.1.5.1.5,125".to_owned(),
+            vec![0],
+        )
+        .unwrap();
+        assert!(!FullState::new(&spec).has_tenors());
+    }
+
+    #[test]
+    fn true_leads_are_not_false() {
+        let mut spec = spec_with_methods(&[CAMBRIDGE]);
+        spec.add_fragment(MethodIdx::new(0), true, Position::new(0.0, 0.0))
+            .unwrap();
+        // A plain course of Cambridge is true
+        assert!(FullState::new(&spec).false_leads.is_empty());
+    }
+}
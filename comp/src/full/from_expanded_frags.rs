@@ -4,44 +4,139 @@
 // point to).  See https://github.com/rust-lang/rust-clippy/issues/6745
 #![allow(clippy::mutable_key_type)]
 
-use std::{collections::HashMap, rc::Rc};
+use std::{cell::OnceCell, collections::HashMap, rc::Rc};
 
-use bellframe::Stage;
+use bellframe::{Bell, Row, RowBuf, Stage};
+use index_vec::index_vec;
 use itertools::Itertools;
-use jigsaw_utils::indexed_vec::{FragSlice, FragVec, MethodIdx, MethodSlice, MethodVec, RowVec};
+use jigsaw_utils::{
+    indexed_vec::{
+        CallIdx, CallSlice, CallVec, FragSlice, FragVec, MethodIdx, MethodSlice, MethodVec,
+        PartIdx, PartVec, RowIdx, RowVec,
+    },
+    types::{RowLocation, RowSource},
+};
 
 use crate::{
-    expanded_frag::ExpandedFrag,
-    full, music,
+    expanded_frag::{ExpandedFrag, RowData},
+    full,
     spec::{self, part_heads::PartHeads},
 };
 
-use super::{FullState, Stats};
+use super::{buffers::Buffers, problems, BellWork, FullState, Source, Stats};
 
 pub(super) fn from_expanded_frags(
-    expanded_frags: FragVec<ExpandedFrag>,
-    spec_methods: &MethodSlice<Rc<spec::Method>>,
+    source: Source,
     part_heads: Rc<PartHeads>,
-    music: &[music::Music],
     stage: Stage,
+    buffers: &mut Buffers,
 ) -> FullState {
-    let (method_map, methods) = expand_methods(spec_methods, &expanded_frags, part_heads.len());
-    let stats = generate_stats(&expanded_frags);
-    let (music, frag_musics) = music_gen::compute_music(music, &expanded_frags, stage);
+    let expanded_frags = &source.expansion().expanded_frags;
+    let (method_map, methods) = expand_methods(&source.methods, expanded_frags, part_heads.len());
+    let (call_map, calls) = expand_calls(&source.calls, expanded_frags, part_heads.len());
+    let (frag_links, link_groups_per_frag) = find_frag_links(expanded_frags, &part_heads);
+    let frag_ends = find_frag_ends(expanded_frags, &frag_links);
+    let stats = generate_stats(
+        expanded_frags,
+        &source.methods,
+        &method_map,
+        &call_map,
+        &frag_links,
+        &frag_ends,
+        stage,
+    );
+    // PERF: Music is still recomputed for every row in every part, even if most of the fragments
+    // haven't changed
+    let (music, frag_musics) = music_gen::compute_music(
+        &source.music,
+        expanded_frags,
+        part_heads.len(),
+        stage,
+        buffers,
+    );
+    let (falseness, false_groups_per_frag) =
+        source.expansion().prover.falseness(expanded_frags, buffers);
+    let false_leads = source
+        .falseness_table
+        .false_leads(expanded_frags, &method_map);
+    let lead_heads = find_lead_heads(
+        expanded_frags,
+        &method_map,
+        part_heads.len(),
+        source.observation_bell,
+    );
+    let row_index = index_rows(expanded_frags, part_heads.len());
     let fragments = expanded_frags
-        .into_iter()
+        .iter()
         .zip(frag_musics)
-        .map(|(exp_frag, music)| expand_frag(exp_frag, music, &method_map, &methods))
+        .zip(false_groups_per_frag)
+        .zip(link_groups_per_frag)
+        .zip(frag_ends)
+        .map(|((((exp_frag, music), false_groups), link_groups), end)| {
+            let annotations = Annotations {
+                method_map: &method_map,
+                methods: &methods,
+                call_map: &call_map,
+                calls: &calls,
+            };
+            expand_frag(
+                exp_frag,
+                music,
+                false_groups,
+                link_groups,
+                end,
+                annotations,
+                source.observation_bell,
+                buffers.row_data(),
+            )
+        })
         .collect();
 
-    FullState {
+    let mut full_state = FullState {
         part_heads,
         fragments,
         methods,
+        calls,
         music,
+        falseness,
+        false_leads,
+        frag_links,
+        lead_heads,
         stats,
+        problems: Vec::new(), // Filled in below, since checking needs the rest of the state
         stage,
+        row_index,
+        source,
+        previous: None,
+    };
+    full_state.problems = problems::find_problems(&full_state);
+    full_state
+}
+
+/// Finds every location of each [`Row`] in the composition, ordered by part.  Leftover rows
+/// aren't included, since they're the same as the first row of whatever comes next.
+fn index_rows(
+    frags: &FragSlice<ExpandedFrag>,
+    num_parts: usize,
+) -> HashMap<RowBuf, Vec<RowLocation>> {
+    let mut locations_by_row = HashMap::<RowBuf, Vec<RowLocation>>::new();
+    for part_index in (0..num_parts).map(PartIdx::new) {
+        for (frag_index, frag) in frags.iter_enumerated() {
+            let num_rows = frag.row_data.len() - 1;
+            for (row_index, row) in frag
+                .rows_in_part_iter(part_index)
+                .take(num_rows)
+                .enumerate()
+            {
+                locations_by_row.entry(row).or_default().push(RowLocation {
+                    frag_index,
+                    row_index: RowIdx::new(row_index),
+                    part_index,
+                });
+            }
+        }
     }
+    locations_by_row
 }
 
 fn expand_methods(
@@ -94,10 +189,294 @@ fn expand_methods(
     (method_map, methods)
 }
 
-fn generate_stats(frags: &FragSlice<ExpandedFrag>) -> Stats {
+fn expand_calls(
+    calls: &CallSlice<Rc<spec::Call>>,
+    frags: &FragSlice<ExpandedFrag>,
+    num_parts: usize,
+) -> (HashMap<*const spec::Call, CallIdx>, CallVec<Rc<full::Call>>) {
+    // Maps source calls [`spec::Call`] (hashed by their memory addresses) to the expanded
+    // [`full::Call`]s.  This works exactly like `method_map` in `expand_methods`.
+    let call_map = calls
+        .iter_enumerated()
+        .map(|(idx, c)| (c.as_ref() as *const spec::Call, idx))
+        .collect::<HashMap<_, _>>();
+    let mut calls = calls
+        .iter()
+        .map(|c| full::Call {
+            source: c.clone(),
+            // Will be accumulated later
+            num_uses: 0,
+            num_proved_uses: 0,
+        })
+        .collect::<CallVec<_>>();
+
+    // Iterate through all the fragments, and count how many times each call is used.  Each call is
+    // counted at its first row, so that calls are only counted once.
+    for f in frags {
+        for row_data in &f.row_data {
+            if let Some((spec_call, 0)) = &row_data.call_source {
+                let spec_call_ptr = spec_call.as_ref() as *const spec::Call;
+                let full_call = &mut calls[call_map[&spec_call_ptr]];
+                // As with methods, this `row_data` corresponds to one row for each part
+                full_call.num_uses += num_parts;
+                if row_data.is_proved {
+                    full_call.num_proved_uses += num_parts;
+                }
+            }
+        }
+    }
+
+    let calls = calls.into_iter().map(Rc::new).collect();
+    (call_map, calls)
+}
+
+/// Finds which pairs of [`Fragment`](full::Fragment)s are linked (i.e. `f -> g` iff the leftover
+/// row of `f` is equivalent to the first row of `g`), and which link groups the top and bottom of
+/// every [`Fragment`](full::Fragment) belong to.  Only the rows in the first part need to be
+/// compared, since [`PartHeads::are_equivalent`] takes the other parts into account.
+fn find_frag_links(
+    frags: &FragSlice<ExpandedFrag>,
+    part_heads: &PartHeads,
+) -> (Vec<full::FragLink>, FragVec<full::FragLinkGroups>) {
+    // Maps each leftover row to its group, so that all the links from the same row get the same
+    // colour
+    let mut groups: HashMap<&Row, usize> = HashMap::new();
+    let mut frag_links = Vec::new();
+    let mut link_groups = index_vec![full::FragLinkGroups::default(); frags.len()];
+
+    // Test every pair of fragments `f -> g` ...
+    for (f_idx, f) in frags.iter_enumerated() {
+        for (g_idx, g) in frags.iter_enumerated() {
+            // Unwraps are safe because every fragment contains at least its leftover row
+            let leftover_row_of_f = f.rows_in_one_part.last().unwrap();
+            let first_row_of_g = g.rows_in_one_part.first().unwrap();
+            // ... and link them if `g` starts with the leftover row of `f`.  The unwrap is safe
+            // because every row in the composition has the same stage.
+            if part_heads
+                .are_equivalent(leftover_row_of_f, first_row_of_g)
+                .unwrap()
+            {
+                let num_groups = groups.len();
+                let group = *groups.entry(leftover_row_of_f).or_insert(num_groups);
+                frag_links.push(full::FragLink {
+                    from: f_idx,
+                    to: g_idx,
+                    group,
+                });
+                link_groups[f_idx].bottom = Some(group);
+                link_groups[g_idx].top = Some(group);
+            }
+        }
+    }
+    (frag_links, link_groups)
+}
+
+/// Determines how the leftover row of each fragment joins onto the rest of the composition.
+/// Coming round takes precedence over links, since a leftover row of rounds also links onto any
+/// fragment which starts at rounds.
+fn find_frag_ends(
+    frags: &FragSlice<ExpandedFrag>,
+    frag_links: &[full::FragLink],
+) -> FragVec<full::FragEnd> {
+    frags
+        .iter_enumerated()
+        .map(|(f_idx, f)| {
+            // Every fragment contains at least its leftover row
+            let leftover_row_idx = RowIdx::new(f.row_data.len() - 1);
+            let comes_round = (0..f.num_parts()).any(|part| {
+                f.row_in_part(PartIdx::new(part), leftover_row_idx)
+                    .is_rounds()
+            });
+            if comes_round {
+                full::FragEnd::ComesRound
+            } else if frag_links.iter().any(|link| link.from == f_idx) {
+                full::FragEnd::Linked
+            } else {
+                full::FragEnd::Open
+            }
+        })
+        .collect()
+}
+
+/// Follows the links from the first proved fragment which starts at rounds, returning the number
+/// of changes (in every part) rung before reaching a fragment which comes round.  If a fragment
+/// links onto several others, the first proved one which hasn't been visited is followed.
+fn comes_round_after(
+    frags: &FragSlice<ExpandedFrag>,
+    frag_links: &[full::FragLink],
+    frag_ends: &FragSlice<full::FragEnd>,
+) -> Option<usize> {
+    let (mut f_idx, _) = frags
+        .iter_enumerated()
+        .find(|(_, f)| f.is_proved && f.rows_in_one_part.first().unwrap().is_rounds())?;
+    let mut is_visited = index_vec![false; frags.len()];
+    let mut part_len = 0;
+    loop {
+        is_visited[f_idx] = true;
+        part_len += frags[f_idx].len();
+        if frag_ends[f_idx] == full::FragEnd::ComesRound {
+            return Some(part_len * frags[f_idx].num_parts());
+        }
+        f_idx = frag_links
+            .iter()
+            .filter(|link| link.from == f_idx)
+            .map(|link| link.to)
+            .find(|&to| frags[to].is_proved && !is_visited[to])?;
+    }
+}
+
+/// Finds the lead heads of every proved [`Fragment`](full::Fragment), in every part.  The leftover
+/// row of a [`Fragment`](full::Fragment) is also counted as a lead head, since it's the lead head
+/// reached at the end of that [`Fragment`](full::Fragment).  Course ends are the lead heads where
+/// the `observation_bell` is at home.
+fn find_lead_heads(
+    frags: &FragSlice<ExpandedFrag>,
+    method_map: &HashMap<*const spec::Method, MethodIdx>,
+    num_parts: usize,
+    observation_bell: Bell,
+) -> PartVec<Vec<full::LeadHead>> {
+    let mut lead_heads: PartVec<Vec<full::LeadHead>> = index_vec![Vec::new(); num_parts];
+    for (frag_index, frag) in frags.iter_enumerated().filter(|(_, f)| f.is_proved) {
+        for (row_index, row_data) in frag.row_data.iter_enumerated() {
+            let method = match &row_data.method_source {
+                Some((method, 0)) => Some(method_map[&(method.as_ref() as *const spec::Method)]),
+                Some(_) => continue, // Not a lead head
+                None => None,        // The leftover row
+            };
+            for (part_index, part_lead_heads) in lead_heads.iter_mut_enumerated() {
+                let row = frag.row_in_part(part_index, row_index);
+                part_lead_heads.push(full::LeadHead {
+                    is_course_end: row.place_of(observation_bell) == Some(observation_bell.index()),
+                    row,
+                    source: RowSource {
+                        frag_index,
+                        row_index,
+                    },
+                    method,
+                });
+            }
+        }
+    }
+    lead_heads
+}
+
+fn generate_stats(
+    frags: &FragSlice<ExpandedFrag>,
+    methods: &MethodSlice<Rc<spec::Method>>,
+    method_map: &HashMap<*const spec::Method, MethodIdx>,
+    call_map: &HashMap<*const spec::Call, CallIdx>,
+    frag_links: &[full::FragLink],
+    frag_ends: &FragSlice<full::FragEnd>,
+    stage: Stage,
+) -> Stats {
+    let num_bells = stage.num_bells();
     // The total length of a part is the sum of the lengths of fragments
     let part_len = frags.iter().map(|f| f.len()).sum();
-    Stats { part_len }
+
+    let mut changes_per_method: MethodVec<usize> = index_vec![0; method_map.len()];
+    let mut leads_per_method: MethodVec<usize> = index_vec![0; method_map.len()];
+    let mut uses_per_call: CallVec<usize> = index_vec![0; call_map.len()];
+    let mut num_coms = 0;
+    let mut longest_run: Option<(MethodIdx, usize)> = None;
+    // For each method, which bells start a lead of that method in which places (in any part),
+    // indexed by `bell * num_bells + place`
+    let mut place_bells_rung: MethodVec<Vec<bool>> =
+        index_vec![vec![false; num_bells * num_bells]; methods.len()];
+    for f in frags.iter().filter(|f| f.is_proved) {
+        // The method of the run which is currently being counted, and its length so far
+        let mut current_run: Option<(MethodIdx, usize)> = None;
+        for (row_idx, row_data) in f.row_data.iter_enumerated() {
+            if let Some((spec_call, 0)) = &row_data.call_source {
+                uses_per_call[call_map[&(spec_call.as_ref() as *const spec::Call)]] += 1;
+            }
+            // Only the leftover row has no method, and that doesn't count towards any stats
+            let method_idx = match &row_data.method_source {
+                Some((spec_method, _)) => {
+                    method_map[&(spec_method.as_ref() as *const spec::Method)]
+                }
+                None => continue,
+            };
+            changes_per_method[method_idx] += 1;
+            if let Some((_, 0)) = &row_data.method_source {
+                leads_per_method[method_idx] += 1;
+                for part in (0..f.num_parts()).map(PartIdx::new) {
+                    for (place, bell) in f.row_in_part(part, row_idx).bell_iter().enumerate() {
+                        place_bells_rung[method_idx][bell.index() * num_bells + place] = true;
+                    }
+                }
+            }
+            current_run = match current_run {
+                Some((run_method, run_len)) if run_method == method_idx => {
+                    Some((run_method, run_len + 1))
+                }
+                Some(_) => {
+                    num_coms += 1;
+                    Some((method_idx, 1))
+                }
+                None => Some((method_idx, 1)),
+            };
+            // Keep track of the longest run seen so far.  Ties are broken in favour of the first
+            // run found.
+            let longest_len = longest_run.map(|(_, len)| len).unwrap_or(0);
+            if let Some((_, run_len)) = current_run {
+                if run_len > longest_len {
+                    longest_run = current_run;
+                }
+            }
+        }
+    }
+
+    let atw_per_method = methods
+        .iter()
+        .zip_eq(&place_bells_rung)
+        .map(|(method, rung)| {
+            // Bells which are fixed by the lead head (e.g. the treble in treble-dominated methods)
+            // don't do any of the work.  The working places are exactly the working bells'
+            // home positions, so the same list is used for both.
+            let lead_head = method.lead_head();
+            let working_bells = stage
+                .bells()
+                .filter(|b| lead_head.place_of(*b) != Some(b.index()))
+                .collect_vec();
+            let num_rung = working_bells
+                .iter()
+                .cartesian_product(&working_bells)
+                .filter(|(bell, place)| rung[bell.index() * num_bells + place.index()])
+                .count();
+            match working_bells.len() {
+                0 => 0.0,
+                n => num_rung as f32 / (n * n) as f32,
+            }
+        })
+        .collect();
+
+    // Follow each bell's path through every part of every proved fragment
+    let mut work_per_bell = vec![BellWork::default(); num_bells];
+    for f in frags.iter().filter(|f| f.is_proved) {
+        for part in (0..f.num_parts()).map(PartIdx::new) {
+            let mut paths = vec![Vec::with_capacity(f.row_data.len()); num_bells];
+            for row in f.rows_in_part_iter(part) {
+                for (place, bell) in row.bell_iter().enumerate() {
+                    paths[bell.index()].push(place);
+                }
+            }
+            for (work, path) in work_per_bell.iter_mut().zip_eq(&paths) {
+                work.add_path(path);
+            }
+        }
+    }
+
+    Stats {
+        part_len,
+        changes_per_method,
+        leads_per_method,
+        uses_per_call,
+        num_coms,
+        longest_run,
+        atw_per_method,
+        work_per_bell,
+        comes_round_after: comes_round_after(frags, frag_links, frag_ends),
+    }
 }
 
 ////////////////////
@@ -105,121 +484,177 @@ fn generate_stats(frags: &FragSlice<ExpandedFrag>) -> Stats {
 ////////////////////
 
 mod music_gen {
-    use std::rc::Rc;
+    use std::{collections::HashMap, rc::Rc};
 
-    use bellframe::Stage;
+    use bellframe::{music::Regex, RowBuf, Stage};
     use index_vec::index_vec;
     use itertools::Itertools;
     use jigsaw_utils::{
         indexed_vec::{FragSlice, FragVec, PartVec, RowIdx},
-        types::RowLocation,
+        types::{RowLocation, RowSource},
+    };
+
+    use crate::{
+        expanded_frag::ExpandedFrag,
+        full,
+        music::{self, WrapRegex},
+        par,
     };
 
-    use crate::{expanded_frag::ExpandedFrag, full, music};
+    use super::Buffers;
+
+    // Imports only used for doc comments
+    #[allow(unused_imports)]
+    use bellframe::Row;
 
     pub(super) fn compute_music(
         music: &[music::Music],
         expanded_frags: &FragSlice<ExpandedFrag>,
+        num_parts: usize,
         stage: Stage,
+        buffers: &mut Buffers,
     ) -> (full::Music, FragVec<FragMusic>) {
         // Create a set of `FragMusic`s per part, who's counters will be incremented whilst computing
         // the music
         let mut frag_musics: FragVec<FragMusic> = expanded_frags
             .iter()
-            .map(|frag| FragMusic::all_counters_zero(frag, stage))
+            .map(|frag| FragMusic::all_counters_zero(frag, stage, buffers))
             .collect();
-        let (groups, total_count, max_count) =
-            expand_music_groups(music, expanded_frags, &mut frag_musics, stage);
+        let top_level = Inherited {
+            weight: music::Music::DEFAULT_WEIGHT,
+            is_part_counted: index_vec![true; num_parts],
+        };
+        let (groups, total_count, max_count, total_score) =
+            expand_music_groups(music, &top_level, expanded_frags, &mut frag_musics, stage);
+        let mut matches_by_row = HashMap::new();
+        for group in &groups {
+            add_matches_by_row(group, &mut Vec::new(), &mut matches_by_row);
+        }
 
         let music = full::Music {
             groups,
             total_count,
             max_count,
+            total_score,
+            matches_by_row,
         };
         (music, frag_musics)
     }
 
-    /// Recursively expand a sequence of music groups, totalling the number of occurrences
+    /// Recursively add every [`Row`] matched by `group` to the reverse index `matches_by_row`.
+    /// `group_names` is the names of every group containing `group`, and is left unchanged
+    /// once this returns.
+    fn add_matches_by_row(
+        group: &full::MusicGroup,
+        group_names: &mut Vec<String>,
+        matches_by_row: &mut HashMap<RowSource, Vec<full::MusicMatch>>,
+    ) {
+        group_names.push(group.name.clone());
+        match &group.inner {
+            full::MusicGroupInner::Leaf { rows_matched, .. } => {
+                for loc in rows_matched {
+                    let music_match = full::MusicMatch {
+                        part: loc.part_index,
+                        group_names: group_names.clone(),
+                    };
+                    matches_by_row
+                        .entry(loc.as_source())
+                        .or_default()
+                        .push(music_match);
+                }
+            }
+            full::MusicGroupInner::Group { sub_groups, .. } => {
+                for sub_group in sub_groups {
+                    add_matches_by_row(sub_group, group_names, matches_by_row);
+                }
+            }
+        }
+        group_names.pop();
+    }
+
+    /// Recursively expand a sequence of music groups, totalling the number of occurrences and
+    /// their scores.  `parent` is inherited from the group containing `music`.
     fn expand_music_groups(
         music: &[music::Music],
+        parent: &Inherited,
         expanded_frags: &FragSlice<ExpandedFrag>,
         frag_musics: &mut FragSlice<FragMusic>,
         stage: Stage,
-    ) -> (Vec<Rc<full::MusicGroup>>, usize, usize) {
+    ) -> (Vec<Rc<full::MusicGroup>>, usize, usize, f32) {
         // Expand groups individually
         let music_groups = music
             .iter()
-            .map(|m| expand_music_group(m, expanded_frags, frag_musics, stage))
+            .map(|m| expand_music_group(m, parent, expanded_frags, frag_musics, stage))
             .map(Rc::new)
             .collect_vec();
         // Sum their instances (ignoring the fact that we might double count identical regexes in
         // different groups)
         let total_count = music_groups.iter().map(|g| g.inner.count()).sum();
         let max_count = music_groups.iter().map(|g| g.max_count).sum();
-        (music_groups, total_count, max_count)
+        let total_score = music_groups.iter().map(|g| g.score).sum();
+        (music_groups, total_count, max_count, total_score)
     }
 
     /// Recursively expand a single [`music::Music`] group
     fn expand_music_group(
         group: &music::Music,
+        parent: &Inherited,
         expanded_frags: &FragSlice<ExpandedFrag>,
         frag_musics: &mut FragSlice<FragMusic>,
         stage: Stage,
     ) -> full::MusicGroup {
+        let inherited = parent.child(group);
         match group {
-            music::Music::Regex(name, regex) => {
-                // Compute where this `Regex` is matched in the composition
-                let mut rows_matched = Vec::<RowLocation>::new();
-                // For each fragment ...
-                for ((frag_index, expanded_frag), frag_music) in
-                    expanded_frags.iter_enumerated().zip_eq(frag_musics)
+            music::Music::Regex {
+                name,
+                regex,
+                weight,
+                count_wraps,
+                parts: _,
+            } => {
+                // Rows which lower the score of the composition are highlighted separately, so
+                // that the composer can see which rows to avoid
+                let is_anti_music = inherited.weight < 0.0;
+                // Compute where this `Regex` is matched in the composition.  Each part of each
+                // fragment is matched independently (and in parallel, if possible)
+                let is_proved_per_frag = expanded_frags
+                    .iter()
+                    .map(|f| f.row_data.iter().map(|d| d.is_proved).collect_vec())
+                    .collect_vec();
+                let mut jobs = Vec::new();
+                for (((frag_index, expanded_frag), frag_music), is_proved) in expanded_frags
+                    .iter_enumerated()
+                    .zip_eq(frag_musics.iter_mut())
+                    .zip_eq(&is_proved_per_frag)
                 {
-                    // ... for each part ...
-                    for ((part_index, rows), part_music_counters) in expanded_frag
-                        .rows_per_part
-                        .iter_enumerated()
-                        .zip_eq(&mut frag_music.music_highlights_per_part)
+                    for (part_index, counters) in frag_music
+                        .highlights_per_part_mut(is_anti_music)
+                        .iter_mut_enumerated()
                     {
-                        // ... for each row ...
-                        //
-                        // PERF: This whole calculation can probably be done in one vectorised pass
-                        for (row_index, ((row, music_counters), row_data)) in rows
-                            .iter()
-                            .zip_eq(part_music_counters.chunks_mut(stage.num_bells()))
-                            .zip_eq(&expanded_frag.row_data)
-                            .enumerate()
-                        {
-                            // Sanity check that all the elements are the same length.  The code
-                            // will likely panic anyway, but this assertion is easier to debug
-                            assert_eq!(music_counters.len(), stage.num_bells());
-                            // ... if the row matches this music pattern ...
-                            if let Some(matched_places) = regex.match_pattern(row) {
-                                // ... mark the row's places as highlight-able
-                                for matched_place in matched_places {
-                                    let counter = &mut music_counters[matched_place];
-                                    match counter.checked_add(1) {
-                                        // No problem if the counter didn't overflow
-                                        Some(v) => *counter = v,
-                                        None => {
-                                            eprintln!("WARNING: A place is matched by more than 255 music scores, clamping value to 255");
-                                            // Don't write to the counter, because its value is
-                                            // already 255
-                                        }
-                                    }
-                                }
-                                // ... and if the row is proved, include this row's location in the
-                                // music group
-                                if row_data.is_proved {
-                                    rows_matched.push(RowLocation {
-                                        frag_index,
-                                        row_index: RowIdx::new(row_index),
-                                        part_index,
-                                    });
-                                }
-                            }
+                        // Music which isn't counted in this part shouldn't be highlighted either
+                        if inherited.is_part_counted[part_index] {
+                            let loc = RowLocation {
+                                frag_index,
+                                row_index: RowIdx::new(0),
+                                part_index,
+                            };
+                            let rows = expanded_frag.rows_in_part_iter(part_index);
+                            jobs.push((loc, rows, counters.as_mut_slice(), is_proved.as_slice()));
                         }
                     }
                 }
+                let rows_matched = par::map(jobs, |(loc, rows, counters, is_proved)| {
+                    match_rows(regex, loc, rows, counters, is_proved, stage)
+                })
+                .into_iter()
+                .flatten()
+                .collect_vec();
+                let wraps_matched = if *count_wraps {
+                    let wrap_regex = WrapRegex::new(regex);
+                    find_wraps(&wrap_regex, &inherited, expanded_frags, frag_musics, stage)
+                } else {
+                    Vec::new()
+                };
 
                 // Use the music group's name, falling back on the regex's representation
                 let name = name
@@ -231,20 +666,178 @@ mod music_gen {
                 full::MusicGroup {
                     name,
                     max_count,
-                    inner: full::MusicGroupInner::Leaf { rows_matched },
+                    score: (rows_matched.len() + wraps_matched.len()) as f32 * weight,
+                    inner: full::MusicGroupInner::Leaf {
+                        rows_matched,
+                        wraps_matched,
+                    },
                 }
             }
-            music::Music::Group(name, source_sub_groups) => {
+            music::Music::Group {
+                name,
+                sub_groups: source_sub_groups,
+                weight,
+                parts: _,
+            } => {
                 // For a music group, expand the sub-groups in turn and total the match counts
-                let (sub_groups, count, max_count) =
-                    expand_music_groups(source_sub_groups, expanded_frags, frag_musics, stage);
+                let (sub_groups, count, max_count, sub_score) = expand_music_groups(
+                    source_sub_groups,
+                    &inherited,
+                    expanded_frags,
+                    frag_musics,
+                    stage,
+                );
                 full::MusicGroup {
                     name: name.to_owned(),
                     max_count,
-                    inner: full::MusicGroupInner::Group { count, sub_groups },
+                    score: sub_score * weight,
+                    inner: full::MusicGroupInner::Group {
+                        wrap_count: sub_groups.iter().map(|g| g.inner.wrap_count()).sum(),
+                        count,
+                        sub_groups,
+                    },
+                }
+            }
+        }
+    }
+
+    /// Matches a [`Regex`] against every row in one part of a fragment, incrementing the music
+    /// counters of the matched places.  `first_loc` is the location of the first row in `rows`.
+    /// Returns the locations of the matching proved rows.
+    fn match_rows(
+        regex: &Regex,
+        first_loc: RowLocation,
+        rows: impl Iterator<Item = RowBuf>,
+        music_counters: &mut [u8],
+        is_proved: &[bool],
+        stage: Stage,
+    ) -> Vec<RowLocation> {
+        let mut rows_matched = Vec::new();
+        // PERF: This whole calculation can probably be done in one vectorised pass
+        for (row_index, ((row, music_counters), is_proved)) in rows
+            .zip_eq(music_counters.chunks_mut(stage.num_bells()))
+            .zip_eq(is_proved)
+            .enumerate()
+        {
+            // Sanity check that all the elements are the same length.  The code will likely panic
+            // anyway, but this assertion is easier to debug
+            assert_eq!(music_counters.len(), stage.num_bells());
+            // If the row matches this music pattern ...
+            if let Some(matched_places) = regex.match_pattern(&row) {
+                // ... mark the row's places as highlight-able
+                for matched_place in matched_places {
+                    increment_counter(&mut music_counters[matched_place]);
+                }
+                // ... and if the row is proved, include this row's location in the music group
+                if *is_proved {
+                    rows_matched.push(RowLocation {
+                        row_index: RowIdx::new(row_index),
+                        ..first_loc
+                    });
+                }
+            }
+        }
+        rows_matched
+    }
+
+    /// Finds every wrap of a [`WrapRegex`] between two consecutive proved rows, highlighting the
+    /// places involved.  Returns the location of the first row of each wrap.
+    fn find_wraps(
+        regex: &WrapRegex,
+        inherited: &Inherited,
+        expanded_frags: &FragSlice<ExpandedFrag>,
+        frag_musics: &mut FragSlice<FragMusic>,
+        stage: Stage,
+    ) -> Vec<RowLocation> {
+        let num_bells = stage.num_bells();
+        let mut wraps_matched = Vec::new();
+        for ((frag_index, expanded_frag), frag_music) in
+            expanded_frags.iter_enumerated().zip_eq(frag_musics)
+        {
+            let row_data = &expanded_frag.row_data;
+            for (part_index, part_music_counters) in frag_music
+                .wrap_highlights_per_part_mut(inherited.weight < 0.0)
+                .iter_mut_enumerated()
+            {
+                if !inherited.is_part_counted[part_index] {
+                    continue;
+                }
+                let mut rows = expanded_frag.rows_in_part_iter(part_index);
+                let mut row = match rows.next() {
+                    Some(row) => row,
+                    None => continue,
+                };
+                for (row_index, next_row) in rows.enumerate() {
+                    let next_index = row_index + 1;
+                    // Wraps can only be rung between two rows which are both part of the
+                    // composition
+                    let is_proved = row_data[RowIdx::new(row_index)].is_proved
+                        && row_data[RowIdx::new(next_index)].is_proved;
+                    let prev_row = std::mem::replace(&mut row, next_row);
+                    if !is_proved {
+                        continue;
+                    }
+                    let matches = regex.wrapped_matches(&prev_row, &row);
+                    for (places, next_places) in matches {
+                        for place in places {
+                            increment_counter(
+                                &mut part_music_counters[row_index * num_bells + place],
+                            );
+                        }
+                        for place in next_places {
+                            increment_counter(
+                                &mut part_music_counters[next_index * num_bells + place],
+                            );
+                        }
+                        wraps_matched.push(RowLocation {
+                            frag_index,
+                            row_index: RowIdx::new(row_index),
+                            part_index,
+                        });
+                    }
                 }
             }
         }
+        wraps_matched
+    }
+
+    /// The properties which a [`music::Music`] inherits from the groups which contain it
+    #[derive(Debug, Clone)]
+    struct Inherited {
+        /// The product of the weights of the [`music::Music`] and every group containing it
+        weight: f32,
+        /// For each part, `true` if the [`music::Music`] and every group containing it are
+        /// counted in that part
+        is_part_counted: PartVec<bool>,
+    }
+
+    impl Inherited {
+        /// The properties inherited by `music`, when it is contained in a group with properties
+        /// `self`
+        fn child(&self, music: &music::Music) -> Self {
+            let mut is_part_counted = self.is_part_counted.clone();
+            if let Some(parts) = music.parts() {
+                for (part_idx, is_counted) in is_part_counted.iter_mut_enumerated() {
+                    *is_counted &= parts.contains(&part_idx);
+                }
+            }
+            Self {
+                weight: self.weight * music.weight(),
+                is_part_counted,
+            }
+        }
+    }
+
+    /// Increments a music highlight counter, saturating (with a warning) instead of overflowing
+    fn increment_counter(counter: &mut u8) {
+        match counter.checked_add(1) {
+            // No problem if the counter didn't overflow
+            Some(v) => *counter = v,
+            None => {
+                eprintln!("WARNING: A place is matched by more than 255 music scores, clamping value to 255");
+                // Don't write to the counter, because its value is already 255
+            }
+        }
     }
 
     /// The music annotations for a single [`Fragment`]
@@ -255,47 +848,111 @@ mod music_gen {
         /// place in more than 255 different music classes.  If we do manage that, the code will
         /// gracefully print a warning and saturate the value at 255.
         pub(super) music_highlights_per_part: PartVec<Vec<u8>>,
+        /// Like `music_highlights_per_part`, but counting the leaf music groups with negative
+        /// weights (i.e. anti-music)
+        pub(super) anti_music_highlights_per_part: PartVec<Vec<u8>>,
+        /// Like `music_highlights_per_part`, but counting the (non-anti-music) matches which wrap
+        /// over the boundary between two rows
+        pub(super) wrap_highlights_per_part: PartVec<Vec<u8>>,
     }
 
     impl FragMusic {
-        fn all_counters_zero(frag: &ExpandedFrag, stage: Stage) -> Self {
-            let num_parts = frag.rows_per_part.len();
+        fn all_counters_zero(frag: &ExpandedFrag, stage: Stage, buffers: &mut Buffers) -> Self {
+            let num_parts = frag.num_parts();
+            let num_places = frag.row_data.len() * stage.num_bells();
+            // For each part, for each place, we initialise the counters to 0
+            let mut counters = || {
+                (0..num_parts)
+                    .map(|_| buffers.counters(num_places))
+                    .collect::<PartVec<_>>()
+            };
             Self {
-                music_highlights_per_part: {
-                    // For each part ...
-                    index_vec![
-                        // ... for each place, we initialise the counters to 0
-                        vec![0u8; frag.row_data.len() * stage.num_bells()];
-                        num_parts
-                    ]
-                },
+                music_highlights_per_part: counters(),
+                anti_music_highlights_per_part: counters(),
+                wrap_highlights_per_part: counters(),
+            }
+        }
+
+        /// The counters which should be incremented by either music or anti-music
+        fn highlights_per_part_mut(&mut self, is_anti_music: bool) -> &mut PartVec<Vec<u8>> {
+            if is_anti_music {
+                &mut self.anti_music_highlights_per_part
+            } else {
+                &mut self.music_highlights_per_part
+            }
+        }
+
+        /// The counters which should be incremented by wraps of either music or anti-music.
+        /// Anti-music is always highlighted as anti-music, whether or not it wraps.
+        fn wrap_highlights_per_part_mut(&mut self, is_anti_music: bool) -> &mut PartVec<Vec<u8>> {
+            if is_anti_music {
+                &mut self.anti_music_highlights_per_part
+            } else {
+                &mut self.wrap_highlights_per_part
             }
         }
     }
 }
 
+/// The lookup tables required to convert [`spec::Method`]s and [`spec::Call`]s into their `full`
+/// equivalents
+#[derive(Debug, Clone, Copy)]
+struct Annotations<'a> {
+    method_map: &'a HashMap<*const spec::Method, MethodIdx>,
+    methods: &'a MethodSlice<Rc<full::Method>>,
+    call_map: &'a HashMap<*const spec::Call, CallIdx>,
+    calls: &'a CallSlice<Rc<full::Call>>,
+}
+
+#[allow(clippy::too_many_arguments)]
 fn expand_frag(
-    exp_frag: ExpandedFrag,
+    exp_frag: &ExpandedFrag,
     music: music_gen::FragMusic,
-    method_map: &HashMap<*const spec::Method, MethodIdx>,
-    methods: &MethodSlice<Rc<full::Method>>,
+    false_groups_per_part: PartVec<RowVec<Option<usize>>>,
+    link_groups: full::FragLinkGroups,
+    end: full::FragEnd,
+    annotations: Annotations,
+    observation_bell: Bell,
+    // An empty buffer used to store the `RowData` of each row
+    mut full_row_data: RowVec<full::RowData>,
 ) -> full::Fragment {
-    // Helper function to convert between `spec::Method` and the corresponding `full::Method`
+    let courses = find_courses(exp_frag, observation_bell);
+
+    // Helper functions to convert between `spec::Method`/`spec::Call` and the corresponding
+    // `full::Method`/`full::Call`
     let spec_to_full_method = |spec: &Rc<spec::Method>| {
         let spec_method_ptr = spec.as_ref() as *const spec::Method;
-        methods[method_map[&spec_method_ptr]].clone()
+        annotations.methods[annotations.method_map[&spec_method_ptr]].clone()
+    };
+    let spec_to_full_call = |spec: &Rc<spec::Call>| {
+        let spec_call_ptr = spec.as_ref() as *const spec::Call;
+        annotations.calls[annotations.call_map[&spec_call_ptr]].clone()
     };
 
     // Generate `row_data` elements, with some fields left 'empty' to be filled in later
-    let mut full_row_data: RowVec<full::RowData> = exp_frag
-        .row_data
-        .iter()
-        .map(|row_data| full::RowData {
-            is_proved: row_data.is_proved,
-            ruleoff_above: false,    // Set later in this function
-            method_annotation: None, // Set later in this function
-        })
-        .collect();
+    full_row_data.extend(
+        exp_frag
+            .row_data
+            .iter_enumerated()
+            .map(|(row_idx, row_data)| {
+                full::RowData {
+                    is_proved: row_data.is_proved,
+                    ruleoff_above: false,    // Set later in this function
+                    method_annotation: None, // Set later in this function
+                    // Calls are labelled on their first row
+                    call_annotation: match &row_data.call_source {
+                        Some((call, 0)) => Some(spec_to_full_call(call)),
+                        _ => None,
+                    },
+                    is_lead_head: matches!(row_data.method_source, Some((_, 0))),
+                    // A row is false if it's false in any part
+                    is_false: false_groups_per_part
+                        .iter()
+                        .any(|groups| groups[row_idx].is_some()),
+                    method_source: row_data.method_source.clone(),
+                }
+            }),
+    );
 
     for ((prev_source_row, source_row), full_row) in exp_frag
         .row_data
@@ -350,8 +1007,58 @@ fn expand_frag(
 
     full::Fragment {
         position: exp_frag.position,
-        rows_per_part: exp_frag.rows_per_part,
+        show_bluelines: exp_frag.show_bluelines,
+        link_groups,
+        end,
+        courses,
+        rows_in_one_part: exp_frag.rows_in_one_part.clone(),
+        part_heads: exp_frag.part_heads.clone(),
+        rows_per_part: (0..exp_frag.num_parts()).map(|_| OnceCell::new()).collect(),
         music_highlights_per_part: music.music_highlights_per_part,
+        anti_music_highlights_per_part: music.anti_music_highlights_per_part,
+        wrap_highlights_per_part: music.wrap_highlights_per_part,
+        false_groups_per_part,
         row_data: full_row_data,
+        observation_bell,
+    }
+}
+
+/// Splits a [`Fragment`](full::Fragment) into courses, finding the calling of each one.  Course
+/// ends are lead heads where the `observation_bell` is at home in the first part.
+fn find_courses(exp_frag: &ExpandedFrag, observation_bell: Bell) -> Vec<full::Course> {
+    let rows = &exp_frag.rows_in_one_part;
+    let last_row_idx = exp_frag.row_data.len() - 1;
+
+    let mut courses = Vec::new();
+    let mut course_start = RowIdx::new(0);
+    let mut calls = Vec::<String>::new();
+    let is_lead_head = |row_data: &RowData| match &row_data.method_source {
+        Some((_, sub_lead_idx)) => *sub_lead_idx == 0,
+        None => true, // The leftover row
+    };
+    for (row_idx, row_data) in exp_frag.row_data.iter_enumerated() {
+        // Calls are named after the position of the observation bell in the lead head after the
+        // call.  This is further down the lead if the call isn't made at the lead end.
+        if let Some((call, 0)) = &row_data.call_source {
+            let lead_head = exp_frag
+                .row_data
+                .iter_enumerated()
+                .skip(row_idx.index() + call.len())
+                .find(|(_, data)| is_lead_head(data))
+                .map(|(idx, _)| &rows[idx.index()]);
+            calls.push(call.label(lead_head, observation_bell));
+        }
+
+        let is_course_end = is_lead_head(row_data)
+            && rows[row_idx.index()].place_of(observation_bell) == Some(observation_bell.index());
+        if row_idx > course_start && (is_course_end || row_idx.index() == last_row_idx) {
+            courses.push(full::Course {
+                start: course_start,
+                end: row_idx,
+                calling: calls.drain(..).join(" "),
+            });
+            course_start = row_idx;
+        }
     }
+    courses
 }
@@ -1,22 +1,35 @@
 //! The fully annotated state of a composition used for querying and rendering.
 
-use std::{ops::Deref, rc::Rc};
+use std::{cell::OnceCell, collections::HashMap, ops::Deref, rc::Rc};
 
-use bellframe::{SameStageVec, Stage};
-use emath::Pos2;
+use bellframe::{method::class::FullClass, music::Regex, Bell, Row, RowBuf, SameStageVec, Stage};
 use itertools::Itertools;
 use jigsaw_utils::{
-    indexed_vec::{FragVec, MethodVec, PartIdx, PartVec, RowIdx, RowVec},
-    types::{RowLocation, RowSource},
+    indexed_vec::{
+        CallVec, FragIdx, FragVec, MethodIdx, MethodVec, PartIdx, PartVec, RowIdx, RowVec,
+    },
+    types::{Position, RowLocation, RowSource},
 };
 
-use crate::spec::{self, part_heads::PartHeads, CompSpec};
+use crate::{
+    expanded_frag::ExpandedFrag,
+    music,
+    spec::{self, part_heads::PartHeads, proof::ProofSettings, CompSpec},
+};
 
-// Imports only used for doc comments
-#[allow(unused_imports)]
-use bellframe::Row;
+use self::{buffers::Buffers, falseness_table::FalsenessTable, proving::Prover};
 
+#[cfg(feature = "bench")]
+pub mod bench;
+mod buffers;
+mod export;
+mod falseness_table;
 mod from_expanded_frags;
+mod problems;
+mod proving;
+
+pub use export::{ExportFormat, ExportOptions};
+pub use problems::Problem;
 
 /// The fully specified state of a composition.  This is designed to be efficient to query and easy
 /// to render from, unlike [`CompSpec`] which is designed to be compact and easy to modify or store
@@ -25,35 +38,357 @@ mod from_expanded_frags;
 /// There will only be one copy of [`FullState`] instantiated at a time, and it is up to the
 /// `JigsawApp` instance to make sure that it always represents the data that the user expects to
 /// see.  Every time the [`CompSpec`] being viewed changes (either through the user's changes or
-/// through undo/redo), the [`FullState`] is recomputed for the new [`CompSpec`].
+/// through undo/redo), the [`FullState`] is updated to match the new [`CompSpec`].  The previous
+/// [`FullState`] is kept, so that undoing and then redoing an edit doesn't recompute anything.
 #[derive(Debug)]
 pub struct FullState {
     pub part_heads: Rc<PartHeads>,
     pub fragments: FragVec<Fragment>,
     pub methods: MethodVec<Rc<Method>>,
+    pub calls: CallVec<Rc<Call>>,
     pub music: Music,
+    /// Which [`Row`]s in the composition are rung more than once
+    pub falseness: Falseness,
+    /// Every pair of complete plain leads which share at least one [`Row`], in the order that the
+    /// leads appear in the composition
+    pub false_leads: Vec<(PlainLead, PlainLead)>,
+    /// Every pair of [`Fragment`]s where one can be joined onto the end of the other
+    pub frag_links: Vec<FragLink>,
+    /// For each part, every lead head reached by the proved [`Fragment`]s
+    pub lead_heads: PartVec<Vec<LeadHead>>,
     /// Misc statistics about the composition (e.g. part length)
     pub stats: Stats,
+    /// Possible mistakes in the composition, which should be pointed out to the user
+    pub problems: Vec<Problem>,
     pub stage: Stage,
+    /// Every location of each [`Row`] in the composition, so that [`Row`]s can be searched for
+    row_index: HashMap<RowBuf, Vec<RowLocation>>,
+    source: Source,
+    /// The [`FullState`] which `self` was updated from, if any.  This is never nested more than
+    /// one level deep.
+    previous: Option<Box<FullState>>,
 }
 
 impl FullState {
     /// Creates a new [`FullState`] representing the same composition as a given [`CompSpec`].
     pub fn new(spec: &CompSpec) -> Self {
+        Self::with_buffers(spec, &mut Buffers::default(), None)
+    }
+
+    /// Creates a new [`FullState`] for a [`CompSpec`], reusing allocations from `buffers` where
+    /// possible.  If `falseness_table` is given, it must have been built from the same methods as
+    /// `spec`.
+    fn with_buffers(
+        spec: &CompSpec,
+        buffers: &mut Buffers,
+        falseness_table: Option<Rc<FalsenessTable>>,
+    ) -> Self {
         let expanded_frags = spec.expand_fragments();
+        let source = Source {
+            fragments: spec.fragments().to_owned(),
+            expansion: Some(Expansion {
+                prover: Prover::new(&expanded_frags, spec.proof_settings().clone()),
+                expanded_frags,
+            }),
+            replaced_frags: Vec::new(),
+            methods: spec.methods().to_owned(),
+            falseness_table: falseness_table
+                .unwrap_or_else(|| Rc::new(FalsenessTable::new(spec.methods()))),
+            calls: spec.calls().to_owned(),
+            music: spec.music().clone(),
+            proof_settings: spec.proof_settings().clone(),
+            observation_bell: spec.observation_bell(),
+            length_target: spec.length_target(),
+        };
         from_expanded_frags::from_expanded_frags(
-            expanded_frags,
-            spec.methods(),
+            source,
             spec.part_heads().clone(),
-            spec.music(),
             spec.stage(),
+            buffers,
         )
     }
 
-    /// Updates `self` to represent the same composition as a given [`CompSpec`]
+    /// The definitions of the music groups, with the same tree structure as [`FullState::music`]
+    pub fn music_definitions(&self) -> &[music::Music] {
+        &self.source.music
+    }
+
+    /// Every location of a given [`Row`] in the composition (in any part, and including muted
+    /// [`Fragment`]s), ordered by part
+    pub fn find_row(&self, row: &Row) -> &[RowLocation] {
+        self.row_index.get(row).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every location of every [`Row`] which matches a given [`Regex`] (in any part, and
+    /// including muted [`Fragment`]s), ordered by part
+    pub fn find_rows_matching(&self, regex: &Regex) -> Vec<RowLocation> {
+        let mut locations = self
+            .row_index
+            .iter()
+            .filter(|(row, _)| regex.matches(row))
+            .flat_map(|(_, locations)| locations.iter().copied())
+            .collect_vec();
+        locations.sort_by_key(|loc| (loc.part_index, loc.frag_index, loc.row_index));
+        locations
+    }
+
+    /// The settings which determine which [`Row`]s are compared when proving the composition
+    pub fn proof_settings(&self) -> &ProofSettings {
+        &self.source.proof_settings
+    }
+
+    /// The [`Bell`] whose position names the calling positions and course ends
+    pub fn observation_bell(&self) -> Bell {
+        self.source.observation_bell
+    }
+
+    /// The false course heads of `method_a` against `method_b`: a plain course of `method_b`
+    /// starting at any of these course heads shares at least one [`Row`] with the plain course of
+    /// `method_a`.  If `tenors_together` is `true`, only the course heads which leave the tenors
+    /// (see [`FullState::has_tenors`]) in their home positions are returned.
+    pub fn false_course_heads(
+        &self,
+        method_a: MethodIdx,
+        method_b: MethodIdx,
+        tenors_together: bool,
+    ) -> Vec<&Row> {
+        let course_heads = self
+            .source
+            .falseness_table
+            .false_course_heads(method_a, method_b)
+            .unwrap_or_default();
+        course_heads
+            .iter()
+            .map(RowBuf::as_row)
+            .filter(|row| !tenors_together || is_tenors_together(row))
+            .collect_vec()
+    }
+
+    /// Returns `true` if this composition's [`Stage`] has tenors which can be kept together: every
+    /// bell from the 7th upwards on Major and above, or the back two bells on Minor and Triples.
+    /// On lower stages, no course heads keep the tenors together.
+    pub fn has_tenors(&self) -> bool {
+        !tenor_places(self.stage).is_empty()
+    }
+
+    /// Updates `self` to represent the same composition as a given [`CompSpec`].  If only
+    /// [`Fragment`]s have changed, then only the changed [`Fragment`]s are re-expanded and
+    /// re-proved.
+    ///
+    /// If `spec` is the same as the [`CompSpec`] used to build `self` (e.g. if an edit didn't
+    /// change anything), or the one used to build the previous [`FullState`] (e.g. after undoing
+    /// and redoing an edit), then nothing is recomputed.  Otherwise, the buffers of the previous
+    /// [`FullState`] are reused to build the new one.
+    ///
+    /// An incremental update moves the [`Expansion`] out of `self` (rather than copying it), so
+    /// that each edit only costs as much as the [`Fragment`]s it changes.  The replaced
+    /// [`ExpandedFrag`]s are kept in the previous state, so that it can take the [`Expansion`]
+    /// back if it is restored by undo or redo.
     pub fn update(&mut self, spec: &CompSpec) {
-        // For now, just overwrite `self` without reusing any allocations
-        *self = Self::new(spec);
+        if self.represents(spec) {
+            return;
+        }
+        let mut buffers = Buffers::default();
+        if let Some(mut previous) = self.previous.take() {
+            if previous.represents(spec) {
+                // Swap `self` with the previous state, so that the current state becomes the
+                // previous one
+                std::mem::swap(self, &mut previous);
+                self.reclaim_expansion(&mut previous);
+                self.previous = Some(previous);
+                return;
+            }
+            // The previous state is about to be replaced, so its allocations can be reused
+            buffers.recycle(*previous);
+        }
+
+        let new_state = self.updated(spec, &mut buffers);
+        let old_state = std::mem::replace(self, new_state);
+        self.previous = Some(Box::new(old_state));
+    }
+
+    /// Creates a new [`FullState`] for `spec`, reusing the parts of `self` which haven't changed
+    fn updated(&mut self, spec: &CompSpec, buffers: &mut Buffers) -> Self {
+        if !self.can_update_incrementally(spec) {
+            // The falseness table is expensive to build, and only depends on the methods
+            let falseness_table = self
+                .has_same_methods(spec)
+                .then(|| self.source.falseness_table.clone());
+            return Self::with_buffers(spec, buffers, falseness_table);
+        }
+
+        // Fragments are never modified in-place, so any unchanged fragments will still point to
+        // the same memory
+        let new_frags = spec
+            .fragments()
+            .iter_enumerated()
+            .filter(|(frag_idx, new_frag)| !Rc::ptr_eq(new_frag, &self.source.fragments[*frag_idx]))
+            .map(|(frag_idx, new_frag)| (frag_idx, new_frag.expand(&self.part_heads)))
+            .collect_vec();
+        // `self` is kept as the previous state, so it keeps the `ExpandedFrag`s which are replaced
+        let mut expansion = self.source.take_expansion();
+        self.source.replaced_frags = expansion.replace_frags(new_frags);
+        let source = Source {
+            fragments: spec.fragments().to_owned(),
+            expansion: Some(expansion),
+            replaced_frags: Vec::new(),
+            methods: self.source.methods.clone(),
+            falseness_table: self.source.falseness_table.clone(),
+            calls: self.source.calls.clone(),
+            music: self.source.music.clone(),
+            proof_settings: self.source.proof_settings.clone(),
+            observation_bell: self.source.observation_bell,
+            length_target: self.source.length_target,
+        };
+        from_expanded_frags::from_expanded_frags(
+            source,
+            self.part_heads.clone(),
+            self.stage,
+            buffers,
+        )
+    }
+
+    /// If `self`'s [`Expansion`] was moved into `other` by an incremental update, then move it
+    /// back into `self`, leaving `other` with the [`ExpandedFrag`]s which `self` replaced
+    fn reclaim_expansion(&mut self, other: &mut FullState) {
+        if self.source.expansion.is_some() {
+            return;
+        }
+        let mut expansion = other.source.take_expansion();
+        let replaced_frags = std::mem::take(&mut self.source.replaced_frags);
+        other.source.replaced_frags = expansion.replace_frags(replaced_frags);
+        self.source.expansion = Some(expansion);
+    }
+
+    /// Returns `true` if `self` was built from exactly the same [`CompSpec`] as `spec`
+    fn represents(&self, spec: &CompSpec) -> bool {
+        self.can_update_incrementally(spec)
+            && spec
+                .fragments()
+                .iter()
+                .zip_eq(&self.source.fragments)
+                .all(|(f1, f2)| Rc::ptr_eq(f1, f2))
+    }
+
+    /// Returns `true` if `spec` only differs from the [`CompSpec`] used to build `self` by the
+    /// contents of its [`Fragment`]s
+    fn can_update_incrementally(&self, spec: &CompSpec) -> bool {
+        let source = &self.source;
+        spec.stage() == self.stage
+            && spec.observation_bell() == source.observation_bell
+            && spec.length_target() == source.length_target
+            && Rc::ptr_eq(spec.part_heads(), &self.part_heads)
+            && Rc::ptr_eq(spec.music(), &source.music)
+            && Rc::ptr_eq(spec.proof_settings(), &source.proof_settings)
+            && spec.fragments().len() == source.fragments.len()
+            && self.has_same_methods(spec)
+            && spec.calls().len() == source.calls.len()
+            && spec
+                .calls()
+                .iter()
+                .zip_eq(&source.calls)
+                .all(|(c1, c2)| Rc::ptr_eq(c1, c2))
+    }
+
+    /// Returns `true` if `spec` has exactly the same [`Method`](spec::Method)s as the
+    /// [`CompSpec`] used to build `self`
+    fn has_same_methods(&self, spec: &CompSpec) -> bool {
+        spec.methods().len() == self.source.methods.len()
+            && spec
+                .methods()
+                .iter()
+                .zip_eq(&self.source.methods)
+                .all(|(m1, m2)| Rc::ptr_eq(m1, m2))
+    }
+}
+
+/// Returns `true` if every tenor (see [`tenor_places`]) is in its home position in `row`
+fn is_tenors_together(row: &Row) -> bool {
+    tenor_places(row.stage()).all(|place| row[place].index() == place)
+}
+
+/// The places of the bells which are kept together in tenors-together course heads: every bell
+/// from the 7th upwards on Major and above, the back two bells on Minor and Triples, and none on
+/// lower stages
+fn tenor_places(stage: Stage) -> std::ops::Range<usize> {
+    let num_bells = stage.num_bells();
+    let first_tenor = match num_bells {
+        0..=5 => num_bells,
+        6 | 7 => num_bells - 2,
+        _ => 6,
+    };
+    first_tenor..num_bells
+}
+
+/// The data from which a [`FullState`] was built, kept so that [`FullState::update`] can reuse the
+/// parts of the composition which haven't changed
+#[derive(Debug)]
+struct Source {
+    fragments: FragVec<Rc<spec::Fragment>>,
+    /// The expanded [`Row`]s of the composition.  This is `None` if it has been moved into the
+    /// [`FullState`] which was incrementally updated from this one.
+    expansion: Option<Expansion>,
+    /// If `expansion` has been moved, the [`ExpandedFrag`]s which the update replaced (so that
+    /// `expansion` can be moved back).  Otherwise, this is empty.
+    replaced_frags: Vec<(FragIdx, ExpandedFrag)>,
+    methods: MethodVec<Rc<spec::Method>>,
+    /// The lead-level falseness between every pair of `methods`
+    falseness_table: Rc<FalsenessTable>,
+    calls: CallVec<Rc<spec::Call>>,
+    music: Rc<Vec<music::Music>>,
+    proof_settings: Rc<ProofSettings>,
+    observation_bell: Bell,
+    /// Only used to warn the user about the composition's length
+    length_target: Option<spec::LengthTarget>,
+}
+
+impl Source {
+    /// # Panics
+    ///
+    /// Panics if the [`Expansion`] has been moved to another [`FullState`]
+    fn expansion(&self) -> &Expansion {
+        self.expansion
+            .as_ref()
+            .expect("Expansion was moved to another `FullState`")
+    }
+
+    /// Moves the [`Expansion`] out of `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Expansion`] has already been moved to another [`FullState`]
+    fn take_expansion(&mut self) -> Expansion {
+        self.expansion
+            .take()
+            .expect("Expansion was moved to another `FullState`")
+    }
+}
+
+/// The parts of a [`Source`] which contain every [`Row`] of the composition, and are therefore too
+/// expensive to copy on every edit
+#[derive(Debug)]
+struct Expansion {
+    expanded_frags: FragVec<ExpandedFrag>,
+    prover: Prover,
+}
+
+impl Expansion {
+    /// Replaces some of the [`ExpandedFrag`]s (updating the [`Prover`] to match), returning the
+    /// [`ExpandedFrag`]s which were replaced
+    fn replace_frags(
+        &mut self,
+        new_frags: Vec<(FragIdx, ExpandedFrag)>,
+    ) -> Vec<(FragIdx, ExpandedFrag)> {
+        new_frags
+            .into_iter()
+            .map(|(frag_idx, new_frag)| {
+                self.prover
+                    .remove_frag(frag_idx, &self.expanded_frags[frag_idx]);
+                self.prover.add_frag(frag_idx, &new_frag);
+                let old_frag = std::mem::replace(&mut self.expanded_frags[frag_idx], new_frag);
+                (frag_idx, old_frag)
+            })
+            .collect()
     }
 }
 
@@ -64,16 +399,40 @@ impl FullState {
 #[derive(Debug, Clone)]
 pub struct Fragment {
     /// The position of the top-left corner of the first [`Row`] in this `Fragment`
-    pub position: Pos2,
-    /// For each part, which [`Row`]s make up this `Fragment`
-    rows_per_part: PartVec<SameStageVec>,
+    pub position: Position,
+    /// If `true`, this `Fragment` should be drawn with bluelines instead of bell names
+    pub show_bluelines: bool,
+    /// Which [`FragLink`] groups the top and bottom of this `Fragment` belong to
+    pub link_groups: FragLinkGroups,
+    /// How the leftover [`Row`] of this `Fragment` joins onto the rest of the composition
+    pub end: FragEnd,
+    /// The courses of this `Fragment`, used to summarise it like a printed composition
+    pub courses: Vec<Course>,
+    /// The [`Row`]s which make up this `Fragment` in the first part (whose part head is rounds).
+    /// These are shared with the [`ExpandedFrag`] which this was built from.
+    rows_in_one_part: Rc<SameStageVec>,
+    /// The part heads which transpose `rows_in_one_part` into the [`Row`]s of each part
+    part_heads: Rc<PartHeads>,
+    /// For each part, which [`Row`]s make up this `Fragment`.  Each part is only generated from
+    /// `rows_in_one_part` the first time it's used (usually when it's drawn), so parts which are
+    /// never looked at don't take up any memory.
+    rows_per_part: PartVec<OnceCell<SameStageVec>>,
     /// For each part, how many leaf music groups match each place in the [`Row`]s from that part.
     /// I find it extremely unlikely that we'll overflow `u8`s here (since we'd need at least 256
     /// music groups to apply to the same position in a row).  Even then, the code saturates
     /// instead of overflowing and prints a warning to stderr.
     music_highlights_per_part: PartVec<Vec<u8>>,
+    /// Like `music_highlights_per_part`, but only counting music groups with negative weights
+    anti_music_highlights_per_part: PartVec<Vec<u8>>,
+    /// Like `music_highlights_per_part`, but only counting matches which wrap between two rows
+    wrap_highlights_per_part: PartVec<Vec<u8>>,
+    /// For each part, the index of the [`FalseGroup`] containing each [`Row`] (or `None` if that
+    /// [`Row`] is true)
+    false_groups_per_part: PartVec<RowVec<Option<usize>>>,
     /// Extra non-part-specific data about each row to help the rendering
     row_data: RowVec<RowData>,
+    /// The [`Bell`] whose position in each part names the calls in that part
+    observation_bell: Bell,
 }
 
 impl Fragment {
@@ -82,19 +441,46 @@ impl Fragment {
     }
 
     pub fn rows_in_part(&self, part: PartIdx) -> impl Iterator<Item = (RowIdx, RowDataForOnePart)> {
-        let row_vec = &self.rows_per_part[part];
+        let row_vec = self.rows(part);
         let stage = row_vec.stage();
+        let num_bells = stage.num_bells();
         row_vec
             .iter()
             .zip_eq(&self.row_data)
-            .zip_eq(self.music_highlights_per_part[part].chunks(stage.num_bells()))
+            .zip_eq(self.music_highlights_per_part[part].chunks(num_bells))
+            .zip_eq(self.anti_music_highlights_per_part[part].chunks(num_bells))
+            .zip_eq(self.wrap_highlights_per_part[part].chunks(num_bells))
+            .zip_eq(&self.false_groups_per_part[part])
             .enumerate()
-            .map(|(idx, ((row, data), music_counts))| {
-                (
-                    RowIdx::new(idx),
-                    RowDataForOnePart::new(row, music_counts, data),
-                )
-            })
+            .map(
+                |(idx, (((((row, data), music), anti_music), wraps), false_group))| {
+                    let counts = MusicCounts {
+                        music,
+                        anti_music,
+                        wraps,
+                    };
+                    (
+                        RowIdx::new(idx),
+                        RowDataForOnePart::new(row, counts, *false_group, data),
+                    )
+                },
+            )
+    }
+
+    /// Returns the [`Row`] at a given index in a given part
+    pub fn row_in_part(&self, part: PartIdx, row_idx: RowIdx) -> &Row {
+        &self.rows(part)[row_idx.index()]
+    }
+
+    /// Returns the [`Row`]s of this `Fragment` in a given part, generating them if this is the
+    /// first time that part has been used
+    fn rows(&self, part: PartIdx) -> &SameStageVec {
+        let part_head = &self.part_heads.rows()[part.index()];
+        if part_head.is_rounds() {
+            return &self.rows_in_one_part; // Don't store a copy of the first part
+        }
+        self.rows_per_part[part]
+            .get_or_init(|| self.rows_in_one_part.pre_multiplied(part_head).unwrap())
     }
 
     /// Returns the (index, distance) of the nearest rule-off to a given `target` row position
@@ -118,26 +504,116 @@ impl Fragment {
         }
         nearest_dist
     }
+
+    /// Returns the label of the [`Call`] (if any) which starts at `row_idx`, as it appears in a
+    /// given part (e.g. `"sH"`).  The calling position depends on where the observation bell is
+    /// after the call, so the same call can have different labels in different parts.
+    pub fn call_label(&self, part: PartIdx, row_idx: RowIdx) -> Option<String> {
+        let call = self.row_data[row_idx].call_annotation.as_ref()?;
+        // Calls which aren't made at the lead end (e.g. half-lead calls) are still named after the
+        // next lead head
+        let lead_head = self
+            .next_lead_head(row_idx.index() + call.num_rows())
+            .map(|idx| self.row_in_part(part, idx));
+        Some(call.source.label(lead_head, self.observation_bell))
+    }
+
+    /// Returns `true` if a given [`Call`] can finish directly above the [`Row`] at `row_idx`, i.e.
+    /// if `row_idx` is at the [`Call`]'s location in the lead
+    pub fn can_end_call_at(&self, call: &Call, row_idx: RowIdx) -> bool {
+        let prev_row_idx = match row_idx.index().checked_sub(1) {
+            Some(idx) => RowIdx::new(idx),
+            None => return false, // Calls can't finish above the first row
+        };
+        match &self.row_data[prev_row_idx].method_source {
+            Some((method, sub_lead_idx)) => call.source.can_end_at(method, sub_lead_idx + 1),
+            None => false,
+        }
+    }
+
+    /// Returns the index of the first lead head (or the leftover row) at or after `row_idx`
+    fn next_lead_head(&self, row_idx: usize) -> Option<RowIdx> {
+        let leftover_row_idx = self.num_rows() - 1;
+        self.row_data
+            .iter_enumerated()
+            .skip(row_idx)
+            .find(|(idx, data)| data.is_lead_head || idx.index() == leftover_row_idx)
+            .map(|(idx, _)| idx)
+    }
+
+    /// If a lead starts at `row_idx`, returns the place (counting from 0) of the observation bell
+    /// in that lead head in a given part.  This is the place bell which the observation bell
+    /// becomes for that lead.
+    pub fn place_bell(&self, part: PartIdx, row_idx: RowIdx) -> Option<usize> {
+        if !self.row_data[row_idx].is_lead_head {
+            return None;
+        }
+        self.row_in_part(part, row_idx)
+            .place_of(self.observation_bell)
+    }
+
+    /// Returns the [`Call`] (if any) which ends directly above the [`Row`] at `row_idx`
+    pub fn call_ending_at(&self, row_idx: RowIdx) -> Option<&Rc<Call>> {
+        self.row_data
+            .iter_enumerated()
+            .take(row_idx.index())
+            .find_map(|(idx, data)| {
+                let call = data.call_annotation.as_ref()?;
+                (idx.index() + call.num_rows() == row_idx.index()).then_some(call)
+            })
+    }
+}
+
+/// A course of a [`Fragment`], which runs up to the next course end (or to the end of the
+/// [`Fragment`])
+#[derive(Debug, Clone)]
+pub struct Course {
+    /// The index of the first [`Row`] of this course
+    pub start: RowIdx,
+    /// The index of the course end which finishes this course.  The last course of a
+    /// [`Fragment`] finishes at the leftover row, even if it isn't a course end.
+    pub end: RowIdx,
+    /// The calls made in this course, written as calling positions (e.g. `"W sB H"`).  Bobs are
+    /// written as just their calling position, whereas other calls are prefixed by their symbol.
+    pub calling: String,
 }
 
 /// All the data required to render a row to the screen
 #[derive(Debug, Clone)]
 pub struct RowDataForOnePart<'frag> {
     pub row: &'frag Row,
-    pub music_counts: &'frag [u8],
+    pub music_counts: MusicCounts<'frag>,
+    /// The index of the [`FalseGroup`] containing this [`Row`], or `None` if this [`Row`] is true
+    pub false_group: Option<usize>,
     data: &'frag RowData,
 }
 
 impl<'frag> RowDataForOnePart<'frag> {
-    pub fn new(row: &'frag Row, music_counts: &'frag [u8], data: &'frag RowData) -> Self {
+    pub fn new(
+        row: &'frag Row,
+        music_counts: MusicCounts<'frag>,
+        false_group: Option<usize>,
+        data: &'frag RowData,
+    ) -> Self {
         Self {
             row,
             music_counts,
+            false_group,
             data,
         }
     }
 }
 
+/// For each place in a [`Row`], how many leaf music groups of each kind match it
+#[derive(Debug, Clone, Copy)]
+pub struct MusicCounts<'frag> {
+    pub music: &'frag [u8],
+    /// Matches of music groups with negative weights (i.e. anti-music)
+    pub anti_music: &'frag [u8],
+    /// Matches which wrap over the boundary between this [`Row`] and an adjacent [`Row`]
+    pub wraps: &'frag [u8],
+}
+
 impl<'frag> Deref for RowDataForOnePart<'frag> {
     type Target = &'frag RowData;
 
@@ -158,10 +634,15 @@ pub struct RowData {
     pub ruleoff_above: bool,
     /// What method name should be placed here
     pub method_annotation: Option<Rc<Method>>,
-    /*
+    /// Which call (if any) starts at this [`Row`]
+    pub call_annotation: Option<Rc<Call>>,
+    /// `true` if a lead of a [`Method`] starts at this [`Row`]
+    pub is_lead_head: bool,
     /// Do any of these [`Row`]s appear elsewhere in the composition?
     pub is_false: bool,
-    */
+    /// The [`Method`] which this [`Row`] belongs to, along with its index within the lead (or
+    /// `None` if this is the leftover [`Row`])
+    pub(crate) method_source: Option<(Rc<spec::Method>, usize)>,
 }
 
 /////////////
@@ -187,6 +668,82 @@ impl Method {
     pub fn shorthand(&self) -> String {
         self.source.shorthand().to_owned()
     }
+
+    /// The full title of this [`Method`], e.g. `"Bristol Surprise Major"`
+    #[inline]
+    pub fn title(&self) -> String {
+        self.source.title()
+    }
+
+    #[inline]
+    pub fn class(&self) -> FullClass {
+        self.source.class()
+    }
+
+    #[inline]
+    pub fn place_notation(&self) -> String {
+        self.source.place_notation().to_owned()
+    }
+
+    /// The standard lead head code of this [`Method`] (e.g. `"m"` for Bristol Surprise Major)
+    #[inline]
+    pub fn lead_head_code(&self) -> Option<String> {
+        self.source.lead_head_code()
+    }
+
+    #[inline]
+    pub fn ruleoffs_above(&self) -> Vec<usize> {
+        self.source.ruleoffs_above()
+    }
+
+    /// The [`Row`]s of a plain lead of this [`Method`], starting from rounds and finishing with
+    /// the lead head reached at the end of the lead
+    pub fn plain_lead(&self) -> impl Iterator<Item = &Row> + '_ {
+        self.source
+            .plain_lead()
+            .chain(std::iter::once(self.source.lead_head()))
+    }
+}
+
+///////////
+// CALLS //
+///////////
+
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub(crate) source: Rc<spec::Call>,
+    /// Total number of times that this [`Call`] is used
+    pub num_uses: usize,
+    /// Number of times that this [`Call`] is used in proved [`Row`]s
+    pub num_proved_uses: usize,
+}
+
+impl Call {
+    #[inline]
+    pub fn symbol(&self) -> char {
+        self.source.symbol()
+    }
+
+    #[inline]
+    pub fn place_notation(&self) -> String {
+        self.source.place_notation().to_owned()
+    }
+
+    #[inline]
+    pub fn lead_location(&self) -> String {
+        self.source.lead_location().to_owned()
+    }
+
+    #[inline]
+    pub fn calling_positions(&self) -> String {
+        self.source.calling_positions().to_owned()
+    }
+
+    /// The number of [`Row`]s covered by this [`Call`]
+    #[inline]
+    pub fn num_rows(&self) -> usize {
+        self.source.len()
+    }
 }
 
 ///////////
@@ -199,6 +756,10 @@ pub struct Music {
     pub(super) groups: Vec<Rc<MusicGroup>>,
     pub(super) total_count: usize,
     pub(super) max_count: usize,
+    /// The sum of the weighted scores of every top-level [`MusicGroup`]
+    pub(super) total_score: f32,
+    /// Reverse index of `groups`: for each proved [`Row`], which leaf [`MusicGroup`]s it matches
+    pub(super) matches_by_row: HashMap<RowSource, Vec<MusicMatch>>,
 }
 
 impl Music {
@@ -214,6 +775,27 @@ impl Music {
     pub fn max_count(&self) -> &usize {
         &self.max_count
     }
+
+    /// The weighted score of the whole composition, used to compare compositions numerically
+    pub fn total_score(&self) -> f32 {
+        self.total_score
+    }
+
+    /// Returns every leaf [`MusicGroup`] matched by the [`Row`]s at a given [`RowSource`] (in any
+    /// part)
+    pub fn matches_for_row(&self, source: RowSource) -> &[MusicMatch] {
+        self.matches_by_row.get(&source).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// A single match of a leaf [`MusicGroup`] against a [`Row`]
+#[derive(Debug, Clone)]
+pub struct MusicMatch {
+    /// The part containing the matched [`Row`]
+    pub part: PartIdx,
+    /// The names of the [`MusicGroup`]s containing the match, from the top-level group down to
+    /// the leaf group which matched the [`Row`]
+    pub group_names: Vec<String>,
 }
 
 /// A group of musical rows, potentially subdivided into more groups.  This strongly follows the
@@ -222,6 +804,9 @@ impl Music {
 pub struct MusicGroup {
     pub name: String,
     pub max_count: usize,
+    /// The weighted score of this group, i.e. its weight multiplied by either the number of
+    /// matching [`Row`]s or the total score of its sub-groups
+    pub score: f32,
     // If empty, then this [`MusicGroup`] is a 'leaf' of the tree
     pub inner: MusicGroupInner,
 }
@@ -231,8 +816,12 @@ impl MusicGroup {
     /// [`RowSource`]s may be added multiple times.
     pub fn add_row_sources(&self, out: &mut impl Extend<RowSource>) {
         match &self.inner {
-            MusicGroupInner::Leaf { rows_matched } => {
-                out.extend(rows_matched.iter().map(|loc| loc.as_source()))
+            MusicGroupInner::Leaf {
+                rows_matched,
+                wraps_matched,
+            } => {
+                out.extend(rows_matched.iter().map(|loc| loc.as_source()));
+                out.extend(wraps_matched.iter().map(|loc| loc.as_source()));
             }
             MusicGroupInner::Group { sub_groups, .. } => {
                 for g in sub_groups {
@@ -247,10 +836,14 @@ impl MusicGroup {
 pub enum MusicGroupInner {
     Leaf {
         rows_matched: Vec<RowLocation>,
+        /// The location of the first [`Row`] of every match which wraps over the boundary between
+        /// two [`Row`]s.  This is always empty if wraps aren't being counted.
+        wraps_matched: Vec<RowLocation>,
     },
     Group {
         sub_groups: Vec<Rc<MusicGroup>>,
         count: usize,
+        wrap_count: usize,
     },
 }
 
@@ -258,10 +851,141 @@ impl MusicGroupInner {
     /// Returns the number of times that this [`MusicGroup`] was matched in the composition
     pub fn count(&self) -> usize {
         match self {
-            MusicGroupInner::Leaf { rows_matched } => rows_matched.len(),
+            MusicGroupInner::Leaf { rows_matched, .. } => rows_matched.len(),
             MusicGroupInner::Group { count, .. } => *count,
         }
     }
+
+    /// Returns the number of wrapped matches of this [`MusicGroup`] in the composition.  These
+    /// aren't included in [`MusicGroupInner::count`].
+    pub fn wrap_count(&self) -> usize {
+        match self {
+            MusicGroupInner::Leaf { wraps_matched, .. } => wraps_matched.len(),
+            MusicGroupInner::Group { wrap_count, .. } => *wrap_count,
+        }
+    }
+}
+
+///////////////
+// FALSENESS //
+///////////////
+
+/// The falseness of a composition, i.e. every [`Row`] which is rung more than once
+#[derive(Debug, Clone, Default)]
+pub struct Falseness {
+    pub groups: Vec<FalseGroup>,
+    /// Reverse index of `groups`: for each false [`Row`], the indices of the groups containing it
+    /// (in any part)
+    pub(super) groups_by_row: HashMap<RowSource, Vec<usize>>,
+}
+
+impl Falseness {
+    /// Returns `true` if no [`Row`]s are repeated
+    pub fn is_true(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// The total number of false [`Row`]s, over all parts of the composition
+    pub fn num_false_rows(&self) -> usize {
+        self.groups.iter().map(|g| g.rows.len()).sum()
+    }
+
+    /// Returns the indices (into `self.groups`) of every [`FalseGroup`] containing the [`Row`]s
+    /// at a given [`RowSource`] (in any part)
+    pub fn groups_for_row(&self, source: RowSource) -> &[usize] {
+        self.groups_by_row.get(&source).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// A complete plain lead of a [`Method`] in the composition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlainLead {
+    pub method: MethodIdx,
+    /// The location of the first [`Row`] of the lead
+    pub head: RowLocation,
+    /// The number of [`Row`]s in the lead
+    pub len: usize,
+}
+
+impl PlainLead {
+    /// Adds the [`RowSource`] of every [`Row`] in this lead to `out`
+    pub fn add_row_sources(&self, out: &mut impl Extend<RowSource>) {
+        let first_row = self.head.row_index.index();
+        out.extend(
+            (first_row..first_row + self.len).map(|row_index| RowSource {
+                frag_index: self.head.frag_index,
+                row_index: RowIdx::new(row_index),
+            }),
+        )
+    }
+}
+
+/// A set of locations which all contain the same [`Row`]
+#[derive(Debug, Clone)]
+pub struct FalseGroup {
+    /// The locations of the [`Row`]s in this group, in increasing order.  This always contains at
+    /// least two locations.
+    pub rows: Vec<RowLocation>,
+}
+
+impl FalseGroup {
+    /// Add the [`RowSource`] of every [`Row`] in this group.  [`RowSource`]s may be added multiple
+    /// times.
+    pub fn add_row_sources(&self, out: &mut impl Extend<RowSource>) {
+        out.extend(self.rows.iter().map(|loc| loc.as_source()))
+    }
+}
+
+///////////
+// LINKS //
+///////////
+
+/// A link saying that [`Fragment`] `to` can be joined onto the end of [`Fragment`] `from`,
+/// because the leftover [`Row`] of `from` is equivalent (modulo the part heads) to the first
+/// [`Row`] of `to`.  Links from the same leftover [`Row`] share a `group`, so that interconnected
+/// links can be drawn in the same colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FragLink {
+    pub from: FragIdx,
+    pub to: FragIdx,
+    pub group: usize,
+}
+
+/// How the leftover [`Row`] of a [`Fragment`] joins onto the rest of the composition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FragEnd {
+    /// The leftover [`Row`] is rounds (in some part), so the composition comes round at the end
+    /// of this [`Fragment`]
+    ComesRound,
+    /// The leftover [`Row`] is the first [`Row`] of another [`Fragment`] (see [`FragLink`])
+    Linked,
+    /// Nothing follows the leftover [`Row`], so the composition needs to be continued from it
+    Open,
+}
+
+/// Which [`FragLink`] groups the top and bottom of a [`Fragment`] belong to.  This determines
+/// which colours are drawn at either end of the [`Fragment`], so that round blocks can be
+/// spotted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FragLinkGroups {
+    pub top: Option<usize>,
+    pub bottom: Option<usize>,
+}
+
+////////////////
+// LEAD HEADS //
+////////////////
+
+/// A lead head reached in the composition, like those listed on a printed composition
+#[derive(Debug, Clone)]
+pub struct LeadHead {
+    pub row: RowBuf,
+    pub source: RowSource,
+    /// The [`Method`] of the lead which starts at this lead head, or `None` if this is the
+    /// leftover row of a [`Fragment`]
+    pub method: Option<MethodIdx>,
+    /// `true` if the observation bell is at home in this lead head
+    pub is_course_end: bool,
 }
 
 /////////////////////
@@ -272,12 +996,272 @@ impl MusicGroupInner {
 pub struct Stats {
     /// The number of [`Row`]s in each part of the composition
     pub part_len: usize,
+    /// The number of proved [`Row`]s (i.e. changes) of each [`Method`], in each part
+    pub changes_per_method: MethodVec<usize>,
+    /// The number of proved lead heads of each [`Method`], in each part.  Leads which don't
+    /// include their lead head (e.g. snap starts) aren't counted.
+    pub leads_per_method: MethodVec<usize>,
+    /// The number of proved uses of each [`Call`], in each part
+    pub uses_per_call: CallVec<usize>,
+    /// The number of changes of method (COM) in each part.  Fragments aren't connected, so only
+    /// changes of method within a single [`Fragment`] are counted.
+    pub num_coms: usize,
+    /// The longest run of consecutive proved [`Row`]s of a single [`Method`], along with the
+    /// length of that run.  This is `None` if no rows are proved.
+    pub longest_run: Option<(MethodIdx, usize)>,
+    /// For each [`Method`], the fraction of its work which is rung (in any part).  The work of a
+    /// [`Method`] is every working bell ringing every working place bell, where bells which are
+    /// fixed by the lead head (e.g. the treble in treble-dominated methods) aren't working.
+    pub atw_per_method: MethodVec<f32>,
+    /// The work done by each [`Bell`] (summed over every part), indexed by [`Bell::index`]
+    pub work_per_bell: Vec<BellWork>,
+    /// The number of changes (in every part) rung before the composition comes round, following
+    /// the [`FragLink`]s from the first proved [`Fragment`] which starts at rounds.  This is
+    /// `None` if those links never reach a [`Fragment`] which [comes
+    /// round](FragEnd::ComesRound).
+    pub comes_round_after: Option<usize>,
+}
+
+impl Stats {
+    /// Determines how well the methods of a spliced composition are balanced, or returns `None`
+    /// if fewer than two [`Method`]s are rung.  [`Method`]s which aren't rung at all are ignored.
+    pub fn spliced_balance(&self) -> Option<SplicedBalance> {
+        let methods_rung = self
+            .changes_per_method
+            .iter_enumerated()
+            .filter(|(_, num_changes)| **num_changes > 0)
+            .map(|(idx, _)| idx)
+            .collect_vec();
+        if methods_rung.len() < 2 {
+            return None;
+        }
+        let leads = methods_rung.iter().map(|idx| self.leads_per_method[*idx]);
+        // Unwraps are safe because at least two methods are rung
+        let min_leads = leads.clone().min().unwrap();
+        let max_leads = leads.max().unwrap();
+        let all_the_work = methods_rung
+            .iter()
+            .all(|idx| self.atw_per_method[*idx] >= 1.0);
+        Some(SplicedBalance {
+            num_methods: methods_rung.len(),
+            min_leads,
+            max_leads,
+            all_the_work,
+        })
+    }
 }
 
 impl Default for Stats {
     fn default() -> Self {
         Self {
             part_len: Default::default(),
+            changes_per_method: MethodVec::new(),
+            leads_per_method: MethodVec::new(),
+            uses_per_call: CallVec::new(),
+            num_coms: 0,
+            longest_run: None,
+            atw_per_method: MethodVec::new(),
+            work_per_bell: Vec::new(),
+            comes_round_after: None,
+        }
+    }
+}
+
+/// The work done by a single [`Bell`] in the proved [`Row`]s of a composition, measured by how it
+/// moves between consecutive [`Row`]s.  A bell which stays in one place for consecutive blows is
+/// 'making a place', or is 'resting' if it stays there for [`LONG_REST_LENGTH`] blows or more.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BellWork {
+    /// The number of changes rung by this bell
+    pub num_changes: usize,
+    /// The number of changes where this bell keeps moving in the same direction
+    pub hunting: usize,
+    /// The number of times this bell moves one way and then straight back
+    pub dodges: usize,
+    /// The number of times this bell stays in one place for fewer than [`LONG_REST_LENGTH`] blows
+    pub places: usize,
+    /// The number of times this bell stays in one place for at least [`LONG_REST_LENGTH`] blows
+    /// (e.g. lying behind)
+    pub long_rests: usize,
+}
+
+/// The number of consecutive blows which a [`Bell`] has to ring in the same place to count as a
+/// long rest, rather than a place
+pub const LONG_REST_LENGTH: usize = 4;
+
+impl BellWork {
+    /// Adds the work done by a bell which rings in each of `places` (one per consecutive
+    /// [`Row`]).  Moves between two sequences of places aren't counted.
+    pub(crate) fn add_path(&mut self, places: &[usize]) {
+        // The direction of each change (-1 for down, 0 for a place, 1 for up)
+        let moves = places
+            .iter()
+            .tuple_windows()
+            .map(|(&a, &b)| b as isize - a as isize)
+            .collect_vec();
+        self.num_changes += moves.len();
+        for (&prev, &next) in moves.iter().tuple_windows() {
+            if next != 0 && prev == next {
+                self.hunting += 1;
+            }
+            if next != 0 && prev == -next {
+                self.dodges += 1;
+            }
+        }
+        // Group the places into runs of consecutive blows in the same place
+        for (is_place, run) in &moves.iter().group_by(|&&m| m == 0) {
+            if !is_place {
+                continue;
+            }
+            let num_blows = run.count() + 1;
+            if num_blows >= LONG_REST_LENGTH {
+                self.long_rests += 1;
+            } else {
+                self.places += 1;
+            }
+        }
+    }
+}
+
+/// How well the methods of a spliced composition are balanced, as returned by
+/// [`Stats::spliced_balance`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplicedBalance {
+    /// The number of [`Method`]s which are rung
+    pub num_methods: usize,
+    /// The fewest lead heads of any [`Method`] which is rung, in each part
+    pub min_leads: usize,
+    /// The most lead heads of any [`Method`] which is rung, in each part
+    pub max_leads: usize,
+    /// `true` if every [`Method`] which is rung rings all the work
+    pub all_the_work: bool,
+}
+
+impl SplicedBalance {
+    /// Returns `true` if the composition meets the usual criteria for balanced spliced: every
+    /// [`Method`] is rung for the same number of leads (give or take one) and rings all the work
+    pub fn is_balanced(&self) -> bool {
+        self.max_leads - self.min_leads <= 1 && self.all_the_work
+    }
+}
+
+impl std::fmt::Display for SplicedBalance {
+    /// Summarises the balance, e.g. `Balanced spliced (4 methods, 8 leads each, all the work)`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let balanced = if self.is_balanced() {
+            "Balanced"
+        } else {
+            "Not balanced"
+        };
+        write!(f, "{} spliced ({} methods, ", balanced, self.num_methods)?;
+        if self.min_leads == self.max_leads {
+            write!(f, "{} leads each, ", self.min_leads)?;
+        } else {
+            write!(f, "{}-{} leads each, ", self.min_leads, self.max_leads)?;
+        }
+        let work = if self.all_the_work {
+            "all the work"
+        } else {
+            "not all the work"
+        };
+        write!(f, "{})", work)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bellframe::Stage;
+    use jigsaw_utils::types::Position;
+
+    use super::*;
+
+    /// The locations of every false [`Row`], in a canonical order
+    fn false_rows(state: &FullState) -> Vec<Vec<RowLocation>> {
+        state
+            .falseness
+            .groups
+            .iter()
+            .map(|g| g.rows.clone())
+            .collect()
+    }
+
+    #[test]
+    fn incremental_updates_survive_undo_and_redo() {
+        let mut spec = CompSpec::empty(Stage::MINOR);
+        spec.add_method(
+            "Cambridge".to_owned(),
+            "C".to_owned(),
+            "-36-14-12-36-14-56,12".to_owned(),
+            vec![0],
+        )
+        .unwrap();
+        for _ in 0..3 {
+            spec.add_fragment(MethodIdx::new(0), false, Position::new(0.0, 0.0))
+                .unwrap();
+        }
+        let spec0 = spec.clone();
+        spec.extend_fragment(FragIdx::new(1), MethodIdx::new(0), false)
+            .unwrap();
+        let spec1 = spec.clone();
+        spec.extend_fragment(FragIdx::new(2), MethodIdx::new(0), true)
+            .unwrap();
+        let spec2 = spec;
+
+        // Edit, then undo and redo several times before making a different edit
+        let mut state = FullState::new(&spec0);
+        for spec in [
+            &spec1, &spec0, &spec1, &spec0, &spec1, &spec2, &spec1, &spec0,
+        ] {
+            state.update(spec);
+            let fresh = FullState::new(spec);
+            assert_eq!(false_rows(&state), false_rows(&fresh));
+            assert_eq!(state.fragments.len(), fresh.fragments.len());
+            for (frag, fresh_frag) in state.fragments.iter().zip_eq(&fresh.fragments) {
+                assert_eq!(frag.num_rows(), fresh_frag.num_rows());
+            }
+        }
+    }
+
+    #[test]
+    fn rows_are_generated_for_every_part() {
+        let mut spec = CompSpec::empty(Stage::MINOR);
+        spec.add_method(
+            "Cambridge".to_owned(),
+            "C".to_owned(),
+            "-36-14-12-36-14-56,12".to_owned(),
+            vec![0],
+        )
+        .unwrap();
+        spec.add_fragment(MethodIdx::new(0), true, Position::new(0.0, 0.0))
+            .unwrap();
+        // Using a lead head of the course as the part head makes every part false against the
+        // others
+        let lead_head = FullState::new(&spec).fragments[FragIdx::new(0)]
+            .row_in_part(PartIdx::new(0), RowIdx::new(24))
+            .to_string();
+        spec.set_part_heads(PartHeads::parse(&lead_head, Stage::MINOR).unwrap());
+        let state = FullState::new(&spec);
+        assert_eq!(state.part_heads.len(), 5);
+
+        // Every part should be the first part, transposed by its part head
+        let frag = &state.fragments[FragIdx::new(0)];
+        let mut num_locations_by_row = HashMap::<RowBuf, usize>::new();
+        for (part_idx, part_head) in state.part_heads.rows().iter().enumerate() {
+            let part = PartIdx::new(part_idx);
+            for (row_idx, data) in frag.rows_in_part(part) {
+                let first_part_row = frag.row_in_part(PartIdx::new(0), row_idx);
+                assert_eq!(data.row.to_owned(), part_head.as_row() * first_part_row);
+                if data.is_proved {
+                    *num_locations_by_row.entry(data.row.to_owned()).or_default() += 1;
+                }
+            }
         }
+        // The lazily generated rows should be proved the same as the rows read back out
+        let num_false_rows = num_locations_by_row
+            .values()
+            .filter(|&&n| n > 1)
+            .sum::<usize>();
+        assert!(num_false_rows > 0);
+        assert_eq!(state.falseness.num_false_rows(), num_false_rows);
     }
 }
@@ -0,0 +1,257 @@
+//! Checks for mistakes in a composition which don't stop it from being proved, but which the user
+//! should probably be warned about
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    rc::Rc,
+};
+
+use itertools::Itertools;
+use jigsaw_utils::{
+    indexed_vec::{FragIdx, PartIdx, RowIdx},
+    types::RowLocation,
+};
+
+use crate::{
+    expanded_frag::RowData,
+    spec::{self, LengthStatus},
+};
+
+use super::FullState;
+
+/// A (non-blocking) problem with a composition, which is shown to the user as a warning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// No proved [`Fragment`](super::Fragment) starts from rounds, so the composition has no start
+    NoStart,
+    /// Several proved [`Fragment`](super::Fragment)s start from rounds, so it isn't clear which
+    /// of them starts the composition
+    MultipleStarts(Vec<FragIdx>),
+    /// Some [`Row`](bellframe::Row)s are rung more than once.  `group` is the index of the
+    /// [`FalseGroup`](super::FalseGroup) containing them.
+    FalseRows {
+        group: usize,
+        rows: Vec<RowLocation>,
+    },
+    /// The part heads don't form a group, so the parts don't necessarily join up into a round
+    /// block
+    PartHeadsNotGroup,
+    /// A [`Method`](super::Method) has no shorthand, so it can't be typed when composing by
+    /// keyboard
+    MissingShorthand { method: String },
+    /// Several [`Method`](super::Method)s share the same shorthand, so typing it always adds the
+    /// first of them
+    DuplicateShorthand {
+        shorthand: String,
+        methods: Vec<String>,
+    },
+    /// The composition's length isn't within its target.  This is never
+    /// [`LengthStatus::InRange`].
+    Length(LengthStatus),
+    /// A [`Fragment`](super::Fragment) changes [`Method`](super::Method) somewhere other than
+    /// the lead end or half lead.  This is fine when composing link methods, so the GUI can be
+    /// told to ignore it.  `row` is the first [`Row`](bellframe::Row) of the new method.
+    MidLeadSplice {
+        row: RowLocation,
+        from: String,
+        to: String,
+    },
+}
+
+impl Problem {
+    /// The locations of the [`Row`](bellframe::Row)s which cause this `Problem`, if any.  The
+    /// first of these is the most useful place to go to when fixing it.
+    pub fn rows(&self) -> Vec<RowLocation> {
+        match self {
+            Problem::MultipleStarts(frags) => frags
+                .iter()
+                .map(|&frag_index| RowLocation {
+                    frag_index,
+                    row_index: RowIdx::new(0),
+                    part_index: PartIdx::new(0),
+                })
+                .collect_vec(),
+            Problem::FalseRows { rows, .. } => rows.clone(),
+            Problem::MidLeadSplice { row, .. } => vec![*row],
+            Problem::NoStart
+            | Problem::PartHeadsNotGroup
+            | Problem::MissingShorthand { .. }
+            | Problem::DuplicateShorthand { .. }
+            | Problem::Length(_) => Vec::new(),
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::NoStart => write!(f, "No proved fragment starts from rounds"),
+            Problem::MultipleStarts(frags) => {
+                write!(f, "{} proved fragments start from rounds", frags.len())
+            }
+            Problem::FalseRows { group, rows } => {
+                write!(f, "False group #{}: {} rows", group, rows.len())
+            }
+            Problem::PartHeadsNotGroup => write!(f, "The part heads don't form a group"),
+            Problem::MissingShorthand { method } => write!(f, "{} has no shorthand", method),
+            Problem::DuplicateShorthand { shorthand, methods } => write!(
+                f,
+                "'{}' is the shorthand of {}",
+                shorthand,
+                methods.join(" and ")
+            ),
+            Problem::Length(LengthStatus::Short(rows)) => {
+                write!(f, "{} rows short of the target length", rows)
+            }
+            Problem::Length(LengthStatus::Long(rows)) => {
+                write!(f, "Exceeds the target length by {} rows", rows)
+            }
+            Problem::Length(LengthStatus::Unreachable) => {
+                write!(
+                    f,
+                    "No multiple of the number of parts is within the target length"
+                )
+            }
+            Problem::Length(LengthStatus::InRange) => write!(f, "Within the target length"),
+            Problem::MidLeadSplice { from, to, .. } => {
+                write!(f, "{} is spliced into {} mid-lead", from, to)
+            }
+        }
+    }
+}
+
+/// Finds every [`Problem`] with a composition
+pub(super) fn find_problems(state: &FullState) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    // Most compositions should have exactly one start.  Fragments which start at another part
+    // head also count, since they start from rounds in one of the parts.
+    let starts = state
+        .source
+        .expansion()
+        .expanded_frags
+        .iter_enumerated()
+        .filter(|(_, f)| f.is_proved)
+        // Unwrap is safe because every fragment contains at least its leftover row
+        .filter(|(_, f)| {
+            (0..f.num_parts()).any(|part| {
+                f.row_in_part(PartIdx::new(part), RowIdx::new(0))
+                    .is_rounds()
+            })
+        })
+        .map(|(f_idx, _)| f_idx)
+        .collect::<Vec<_>>();
+    match starts.len() {
+        0 => problems.push(Problem::NoStart),
+        1 => {}
+        _ => problems.push(Problem::MultipleStarts(starts)),
+    }
+
+    // Falseness
+    for (group, false_group) in state.falseness.groups.iter().enumerate() {
+        problems.push(Problem::FalseRows {
+            group,
+            rows: false_group.rows.clone(),
+        });
+    }
+
+    // Part heads
+    if !state.part_heads.is_group() {
+        problems.push(Problem::PartHeadsNotGroup);
+    }
+
+    // Method shorthands
+    let mut methods_by_shorthand = HashMap::<String, Vec<String>>::new();
+    for method in &state.methods {
+        let shorthand = method.shorthand();
+        if shorthand.is_empty() {
+            problems.push(Problem::MissingShorthand {
+                method: method.name(),
+            });
+        } else {
+            let methods = methods_by_shorthand.entry(shorthand).or_default();
+            methods.push(method.name());
+        }
+    }
+    let duplicate_shorthands = methods_by_shorthand
+        .into_iter()
+        .filter(|(_, methods)| methods.len() > 1)
+        .sorted();
+    for (shorthand, methods) in duplicate_shorthands {
+        problems.push(Problem::DuplicateShorthand { shorthand, methods });
+    }
+
+    // Splices
+    for (frag_index, frag) in state.source.expansion().expanded_frags.iter_enumerated() {
+        if !frag.is_proved {
+            continue;
+        }
+        for (row_index, (before, after)) in frag.row_data.iter().tuple_windows().enumerate() {
+            if let Some((from, to)) = mid_lead_splice(before, after) {
+                problems.push(Problem::MidLeadSplice {
+                    row: RowLocation {
+                        frag_index,
+                        row_index: RowIdx::new(row_index + 1),
+                        part_index: PartIdx::new(0),
+                    },
+                    from: from.name().to_owned(),
+                    to: to.name().to_owned(),
+                });
+            }
+        }
+    }
+
+    // Length
+    if let Some(target) = state.source.length_target {
+        let num_parts = state.part_heads.len();
+        let status = target.status(state.stats.part_len * num_parts, num_parts);
+        if status != LengthStatus::InRange {
+            problems.push(Problem::Length(status));
+        }
+    }
+
+    problems
+}
+
+/// If two adjacent rows are from different [`Method`](spec::Method)s which aren't joined at the
+/// same lead end or half lead, returns the methods of the `(before, after)` rows
+fn mid_lead_splice<'d>(
+    before: &'d RowData,
+    after: &'d RowData,
+) -> Option<(&'d spec::Method, &'d spec::Method)> {
+    let (from, from_sub_lead_idx) = before.method_source.as_ref()?;
+    let (to, to_sub_lead_idx) = after.method_source.as_ref()?;
+    if Rc::ptr_eq(from, to) {
+        return None; // Not a splice
+    }
+    // `from` ends at the location before `*from_sub_lead_idx + 1`, whereas `to` starts at
+    // `*to_sub_lead_idx`
+    let end_location = SpliceLocation::new(from, (*from_sub_lead_idx + 1) % from.lead_len());
+    let start_location = SpliceLocation::new(to, *to_sub_lead_idx);
+    match (end_location, start_location) {
+        (Some(end), Some(start)) if end == start => None,
+        _ => Some((from, to)),
+    }
+}
+
+/// The places in a lead where one method can be spliced into another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpliceLocation {
+    LeadEnd,
+    HalfLead,
+}
+
+impl SpliceLocation {
+    /// The `SpliceLocation` directly above the row at `sub_lead_idx` in a lead of `method`, if
+    /// there is one
+    fn new(method: &spec::Method, sub_lead_idx: usize) -> Option<Self> {
+        if sub_lead_idx == 0 {
+            Some(SpliceLocation::LeadEnd)
+        } else if sub_lead_idx * 2 == method.lead_len() {
+            Some(SpliceLocation::HalfLead)
+        } else {
+            None
+        }
+    }
+}
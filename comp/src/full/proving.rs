@@ -0,0 +1,176 @@
+//! Code to prove a composition, i.e. to find every [`Row`] which is rung more than once.
+
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use bellframe::{Row, SameStageVec};
+use itertools::Itertools;
+use jigsaw_utils::{
+    indexed_vec::{FragIdx, FragSlice, FragVec, PartIdx, PartVec, RowIdx, RowVec},
+    types::{RowLocation, RowSource},
+};
+
+use crate::{
+    expanded_frag::ExpandedFrag,
+    full, par,
+    spec::proof::{ProofKey, ProofSettings},
+};
+
+use super::buffers::Buffers;
+
+/// For each [`Fragment`](full::Fragment), for each part, which false group (if any) contains each
+/// [`Row`]
+pub(super) type FalseGroupsPerFrag = FragVec<PartVec<RowVec<Option<usize>>>>;
+
+/// Persistent proving state of a composition.  This stores the location of every proved [`Row`],
+/// so that [`Fragment`](full::Fragment)s can be added or removed without re-proving the rest of
+/// the composition.  [`Row`]s are compared by their [`ProofKey`]s, so that the
+/// [`ProofSettings`] can relax which [`Row`]s count as being the same.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Prover {
+    settings: Rc<ProofSettings>,
+    /// The locations of every proved [`Row`] in the composition
+    locations_by_row: HashMap<ProofKey, Vec<RowLocation>>,
+    /// The [`ProofKey`]s which have more than one location
+    false_rows: HashSet<ProofKey>,
+}
+
+impl Prover {
+    /// Creates a [`Prover`] containing the [`Row`]s of every [`ExpandedFrag`]
+    pub(super) fn new(
+        expanded_frags: &FragSlice<ExpandedFrag>,
+        settings: Rc<ProofSettings>,
+    ) -> Self {
+        let mut prover = Self {
+            settings,
+            ..Self::default()
+        };
+        for (frag_index, frag) in expanded_frags.iter_enumerated() {
+            prover.add_frag(frag_index, frag);
+        }
+        prover
+    }
+
+    /// Adds the proved [`Row`]s of an [`ExpandedFrag`], which will be placed at `frag_index`
+    pub(super) fn add_frag(&mut self, frag_index: FragIdx, frag: &ExpandedFrag) {
+        for (loc, key) in proof_keys(&self.settings, frag_index, frag) {
+            let locations = self.locations_by_row.entry(key.clone()).or_default();
+            locations.push(loc);
+            if locations.len() > 1 {
+                self.false_rows.insert(key);
+            }
+        }
+    }
+
+    /// Removes the proved [`Row`]s of an [`ExpandedFrag`] which was previously added at
+    /// `frag_index`
+    pub(super) fn remove_frag(&mut self, frag_index: FragIdx, frag: &ExpandedFrag) {
+        for (_loc, key) in proof_keys(&self.settings, frag_index, frag) {
+            // If the same row appears multiple times in `frag`, then the first removal will
+            // remove all its locations and later removals will find nothing to remove
+            if let Some(locations) = self.locations_by_row.get_mut(&key) {
+                locations.retain(|loc| loc.frag_index != frag_index);
+                if locations.len() <= 1 {
+                    self.false_rows.remove(&key);
+                }
+                if locations.is_empty() {
+                    self.locations_by_row.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Groups the false [`Row`]s, and returns the false group of every [`Row`] in every
+    /// [`Fragment`](full::Fragment).  This only inspects the false [`Row`]s, so the grouping
+    /// doesn't depend on the length of the composition.
+    pub(super) fn falseness(
+        &self,
+        expanded_frags: &FragSlice<ExpandedFrag>,
+        buffers: &mut Buffers,
+    ) -> (full::Falseness, FalseGroupsPerFrag) {
+        // Sort each group's locations, and then sort the groups by their first location.  This
+        // stops the group ordering from changing between frames (since `HashSet`'s iteration
+        // order is non-deterministic).
+        let loc_key = |loc: &RowLocation| (loc.frag_index, loc.part_index, loc.row_index);
+        let mut groups = self
+            .false_rows
+            .iter()
+            .map(|key| {
+                let mut rows = self.locations_by_row[key].clone();
+                rows.sort_by_key(loc_key);
+                full::FalseGroup { rows }
+            })
+            .collect::<Vec<_>>();
+        groups.sort_by_key(|group| loc_key(&group.rows[0]));
+
+        // Write the group index of every false row into a lookup table, so that the rows can be
+        // annotated without searching through the groups
+        let mut false_groups_per_frag: FalseGroupsPerFrag = expanded_frags
+            .iter()
+            .map(|frag| {
+                (0..frag.num_parts())
+                    .map(|_| buffers.false_groups(frag.row_data.len()))
+                    .collect()
+            })
+            .collect();
+        let mut groups_by_row = HashMap::<RowSource, Vec<usize>>::new();
+        for (group_idx, group) in groups.iter().enumerate() {
+            for loc in &group.rows {
+                false_groups_per_frag[loc.frag_index][loc.part_index][loc.row_index] =
+                    Some(group_idx);
+                // A group can contain the same `RowSource` in several parts, but should only be
+                // listed once per row
+                let groups_for_row = groups_by_row.entry(loc.as_source()).or_default();
+                if groups_for_row.last() != Some(&group_idx) {
+                    groups_for_row.push(group_idx);
+                }
+            }
+        }
+
+        let falseness = full::Falseness {
+            groups,
+            groups_by_row,
+        };
+        (falseness, false_groups_per_frag)
+    }
+}
+
+/// Finds the location and [`ProofKey`] of every proved [`Row`] in an [`ExpandedFrag`].  Computing
+/// the keys is the slowest part of proving, so each part is handled in parallel (if possible).
+/// The [`Row`]s of each part are generated as their keys are computed, so only the keys are ever
+/// stored in bulk.
+fn proof_keys(
+    settings: &ProofSettings,
+    frag_index: FragIdx,
+    frag: &ExpandedFrag,
+) -> Vec<(RowLocation, ProofKey)> {
+    // Leftover rows and rows in muted fragments aren't part of the composition, so can't cause
+    // falseness
+    let is_proved = frag.row_data.iter().map(|d| d.is_proved).collect_vec();
+    // `ExpandedFrag`s can't be shared between threads, but their rows and part heads can
+    let rows_in_one_part: &SameStageVec = &frag.rows_in_one_part;
+    let part_heads = frag.part_heads.rows();
+    let parts = (0..frag.num_parts()).map(PartIdx::new).collect_vec();
+    let keys_per_part = par::map(parts, |part_index| {
+        let part_head: &Row = &part_heads[part_index.index()];
+        rows_in_one_part
+            .iter()
+            .zip_eq(&is_proved)
+            .enumerate()
+            .filter(|(_, (_, is_proved))| **is_proved)
+            .filter_map(|(row_index, (row, _))| {
+                // Rows which are allowed to repeat can never be false
+                let key = settings.proof_key(&(part_head * row))?;
+                let loc = RowLocation {
+                    frag_index,
+                    row_index: RowIdx::new(row_index),
+                    part_index,
+                };
+                Some((loc, key))
+            })
+            .collect_vec()
+    });
+    keys_per_part.into_iter().flatten().collect()
+}
@@ -3,78 +3,199 @@
 use std::{collections::VecDeque, iter};
 
 use jigsaw_utils::indexed_vec::FragIdx;
+use serde::{Deserialize, Serialize};
 
 use crate::spec::{self, EditError};
 
 use super::spec::CompSpec;
 
+/// Settings which determine how much undo history is kept, and how edits are grouped into undo
+/// steps.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// The maximum number of undo steps which are kept.  Once the history grows past this, the
+    /// oldest steps are dropped.
+    pub max_steps: usize,
+    /// The maximum (estimated) number of bytes used by the undo history.  The oldest steps are
+    /// dropped if the history grows past this, though the current step is always kept.
+    pub max_memory: usize,
+    /// Grouped edits of the same kind which are made less than this many seconds apart are
+    /// coalesced into a single undo step.  See [`History::apply_grouped_edit`].
+    pub group_timeout: f64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_steps: 1000,
+            max_memory: 64 * 1024 * 1024, // 64MB
+            group_timeout: 1.0,           // seconds
+        }
+    }
+}
+
 /// An undo history of the composition being edited by Jigsaw.
 #[derive(Debug, Clone)]
 pub struct History {
-    /// The sequence of [`CompSpec`]s representing the most recent undo history.  This is ordered
+    /// The sequence of [`Step`]s representing the most recent undo history.  This is ordered
     /// chronologically with the most recent edit at the end.
-    history: VecDeque<CompSpec>,
+    history: VecDeque<Step>,
     /// The index within `history` of the [`CompSpec`] being currently displayed.  Redo and undo
     /// corresponds to incrementing/decrementing this pointer, respectively.
     current_undo_index: usize,
+    /// The sum of the estimated sizes of every [`CompSpec`] in `history`
+    total_size: usize,
+    /// The kind and time (in seconds) of the last grouped edit, if the most recent undo step was
+    /// made by a grouped edit which could still be extended
+    last_group: Option<(&'static str, f64)>,
+    config: HistoryConfig,
+}
+
+/// A single step in the undo history
+#[derive(Debug, Clone)]
+struct Step {
+    spec: CompSpec,
+    /// The estimated size of `spec`, in bytes
+    size: usize,
+    /// A human-readable description of the edit which created this step
+    description: String,
+}
+
+impl Step {
+    fn new(spec: CompSpec, description: String) -> Self {
+        Self {
+            size: spec.estimated_size(),
+            spec,
+            description,
+        }
+    }
 }
 
 impl History {
-    /// Creates a new [`History`] containing only one [`CompSpec`]
+    /// Creates a new [`History`] containing only one [`CompSpec`], using the default
+    /// [`HistoryConfig`]
     pub fn new(spec: CompSpec) -> Self {
+        Self::with_config(spec, HistoryConfig::default())
+    }
+
+    /// Creates a new [`History`] containing only one [`CompSpec`]
+    pub fn with_config(spec: CompSpec, config: HistoryConfig) -> Self {
+        let first_step = Step::new(spec, "Initial composition".to_owned());
+        let total_size = first_step.size;
         Self {
-            history: iter::once(spec).collect(),
+            history: iter::once(first_step).collect(),
             current_undo_index: 0,
+            total_size,
+            last_group: None,
+            config,
         }
     }
 
     /// Moves one step backwards in the undo history.  Returns `false` if we are already on the
     /// oldest undo step.
     pub fn undo(&mut self) -> bool {
-        if self.current_undo_index == 0 {
-            false
-        } else {
-            self.current_undo_index -= 1;
-            true
+        match self.current_undo_index.checked_sub(1) {
+            Some(new_index) => self.jump_to(new_index),
+            None => false,
         }
     }
 
     /// Moves one step forwards in the undo history.  Returns `false` if we are already on the
     /// most recent undo step.
     pub fn redo(&mut self) -> bool {
-        if self.current_undo_index == self.history.len() - 1 {
-            false
-        } else {
-            self.current_undo_index += 1;
-            true
+        self.jump_to(self.current_undo_index + 1)
+    }
+
+    /// Moves directly to the step at `index` in the undo history, where `0` is the oldest step
+    /// still stored.  Returns `false` if no such step exists.
+    pub fn jump_to(&mut self, index: usize) -> bool {
+        if index >= self.history.len() {
+            return false;
         }
+        self.current_undo_index = index;
+        // Moving through the history ends any group, since the grouped step is no longer the one
+        // being edited
+        self.last_group = None;
+        true
+    }
+
+    /// Returns the human-readable descriptions of every step in the undo history, oldest first
+    pub fn step_descriptions(&self) -> impl Iterator<Item = &str> + '_ {
+        self.history.iter().map(|step| step.description.as_str())
+    }
+
+    /// Returns the index of the step being currently displayed, in the same order as
+    /// [`History::step_descriptions`]
+    pub fn current_step(&self) -> usize {
+        self.current_undo_index
     }
 
     /// Apply a closure to modify current [`CompSpec`], thus creating a new step in the undo
-    /// history.  If `Err(_)` is returned, then the edit is 'aborted' and no new history step
-    /// is created.
+    /// history with the given `description`.  If `Err(_)` is returned, then the edit is 'aborted'
+    /// and no new history step is created.
     pub fn apply_edit<O, E>(
         &mut self,
+        description: String,
         edit: impl FnOnce(&mut CompSpec) -> Result<O, E>,
     ) -> Result<O, E> {
         // Apply the edit to a clone of the current spec
         let mut new_spec = self.comp_spec().clone();
         let edit_value = edit(&mut new_spec)?;
         // Add this new spec to the undo history
-        self.append_history(new_spec);
+        self.append_history(Step::new(new_spec, description));
         // Bubble the result
         Ok(edit_value)
     }
 
+    /// Apply a closure to modify current [`CompSpec`] as part of a group of edits of the same
+    /// `kind` (e.g. typing into a text box or dragging something across the screen).  If the
+    /// previous edit was of the same `kind` and made less than
+    /// [`group_timeout`](HistoryConfig::group_timeout) seconds before `time`, then the current
+    /// undo step is replaced rather than creating a new one, so that the whole group can be
+    /// undone in one go.  The step's `description` is always replaced by that of the latest edit.
+    /// If `Err(_)` is returned, then the edit is 'aborted' and the history is left unchanged.
+    pub fn apply_grouped_edit<O, E>(
+        &mut self,
+        kind: &'static str,
+        time: f64,
+        description: String,
+        edit: impl FnOnce(&mut CompSpec) -> Result<O, E>,
+    ) -> Result<O, E> {
+        // Apply the edit to a clone of the current spec
+        let mut new_spec = self.comp_spec().clone();
+        let edit_value = edit(&mut new_spec)?;
+        // Decide whether this edit extends the previous group, or starts a new undo step
+        let extends_group = match self.last_group {
+            Some((last_kind, last_time)) => {
+                last_kind == kind && time - last_time < self.config.group_timeout
+            }
+            None => false,
+        };
+        let new_step = Step::new(new_spec, description);
+        if extends_group {
+            self.replace_current(new_step);
+        } else {
+            self.append_history(new_step);
+        }
+        // Measure the timeout from the most recent edit, so that long continuous edits (like
+        // dragging) still form one group
+        self.last_group = Some((kind, time));
+        Ok(edit_value)
+    }
+
     /// Apply a closure to modify current [`CompSpec`], thus creating a new step in the undo
     /// history.  Unlike [`History::apply_edit`], this assumes that the edit will
     /// always succeed.
-    pub fn apply_infallible_edit<R>(&mut self, edit: impl FnOnce(&mut CompSpec) -> R) -> R {
+    pub fn apply_infallible_edit<R>(
+        &mut self,
+        description: String,
+        edit: impl FnOnce(&mut CompSpec) -> R,
+    ) -> R {
         // Apply the edit to a clone of the current spec
         let mut new_spec = self.comp_spec().to_owned();
         let result = edit(&mut new_spec);
         // Add this new spec to the undo history
-        self.append_history(new_spec);
+        self.append_history(Step::new(new_spec, description));
         result // bubble the result
     }
 
@@ -83,6 +204,7 @@ impl History {
     /// the edit is 'aborted' and no new history step is created.
     pub fn apply_frag_edit<O, E>(
         &mut self,
+        description: String,
         frag_idx: FragIdx,
         edit: impl FnOnce(&mut spec::Fragment) -> Result<O, E>,
     ) -> Result<O, E>
@@ -94,26 +216,55 @@ impl History {
         let frag = new_spec.get_fragment_mut(frag_idx)?;
         let edit_value = edit(frag)?;
         // Add this new spec to the undo history
-        self.append_history(new_spec);
+        self.append_history(Step::new(new_spec, description));
         // Bubble the result
         Ok(edit_value)
     }
 
-    /// Add a new [`CompSpec`] to the undo history, after the [`CompSpec`] currently being viewed.
-    fn append_history(&mut self, new_spec: CompSpec) {
+    /// Add a new [`Step`] to the undo history, after the [`Step`] currently being viewed.
+    fn append_history(&mut self, new_step: Step) {
         // Before making the edit, remove any undo history that happens **after** the current edit
         // (i.e. edits which could be redone).  This will be **replaced** by the new change
-        self.history.drain(self.current_undo_index + 1..);
+        for step in self.history.drain(self.current_undo_index + 1..) {
+            self.total_size -= step.size;
+        }
         // Add the new entry, and update the pointer to point to it
-        self.history.push_back(new_spec);
+        self.total_size += new_step.size;
+        self.history.push_back(new_step);
         self.current_undo_index += 1;
         // Sanity check that `self.current_undo_index` should point to the last snapshot in the
         // history.  This should be guaranteed because we `drain` everything else
         assert_eq!(self.current_undo_index, self.history.len() - 1);
-        // TODO: Possibly drop old history if the chain gets too long
+        // Any new edit ends the current group.  Grouped edits will restart it themselves.
+        self.last_group = None;
+        self.trim();
+    }
+
+    /// Replace the [`Step`] currently being viewed (which must be the most recent one) with
+    /// `new_step`, without creating a new undo step
+    fn replace_current(&mut self, new_step: Step) {
+        assert_eq!(self.current_undo_index, self.history.len() - 1);
+        let current = &mut self.history[self.current_undo_index];
+        self.total_size = self.total_size - current.size + new_step.size;
+        *current = new_step;
+        self.trim();
+    }
+
+    /// Drop the oldest undo steps until the history fits within the limits set by
+    /// `self.config`.  The [`CompSpec`] being viewed is never dropped.
+    fn trim(&mut self) {
+        while self.current_undo_index > 0
+            && (self.history.len() > self.config.max_steps
+                || self.total_size > self.config.max_memory)
+        {
+            // Unwrap is safe because `current_undo_index > 0`, so the history isn't empty
+            let step = self.history.pop_front().unwrap();
+            self.total_size -= step.size;
+            self.current_undo_index -= 1;
+        }
     }
 
     pub fn comp_spec(&self) -> &CompSpec {
-        &self.history[self.current_undo_index]
+        &self.history[self.current_undo_index].spec
     }
 }
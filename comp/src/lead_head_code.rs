@@ -0,0 +1,155 @@
+//! Standard lead head codes of methods (e.g. `"b"` for Cambridge Surprise Major), which group
+//! methods with the same lead heads and lead end places.
+
+use bellframe::{Bell, Row};
+use itertools::Itertools;
+
+/// Returns the standard lead head code of a method (e.g. `"b"` for Cambridge Surprise Major, or
+/// `"m"` for Bristol Surprise Major), given the last [`Row`] of its plain lead (`lead_end`) and its
+/// `lead_head`.  Codes `a`, `b`, ..., `f` are used for methods with the same lead heads as Plain
+/// Bob and 2nds place lead ends, and `g`, `h`, ..., `m` for the same lead heads with Nths place
+/// lead ends.  Stages above Major have more lead heads, which are given numbered codes (e.g. `c1`
+/// or `k2`).  Returns `None` if the lead head isn't a Plain Bob lead head, or if the stage is odd
+/// or smaller than Minor.
+pub(crate) fn lead_head_code(lead_end: &Row, lead_head: &Row) -> Option<String> {
+    let num_bells = lead_head.stage().num_bells();
+    if num_bells < 6 || num_bells % 2 == 1 {
+        return None;
+    }
+    // Only methods where the treble is a hunt bell have lead head codes
+    if lead_head.place_of(Bell::TREBLE) != Some(0) {
+        return None;
+    }
+    // Lead heads are either 2nds place or Nths place, depending on the lead end change
+    let makes_place =
+        |place: usize| lead_end.bell_iter().nth(place) == lead_head.bell_iter().nth(place);
+    let letters = if makes_place(1) {
+        ["a", "b", "c", "d", "e", "f"]
+    } else if makes_place(num_bells - 1) {
+        ["g", "h", "j", "k", "l", "m"]
+    } else {
+        return None;
+    };
+
+    // Find which power of the Plain Bob lead head is the same as `lead_head`
+    let lead_head = lead_head.bell_iter().map(Bell::index).collect_vec();
+    let plain_bob_lead_head = plain_bob_lead_head(num_bells);
+    let num_lead_heads = num_bells - 2; // Not including rounds
+    let mut power = (0..num_bells).collect_vec();
+    for lead_idx in 0..num_lead_heads {
+        power = plain_bob_lead_head.iter().map(|&i| power[i]).collect_vec();
+        if power != lead_head {
+            continue;
+        }
+        // The first two and last two lead heads always get the same letters.  The lead heads in
+        // between are split between the middle two letters, and are numbered above Major.
+        let num_middle = num_lead_heads - 4;
+        return Some(if lead_idx < 2 {
+            letters[lead_idx].to_owned()
+        } else if lead_idx >= num_lead_heads - 2 {
+            letters[lead_idx + 6 - num_lead_heads].to_owned()
+        } else {
+            let middle_idx = lead_idx - 2;
+            let half = num_middle / 2;
+            let (letter, number) = if middle_idx < half {
+                (letters[2], middle_idx + 1)
+            } else {
+                (letters[3], middle_idx - half + 1)
+            };
+            if half == 1 {
+                letter.to_owned()
+            } else {
+                format!("{}{}", letter, number)
+            }
+        });
+    }
+    None
+}
+
+/// The lead head of Plain Bob on an even number of bells (e.g. `13527486` for Major), as a list of
+/// bell indices
+fn plain_bob_lead_head(num_bells: usize) -> Vec<usize> {
+    let mut row = (0..num_bells).collect_vec();
+    for change_idx in 0..num_bells * 2 {
+        // Plain Bob alternates between `x` and `1N`, with a `12` at the lead end
+        let first_swap = if change_idx % 2 == 0 {
+            0
+        } else if change_idx == num_bells * 2 - 1 {
+            2
+        } else {
+            1
+        };
+        for place in (first_swap..num_bells - 1).step_by(2) {
+            row.swap(place, place + 1);
+        }
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use bellframe::Stage;
+
+    use super::*;
+    use crate::place_not;
+
+    /// The lead head code of the method with the given place notation
+    fn code(pn: &str, stage: Stage) -> Option<String> {
+        let method = place_not::parse_method(pn, stage).unwrap();
+        let lead_end = method.row_in_plain_lead(method.lead_len() - 1);
+        lead_head_code(lead_end, method.lead_head())
+    }
+
+    #[test]
+    fn plain_bob_lead_heads() {
+        assert_eq!(plain_bob_lead_head(6), vec![0, 2, 4, 1, 5, 3]);
+        assert_eq!(plain_bob_lead_head(8), vec![0, 2, 4, 1, 6, 3, 7, 5]);
+    }
+
+    #[test]
+    fn minor() {
+        assert_eq!(code("x16x16x16,12", Stage::MINOR).as_deref(), Some("a"));
+        assert_eq!(
+            code("x36x14x12x36x14x56,12", Stage::MINOR).as_deref(),
+            Some("b")
+        );
+    }
+
+    #[test]
+    fn major() {
+        assert_eq!(code("x18x18x18x18,12", Stage::MAJOR).as_deref(), Some("a"));
+        assert_eq!(
+            code("x38x14x1258x36x14x58x16x78,12", Stage::MAJOR).as_deref(),
+            Some("b")
+        );
+        assert_eq!(
+            code("x58x14.58x58.36.14x14.58x14x18,18", Stage::MAJOR).as_deref(),
+            Some("m")
+        );
+    }
+
+    #[test]
+    fn royal() {
+        assert_eq!(
+            code("x30x14x1250x36x1470x58x16x70x18x90,12", Stage::ROYAL).as_deref(),
+            Some("b")
+        );
+        assert_eq!(
+            code("x10x10x10x10x10,12", Stage::ROYAL).as_deref(),
+            Some("a")
+        );
+        // Cambridge with a 10ths place lead end
+        assert_eq!(
+            code("x30x14x1250x36x1470x58x16x70x18x90,10", Stage::ROYAL).as_deref(),
+            Some("g")
+        );
+    }
+
+    #[test]
+    fn no_code() {
+        // Odd stages don't have lead head codes
+        assert_eq!(code("3,1.7.1.7.1.7.1", Stage::TRIPLES), None);
+        // Nor do lead heads which aren't Plain Bob lead heads (plain hunt's lead head is rounds)
+        assert_eq!(code("x16x16x16,16", Stage::MINOR), None);
+    }
+}
@@ -1,10 +1,19 @@
 #![allow(rustdoc::private_intra_doc_links)] // We're not exporting a public API, so internal docs are OK
 
+pub mod analysis;
 mod expanded_frag;
 pub mod full;
 mod history;
+mod lead_head_code;
+pub mod method_lib;
 mod music;
+mod pace;
+mod par;
+pub mod place_not;
+pub mod report;
+pub mod search;
 pub mod spec;
 
-pub use history::History;
-pub use music::Music;
+pub use history::{History, HistoryConfig};
+pub use music::{check_regex, Music, RegexError};
+pub use pace::Pace;
@@ -0,0 +1,236 @@
+//! Libraries of methods, so that methods can be added by name rather than by typing out their
+//! place notation.  Libraries can be loaded from either the CCCBR's XML format or the plain text
+//! format used by MicroSIRIL.
+
+use bellframe::Stage;
+use itertools::Itertools;
+
+/// A library of [`LibMethod`]s, which can be searched by title
+#[derive(Debug, Clone, Default)]
+pub struct MethodLib {
+    methods: Vec<LibMethod>,
+}
+
+/// A single method stored in a [`MethodLib`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibMethod {
+    /// The name of the method, e.g. `"Cambridge"`
+    pub name: String,
+    /// The full title of the method, e.g. `"Cambridge Surprise Major"`.  Libraries which don't
+    /// store classes (e.g. MicroSIRIL libraries) will have the same name and title.
+    pub title: String,
+    pub stage: Stage,
+    /// The place notation of one lead of the method, in the form accepted by
+    /// [`PnBlock::parse`](bellframe::PnBlock::parse)
+    pub place_notation: String,
+}
+
+impl LibMethod {
+    /// The default shorthand for this method: the first character of its name
+    pub fn default_shorthand(&self) -> String {
+        self.name.chars().take(1).collect()
+    }
+}
+
+impl MethodLib {
+    /// Parses a [`MethodLib`] from the XML format used by the CCCBR's method collections.  Every
+    /// `<method>` must have a `<name>`, `<title>` and `<notation>`, and either it or its enclosing
+    /// `<methodSet>` must specify a `<stage>`.
+    pub fn from_cccbr_xml(xml: &str) -> Result<Self, LibError> {
+        let doc = roxmltree::Document::parse(xml).map_err(LibError::Xml)?;
+
+        let mut methods = Vec::new();
+        for method_node in doc.descendants().filter(|n| n.has_tag_name("method")) {
+            // All methods have a title, so we use it to make errors easier to find
+            let title = child_text(method_node, "title").ok_or(LibError::MissingField {
+                method: None,
+                field: "title",
+            })?;
+            let missing_field = |field: &'static str| LibError::MissingField {
+                method: Some(title.to_owned()),
+                field,
+            };
+            let name = child_text(method_node, "name").ok_or_else(|| missing_field("name"))?;
+            let place_notation =
+                child_text(method_node, "notation").ok_or_else(|| missing_field("notation"))?;
+            // The stage is usually shared between all the methods in a `<methodSet>`, but can also
+            // be overridden by individual methods
+            let stage_str = method_node
+                .ancestors()
+                .find_map(|n| {
+                    let properties = n.children().find(|c| c.has_tag_name("properties"))?;
+                    child_text(properties, "stage")
+                })
+                .ok_or_else(|| missing_field("stage"))?;
+            let stage = parse_stage(stage_str).ok_or_else(|| LibError::InvalidStage {
+                method: title.to_owned(),
+                stage: stage_str.to_owned(),
+            })?;
+
+            methods.push(LibMethod {
+                name: name.to_owned(),
+                title: title.to_owned(),
+                stage,
+                place_notation: place_notation.to_owned(),
+            });
+        }
+        Ok(Self { methods })
+    }
+
+    /// Parses a [`MethodLib`] from a MicroSIRIL library file.  Each non-empty line contains a
+    /// method name followed by its place notation (e.g.
+    /// `Cambridge &-38-14-1258-36-14-58-16-78,+12`), and lines starting with `/` are comments.  MicroSIRIL libraries don't specify stages, so
+    /// every method is given the same `stage`.
+    pub fn from_microsiril(text: &str, stage: Stage) -> Result<Self, LibError> {
+        let mut methods = Vec::new();
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('/') {
+                continue;
+            }
+            // The place notation is the last word on the line, and the name is everything before
+            // it
+            let (name, place_notation) = line
+                .rsplit_once(char::is_whitespace)
+                .ok_or(LibError::InvalidLine { line: line_idx + 1 })?;
+            let name = name.trim();
+            methods.push(LibMethod {
+                name: name.to_owned(),
+                title: name.to_owned(),
+                stage,
+                place_notation: convert_microsiril_pn(place_notation),
+            });
+        }
+        Ok(Self { methods })
+    }
+
+    /// The number of methods in this library
+    pub fn len(&self) -> usize {
+        self.methods.len()
+    }
+
+    /// Returns `true` if this library contains no methods
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+
+    /// Returns the method of a given [`Stage`] with a given `title`, if it's in this library.
+    /// Titles are compared case-insensitively.
+    pub fn get(&self, title: &str, stage: Stage) -> Option<&LibMethod> {
+        self.methods
+            .iter()
+            .find(|m| m.stage == stage && m.title.eq_ignore_ascii_case(title))
+    }
+
+    /// Returns the methods of a given [`Stage`] whose titles match a search `query`.  Each word of
+    /// the `query` must match the words of the title in order, where a query word can either be
+    /// the start of a title word (e.g. `"Camb"` matches `"Cambridge"`) or the initials of
+    /// consecutive title words (e.g. `"SM"` matches `"Surprise Major"`).  All comparisons are
+    /// case-insensitive, so `"cambridge sm"` will match `"Cambridge Surprise Major"`.
+    pub fn search<'lib>(
+        &'lib self,
+        query: &str,
+        stage: Stage,
+    ) -> impl Iterator<Item = &'lib LibMethod> {
+        let query_words = lowercase_words(query);
+        self.methods.iter().filter(move |m| {
+            m.stage == stage && words_match(&lowercase_words(&m.title), &query_words)
+        })
+    }
+}
+
+/// Returns the text contained in the first child of `node` with a given tag name
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, tag_name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|c| c.has_tag_name(tag_name))
+        .and_then(|c| c.text())
+        .map(str::trim)
+}
+
+/// Parses a [`Stage`] from its number of bells (e.g. `"8"` for [`Stage::MAJOR`])
+fn parse_stage(s: &str) -> Option<Stage> {
+    match s.trim().parse::<usize>() {
+        Ok(0) | Err(_) => None,
+        Ok(num_bells) => Some(Stage::new(num_bells)),
+    }
+}
+
+/// Converts MicroSIRIL's place notation syntax into the syntax accepted by
+/// [`PnBlock::parse`](bellframe::PnBlock::parse).  MicroSIRIL prefixes symmetric blocks with `&`
+/// and asymmetric blocks with `+`, so e.g. `&-38-14-1258-36-14-58-16-78,+12` becomes
+/// `-38-14-1258-36-14-58-16-78,12`.
+fn convert_microsiril_pn(pn: &str) -> String {
+    pn.split(',')
+        .map(|block| block.trim_start_matches(&['&', '+'][..]))
+        .join(",")
+}
+
+fn lowercase_words(s: &str) -> Vec<String> {
+    s.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// Returns `true` if every word in `query_words` matches some part of `title_words`, in order.  See
+/// [`MethodLib::search`] for what it means for words to match.
+fn words_match(title_words: &[String], query_words: &[String]) -> bool {
+    let (query_word, rest_of_query) = match query_words.split_first() {
+        Some(v) => v,
+        None => return true, // An empty query matches anything
+    };
+    let num_initials = query_word.chars().count();
+    // Try to match `query_word` starting at every title word
+    (0..title_words.len()).any(|start_idx| {
+        let title_words = &title_words[start_idx..];
+        // `query_word` is the start of this title word
+        let is_word_match = title_words[0].starts_with(query_word.as_str())
+            && words_match(&title_words[1..], rest_of_query);
+        // `query_word` is the initials of several title words
+        let is_initials_match = num_initials <= title_words.len()
+            && query_word
+                .chars()
+                .zip_eq(&title_words[..num_initials])
+                .all(|(c, w)| w.starts_with(c))
+            && words_match(&title_words[num_initials..], rest_of_query);
+        is_word_match || is_initials_match
+    })
+}
+
+/// The possible ways that loading a [`MethodLib`] can fail
+#[derive(Debug, Clone)]
+pub enum LibError {
+    /// The input wasn't valid XML
+    Xml(roxmltree::Error),
+    /// A method was missing a required field.  `method` is the title of the method, if it has one.
+    MissingField {
+        method: Option<String>,
+        field: &'static str,
+    },
+    /// A method's stage wasn't a positive integer
+    InvalidStage { method: String, stage: String },
+    /// A line of a MicroSIRIL library didn't contain both a name and place notation.  Lines are
+    /// numbered from 1.
+    InvalidLine { line: usize },
+}
+
+impl std::fmt::Display for LibError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LibError::Xml(e) => write!(f, "Invalid XML: {}", e),
+            LibError::MissingField {
+                method: Some(method),
+                field,
+            } => write!(f, "Method {:?} has no {}", method, field),
+            LibError::MissingField {
+                method: None,
+                field,
+            } => write!(f, "A method has no {}", field),
+            LibError::InvalidStage { method, stage } => {
+                write!(f, "Method {:?} has invalid stage {:?}", method, stage)
+            }
+            LibError::InvalidLine { line } => {
+                write!(f, "Line {} needs both a name and place notation", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LibError {}
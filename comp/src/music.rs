@@ -1,23 +1,42 @@
 //! Representation of musical [`Row`]s
 
-use bellframe::{music::Regex, Stage};
+use bellframe::{music::Regex, Bell, Row, Stage};
 use itertools::Itertools;
+use jigsaw_utils::indexed_vec::PartIdx;
 
-// Imports only used for doc comments
-#[allow(unused_imports)]
-use bellframe::Row;
-
-/// A tree-like structure which recursively combines groups of musical [`Row`]s
+/// A tree-like structure which recursively combines groups of musical [`Row`]s.  Every `Music`
+/// has a weight, which is multiplied by its score to give its contribution to the score of the
+/// composition.  Every `Music` can also be restricted to a set of parts, in which case only
+/// [`Row`]s in those parts are counted (e.g. to only count runs in the first part).
 #[derive(Debug, Clone)]
 pub enum Music {
     /// An optionally named group of musical [`Row`]s, specified by a single [`Regex`] over
-    /// [`Row`]s.  This cannot have any sub-groups.
-    Regex(Option<String>, Regex),
-    /// A named group of sub-groups of musical [`Row`]s
-    Group(String, Vec<Music>),
+    /// [`Row`]s.  This cannot have any sub-groups.  Each matching [`Row`] scores the weight.
+    Regex {
+        name: Option<String>,
+        regex: Regex,
+        weight: f32,
+        /// If `true`, matches which wrap over the boundary between two consecutive proved
+        /// [`Row`]s (e.g. wraps of rounds) are also counted
+        count_wraps: bool,
+        /// The parts in which this is counted, or `None` for every part
+        parts: Option<Vec<PartIdx>>,
+    },
+    /// A named group of sub-groups of musical [`Row`]s.  This scores the total score of its
+    /// sub-groups, multiplied by the weight.
+    Group {
+        name: String,
+        sub_groups: Vec<Music>,
+        weight: f32,
+        /// The parts in which the sub-groups are counted, or `None` for every part
+        parts: Option<Vec<PartIdx>>,
+    },
 }
 
 impl Music {
+    /// The weight given to new [`Music`], which makes scores equal to counts
+    pub const DEFAULT_WEIGHT: f32 = 1.0;
+
     /// Creates a [`Music`] group for
     pub fn runs_front_and_back(stage: Stage, len: usize) -> Music {
         let name = format!("{}-bell runs", len);
@@ -25,15 +44,248 @@ impl Music {
             Self::group_from_regexes("front", Regex::runs_front(stage, len)),
             Self::group_from_regexes("back", Regex::runs_back(stage, len)),
         ];
-        Music::Group(name, sub_classes)
+        Music::Group {
+            name,
+            sub_groups: sub_classes,
+            weight: Self::DEFAULT_WEIGHT,
+            parts: None,
+        }
+    }
+
+    /// Creates a [`Music::Regex`] by parsing a [`Regex`] typed by the user.  The [`Regex`] is
+    /// checked before being parsed, since [`Regex::parse`] assumes that its input is valid.
+    pub fn parse_regex(
+        name: Option<String>,
+        regex: &str,
+        weight: f32,
+        count_wraps: bool,
+        parts: Option<Vec<PartIdx>>,
+        stage: Stage,
+    ) -> Result<Self, RegexError> {
+        check_regex(regex, stage)?;
+        Ok(Music::Regex {
+            name,
+            regex: Regex::parse(regex),
+            weight,
+            count_wraps,
+            parts,
+        })
+    }
+
+    /// The name of this `Music`.  Unnamed [`Music::Regex`]s return `None`.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Music::Regex { name, .. } => name.as_deref(),
+            Music::Group { name, .. } => Some(name),
+        }
+    }
+
+    /// The weight of this `Music`
+    pub fn weight(&self) -> f32 {
+        match self {
+            Music::Regex { weight, .. } | Music::Group { weight, .. } => *weight,
+        }
+    }
+
+    /// The parts in which this `Music` is counted, or `None` if it's counted in every part
+    pub fn parts(&self) -> Option<&[PartIdx]> {
+        match self {
+            Music::Regex { parts, .. } | Music::Group { parts, .. } => parts.as_deref(),
+        }
+    }
+
+    /// The sub-groups of this `Music`, or `None` if this is a [`Music::Regex`]
+    pub fn sub_groups(&self) -> Option<&[Music]> {
+        match self {
+            Music::Regex { .. } => None,
+            Music::Group { sub_groups, .. } => Some(sub_groups),
+        }
+    }
+
+    /// The score of a single [`Row`] in the first part of the composition (taking weights into
+    /// account).  Wraps aren't counted, since they depend on the next [`Row`].
+    pub(crate) fn score_row(&self, row: &Row) -> f32 {
+        if let Some(parts) = self.parts() {
+            if !parts.contains(&PartIdx::new(0)) {
+                return 0.0;
+            }
+        }
+        match self {
+            Music::Regex { regex, weight, .. } => {
+                if regex.matches(row) {
+                    *weight
+                } else {
+                    0.0
+                }
+            }
+            Music::Group {
+                sub_groups, weight, ..
+            } => sub_groups.iter().map(|g| g.score_row(row)).sum::<f32>() * weight,
+        }
     }
 
     /// Create a [`Music::Group`] containing one unnamed group per [`Regex`] yielded by `regexes`.
     pub fn group_from_regexes(name: &str, regexes: impl IntoIterator<Item = Regex>) -> Self {
         let sub_groups = regexes
             .into_iter()
-            .map(|r| Music::Regex(None, r))
+            .map(|regex| Music::Regex {
+                name: None,
+                regex,
+                weight: Self::DEFAULT_WEIGHT,
+                count_wraps: false,
+                parts: None,
+            })
             .collect_vec();
-        Self::Group(name.to_owned(), sub_groups)
+        Self::Group {
+            name: name.to_owned(),
+            sub_groups,
+            weight: Self::DEFAULT_WEIGHT,
+            parts: None,
+        }
     }
 }
+
+/// A [`Regex`] which can be matched against sequences of [`Bell`]s which aren't valid [`Row`]s.
+/// This is used to find matches which wrap over the boundary between two [`Row`]s.
+#[derive(Debug, Clone)]
+pub(crate) struct WrapRegex {
+    /// The elements of the [`Regex`], where `None` is a glob (`*`) which matches any number of
+    /// [`Bell`]s
+    elems: Vec<Option<Bell>>,
+}
+
+impl WrapRegex {
+    pub(crate) fn new(regex: &Regex) -> Self {
+        // `Regex`es are displayed in the same format as they are parsed from
+        let elems = regex
+            .to_string()
+            .chars()
+            .filter_map(|c| match c {
+                '*' => Some(None),
+                _ => Bell::from_name(c).map(Some),
+            })
+            .collect_vec();
+        Self { elems }
+    }
+
+    /// Finds every match of this [`Regex`] which wraps from the end of `row` to the start of
+    /// `next_row`.  Each match is returned as the matched places in `row` and `next_row`.  Matches
+    /// which only contain [`Bell`]s from one of the [`Row`]s aren't wraps, so are ignored.
+    pub(crate) fn wrapped_matches(
+        &self,
+        row: &Row,
+        next_row: &Row,
+    ) -> Vec<(Vec<usize>, Vec<usize>)> {
+        let num_bells = row.stage().num_bells();
+        let mut window = Vec::with_capacity(num_bells);
+        let mut matches = Vec::new();
+        // Slide a row-sized window over the boundary, starting `offset` places into `row`
+        for offset in 1..num_bells {
+            window.clear();
+            window.extend(row.bell_iter().skip(offset));
+            window.extend(next_row.bell_iter().take(offset));
+
+            let mut matched_places = Vec::new();
+            if !match_elems(&self.elems, &window, 0, &mut matched_places) {
+                continue;
+            }
+            let num_bells_from_row = num_bells - offset;
+            let (places_in_row, places_in_next_row): (Vec<usize>, Vec<usize>) = matched_places
+                .into_iter()
+                .partition(|&place| place < num_bells_from_row);
+            if !places_in_row.is_empty() && !places_in_next_row.is_empty() {
+                matches.push((
+                    places_in_row.into_iter().map(|p| p + offset).collect(),
+                    places_in_next_row
+                        .into_iter()
+                        .map(|p| p - num_bells_from_row)
+                        .collect(),
+                ));
+            }
+        }
+        matches
+    }
+}
+
+/// Recursively matches [`WrapRegex`] elements against `bells[idx..]`, pushing the index of every
+/// [`Bell`] matched by a non-glob element to `matched_places`.  On failure, `matched_places` is
+/// left unchanged.
+fn match_elems(
+    elems: &[Option<Bell>],
+    bells: &[Bell],
+    idx: usize,
+    matched_places: &mut Vec<usize>,
+) -> bool {
+    match elems.split_first() {
+        None => idx == bells.len(),
+        // Globs can match any number of bells, so try every possible length
+        Some((None, rest)) => {
+            (idx..=bells.len()).any(|next| match_elems(rest, bells, next, matched_places))
+        }
+        Some((Some(bell), rest)) => {
+            if bells.get(idx) != Some(bell) {
+                return false;
+            }
+            matched_places.push(idx);
+            let is_match = match_elems(rest, bells, idx + 1, matched_places);
+            if !is_match {
+                matched_places.pop();
+            }
+            is_match
+        }
+    }
+}
+
+/// Checks that a string is a valid [`Regex`] over [`Row`]s of a given [`Stage`].  A valid
+/// [`Regex`] is a non-empty sequence of bell names and `*`s (which match any number of bells),
+/// which doesn't contain more bells than the [`Stage`].
+pub fn check_regex(regex: &str, stage: Stage) -> Result<(), RegexError> {
+    if regex.is_empty() {
+        return Err(RegexError::Empty);
+    }
+    let mut num_bells = 0;
+    for c in regex.chars().filter(|c| *c != '*') {
+        let bell = Bell::from_name(c).ok_or(RegexError::InvalidChar(c))?;
+        if bell.index() >= stage.num_bells() {
+            return Err(RegexError::BellOutOfStage(bell));
+        }
+        num_bells += 1;
+    }
+    if num_bells > stage.num_bells() {
+        return Err(RegexError::TooManyBells { num_bells, stage });
+    }
+    Ok(())
+}
+
+/// The possible ways that a [`Regex`] typed by the user can be invalid
+#[derive(Debug, Clone)]
+pub enum RegexError {
+    Empty,
+    /// The [`Regex`] contained a character which isn't a bell name or `*`
+    InvalidChar(char),
+    /// The [`Regex`] contained a [`Bell`] which isn't in the [`Stage`]
+    BellOutOfStage(Bell),
+    /// The [`Regex`] contained more [`Bell`]s than there are in a [`Row`]
+    TooManyBells {
+        num_bells: usize,
+        stage: Stage,
+    },
+}
+
+impl std::fmt::Display for RegexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegexError::Empty => write!(f, "Music patterns can't be empty"),
+            RegexError::InvalidChar(c) => write!(f, "'{}' isn't a bell name or '*'", c),
+            RegexError::BellOutOfStage(bell) => write!(f, "Bell {} isn't in the stage", bell),
+            RegexError::TooManyBells { num_bells, stage } => write!(
+                f,
+                "Pattern has {} bells, but rows only have {}",
+                num_bells,
+                stage.num_bells()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegexError {}
@@ -0,0 +1,67 @@
+//! A model of the speed and rhythm of ringing, used to work out when each bell is struck
+
+use bellframe::Stage;
+use serde::{Deserialize, Serialize};
+
+/// The number of rows in the peal used to turn [`Pace::peal_time`] into a speed of ringing
+const PEAL_LENGTH: usize = 5040;
+
+/// The speed and rhythm of ringing.  Every bell strikes once per row (at even intervals), and there
+/// is a gap between every backstroke and the following handstroke.  Times are measured in 'blows'
+/// (the interval between two consecutive bells), starting from the first blow of the first row.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pace {
+    /// How long a 5040 would take at this pace, in minutes
+    pub peal_time: f32,
+    /// The length of the gap before each handstroke, measured in blows
+    pub handstroke_gap: f32,
+}
+
+impl Default for Pace {
+    fn default() -> Self {
+        Self {
+            peal_time: 180.0, // minutes
+            handstroke_gap: 1.0,
+        }
+    }
+}
+
+impl Pace {
+    /// The number of blows taken by a handstroke and backstroke, including the handstroke gap
+    pub fn blows_per_whole_pull(&self, stage: Stage) -> f64 {
+        (stage.num_bells() * 2) as f64 + self.handstroke_gap as f64
+    }
+
+    /// The length of one blow, in seconds
+    pub fn blow_duration(&self, stage: Stage) -> f64 {
+        let num_whole_pulls = (PEAL_LENGTH / 2) as f64;
+        let peal_time_secs = self.peal_time as f64 * 60.0;
+        peal_time_secs / (num_whole_pulls * self.blows_per_whole_pull(stage))
+    }
+
+    /// The time (in blows) at which the `row_idx`th row starts
+    pub fn row_start(&self, row_idx: usize, stage: Stage) -> f64 {
+        let whole_pulls = (row_idx / 2) as f64 * self.blows_per_whole_pull(stage);
+        let backstroke_offset = (row_idx % 2 * stage.num_bells()) as f64;
+        whole_pulls + backstroke_offset
+    }
+
+    /// Returns the index of the row being rung at a given time (in blows), along with the place of
+    /// the last bell to strike.  During the handstroke gap, the place is `None` and the row is the
+    /// preceding backstroke.
+    pub fn row_at(&self, time: f64, stage: Stage) -> (usize, Option<usize>) {
+        let num_bells = stage.num_bells();
+        let time = time.max(0.0);
+        let whole_pull_len = self.blows_per_whole_pull(stage);
+        let whole_pull = (time / whole_pull_len).floor();
+        let blow_in_pull = (time - whole_pull * whole_pull_len).floor() as usize;
+        let first_row = whole_pull as usize * 2;
+        if blow_in_pull < num_bells * 2 {
+            let row_in_pull = blow_in_pull / num_bells;
+            (first_row + row_in_pull, Some(blow_in_pull % num_bells))
+        } else {
+            (first_row + 1, None)
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! Helpers for running embarrassingly parallel loops (like proving or music matching) on every
+//! core.  The loops only run in parallel if the `rayon` feature is enabled, which it isn't for
+//! the web build (since WASM doesn't have threads).
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Applies `f` to every item in `items`, in parallel, returning the results in the same order
+#[cfg(feature = "rayon")]
+pub(crate) fn map<T: Send, U: Send>(items: Vec<T>, f: impl Fn(T) -> U + Sync + Send) -> Vec<U> {
+    items.into_par_iter().map(f).collect()
+}
+
+/// Applies `f` to every item in `items`, returning the results in the same order
+#[cfg(not(feature = "rayon"))]
+pub(crate) fn map<T: Send, U: Send>(items: Vec<T>, f: impl Fn(T) -> U + Sync + Send) -> Vec<U> {
+    items.into_iter().map(f).collect()
+}
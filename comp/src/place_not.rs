@@ -0,0 +1,418 @@
+//! Place notation which can contain jump changes (e.g. for Cambridge Jump methods).  Bellframe's
+//! [`PnBlock`](bellframe::PnBlock) can only represent changes where every bell moves by at most
+//! one place, so any place notation containing jumps is parsed by [`JumpPnBlock`] instead.
+//!
+//! Jumps are written in the established bracket notation: `(ab)` means that the bell in place
+//! `a` jumps to place `b`, and every bell between them moves one place towards `a`.  Jumps can
+//! be mixed with places in the same change, so `(13)6` would be a valid change on Minor.
+
+use std::fmt::{Display, Formatter};
+
+use bellframe::{
+    place_not::PnBlockParseError, row::RowAccumulator, AnnotBlock, Bell, IncompatibleStages, Row,
+    RowBuf, SameStageVec, Stage,
+};
+use itertools::Itertools;
+
+/// Parses a string of place notation (which may contain jumps) into the first lead of a
+/// [`bellframe::Method`].  Place notation without jumps is parsed by Bellframe as usual.
+pub fn parse_method(place_notation: &str, stage: Stage) -> Result<bellframe::Method, PnError> {
+    if !contains_jumps(place_notation) {
+        return bellframe::Method::from_place_not_string(String::new(), stage, place_notation)
+            .map_err(PnError::Standard);
+    }
+    let block = JumpPnBlock::parse(place_notation, stage).map_err(PnError::Jump)?;
+    Ok(bellframe::Method::with_name(
+        String::new(),
+        block.to_block_from_rounds(),
+    ))
+}
+
+/// Returns `true` if a string of place notation contains any jump changes
+pub fn contains_jumps(place_notation: &str) -> bool {
+    place_notation.contains('(')
+}
+
+/// A block of place notation, where each change can contain jumps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpPnBlock {
+    stage: Stage,
+    changes: Vec<JumpChange>,
+}
+
+impl JumpPnBlock {
+    /// Parses a string of place notation containing jumps.  Like normal place notation, `,`
+    /// splits the block into sections which are each palindromic, and sections can be prefixed
+    /// with `&` (palindromic) or `+` (not palindromic).
+    pub fn parse(s: &str, stage: Stage) -> Result<Self, JumpPnParseError> {
+        let sections = s.split(',').collect_vec();
+        let mut changes = Vec::new();
+        for section in &sections {
+            let section = section.trim();
+            // Sections are palindromic if they're prefixed with `&` or if there are several
+            // comma-separated sections
+            let (is_palindrome, section) = if let Some(rest) = section.strip_prefix('&') {
+                (true, rest)
+            } else if let Some(rest) = section.strip_prefix('+') {
+                (false, rest)
+            } else {
+                (sections.len() > 1, section)
+            };
+            let section_changes = parse_section(section, stage)?;
+            changes.extend(section_changes.iter().cloned());
+            if is_palindrome {
+                // Mirror every change except the last (which is the half-lead)
+                let num_mirrored = section_changes.len().saturating_sub(1);
+                changes.extend(section_changes[..num_mirrored].iter().rev().cloned());
+            }
+        }
+        if changes.is_empty() {
+            return Err(JumpPnParseError::NoChanges);
+        }
+        Ok(Self { stage, changes })
+    }
+
+    /// The number of changes in this block
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Always returns `false`, since parsing fails if there are no changes
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// Returns `true` if any of the changes in this block contain jumps
+    pub fn has_jumps(&self) -> bool {
+        self.changes.iter().any(JumpChange::has_jump)
+    }
+
+    /// Converts this into an [`AnnotBlock`] starting at rounds, where every [`Row`] has the
+    /// default annotation.  The leftover [`Row`] is the lead head if this block is the first lead
+    /// of a method.
+    pub fn to_block_from_rounds<A: Default>(&self) -> AnnotBlock<A> {
+        let mut rows = SameStageVec::with_capacity(self.stage, self.len() + 1);
+        let mut accum = RowAccumulator::rounds(self.stage);
+        // The unwraps are safe because every change has the same stage as `self`
+        rows.push(accum.total()).unwrap();
+        for change in &self.changes {
+            accum.accumulate(&change.permutation).unwrap();
+            rows.push(accum.total()).unwrap();
+        }
+        AnnotBlock::with_default_annots(rows)
+    }
+}
+
+/// A single change, which may contain jumps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpChange {
+    /// The permutation made by this change: the bell which moves to place `i` is the one which
+    /// was in place `permutation[i]`
+    permutation: RowBuf,
+}
+
+impl JumpChange {
+    /// Returns `true` if any bell moves more than one place in this change
+    pub fn has_jump(&self) -> bool {
+        self.permutation
+            .bell_iter()
+            .enumerate()
+            .any(|(place, bell)| (place as isize - bell.index() as isize).abs() > 1)
+    }
+
+    /// Returns the [`Row`] generated by applying this change to `row`
+    pub fn apply(&self, row: &Row) -> Result<RowBuf, IncompatibleStages> {
+        row.mul_result(&self.permutation)
+    }
+}
+
+/// Parses a section of place notation which doesn't contain `,`, `&` or `+`
+fn parse_section(s: &str, stage: Stage) -> Result<Vec<JumpChange>, JumpPnParseError> {
+    let mut changes = Vec::new();
+    // The places and jumps of the change currently being parsed
+    let mut places = Vec::<usize>::new();
+    let mut jumps = Vec::<(usize, usize)>::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => finish_change(&mut changes, &mut places, &mut jumps, stage)?,
+            'x' | 'X' | '-' => {
+                finish_change(&mut changes, &mut places, &mut jumps, stage)?;
+                changes.push(cross_change(stage)?);
+            }
+            '(' => {
+                let jump_str = chars.by_ref().take_while(|&c| c != ')').collect::<String>();
+                let jump_places = jump_str
+                    .chars()
+                    .map(|c| parse_place(c, stage))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match *jump_places.as_slice() {
+                    [from, to] if from != to => jumps.push((from, to)),
+                    _ => return Err(JumpPnParseError::InvalidJump(jump_str)),
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => places.push(parse_place(c, stage)?),
+        }
+    }
+    finish_change(&mut changes, &mut places, &mut jumps, stage)?;
+    Ok(changes)
+}
+
+/// Adds the change made from `places` and `jumps` to `changes` (if any places or jumps have been
+/// parsed), leaving `places` and `jumps` empty for the next change
+fn finish_change(
+    changes: &mut Vec<JumpChange>,
+    places: &mut Vec<usize>,
+    jumps: &mut Vec<(usize, usize)>,
+    stage: Stage,
+) -> Result<(), JumpPnParseError> {
+    if places.is_empty() && jumps.is_empty() {
+        return Ok(());
+    }
+    changes.push(make_change(places, jumps, stage)?);
+    places.clear();
+    jumps.clear();
+    Ok(())
+}
+
+/// Parses a single place (e.g. `'3'` or `'T'`), returning its 0-indexed place
+fn parse_place(c: char, stage: Stage) -> Result<usize, JumpPnParseError> {
+    let bell = Bell::from_name(c).ok_or(JumpPnParseError::InvalidChar(c))?;
+    if bell.index() >= stage.num_bells() {
+        return Err(JumpPnParseError::PlaceOutOfStage(c));
+    }
+    Ok(bell.index())
+}
+
+/// Creates the cross change, where every pair of bells swaps over
+fn cross_change(stage: Stage) -> Result<JumpChange, JumpPnParseError> {
+    if stage.num_bells() % 2 == 1 {
+        return Err(JumpPnParseError::CrossOnOddStage);
+    }
+    make_change(&[], &[], stage)
+}
+
+/// Creates a [`JumpChange`] where the bells in `places` stay still, the bells in `jumps` jump
+/// and every other bell swaps with one of its neighbours.  Like normal place notation, places at
+/// the front or back of the change can be left implicit.
+fn make_change(
+    places: &[usize],
+    jumps: &[(usize, usize)],
+    stage: Stage,
+) -> Result<JumpChange, JumpPnParseError> {
+    let num_bells = stage.num_bells();
+    // `sources[i]` is the place of the bell which moves to place `i`, or `None` if it hasn't
+    // been decided yet
+    let mut sources = vec![None; num_bells];
+    let mut set_source = |place: usize, source: usize| match sources[place] {
+        Some(_) => Err(JumpPnParseError::Overlap),
+        None => {
+            sources[place] = Some(source);
+            Ok(())
+        }
+    };
+    for &place in places {
+        set_source(place, place)?;
+    }
+    for &(from, to) in jumps {
+        set_source(to, from)?;
+        // Every bell between `from` and `to` moves one place towards `from`
+        if from < to {
+            for place in from..to {
+                set_source(place, place + 1)?;
+            }
+        } else {
+            for place in to + 1..=from {
+                set_source(place, place - 1)?;
+            }
+        }
+    }
+
+    // Fill the gaps with swaps, making implicit places at the ends of odd-length gaps
+    let mut place = 0;
+    while place < num_bells {
+        if sources[place].is_some() {
+            place += 1;
+            continue;
+        }
+        let gap_end = (place..num_bells)
+            .find(|&p| sources[p].is_some())
+            .unwrap_or(num_bells);
+        let mut gap_start = place;
+        if (gap_end - gap_start) % 2 == 1 {
+            if gap_start == 0 {
+                sources[0] = Some(0);
+                gap_start += 1;
+            } else if gap_end == num_bells {
+                sources[num_bells - 1] = Some(num_bells - 1);
+            } else {
+                return Err(JumpPnParseError::OddGap);
+            }
+        }
+        for p in (gap_start..gap_end).step_by(2) {
+            if p + 1 < num_bells && sources[p + 1].is_none() {
+                sources[p] = Some(p + 1);
+                sources[p + 1] = Some(p);
+            }
+        }
+        place = gap_end;
+    }
+
+    // Every place is now filled, so the unwraps are safe
+    let permutation_str = sources
+        .into_iter()
+        .map(|source| Bell::from_index(source.unwrap()).name())
+        .collect::<String>();
+    // The permutation is valid if no bell has been moved to two places
+    let permutation =
+        RowBuf::parse_with_stage(&permutation_str, stage).map_err(|_| JumpPnParseError::Overlap)?;
+    Ok(JumpChange { permutation })
+}
+
+/// The possible ways that parsing place notation with [`parse_method`] can fail
+#[derive(Debug, Clone)]
+pub enum PnError {
+    /// The place notation had no jumps, and Bellframe couldn't parse it
+    Standard(PnBlockParseError),
+    /// The place notation contained jumps, and couldn't be parsed
+    Jump(JumpPnParseError),
+}
+
+impl Display for PnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PnError::Standard(e) => write!(f, "{}", e),
+            PnError::Jump(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// The possible ways that parsing a [`JumpPnBlock`] can fail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpPnParseError {
+    /// The place notation didn't contain any changes
+    NoChanges,
+    /// A character wasn't a place, separator or bracket
+    InvalidChar(char),
+    /// A place was too large for the [`Stage`]
+    PlaceOutOfStage(char),
+    /// A jump wasn't made of exactly two different places (e.g. `(13)`)
+    InvalidJump(String),
+    /// A bell was made to both jump and make a place (or jump twice) in the same change
+    Overlap,
+    /// There were an odd number of bells between two places or jumps, so they can't all swap
+    OddGap,
+    /// The cross change (`x`) was used on an odd stage
+    CrossOnOddStage,
+}
+
+impl Display for JumpPnParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JumpPnParseError::NoChanges => write!(f, "Place notation has no changes"),
+            JumpPnParseError::InvalidChar(c) => write!(f, "'{}' isn't a valid place", c),
+            JumpPnParseError::PlaceOutOfStage(c) => {
+                write!(f, "Place '{}' is too large for the stage", c)
+            }
+            JumpPnParseError::InvalidJump(s) => {
+                write!(f, "Jump '({})' must be two different places", s)
+            }
+            JumpPnParseError::Overlap => write!(f, "A bell moves twice in the same change"),
+            JumpPnParseError::OddGap => {
+                write!(f, "Odd number of bells between places, so they can't swap")
+            }
+            JumpPnParseError::CrossOnOddStage => write!(f, "'x' can't be used on odd stages"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bellframe::PnBlock;
+
+    use super::*;
+
+    /// Every [`Row`] of a block starting at rounds, including the leftover [`Row`]
+    fn all_rows(block: &AnnotBlock<()>) -> Vec<String> {
+        block.all_rows().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn matches_bellframe_without_jumps() {
+        for &(pn, stage) in &[
+            ("-36-14-12-36-14-56,12", Stage::MINOR),
+            ("&-38-14-1258-36-14-58-16-78,+12", Stage::MAJOR),
+            ("3.1.5.1.5.1.5.1.5.1", Stage::DOUBLES),
+        ] {
+            let jump_block = JumpPnBlock::parse(pn, stage).unwrap();
+            let bellframe_block = PnBlock::parse(pn, stage).unwrap();
+            assert!(!jump_block.has_jumps());
+            assert_eq!(jump_block.len(), bellframe_block.len());
+            assert_eq!(
+                all_rows(&jump_block.to_block_from_rounds()),
+                all_rows(&bellframe_block.to_block_from_rounds())
+            );
+        }
+    }
+
+    #[test]
+    fn jumps() {
+        // The treble jumps from lead to thirds, and the 2nd and 3rd move down to make room
+        let block = JumpPnBlock::parse("(13)", Stage::MINIMUS).unwrap();
+        assert!(block.has_jumps());
+        assert_eq!(all_rows(&block.to_block_from_rounds()), ["1234", "2314"]);
+        // Jumps can go downwards, and can be mixed with places and cross changes
+        let block = JumpPnBlock::parse("x(31)6", Stage::MINOR).unwrap();
+        assert_eq!(
+            all_rows(&block.to_block_from_rounds()),
+            ["123456", "214365", "421635"]
+        );
+    }
+
+    #[test]
+    fn jump_palindromes() {
+        // The half-lead change isn't repeated
+        let block = JumpPnBlock::parse("&x(13)x,14", Stage::MINIMUS).unwrap();
+        assert_eq!(block.len(), 6);
+        let block = JumpPnBlock::parse("+x(13)x", Stage::MINIMUS).unwrap();
+        assert_eq!(block.len(), 3);
+    }
+
+    #[test]
+    fn parse_method_with_jumps() {
+        let method = parse_method("x(13)x14", Stage::MINIMUS).unwrap();
+        assert_eq!(method.lead_len(), 4);
+        let method = parse_method("x14x14,12", Stage::MINIMUS).unwrap();
+        assert_eq!(method.lead_len(), 8);
+        assert_eq!(method.lead_head().to_string(), "1342");
+    }
+
+    #[test]
+    fn errors() {
+        let parse_err = |pn: &str, stage: Stage| JumpPnBlock::parse(pn, stage).unwrap_err();
+        assert_eq!(parse_err("", Stage::MINOR), JumpPnParseError::NoChanges);
+        assert_eq!(
+            parse_err("1?", Stage::MINOR),
+            JumpPnParseError::InvalidChar('?')
+        );
+        assert_eq!(
+            parse_err("18", Stage::MINOR),
+            JumpPnParseError::PlaceOutOfStage('8')
+        );
+        assert_eq!(
+            parse_err("(11)", Stage::MINOR),
+            JumpPnParseError::InvalidJump("11".to_owned())
+        );
+        assert_eq!(parse_err("(13)2", Stage::MINOR), JumpPnParseError::Overlap);
+        assert_eq!(parse_err("13", Stage::MINOR), JumpPnParseError::OddGap);
+        assert_eq!(
+            parse_err("x", Stage::DOUBLES),
+            JumpPnParseError::CrossOnOddStage
+        );
+    }
+}
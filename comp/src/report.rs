@@ -0,0 +1,151 @@
+//! A structured summary of a composition, for analysing compositions without the GUI.  Unlike
+//! [`FullState`], a [`Report`] only contains plain data (so can be serialised to JSON) and doesn't
+//! refer to any rendering details like fragment positions.
+
+use serde::Serialize;
+
+use crate::{
+    full::{FullState, MusicGroup, MusicGroupInner},
+    spec::{save_load::LoadError, CompSpec},
+};
+
+/// A summary of the length, truth, methods, calls and music of a composition
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// The total number of [`Row`](bellframe::Row)s in the composition, over all parts
+    pub length: usize,
+    pub num_parts: usize,
+    /// The number of [`Row`](bellframe::Row)s in each part
+    pub part_len: usize,
+    pub is_true: bool,
+    /// The total number of false [`Row`](bellframe::Row)s, over all parts
+    pub num_false_rows: usize,
+    /// The number of changes of method in each part
+    pub num_coms: usize,
+    /// Whether or not the composition is balanced spliced, or `None` if it isn't spliced
+    pub is_balanced_spliced: Option<bool>,
+    pub methods: Vec<MethodReport>,
+    pub calls: Vec<CallReport>,
+    pub music: Vec<MusicReport>,
+    /// The weighted score of all the music in the composition
+    pub music_score: f32,
+}
+
+/// The usage of one [`Method`](crate::full::Method) in a composition
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodReport {
+    pub name: String,
+    /// The full title of the method, e.g. `"Bristol Surprise Major"`
+    pub title: String,
+    pub shorthand: String,
+    pub place_notation: String,
+    /// The number of proved changes of this method, in each part
+    pub num_changes: usize,
+    /// The number of proved lead heads of this method, in each part
+    pub num_leads: usize,
+    /// The fraction of this method's work which is rung
+    pub atw: f32,
+}
+
+/// The usage of one [`Call`](crate::full::Call) in a composition
+#[derive(Debug, Clone, Serialize)]
+pub struct CallReport {
+    pub symbol: char,
+    pub place_notation: String,
+    /// The number of proved uses of this call, in each part
+    pub num_uses: usize,
+}
+
+/// The count of one [`MusicGroup`] in a composition, along with its sub-groups
+#[derive(Debug, Clone, Serialize)]
+pub struct MusicReport {
+    pub name: String,
+    pub count: usize,
+    pub max_count: usize,
+    pub score: f32,
+    /// Empty if this group is a leaf of the music tree
+    pub sub_groups: Vec<MusicReport>,
+}
+
+impl Report {
+    /// Loads a [`CompSpec`] from the JSON generated by [`CompSpec::to_json`], and summarises it
+    pub fn from_json(json: &str) -> Result<Self, LoadError> {
+        let spec = CompSpec::from_json(json)?;
+        Ok(Self::new(&FullState::new(&spec)))
+    }
+
+    /// Summarises the composition represented by a [`FullState`]
+    pub fn new(full_state: &FullState) -> Self {
+        let stats = &full_state.stats;
+        let num_parts = full_state.part_heads.len();
+        let methods = full_state
+            .methods
+            .iter()
+            .zip(&stats.changes_per_method)
+            .zip(&stats.leads_per_method)
+            .zip(&stats.atw_per_method)
+            .map(
+                |(((method, &num_changes), &num_leads), &atw)| MethodReport {
+                    name: method.name(),
+                    title: method.title(),
+                    shorthand: method.shorthand(),
+                    place_notation: method.place_notation(),
+                    num_changes,
+                    num_leads,
+                    atw,
+                },
+            )
+            .collect();
+        let calls = full_state
+            .calls
+            .iter()
+            .zip(&stats.uses_per_call)
+            .map(|(call, &num_uses)| CallReport {
+                symbol: call.symbol(),
+                place_notation: call.place_notation(),
+                num_uses,
+            })
+            .collect();
+        Self {
+            length: stats.part_len * num_parts,
+            num_parts,
+            part_len: stats.part_len,
+            is_true: full_state.falseness.is_true(),
+            num_false_rows: full_state.falseness.num_false_rows(),
+            num_coms: stats.num_coms,
+            is_balanced_spliced: stats.spliced_balance().map(|b| b.is_balanced()),
+            methods,
+            calls,
+            music: full_state
+                .music
+                .groups()
+                .iter()
+                .map(|g| MusicReport::new(g))
+                .collect(),
+            music_score: full_state.music.total_score(),
+        }
+    }
+
+    /// Serialises `self` to a JSON string
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl MusicReport {
+    fn new(group: &MusicGroup) -> Self {
+        let sub_groups = match &group.inner {
+            MusicGroupInner::Leaf { .. } => Vec::new(),
+            MusicGroupInner::Group { sub_groups, .. } => {
+                sub_groups.iter().map(|g| Self::new(g)).collect()
+            }
+        };
+        Self {
+            name: group.name.clone(),
+            count: group.inner.count(),
+            max_count: group.max_count,
+            score: group.score,
+            sub_groups,
+        }
+    }
+}
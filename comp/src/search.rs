@@ -0,0 +1,530 @@
+//! A simple composition search engine, which generates touches by a depth-first search over lead
+//! heads.  Every lead of a touch is either plain or contains one [`Call`](crate::spec::Call),
+//! which is made at the call's lead location (usually the lead end, where it replaces the last
+//! [`Row`]s of the lead).  Touches start and finish at rounds, and only come round at lead heads.
+//! The same search can also look for touches joining two [`Fragment`](crate::spec::Fragment)s
+//! together (see [`CompSpec::start_join_search`]), which start and finish at arbitrary [`Row`]s
+//! instead.
+//!
+//! Searches are run a few leads at a time by [`Search::step`], so that the GUI can show the
+//! touches found so far (and let the user cancel) whilst a long search is running.
+
+use std::{collections::HashSet, rc::Rc};
+
+use bellframe::{Row, RowBuf};
+use itertools::Itertools;
+use jigsaw_utils::indexed_vec::{CallIdx, FragIdx, MethodIdx};
+
+use crate::{
+    spec::{CompSpec, EditError},
+    Music,
+};
+
+/// The most leads which [`CompSpec::search_touches`] will try before giving up.  Without this,
+/// searches for touches which don't exist could run for (effectively) ever.
+const MAX_LEADS_TRIED: usize = 1_000_000;
+
+/// The constraints on the touches generated by a [`Search`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchParams {
+    /// The methods which can be used in the touches
+    pub methods: Vec<MethodIdx>,
+    /// The calls which can be used in the touches
+    pub calls: Vec<CallIdx>,
+    /// The shortest length of touch to generate
+    pub min_length: usize,
+    /// The longest length of touch to generate
+    pub max_length: usize,
+    /// If `true`, only touches which never repeat a [`Row`] are generated
+    pub require_truth: bool,
+    /// Which of the touches are kept
+    pub goal: SearchGoal,
+}
+
+/// Which touches are kept by a [`Search`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchGoal {
+    /// The search stops once this many touches have been found
+    FirstTouches(usize),
+    /// The search tries every touch, keeping this many touches with the highest music scores
+    BestMusic(usize),
+}
+
+/// A touch generated by a [`Search`], which can be added to the composition with
+/// [`CompSpec::add_touch`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Touch {
+    pub leads: Vec<TouchLead>,
+    /// The number of [`Row`]s in the touch, not including the final row
+    pub length: usize,
+    /// The total score of the touch's [`Row`]s, according to the composition's [`Music`]
+    pub music_score: f32,
+}
+
+/// A single lead of a [`Touch`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TouchLead {
+    pub method: MethodIdx,
+    /// The call made in this lead, or `None` if the lead is plain
+    pub call: Option<CallIdx>,
+}
+
+impl CompSpec {
+    /// Searches for touches which use the methods and calls in `params`, returning them in the
+    /// order given by the [`SearchGoal`].  This gives up after trying [`MAX_LEADS_TRIED`] leads.
+    pub fn search_touches(&self, params: SearchParams) -> Result<Vec<Touch>, EditError> {
+        let mut search = self.start_search(params)?;
+        search.step(MAX_LEADS_TRIED);
+        Ok(search.touches)
+    }
+
+    /// Creates a [`Search`] for touches which use the methods and calls in `params`.  Nothing is
+    /// searched until [`Search::step`] is called.
+    pub fn start_search(&self, params: SearchParams) -> Result<Search, EditError> {
+        let rounds = RowBuf::rounds(self.stage());
+        self.search_between(params, rounds.clone(), rounds)
+    }
+
+    /// Creates a [`Search`] for touches which join `frag_b` onto the end of `frag_a`, i.e. which
+    /// start at the leftover [`Row`] of `frag_a` and finish at the first [`Row`] of `frag_b`.
+    /// The touches found can be inserted with [`CompSpec::join_with_touch`].  Truth is only
+    /// checked within the joining touch, not against the rest of the composition.
+    pub fn start_join_search(
+        &self,
+        frag_a: FragIdx,
+        frag_b: FragIdx,
+        params: SearchParams,
+    ) -> Result<Search, EditError> {
+        let (start_row, end_row) = self.join_rows(frag_a, frag_b)?;
+        self.search_between(params, start_row, end_row)
+    }
+
+    /// Creates a [`Search`] for touches which start at `start_row` and finish at `end_row`
+    fn search_between(
+        &self,
+        params: SearchParams,
+        start_row: RowBuf,
+        end_row: RowBuf,
+    ) -> Result<Search, EditError> {
+        let lead_types = self.lead_types(&params)?;
+        let start_frame = Frame {
+            lead_head: start_row,
+            length: 0,
+            music_score: 0.0,
+            next_lead_type_idx: 0,
+            lead: None,
+            rows: Vec::new(),
+        };
+        Ok(Search {
+            params,
+            lead_types,
+            end_row,
+            music: self.music().clone(),
+            rows_rung: HashSet::new(),
+            stack: vec![start_frame],
+            touches: Vec::new(),
+            leads_tried: 0,
+        })
+    }
+
+    /// Computes the [`Row`]s of every possible lead of a touch (plain or called), relative to its
+    /// lead head
+    fn lead_types(&self, params: &SearchParams) -> Result<Vec<LeadType>, EditError> {
+        let mut lead_types = Vec::new();
+        for &method_idx in &params.methods {
+            let method =
+                self.methods()
+                    .get(method_idx)
+                    .ok_or_else(|| EditError::MethodOutOfRange {
+                        idx: method_idx,
+                        len: self.methods().len(),
+                    })?;
+            let plain_lead = method.plain_lead().map(Row::to_owned).collect_vec();
+            lead_types.push(LeadType {
+                lead: TouchLead {
+                    method: method_idx,
+                    call: None,
+                },
+                transposition: method.lead_head().to_owned(),
+                rows: plain_lead.clone(),
+            });
+
+            for &call_idx in &params.calls {
+                let call = self
+                    .calls()
+                    .get(call_idx)
+                    .ok_or_else(|| EditError::CallOutOfRange {
+                        idx: call_idx,
+                        len: self.calls().len(),
+                    })?;
+                let lead_len = method.lead_len();
+                let call_end = match call.end_sub_lead_index(lead_len) {
+                    Some(0) | None => lead_len,
+                    Some(idx) => idx,
+                };
+                // Calls can't replace the whole lead
+                let call_start = match call_end.checked_sub(call.len()) {
+                    Some(idx) if idx > 0 => idx,
+                    _ => continue,
+                };
+                // The call's rows are transposed by the row where it starts.  The unwraps are
+                // safe because all the methods and calls share the composition's `Stage`.
+                let start_row = &plain_lead[call_start];
+                let mut rows = plain_lead[..call_start].to_vec();
+                rows.extend(call.rows().map(|r| start_row.mul_result(r).unwrap()));
+                // The rest of the lead (if the call isn't at the lead end) is transposed by how
+                // far the call moved the row after it
+                let row_after_call = start_row.mul_result(call.transposition()).unwrap();
+                let plain_row_after_call = plain_lead
+                    .get(call_end)
+                    .map_or(method.lead_head(), RowBuf::as_row);
+                let shift = row_after_call
+                    .mul_result(&plain_row_after_call.inv())
+                    .unwrap();
+                rows.extend(
+                    plain_lead[call_end..]
+                        .iter()
+                        .map(|r| shift.mul_result(r).unwrap()),
+                );
+                lead_types.push(LeadType {
+                    lead: TouchLead {
+                        method: method_idx,
+                        call: Some(call_idx),
+                    },
+                    transposition: shift.mul_result(method.lead_head()).unwrap(),
+                    rows,
+                });
+            }
+        }
+        Ok(lead_types)
+    }
+}
+
+/// One way that a lead of a [`Touch`] can be rung, with its [`Row`]s precomputed
+#[derive(Debug, Clone)]
+struct LeadType {
+    lead: TouchLead,
+    /// The [`Row`]s of this lead if it starts at rounds
+    rows: Vec<RowBuf>,
+    /// The lead head reached after this lead, if it starts at rounds
+    transposition: RowBuf,
+}
+
+/// A depth-first search for [`Touch`]es, which can be paused between calls to [`Search::step`]
+#[derive(Debug, Clone)]
+pub struct Search {
+    params: SearchParams,
+    lead_types: Vec<LeadType>,
+    /// The lead head at which every touch finishes
+    end_row: RowBuf,
+    music: Rc<Vec<Music>>,
+    /// The [`Row`]s rung by the leads in `stack`.  This is only used if the touches have to be
+    /// true.
+    rows_rung: HashSet<RowBuf>,
+    /// The leads of the touch being built, starting with a [`Frame`] for the start of the touch.
+    /// The search is finished when this is empty.
+    stack: Vec<Frame>,
+    /// The touches found so far, in the order given by the [`SearchGoal`]
+    touches: Vec<Touch>,
+    leads_tried: usize,
+}
+
+/// A lead head reached by the [`Search`], along with the lead which reached it
+#[derive(Debug, Clone)]
+struct Frame {
+    lead_head: RowBuf,
+    /// The number of [`Row`]s rung before `lead_head`
+    length: usize,
+    /// The music score of the [`Row`]s rung before `lead_head`
+    music_score: f32,
+    /// The index of the next [`LeadType`] to try from `lead_head`
+    next_lead_type_idx: usize,
+    /// The lead which reached `lead_head`, or `None` for the start of the touch
+    lead: Option<TouchLead>,
+    /// The [`Row`]s of `lead`, which are removed from [`Search::rows_rung`] when backtracking
+    rows: Vec<RowBuf>,
+}
+
+impl Search {
+    /// Tries (at most) `max_leads` more leads, returning `true` if the search is finished
+    pub fn step(&mut self, max_leads: usize) -> bool {
+        for _ in 0..max_leads {
+            if self.is_finished() {
+                return true;
+            }
+            self.try_next_lead();
+        }
+        self.is_finished()
+    }
+
+    /// Returns `true` if every touch has been tried, or enough touches have been found
+    pub fn is_finished(&self) -> bool {
+        match self.params.goal {
+            SearchGoal::FirstTouches(num) if self.touches.len() >= num => true,
+            _ => self.stack.is_empty(),
+        }
+    }
+
+    /// The touches found so far, in the order given by the [`SearchGoal`]
+    pub fn touches(&self) -> &[Touch] {
+        &self.touches
+    }
+
+    /// The number of leads tried so far
+    pub fn leads_tried(&self) -> usize {
+        self.leads_tried
+    }
+
+    /// Tries the next lead from the lead head at the top of the stack, or backtracks if every
+    /// lead has already been tried
+    fn try_next_lead(&mut self) {
+        let frame = match self.stack.last_mut() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let lead_type = match self.lead_types.get(frame.next_lead_type_idx) {
+            Some(lead_type) => lead_type,
+            None => {
+                // Every lead from this lead head has been tried, so backtrack
+                let frame = self.stack.pop().unwrap();
+                if self.params.require_truth {
+                    for row in &frame.rows {
+                        self.rows_rung.remove(row);
+                    }
+                }
+                return;
+            }
+        };
+        frame.next_lead_type_idx += 1;
+        self.leads_tried += 1;
+
+        let new_length = frame.length + lead_type.rows.len();
+        if new_length > self.params.max_length {
+            return;
+        }
+        // The unwraps are safe because every lead type shares the composition's `Stage`
+        let lead_head = &frame.lead_head;
+        let rows = lead_type
+            .rows
+            .iter()
+            .map(|r| lead_head.mul_result(r).unwrap())
+            .collect_vec();
+        let next_lead_head = lead_head.mul_result(&lead_type.transposition).unwrap();
+        let music_score = frame.music_score
+            + rows
+                .iter()
+                .map(|row| self.music.iter().map(|m| m.score_row(row)).sum::<f32>())
+                .sum::<f32>();
+        let lead = lead_type.lead;
+        if self.params.require_truth && !add_rows(&mut self.rows_rung, &rows) {
+            return; // This lead is false against the rest of the touch
+        }
+
+        let new_frame = Frame {
+            lead_head: next_lead_head,
+            length: new_length,
+            music_score,
+            next_lead_type_idx: 0,
+            lead: Some(lead),
+            rows,
+        };
+        if new_frame.lead_head == self.end_row {
+            // Touches finish as soon as they reach the end row, so the new frame is never searched
+            if new_length >= self.params.min_length {
+                let leads = self.stack.iter().filter_map(|f| f.lead).chain(Some(lead));
+                self.add_touch(Touch {
+                    leads: leads.collect_vec(),
+                    length: new_length,
+                    music_score,
+                });
+            }
+            if self.params.require_truth {
+                for row in &new_frame.rows {
+                    self.rows_rung.remove(row);
+                }
+            }
+        } else {
+            self.stack.push(new_frame);
+        }
+    }
+
+    /// Adds a newly found [`Touch`] to `self.touches`, keeping only the best touches if the
+    /// search is optimising music
+    fn add_touch(&mut self, touch: Touch) {
+        match self.params.goal {
+            SearchGoal::FirstTouches(_) => self.touches.push(touch),
+            SearchGoal::BestMusic(num) => {
+                // Keep the touches sorted by descending score
+                let idx = self
+                    .touches
+                    .iter()
+                    .position(|t| t.music_score < touch.music_score)
+                    .unwrap_or(self.touches.len());
+                if idx < num {
+                    self.touches.insert(idx, touch);
+                    self.touches.truncate(num);
+                }
+            }
+        }
+    }
+}
+
+/// Adds `rows` to `rows_rung`, returning `false` (and leaving `rows_rung` unchanged) if any of
+/// them have already been rung
+fn add_rows(rows_rung: &mut HashSet<RowBuf>, rows: &[RowBuf]) -> bool {
+    for (idx, row) in rows.iter().enumerate() {
+        if !rows_rung.insert(row.clone()) {
+            for added_row in &rows[..idx] {
+                rows_rung.remove(added_row);
+            }
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use bellframe::Stage;
+    use jigsaw_utils::types::Position;
+
+    use super::*;
+    use crate::full::{FragEnd, FullState};
+
+    /// Plain Bob Minor, with a `14` bob at the lead end
+    fn plain_bob_minor() -> CompSpec {
+        let mut spec = CompSpec::empty(Stage::MINOR);
+        spec.add_method(
+            "Plain".to_owned(),
+            "P".to_owned(),
+            "x16x16x16,12".to_owned(),
+            vec![0],
+        )
+        .unwrap();
+        spec.add_call('-', "14".to_owned(), "LE".to_owned(), String::new())
+            .unwrap();
+        spec
+    }
+
+    fn params(calls: &[usize], require_truth: bool, goal: SearchGoal) -> SearchParams {
+        SearchParams {
+            methods: vec![MethodIdx::new(0)],
+            calls: calls.iter().copied().map(CallIdx::new).collect(),
+            min_length: 1,
+            max_length: 240,
+            require_truth,
+            goal,
+        }
+    }
+
+    /// Adds `touch` to `spec`, returning the resulting [`FullState`] and the new fragment's end
+    fn add_touch(spec: &CompSpec, touch: &Touch) -> (FullState, FragEnd) {
+        let mut spec = spec.clone();
+        let frag_idx = spec.add_touch(touch, Position::new(0.0, 0.0)).unwrap();
+        let full_state = FullState::new(&spec);
+        let end = full_state.fragments[frag_idx].end;
+        (full_state, end)
+    }
+
+    #[test]
+    fn plain_course() {
+        let spec = plain_bob_minor();
+        let touches = spec
+            .search_touches(params(&[], true, SearchGoal::FirstTouches(10)))
+            .unwrap();
+        // The only true touch without calls is the plain course
+        assert_eq!(touches.len(), 1);
+        assert_eq!(touches[0].length, 60);
+        assert_eq!(touches[0].leads.len(), 5);
+        assert!(touches[0].leads.iter().all(|lead| lead.call.is_none()));
+    }
+
+    #[test]
+    fn touches_come_round_and_are_true() {
+        let spec = plain_bob_minor();
+        let touches = spec
+            .search_touches(params(&[0], true, SearchGoal::FirstTouches(20)))
+            .unwrap();
+        assert_eq!(touches.len(), 20);
+        for touch in &touches {
+            assert!(touch.length <= 240);
+            let (full_state, end) = add_touch(&spec, touch);
+            assert_eq!(end, FragEnd::ComesRound);
+            assert_eq!(full_state.stats.part_len, touch.length);
+            assert!(full_state.falseness.is_true());
+        }
+    }
+
+    #[test]
+    fn false_touches() {
+        let spec = plain_bob_minor();
+        // Keep the touches short, so that the search without truth tries every touch
+        let short_params = |require_truth| SearchParams {
+            max_length: 120,
+            ..params(&[0], require_truth, SearchGoal::FirstTouches(usize::MAX))
+        };
+        let true_touches = spec.search_touches(short_params(true)).unwrap();
+        let all_touches = spec.search_touches(short_params(false)).unwrap();
+        // Without truth, every true touch is still found along with some false ones
+        assert!(true_touches.iter().all(|t| all_touches.contains(t)));
+        let false_touches = all_touches
+            .iter()
+            .filter(|t| !true_touches.contains(t))
+            .collect_vec();
+        assert!(!false_touches.is_empty());
+        for touch in false_touches {
+            let (full_state, end) = add_touch(&spec, touch);
+            assert_eq!(end, FragEnd::ComesRound);
+            assert!(!full_state.falseness.is_true());
+        }
+    }
+
+    #[test]
+    fn best_music() {
+        let mut spec = plain_bob_minor();
+        spec.add_music(&[], String::new(), Some("*56"), 1.0, false, None)
+            .unwrap();
+        let all_touches = spec
+            .search_touches(params(&[0], true, SearchGoal::FirstTouches(usize::MAX)))
+            .unwrap();
+        let best_touches = spec
+            .search_touches(params(&[0], true, SearchGoal::BestMusic(5)))
+            .unwrap();
+        assert_eq!(best_touches.len(), 5);
+        // The best touches are sorted by descending score, and no other touch scores more
+        let scores = best_touches.iter().map(|t| t.music_score).collect_vec();
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+        let num_better_touches = all_touches
+            .iter()
+            .filter(|t| t.music_score > scores[4])
+            .count();
+        assert!(num_better_touches < 5);
+    }
+
+    #[test]
+    fn join_fragments() {
+        let mut spec = plain_bob_minor();
+        let frag_a = spec
+            .add_fragment(MethodIdx::new(0), false, Position::new(0.0, 0.0))
+            .unwrap();
+        let frag_b = spec
+            .add_fragment(MethodIdx::new(0), false, Position::new(100.0, 0.0))
+            .unwrap();
+        // Join the lead head reached by `frag_a` back to the rounds at the start of `frag_b`
+        let mut search = spec
+            .start_join_search(
+                frag_a,
+                frag_b,
+                params(&[], true, SearchGoal::FirstTouches(1)),
+            )
+            .unwrap();
+        assert!(search.step(MAX_LEADS_TRIED));
+        let touch = &search.touches()[0];
+        assert_eq!(touch.length, 48);
+        // `frag_b` gets merged onto the end of `frag_a`, making 6 leads
+        spec.join_with_touch(frag_a, frag_b, touch).unwrap();
+        let full_state = FullState::new(&spec);
+        assert_eq!(full_state.fragments.len(), 1);
+        assert_eq!(full_state.fragments[frag_a].num_rows(), 6 * 12 + 1);
+    }
+}
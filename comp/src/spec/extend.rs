@@ -0,0 +1,306 @@
+//! Code for extending compositions to higher [`Stage`]s (e.g. lifting a tenors-together Major
+//! composition to Royal or Maximus).
+//!
+//! Each [`Method`] is replaced by the method from a [`MethodLib`] with the same name and class
+//! on the new [`Stage`] (e.g. Bristol Surprise Major becomes Bristol Surprise Royal).  The extra
+//! bells are added to the back of every [`Row`] in their home positions, so the part heads and
+//! [`Fragment`] start rows are lengthened and the tenors stay together.  Positions within each
+//! lead are kept relative to the nearest lead head or half-lead, so calls stay at their lead ends
+//! (or half-leads) even though the leads get longer.
+
+use std::{collections::HashMap, rc::Rc};
+
+use bellframe::{place_not::PnBlockParseError, Bell, RowBuf, Stage};
+use jigsaw_utils::{
+    indexed_vec::{CallVec, ChunkVec, FragIdx, FragVec, MethodVec},
+    types::Position,
+};
+
+use crate::{method_lib::MethodLib, place_not::PnError, Music};
+
+use super::{
+    method_title,
+    part_heads::{self, PartHeads},
+    Call, Chunk, CompSpec, Fragment, Method,
+};
+
+impl CompSpec {
+    /// Attempts to extend `self` to a larger [`Stage`], using the methods in `method_lib` as
+    /// the extensions of `self`'s [`Method`]s.  If anything can't be extended, then every
+    /// [`ExtensionProblem`] found is returned instead.
+    ///
+    /// [`Call`]s are extended by moving the places in the back half of the [`Row`]s up to the new
+    /// back (e.g. a `16` bob in Bristol Major becomes `18` in Bristol Royal), but lose their
+    /// calling positions.  The [`Music`] is replaced with runs on the new [`Stage`], since music
+    /// patterns are rarely meaningful on other [`Stage`]s.
+    pub fn extended_to_stage(
+        &self,
+        stage: Stage,
+        method_lib: &MethodLib,
+    ) -> Result<Self, Vec<ExtensionProblem>> {
+        if stage.num_bells() <= self.stage.num_bells() {
+            return Err(vec![ExtensionProblem::StageNotLarger {
+                from: self.stage,
+                to: stage,
+            }]);
+        }
+        let mut problems = Vec::new();
+
+        // Methods
+        let mut methods = MethodVec::new();
+        let mut method_map = HashMap::<*const Method, Rc<Method>>::new();
+        for method in &self.methods {
+            match extend_method(method, stage, method_lib) {
+                Ok(new_method) => {
+                    let new_method = Rc::new(new_method);
+                    method_map.insert(Rc::as_ptr(method), new_method.clone());
+                    methods.push(new_method);
+                }
+                Err(problem) => problems.push(problem),
+            }
+        }
+        // Calls
+        let mut calls = CallVec::new();
+        let mut call_map = HashMap::<*const Call, Rc<Call>>::new();
+        for call in &self.calls {
+            let place_notation = extend_place_notation(call.place_notation(), self.stage, stage);
+            match Call::new(
+                call.symbol(),
+                place_notation,
+                call.lead_location().to_owned(),
+                String::new(), // Calling positions can't be extended
+                stage,
+            ) {
+                Ok(new_call) => {
+                    let new_call = Rc::new(new_call);
+                    call_map.insert(Rc::as_ptr(call), new_call.clone());
+                    calls.push(new_call);
+                }
+                Err(error) => problems.push(ExtensionProblem::CallPlaceNotation {
+                    symbol: call.symbol(),
+                    error,
+                }),
+            }
+        }
+        // Part heads are specified by their generators, which are lengthened when parsed on the
+        // larger stage
+        let part_heads = PartHeads::parse(&self.part_heads.spec_string(), stage)
+            .map_err(|e| problems.push(ExtensionProblem::PartHeads(e)));
+        // Allowed repeats and proved bells refer to places on the old stage
+        if !self.proof_settings.is_default() {
+            problems.push(ExtensionProblem::ProofSettings);
+        }
+        // Fragments can only be extended if all their methods and calls were extended
+        let part_heads = match part_heads {
+            Ok(part_heads) if problems.is_empty() => part_heads,
+            _ => return Err(problems),
+        };
+        let mut fragments = FragVec::new();
+        for (frag_idx, frag) in self.fragments.iter_enumerated() {
+            match extend_fragment(frag, self.stage, stage, &method_map, &call_map) {
+                Some(new_frag) => {
+                    fragments.push(Rc::new(new_frag));
+                }
+                None => problems.push(ExtensionProblem::Fragment(frag_idx)),
+            }
+        }
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        let observation_bell = if self.observation_bell == Bell::tenor(self.stage) {
+            Bell::tenor(stage)
+        } else {
+            self.observation_bell
+        };
+        let music = (4..=7)
+            .map(|len| Music::runs_front_and_back(stage, len))
+            .collect();
+        Ok(CompSpec {
+            fragments,
+            part_heads: Rc::new(part_heads),
+            methods,
+            calls,
+            music: Rc::new(music),
+            length_target: self.length_target,
+            proof_settings: self.proof_settings.clone(),
+            observation_bell,
+            stage,
+        })
+    }
+}
+
+/// Finds the extension of `method` in `method_lib`: the method on `stage` with the same name and
+/// class
+fn extend_method(
+    method: &Method,
+    stage: Stage,
+    method_lib: &MethodLib,
+) -> Result<Method, ExtensionProblem> {
+    let title = method_title(&method.name(), method.class(), stage);
+    let lib_method = method_lib
+        .get(&title, stage)
+        .ok_or_else(|| ExtensionProblem::NoExtension(title.clone()))?;
+    let mut new_method = Method::new(
+        method.name().clone(),
+        method.shorthand().clone(),
+        stage,
+        lib_method.place_notation.clone(),
+        Default::default(),
+    )
+    .map_err(|error| ExtensionProblem::MethodPlaceNotation { title, error })?;
+    // Keep the rule-offs in the same places relative to the lead ends and half-leads
+    new_method.ruleoffs_above = method
+        .ruleoffs_above
+        .iter()
+        .map(|&idx| extend_sub_lead_index(idx, method.lead_len(), new_method.lead_len()))
+        .collect();
+    Ok(new_method)
+}
+
+/// Extends a [`Fragment`] to a larger [`Stage`], replacing its [`Method`]s and [`Call`]s with
+/// their extensions.  Returns `None` if any of its [`Chunk`]s end up empty.
+fn extend_fragment(
+    frag: &Fragment,
+    old_stage: Stage,
+    new_stage: Stage,
+    method_map: &HashMap<*const Method, Rc<Method>>,
+    call_map: &HashMap<*const Call, Rc<Call>>,
+) -> Option<Fragment> {
+    let mut chunks = ChunkVec::with_capacity(frag.chunks.len());
+    for chunk in &frag.chunks {
+        let new_chunk = match chunk.as_ref() {
+            Chunk::Method {
+                method,
+                start_sub_lead_index,
+                length,
+                transposition: _,
+            } => {
+                let new_method = method_map[&Rc::as_ptr(method)].clone();
+                let (old_len, new_len) = (method.lead_len(), new_method.lead_len());
+                // Extend both ends of the chunk (relative to the start of its first lead)
+                let end_idx = start_sub_lead_index + length;
+                let new_start = extend_sub_lead_index(*start_sub_lead_index, old_len, new_len);
+                let new_end = end_idx / old_len * new_len
+                    + extend_sub_lead_index(end_idx % old_len, old_len, new_len);
+                if new_end <= new_start {
+                    return None;
+                }
+                Chunk::method(new_method, new_start, new_end - new_start)
+            }
+            Chunk::Call {
+                call,
+                method,
+                start_sub_lead_index,
+            } => {
+                let new_method = method_map[&Rc::as_ptr(method)].clone();
+                let start_sub_lead_index = extend_sub_lead_index(
+                    *start_sub_lead_index,
+                    method.lead_len(),
+                    new_method.lead_len(),
+                );
+                Chunk::Call {
+                    call: call_map[&Rc::as_ptr(call)].clone(),
+                    method: new_method,
+                    start_sub_lead_index,
+                }
+            }
+        };
+        chunks.push(Rc::new(new_chunk));
+    }
+
+    // Add the extra bells to the back of the start row, and spread the fragments out so that the
+    // wider fragments don't overlap
+    let extra_bells = (old_stage.num_bells()..new_stage.num_bells()).map(Bell::from_index);
+    let start_row = RowBuf::from_bell_iter(frag.start_row.bell_iter().chain(extra_bells)).ok()?;
+    let width_ratio = new_stage.num_bells() as f32 / old_stage.num_bells() as f32;
+    Some(Fragment {
+        position: Position::new(frag.position.x * width_ratio, frag.position.y),
+        start_row: Rc::new(start_row),
+        chunks,
+        is_proved: frag.is_proved,
+        show_bluelines: frag.show_bluelines,
+    })
+}
+
+/// Converts an index within a lead of length `old_lead_len` into the corresponding index within a
+/// lead of length `new_lead_len`.  Indices in the first half of the lead stay the same distance
+/// from the lead head, and indices in the second half stay the same distance from the next lead
+/// head.
+fn extend_sub_lead_index(idx: usize, old_lead_len: usize, new_lead_len: usize) -> usize {
+    match (idx * 2).cmp(&old_lead_len) {
+        std::cmp::Ordering::Less => idx,
+        std::cmp::Ordering::Equal => new_lead_len / 2,
+        std::cmp::Ordering::Greater => new_lead_len.saturating_sub(old_lead_len - idx),
+    }
+}
+
+/// Extends some place notation to a larger [`Stage`], by moving every place in the back half of
+/// `old_stage` up so that it's the same distance from the back of `new_stage`
+fn extend_place_notation(place_notation: &str, old_stage: Stage, new_stage: Stage) -> String {
+    let num_extra_bells = new_stage.num_bells() - old_stage.num_bells();
+    place_notation
+        .chars()
+        .map(|c| match Bell::from_name(c) {
+            Some(place) if place.index() * 2 >= old_stage.num_bells() => {
+                Bell::from_index(place.index() + num_extra_bells)
+                    .to_char()
+                    .unwrap_or(c)
+            }
+            _ => c, // Not a place (e.g. `x` or `.`), or in the front half of the row
+        })
+        .collect()
+}
+
+/// The reasons why a composition can't be extended with [`CompSpec::extended_to_stage`]
+#[derive(Debug, Clone)]
+pub enum ExtensionProblem {
+    /// Compositions can only be extended to larger [`Stage`]s
+    StageNotLarger { from: Stage, to: Stage },
+    /// The method library doesn't contain a method with this title
+    NoExtension(String),
+    /// The place notation of a method's extension couldn't be parsed
+    MethodPlaceNotation { title: String, error: PnError },
+    /// The extended place notation of a [`Call`] couldn't be parsed
+    CallPlaceNotation {
+        symbol: char,
+        error: PnBlockParseError,
+    },
+    /// The part heads couldn't be parsed on the new [`Stage`]
+    PartHeads(part_heads::ParseError),
+    /// The composition has non-default proving settings, which only make sense on one [`Stage`]
+    ProofSettings,
+    /// A [`Fragment`] contains a part of a lead which becomes empty when extended
+    Fragment(FragIdx),
+}
+
+impl std::fmt::Display for ExtensionProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExtensionProblem::StageNotLarger { from, to } => write!(
+                f,
+                "Can't extend from {} to {} bells",
+                from.num_bells(),
+                to.num_bells()
+            ),
+            ExtensionProblem::NoExtension(title) => {
+                write!(f, "No method called {} in the method library", title)
+            }
+            ExtensionProblem::MethodPlaceNotation { title, error } => {
+                write!(f, "Invalid place notation for {}: {}", title, error)
+            }
+            ExtensionProblem::CallPlaceNotation { symbol, error } => {
+                write!(f, "Can't extend call '{}': {}", symbol, error)
+            }
+            ExtensionProblem::PartHeads(e) => write!(f, "Can't extend part heads: {}", e),
+            ExtensionProblem::ProofSettings => {
+                write!(f, "Can't extend proving settings to another stage")
+            }
+            ExtensionProblem::Fragment(idx) => {
+                write!(f, "Can't extend the leads in fragment {}", idx.index())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExtensionProblem {}
@@ -0,0 +1,433 @@
+//! Code for importing compositions from plain lists of [`Row`]s (e.g. the row dumps generated by
+//! CompLib).
+//!
+//! Each line of the input contains one [`Row`], and the final [`Row`] is treated as the leftover
+//! row (usually the rounds at the end of the composition).  The [`Row`]s are then split into
+//! leads by greedily matching them against the plain leads of the available [`Method`]s, using
+//! the available [`Call`]s wherever the plain [`Method`] doesn't fit.  Leads which don't follow
+//! on from the previous lead (even with a call) start a new [`Fragment`].
+
+use std::rc::Rc;
+
+use bellframe::{Bell, InvalidRowError, Row, RowBuf, Stage};
+use index_vec::index_vec;
+use jigsaw_utils::{
+    indexed_vec::{CallSlice, CallVec, ChunkVec, FragVec, MethodSlice, MethodVec},
+    types::Position,
+};
+
+use crate::{place_not::PnError, Music};
+
+use super::{part_heads::PartHeads, proof::ProofSettings, Call, Chunk, CompSpec, Fragment, Method};
+
+/// The on-screen position of the first imported [`Fragment`]
+const FIRST_FRAG_POS: Position = Position::new(200.0, 100.0);
+/// The horizontal distance between consecutive imported [`Fragment`]s
+const FRAG_SPACING: f32 = 200.0;
+
+/// The definition of a [`Method`] which can be used to match imported [`Row`]s
+#[derive(Debug, Clone)]
+pub struct ImportMethod {
+    pub name: String,
+    pub shorthand: String,
+    pub place_notation: String,
+}
+
+impl CompSpec {
+    /// Creates a new [`CompSpec`] from a list of [`Row`]s (one per line), using the given
+    /// [`ImportMethod`]s to reconstruct the leads.  No [`Call`]s are used, so every call starts a
+    /// new [`Fragment`].
+    pub fn from_row_dump(
+        stage: Stage,
+        rows: &str,
+        methods: &[ImportMethod],
+    ) -> Result<Self, ImportError> {
+        let methods = methods
+            .iter()
+            .map(|m| {
+                Method::with_lead_end_ruleoff(
+                    m.name.clone(),
+                    m.shorthand.clone(),
+                    stage,
+                    m.place_notation.clone(),
+                )
+                .map(Rc::new)
+                .map_err(|error| ImportError::PlaceNotation {
+                    name: m.name.clone(),
+                    error,
+                })
+            })
+            .collect::<Result<MethodVec<_>, _>>()?;
+        import(stage, rows, methods, index_vec![], Rc::new(Vec::new()))
+    }
+
+    /// Creates a new [`CompSpec`] from a list of [`Row`]s (one per line), using the [`Method`]s,
+    /// [`Call`]s and [`Music`] of `self`.
+    pub fn import_rows(&self, rows: &str) -> Result<Self, ImportError> {
+        import(
+            self.stage,
+            rows,
+            self.methods.clone(),
+            self.calls.clone(),
+            self.music.clone(),
+        )
+    }
+}
+
+/// The possible ways that importing a list of [`Row`]s can fail
+#[derive(Debug, Clone)]
+pub enum ImportError {
+    /// A [`Method`]'s place notation couldn't be parsed
+    PlaceNotation { name: String, error: PnError },
+    /// There were no [`Method`]s to match the [`Row`]s against
+    NoMethods,
+    /// The input contained no [`Row`]s
+    NoRows,
+    /// A line couldn't be parsed as a [`Row`].  Lines are numbered from 1.
+    InvalidRow { line: usize, error: InvalidRowError },
+    /// None of the [`Method`]s (or [`Call`]s) generated a full lead starting at the given line
+    NoMatchingMethod { line: usize },
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::PlaceNotation { name, error } => {
+                write!(f, "Invalid place notation for {}: {}", name, error)
+            }
+            ImportError::NoMethods => write!(f, "No methods to match rows against"),
+            ImportError::NoRows => write!(f, "No rows to import"),
+            ImportError::InvalidRow { line, error } => {
+                write!(f, "Invalid row on line {}: {}", line, error)
+            }
+            ImportError::NoMatchingMethod { line } => {
+                write!(f, "No method matches the lead starting on line {}", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+fn import(
+    stage: Stage,
+    rows_str: &str,
+    methods: MethodVec<Rc<Method>>,
+    calls: CallVec<Rc<Call>>,
+    music: Rc<Vec<Music>>,
+) -> Result<CompSpec, ImportError> {
+    if methods.is_empty() {
+        return Err(ImportError::NoMethods);
+    }
+    // Parse the rows, skipping any blank lines
+    let rows = rows_str
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_idx, line)| {
+            RowBuf::parse_with_stage(line.trim(), stage).map_err(|error| ImportError::InvalidRow {
+                line: line_idx + 1,
+                error,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // The last row is leftover, so we need at least 2 rows to generate anything
+    if rows.len() < 2 {
+        return Err(ImportError::NoRows);
+    }
+    let num_rung_rows = rows.len() - 1;
+
+    let mut fragments = FragVec::new();
+    let mut frag_start_idx = 0;
+    let mut chunks = ChunkVec::new();
+    let mut row_idx = 0;
+    while row_idx < num_rung_rows {
+        let method = best_method(&rows, row_idx, &methods);
+        let lead = match_lead(&rows, row_idx, method, &calls)?;
+        let next_row_idx = row_idx + lead.len;
+        chunks.extend(lead.chunks);
+        // If the next row doesn't follow on from this lead, then start a new fragment
+        if !lead.is_continuous || next_row_idx == num_rung_rows {
+            let mut fragment = Fragment {
+                position: Position::new(
+                    FIRST_FRAG_POS.x + FRAG_SPACING * fragments.len() as f32,
+                    FIRST_FRAG_POS.y,
+                ),
+                start_row: Rc::new(rows[frag_start_idx].clone()),
+                chunks: std::mem::take(&mut chunks),
+                is_proved: true,
+                show_bluelines: false,
+            };
+            // Consecutive plain leads of the same method become one chunk
+            fragment.merge_method_chunks();
+            fragments.push(Rc::new(fragment));
+            frag_start_idx = next_row_idx;
+        }
+        row_idx = next_row_idx;
+    }
+
+    Ok(CompSpec {
+        fragments,
+        part_heads: Rc::new(PartHeads::one_part(stage)),
+        methods,
+        calls,
+        music,
+        length_target: None,
+        proof_settings: Rc::new(ProofSettings::default()),
+        observation_bell: Bell::tenor(stage),
+        stage,
+    })
+}
+
+/// One lead of the imported [`Row`]s, reconstructed from a [`Method`] and some [`Call`]s
+struct LeadMatch {
+    chunks: Vec<Rc<Chunk>>,
+    /// The number of [`Row`]s covered by `chunks` (never more than one lead)
+    len: usize,
+    /// `true` if the [`Row`] after this lead follows on from the last [`Chunk`]
+    is_continuous: bool,
+}
+
+/// Find the [`Method`] whose plain lead matches the most [`Row`]s starting at `start_idx`.  Ties
+/// are broken in favour of the first [`Method`].
+fn best_method<'m>(
+    rows: &[RowBuf],
+    start_idx: usize,
+    methods: &'m MethodSlice<Rc<Method>>,
+) -> &'m Rc<Method> {
+    let num_matching_rows = |method: &Rc<Method>| {
+        // Stop before the leftover row
+        let max_len = method.lead_len().min(rows.len() - 1 - start_idx);
+        (0..max_len)
+            .take_while(|&j| {
+                transposed(&rows[start_idx], method.inner.row_in_plain_lead(j))
+                    == rows[start_idx + j]
+            })
+            .count()
+    };
+    let mut best = &methods[0];
+    let mut best_len = num_matching_rows(best);
+    for method in methods.iter().skip(1) {
+        let len = num_matching_rows(method);
+        if len > best_len {
+            best = method;
+            best_len = len;
+        }
+    }
+    best
+}
+
+/// Match one lead of `method` against the [`Row`]s starting at `start_idx`, replacing the plain
+/// [`Method`] with a [`Call`] wherever the [`Row`]s follow the [`Call`] instead.  The lead may be
+/// cut short by the end of the composition, or by a [`Row`] which follows neither the plain
+/// [`Method`] nor a [`Call`] at the end of the lead.  Any other mismatch is an error.
+fn match_lead(
+    rows: &[RowBuf],
+    start_idx: usize,
+    method: &Rc<Method>,
+    calls: &CallSlice<Rc<Call>>,
+) -> Result<LeadMatch, ImportError> {
+    let num_rung_rows = rows.len() - 1;
+    let lead_len = method.lead_len();
+    // The lead's rows are split into chunks at every call
+    let mut chunks = Vec::new();
+    let mut plain_chunk_start = 0;
+    let mut sub_lead_idx = 0;
+    let mut is_continuous = true;
+    while sub_lead_idx < lead_len && start_idx + sub_lead_idx < num_rung_rows {
+        let row_idx = start_idx + sub_lead_idx;
+        if let Some(call) = matching_call(rows, row_idx, method, sub_lead_idx, calls) {
+            if plain_chunk_start < sub_lead_idx {
+                let plain_len = sub_lead_idx - plain_chunk_start;
+                chunks.push(Rc::new(Chunk::method(
+                    method.clone(),
+                    plain_chunk_start,
+                    plain_len,
+                )));
+            }
+            chunks.push(Rc::new(Chunk::Call {
+                call: call.clone(),
+                method: method.clone(),
+                start_sub_lead_index: sub_lead_idx,
+            }));
+            sub_lead_idx += call.len();
+            plain_chunk_start = sub_lead_idx;
+            continue;
+        }
+        let plain_next_row = transposed(
+            &rows[row_idx],
+            &plain_transposition(method, sub_lead_idx, 1),
+        );
+        if rows[row_idx + 1] == plain_next_row {
+            sub_lead_idx += 1;
+        } else if sub_lead_idx + 1 == lead_len || row_idx + 1 == num_rung_rows {
+            // The lead (or composition) ends here, but the next row doesn't follow on
+            sub_lead_idx += 1;
+            is_continuous = false;
+        } else {
+            return Err(ImportError::NoMatchingMethod {
+                line: start_idx + 1,
+            });
+        }
+    }
+    if plain_chunk_start < sub_lead_idx {
+        let plain_len = sub_lead_idx - plain_chunk_start;
+        chunks.push(Rc::new(Chunk::method(
+            method.clone(),
+            plain_chunk_start,
+            plain_len,
+        )));
+    }
+    Ok(LeadMatch {
+        chunks,
+        len: sub_lead_idx,
+        is_continuous,
+    })
+}
+
+/// Returns the [`Call`] which starts at the [`Row`] at `row_idx` (which is `sub_lead_idx` rows
+/// into a lead of `method`), if the [`Row`]s follow that [`Call`] but not the plain [`Method`].
+/// The whole of the [`Call`]'s cover has to match, including the [`Row`] after it.
+fn matching_call<'c>(
+    rows: &[RowBuf],
+    row_idx: usize,
+    method: &Method,
+    sub_lead_idx: usize,
+    calls: &'c CallSlice<Rc<Call>>,
+) -> Option<&'c Rc<Call>> {
+    let start_row = &rows[row_idx];
+    // Calls can't cover the leftover row, and have to fit inside the lead
+    let fits = |len: usize| row_idx + len < rows.len() && sub_lead_idx + len <= method.lead_len();
+    let follows_plain_method = |len: usize| {
+        (1..=len).all(|i| {
+            rows[row_idx + i]
+                == transposed(start_row, &plain_transposition(method, sub_lead_idx, i))
+        })
+    };
+    calls.iter().find(|call| {
+        let len = call.len();
+        fits(len)
+            && call.can_end_at(method, sub_lead_idx + len)
+            && !follows_plain_method(len)
+            && call
+                .rows()
+                .chain(std::iter::once(call.transposition()))
+                .enumerate()
+                .all(|(i, call_row)| rows[row_idx + i] == transposed(start_row, call_row))
+    })
+}
+
+/// The transposition made by the plain course of `method` from the [`Row`] at `sub_lead_idx` to
+/// the [`Row`] `len` rows later
+fn plain_transposition(method: &Method, sub_lead_idx: usize, len: usize) -> RowBuf {
+    // Unwrap is safe because all the rows of a `Method` share the same `Stage`
+    Row::solve_ax_equals_b(
+        method.inner.row_in_plain_lead(sub_lead_idx),
+        &method.inner.row_in_plain_course(sub_lead_idx + len),
+    )
+    .unwrap()
+}
+
+/// Returns `start_row` transposed by `row` (i.e. `start_row * row`)
+fn transposed(start_row: &Row, row: &Row) -> RowBuf {
+    // Unwrap is safe because all the imported rows, methods and calls share the same stage
+    start_row.mul_result(row).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use jigsaw_utils::indexed_vec::{CallIdx, FragIdx};
+
+    use super::*;
+
+    /// The [`Row`]s of every [`Fragment`] in the first part of `spec`, including the leftover
+    /// [`Row`]s
+    fn fragment_rows(spec: &CompSpec) -> Vec<String> {
+        spec.expand_fragments()
+            .iter()
+            .map(|frag| frag.rows_in_one_part.iter().join("\n"))
+            .collect()
+    }
+
+    /// Imports the first [`Fragment`] of `spec` using its own [`Method`]s and [`Call`]s, and
+    /// checks that the imported [`Fragment`]s generate the same [`Row`]s
+    fn check_round_trip(spec: &CompSpec) -> CompSpec {
+        let rows = fragment_rows(spec).remove(0);
+        let imported = spec.import_rows(&rows).unwrap();
+        assert_eq!(fragment_rows(&imported), vec![rows]);
+        imported
+    }
+
+    fn num_calls(spec: &CompSpec) -> usize {
+        spec.fragments[FragIdx::new(0)]
+            .chunks
+            .iter()
+            .filter(|chunk| matches!(chunk.as_ref(), Chunk::Call { .. }))
+            .count()
+    }
+
+    #[test]
+    fn plain_leads() {
+        let imported = check_round_trip(&CompSpec::example());
+        assert_eq!(num_calls(&imported), 0);
+    }
+
+    #[test]
+    fn calls_stay_in_fragment() {
+        let mut spec = CompSpec::example();
+        // Bob the second lead end, and single the fourth
+        spec.set_call(FragIdx::new(0), 64, Some(CallIdx::new(0)))
+            .unwrap();
+        spec.set_call(FragIdx::new(0), 128, Some(CallIdx::new(1)))
+            .unwrap();
+        let imported = check_round_trip(&spec);
+        assert_eq!(num_calls(&imported), 2);
+    }
+
+    #[test]
+    fn call_covering_several_rows() {
+        let mut spec = CompSpec::example();
+        let call_idx = spec
+            .add_call('x', "58.14".to_owned(), "LE".to_owned(), String::new())
+            .unwrap();
+        spec.set_call(FragIdx::new(0), 32, Some(call_idx)).unwrap();
+        let imported = check_round_trip(&spec);
+        assert_eq!(num_calls(&imported), 1);
+    }
+
+    #[test]
+    fn unknown_call_starts_new_fragment() {
+        let mut spec = CompSpec::example();
+        spec.set_call(FragIdx::new(0), 64, Some(CallIdx::new(0)))
+            .unwrap();
+        let rows = fragment_rows(&spec).remove(0);
+        // Without any calls, the bob can't be matched
+        let spec_without_calls = CompSpec {
+            calls: index_vec![],
+            ..spec
+        };
+        let imported = spec_without_calls.import_rows(&rows).unwrap();
+        assert_eq!(imported.fragments.len(), 2);
+    }
+
+    #[test]
+    fn no_matching_method() {
+        let rows = "12345678\n21436587\n13245678\n12345678";
+        let result = CompSpec::example().import_rows(rows);
+        assert!(matches!(
+            result,
+            Err(ImportError::NoMatchingMethod { line: 1 })
+        ));
+    }
+
+    #[test]
+    fn invalid_row() {
+        let rows = "12345678\n\n11345678\n12345678";
+        let result = CompSpec::example().import_rows(rows);
+        assert!(matches!(
+            result,
+            Err(ImportError::InvalidRow { line: 3, .. })
+        ));
+    }
+}
@@ -1,28 +1,43 @@
+pub mod extend;
+pub mod import;
 pub mod part_heads;
+pub mod proof;
+pub mod save_load;
 
 use std::{
     cell::{Cell, Ref, RefCell},
     collections::HashSet,
     convert::{TryFrom, TryInto},
+    mem::size_of,
     ops::Deref,
     rc::Rc,
 };
 
 use bellframe::{
-    music::Regex, row::RowAccumulator, AnnotBlock, IncompatibleStages, Row, RowBuf, Stage,
+    method::class::FullClass, music::Regex, place_not::PnBlockParseError, row::RowAccumulator,
+    AnnotBlock, Bell, IncompatibleStages, PnBlock, Row, RowBuf, Stage,
 };
-use emath::Pos2;
 use index_vec::index_vec;
-use jigsaw_utils::indexed_vec::{
-    ChunkIdx, ChunkVec, FragIdx, FragVec, MethodSlice, MethodVec, RowIdx, RowVec,
+use itertools::Itertools;
+use jigsaw_utils::{
+    indexed_vec::{
+        CallIdx, CallSlice, CallVec, ChunkIdx, ChunkVec, FragIdx, FragSlice, FragVec, MethodIdx,
+        MethodSlice, MethodVec, PartIdx, RowIdx, RowVec,
+    },
+    types::Position,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    check_regex,
     expanded_frag::{ExpandedFrag, RowData},
-    Music,
+    lead_head_code::lead_head_code,
+    place_not::{self, PnError},
+    search::Touch,
+    Music, RegexError,
 };
 
-use self::part_heads::PartHeads;
+use self::{part_heads::PartHeads, proof::ProofSettings};
 
 /// The minimal but complete specification for a (partial) composition.  `CompSpec` is used for
 /// undo history, and is designed to be a very compact representation which is cheap to clone and
@@ -35,9 +50,16 @@ pub struct CompSpec {
     fragments: FragVec<Rc<Fragment>>,
     part_heads: Rc<PartHeads>,
     methods: MethodVec<Rc<Method>>,
-    calls: Vec<Rc<Call>>,
+    calls: CallVec<Rc<Call>>,
     // TODO: Make this structure use `Rc`s internally
     music: Rc<Vec<Music>>,
+    /// The range of lengths which the user is aiming for, if any
+    length_target: Option<LengthTarget>,
+    /// Which [`Row`]s are compared when proving the composition
+    proof_settings: Rc<ProofSettings>,
+    /// The [`Bell`] whose position names the calling positions and course ends (usually the
+    /// tenor)
+    observation_bell: Bell,
     stage: Stage,
 }
 
@@ -49,14 +71,16 @@ impl CompSpec {
 
     /// Creates a [`CompSpec`] with a given [`Stage`] but no [`PartHeads`], [`Method`]s, [`Call`]s
     /// or [`Fragment`]s.
-    #[allow(dead_code)]
     pub fn empty(stage: Stage) -> Self {
         CompSpec {
             fragments: index_vec![],
             part_heads: Rc::new(PartHeads::one_part(stage)),
             methods: index_vec![],
-            calls: vec![],
+            calls: index_vec![],
             music: Rc::new(vec![]),
+            length_target: None,
+            proof_settings: Rc::new(ProofSettings::default()),
+            observation_bell: Bell::tenor(stage),
             stage,
         }
     }
@@ -68,10 +92,12 @@ impl CompSpec {
         /// Create a new [`Method`] by parsing a string of place notation
         fn gen_method(shorthand: &str, name: &str, pn_str: &str) -> Rc<Method> {
             let method = Method::with_lead_end_ruleoff(
-                bellframe::Method::from_place_not_string(String::new(), STAGE, pn_str).unwrap(),
                 name.to_owned(),
                 shorthand.to_string(),
-            );
+                STAGE,
+                pn_str.to_owned(),
+            )
+            .unwrap();
             Rc::new(method)
         }
 
@@ -98,26 +124,37 @@ impl CompSpec {
             .collect::<ChunkVec<_>>();
 
         let fragment = Fragment {
-            position: Pos2::new(200.0, 100.0),
+            position: Position::new(200.0, 100.0),
             start_row: Rc::new(RowBuf::rounds(STAGE)),
             chunks,
             is_proved: true,
+            show_bluelines: false,
         };
 
+        /// Create a named [`Music::Regex`] with the default weight
+        fn named_regex(name: &str, regex: &str) -> Music {
+            Music::Regex {
+                name: Some(name.to_owned()),
+                regex: Regex::parse(regex),
+                weight: Music::DEFAULT_WEIGHT,
+                count_wraps: false,
+                parts: None,
+            }
+        }
+
         let music = Rc::new(vec![
-            Music::Group(
-                "56s/65s".to_owned(),
-                vec![
-                    Music::Regex(Some("65s".to_owned()), Regex::parse("*6578")),
-                    Music::Regex(Some("56s".to_owned()), Regex::parse("*5678")),
-                ],
-            ),
+            Music::Group {
+                name: "56s/65s".to_owned(),
+                sub_groups: vec![named_regex("65s", "*6578"), named_regex("56s", "*5678")],
+                weight: Music::DEFAULT_WEIGHT,
+                parts: None,
+            },
             Music::runs_front_and_back(Stage::MAJOR, 4),
             Music::runs_front_and_back(Stage::MAJOR, 5),
             Music::runs_front_and_back(Stage::MAJOR, 6),
             Music::runs_front_and_back(Stage::MAJOR, 7),
-            Music::Regex(Some("Queens".to_owned()), Regex::parse("13572468")),
-            Music::Regex(Some("Backrounds".to_owned()), Regex::parse("87654321")),
+            named_regex("Queens", "13572468"),
+            named_regex("Backrounds", "87654321"),
         ]);
 
         CompSpec {
@@ -126,8 +163,32 @@ impl CompSpec {
                 PartHeads::parse("18234567", STAGE).unwrap(), /* PartHeads::one_part(STAGE) */
             ),
             methods,
-            calls: vec![], // No calls for now
+            calls: index_vec![
+                Rc::new(
+                    Call::new(
+                        '-',
+                        "14".to_owned(),
+                        "LE".to_owned(),
+                        "LIBFVMWH".to_owned(),
+                        STAGE
+                    )
+                    .unwrap()
+                ),
+                Rc::new(
+                    Call::new(
+                        's',
+                        "1234".to_owned(),
+                        "LE".to_owned(),
+                        "LBTFVMWH".to_owned(),
+                        STAGE
+                    )
+                    .unwrap()
+                ),
+            ],
             music,
+            length_target: None,
+            proof_settings: Rc::new(ProofSettings::default()),
+            observation_bell: Bell::tenor(STAGE),
             stage: STAGE,
         }
     }
@@ -143,6 +204,14 @@ impl CompSpec {
             .collect()
     }
 
+    pub(crate) fn fragments(&self) -> &FragSlice<Rc<Fragment>> {
+        &self.fragments
+    }
+
+    pub(crate) fn calls(&self) -> &CallSlice<Rc<Call>> {
+        &self.calls
+    }
+
     pub(crate) fn part_heads(&self) -> &Rc<PartHeads> {
         &self.part_heads
     }
@@ -151,14 +220,77 @@ impl CompSpec {
         &self.methods
     }
 
-    pub(crate) fn music(&self) -> &[Music] {
+    pub(crate) fn music(&self) -> &Rc<Vec<Music>> {
         &self.music
     }
 
-    pub(crate) fn stage(&self) -> Stage {
+    pub(crate) fn proof_settings(&self) -> &Rc<ProofSettings> {
+        &self.proof_settings
+    }
+
+    pub fn stage(&self) -> Stage {
         self.stage
     }
 
+    /// The [`Bell`] whose position names the calling positions and course ends
+    pub fn observation_bell(&self) -> Bell {
+        self.observation_bell
+    }
+
+    /// Returns where a [`Fragment`] starts: its first [`Row`], and how far through a lead of its
+    /// first [`Method`] that [`Row`] comes
+    pub fn fragment_start(&self, frag_idx: FragIdx) -> Result<FragStart, EditError> {
+        let frag = self.get_fragment(frag_idx)?;
+        // Unwrap is safe because every `Fragment` contains at least one `Chunk`
+        let (method, sub_lead_index) = frag.chunks.iter().next().unwrap().method_and_start();
+        Ok(FragStart {
+            start_row: frag.start_row.as_ref().clone(),
+            sub_lead_index,
+            lead_len: method.lead_len(),
+        })
+    }
+
+    /// The range of lengths which the user is aiming for, if any
+    pub fn length_target(&self) -> Option<LengthTarget> {
+        self.length_target
+    }
+
+    /// Returns the index of the first [`Method`] with a given `shorthand`, if one exists
+    pub fn method_by_shorthand(&self, shorthand: &str) -> Option<MethodIdx> {
+        self.methods
+            .iter_enumerated()
+            .find(|(_, m)| *m.shorthand() == shorthand)
+            .map(|(idx, _)| idx)
+    }
+
+    /// A rough estimate of the number of bytes of memory used by this [`CompSpec`].  Any data
+    /// shared between [`CompSpec`]s (through [`Rc`]s) is counted in full by every [`CompSpec`]
+    /// which uses it, so this is an overestimate when used over a whole undo history.
+    pub(crate) fn estimated_size(&self) -> usize {
+        let row_size = self.stage.num_bells();
+        let frag_size: usize = self
+            .fragments
+            .iter()
+            .map(|f| {
+                size_of::<Fragment>()
+                    + row_size
+                    + f.chunks.len() * (size_of::<Rc<Chunk>>() + size_of::<Chunk>() + row_size)
+            })
+            .sum();
+        let method_size: usize = self
+            .methods
+            .iter()
+            .map(|m| size_of::<Method>() + m.lead_len() * row_size)
+            .sum();
+        size_of::<CompSpec>()
+            + self.fragments.len() * size_of::<Rc<Fragment>>()
+            + frag_size
+            + method_size
+            + self.calls.len() * (size_of::<Rc<Call>>() + size_of::<Call>())
+            // Part heads are stored twice: once in a `Vec` and once in a `HashSet`
+            + self.part_heads.len() * row_size * 2
+    }
+
     /////////////////////////
     // MODIFIERS & ACTIONS //
     /////////////////////////
@@ -206,6 +338,42 @@ impl CompSpec {
         Ok(())
     }
 
+    /// Moves a [`Fragment`] to a new on-screen `position`
+    pub fn move_fragment(
+        &mut self,
+        frag_idx: FragIdx,
+        position: Position,
+    ) -> Result<(), EditError> {
+        self.get_fragment_mut(frag_idx)?.position = position;
+        Ok(())
+    }
+
+    /// Sets (or, if `None`, removes) the range of lengths which the user is aiming for
+    pub fn set_length_target(&mut self, target: Option<LengthTarget>) -> Result<(), EditError> {
+        if let Some(LengthTarget { min, max }) = target {
+            if min > max {
+                return Err(EditError::InvalidLengthTarget { min, max });
+            }
+        }
+        self.length_target = target;
+        Ok(())
+    }
+
+    /// Overwrites the [`ProofSettings`] of `self`.  These must have been parsed with the same
+    /// [`Stage`] as `self`.
+    pub fn set_proof_settings(&mut self, proof_settings: ProofSettings) {
+        self.proof_settings = Rc::new(proof_settings);
+    }
+
+    /// Sets the [`Bell`] whose position names the calling positions and course ends
+    pub fn set_observation_bell(&mut self, bell: Bell) -> Result<(), EditError> {
+        if bell.index() >= self.stage.num_bells() {
+            return Err(EditError::BellOutOfStage(bell));
+        }
+        self.observation_bell = bell;
+        Ok(())
+    }
+
     /// Deletes the [`Fragment`] with a given [`FragIdx`]
     pub fn delete_fragment(&mut self, frag_idx: FragIdx) -> Result<(), EditError> {
         self.get_fragment(frag_idx)?; // Return error if `frag_idx` is out-of-bounds
@@ -218,7 +386,7 @@ impl CompSpec {
         &mut self,
         frag_idx: FragIdx,
         row_idx: isize,
-        new_frag_pos: Pos2,
+        new_frag_pos: Position,
     ) -> Result<(), EditError> {
         let frag_to_split = self.get_fragment_mut(frag_idx)?;
         let new_frag = frag_to_split.split(frag_idx, row_idx, new_frag_pos)?;
@@ -226,6 +394,602 @@ impl CompSpec {
         Ok(())
     }
 
+    /// Deletes the [`Row`]s in `start..end` of a [`Fragment`].  The [`Row`]s after the deleted
+    /// range are transposed so that they carry straight on from the [`Row`]s before it, unless
+    /// `start` is `0` (in which case the remaining [`Row`]s are left unchanged).
+    pub fn delete_rows(
+        &mut self,
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+    ) -> Result<(), EditError> {
+        self.get_fragment_mut(frag_idx)?
+            .delete_rows(frag_idx, start, end)
+    }
+
+    /// Changes the [`Row`]s in `start..end` of a [`Fragment`] to be rung by a different
+    /// [`Method`], keeping each [`Row`]'s position within the lead.  Any [`Call`]s in that range
+    /// are kept, and the [`Row`]s after the range are transposed so that they still follow on.
+    pub fn set_method(
+        &mut self,
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+        method_idx: MethodIdx,
+    ) -> Result<(), EditError> {
+        let method = self.get_method(method_idx)?.clone();
+        self.get_fragment_mut(frag_idx)?
+            .set_method(frag_idx, start, end, &method)
+    }
+
+    /// Adds a new [`Fragment`] containing either a plain lead or a plain course of a [`Method`],
+    /// starting from rounds.  Returns the index of the new [`Fragment`].
+    pub fn add_fragment(
+        &mut self,
+        method_idx: MethodIdx,
+        is_course: bool,
+        position: Position,
+    ) -> Result<FragIdx, EditError> {
+        let chunk = self.plain_chunk(method_idx, is_course)?;
+        let fragment = Fragment {
+            position,
+            start_row: Rc::new(RowBuf::rounds(self.stage)),
+            chunks: index_vec![Rc::new(chunk)],
+            is_proved: true,
+            show_bluelines: false,
+        };
+        Ok(self.fragments.push(Rc::new(fragment)))
+    }
+
+    /// Adds a new [`Fragment`] containing a [`Touch`] (usually generated by
+    /// [`CompSpec::search_touches`]), starting from rounds at `position`.  Returns the index of the
+    /// new [`Fragment`].
+    pub fn add_touch(&mut self, touch: &Touch, position: Position) -> Result<FragIdx, EditError> {
+        let mut fragment = Fragment {
+            position,
+            start_row: Rc::new(RowBuf::rounds(self.stage)),
+            chunks: self.touch_chunks(touch)?,
+            is_proved: true,
+            show_bluelines: false,
+        };
+        fragment.merge_method_chunks();
+        Ok(self.fragments.push(Rc::new(fragment)))
+    }
+
+    /// Joins `frag_b` onto the end of `frag_a` by inserting the leads of a [`Touch`] (usually
+    /// generated by [`CompSpec::start_join_search`]) between them.  The [`Touch`] must lead from
+    /// the leftover [`Row`] of `frag_a` to the first [`Row`] of `frag_b`.
+    pub fn join_with_touch(
+        &mut self,
+        frag_a: FragIdx,
+        frag_b: FragIdx,
+        touch: &Touch,
+    ) -> Result<(), EditError> {
+        let chunks = self.touch_chunks(touch)?;
+        // Check that the touch still joins the fragments, since they could have been edited since
+        // the touch was found
+        let mut extended_frag = self.get_fragment(frag_a)?.clone();
+        extended_frag.chunks.extend(chunks);
+        if extended_frag.leftover_row() != *self.get_fragment(frag_b)?.start_row {
+            return Err(EditError::TouchDoesntJoin { frag_a, frag_b });
+        }
+        *self.get_fragment_mut(frag_a)? = extended_frag;
+        self.join_fragments(frag_a, frag_b)
+    }
+
+    /// Returns the leftover [`Row`] of `frag_a` and the first [`Row`] of `frag_b`, i.e. the
+    /// [`Row`]s which have to be connected to join `frag_b` onto the end of `frag_a`
+    pub(crate) fn join_rows(
+        &self,
+        frag_a: FragIdx,
+        frag_b: FragIdx,
+    ) -> Result<(RowBuf, RowBuf), EditError> {
+        if frag_a == frag_b {
+            return Err(EditError::JoinFragToItself(frag_a));
+        }
+        let leftover_row = self.get_fragment(frag_a)?.leftover_row();
+        let start_row = self.get_fragment(frag_b)?.start_row.as_ref().clone();
+        Ok((leftover_row, start_row))
+    }
+
+    /// Creates the [`Chunk`]s which ring the leads of a [`Touch`]
+    fn touch_chunks(&self, touch: &Touch) -> Result<ChunkVec<Rc<Chunk>>, EditError> {
+        let mut chunks = ChunkVec::new();
+        for lead in &touch.leads {
+            let method = self.get_method(lead.method)?.clone();
+            let lead_len = method.lead_len();
+            let call = match lead.call {
+                Some(call_idx) => self.get_call(call_idx)?.clone(),
+                None => {
+                    chunks.push(Rc::new(Chunk::method(method, 0, lead_len)));
+                    continue;
+                }
+            };
+            // Calls replace the rows just before their lead location (which is the end of the
+            // lead, unless the call is made somewhere else like the half-lead)
+            let call_end = match call.end_sub_lead_index(lead_len) {
+                Some(0) | None => lead_len,
+                Some(idx) => idx,
+            };
+            let call_start = match call_end.checked_sub(call.len()) {
+                Some(idx) if idx > 0 => idx,
+                _ => return Err(EditError::CallOverSplice),
+            };
+            chunks.push(Rc::new(Chunk::method(method.clone(), 0, call_start)));
+            chunks.push(Rc::new(Chunk::Call {
+                call,
+                method: method.clone(),
+                start_sub_lead_index: call_start,
+            }));
+            if call_end < lead_len {
+                let rest_of_lead = Chunk::method(method, call_end, lead_len - call_end);
+                chunks.push(Rc::new(rest_of_lead));
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// Adds a copy of a [`Fragment`] where every [`Row`] is pre-multiplied by `transposition`
+    /// (usually a part head), placing it at `position`.  This lets the parts of non-group
+    /// multi-part compositions be laid out individually.  Returns the index of the new
+    /// [`Fragment`].
+    pub fn duplicate_transposed(
+        &mut self,
+        frag_idx: FragIdx,
+        transposition: &Row,
+        position: Position,
+    ) -> Result<FragIdx, EditError> {
+        let new_frag = self
+            .get_fragment(frag_idx)?
+            .transposed(transposition)
+            .map_err(EditError::IncompatibleStages)?;
+        Ok(self.fragments.push(Rc::new(Fragment {
+            position,
+            ..new_frag
+        })))
+    }
+
+    /// Extends the end of a [`Fragment`] by either a plain lead or a plain course of a [`Method`]
+    pub fn extend_fragment(
+        &mut self,
+        frag_idx: FragIdx,
+        method_idx: MethodIdx,
+        by_course: bool,
+    ) -> Result<(), EditError> {
+        let chunk = self.plain_chunk(method_idx, by_course)?;
+        let frag = self.get_fragment_mut(frag_idx)?;
+        frag.chunks.push(Rc::new(chunk));
+        frag.merge_method_chunks();
+        Ok(())
+    }
+
+    /// Appends a plain lead of the [`Method`] with a given `shorthand` to the end of a
+    /// [`Fragment`].  This allows spliced compositions to be typed one lead at a time.
+    pub fn append_lead(&mut self, frag_idx: FragIdx, shorthand: &str) -> Result<(), EditError> {
+        let method_idx = self
+            .method_by_shorthand(shorthand)
+            .ok_or_else(|| EditError::NoMethodWithShorthand(shorthand.to_owned()))?;
+        self.extend_fragment(frag_idx, method_idx, false)
+    }
+
+    /// Joins `frag_b` onto the end of `frag_a`, removing `frag_b` from the composition.  `frag_b`
+    /// is transposed so that its first [`Row`] replaces the leftover [`Row`] of `frag_a`.
+    pub fn join_fragments(&mut self, frag_a: FragIdx, frag_b: FragIdx) -> Result<(), EditError> {
+        if frag_a == frag_b {
+            return Err(EditError::JoinFragToItself(frag_a));
+        }
+        // `Chunk`s only store the transpositions between rows, so appending `frag_b`'s chunks to
+        // `frag_a` automatically transposes them to start at `frag_a`'s leftover row
+        let chunks_to_join = self.get_fragment(frag_b)?.chunks.clone();
+        let joined_frag = self.get_fragment_mut(frag_a)?;
+        joined_frag.chunks.extend(chunks_to_join);
+        joined_frag.merge_method_chunks();
+        self.fragments.remove(frag_b);
+        Ok(())
+    }
+
+    /// Rotates the composition so that it starts from the [`Row`] at `row_idx` of a given
+    /// [`Fragment`] (usually a lead head).  That [`Fragment`] must be a round block, and is
+    /// cyclically shifted so that it starts at `row_idx`.  Every [`Fragment`] is then transposed
+    /// so that the rotated [`Fragment`] still starts from the same [`Row`] as before (usually
+    /// rounds).
+    pub fn rotate(&mut self, frag_idx: FragIdx, row_idx: isize) -> Result<(), EditError> {
+        let frag = self.get_fragment_mut(frag_idx)?;
+        let old_start_row = frag.start_row.clone();
+        frag.rotate(frag_idx, row_idx)?;
+        // Compute the transposition which maps the new start row back onto the old one.  The
+        // unwrap is safe because all the rows in a `CompSpec` share the same `Stage`.
+        let transposition = Row::solve_xa_equals_b(&frag.start_row, &old_start_row).unwrap();
+        for f in self.fragments.iter_mut() {
+            Rc::make_mut(f)
+                .pre_transpose(&transposition)
+                .map_err(EditError::IncompatibleStages)?;
+        }
+        Ok(())
+    }
+
+    /// Cyclically shifts which [`Row`] of a [`Fragment`] comes first, by `by_rows` rows (which can
+    /// be negative).  Unlike [`CompSpec::rotate`], no other [`Fragment`]s are changed, so this
+    /// can be used to try different starting points of a course.  The [`Fragment`] must be a
+    /// round block.
+    pub fn rotate_fragment(&mut self, frag_idx: FragIdx, by_rows: isize) -> Result<(), EditError> {
+        let frag = self.get_fragment_mut(frag_idx)?;
+        let len = frag.len() as isize;
+        if len == 0 {
+            return Ok(()); // Rotating an empty fragment does nothing
+        }
+        frag.rotate(frag_idx, by_rows.rem_euclid(len))
+    }
+
+    /// Changes the first [`Row`] of a [`Fragment`], transposing every other [`Row`] along with it.
+    /// This allows fragments (and therefore compositions) to start from rows other than rounds.
+    pub fn set_start_row(&mut self, frag_idx: FragIdx, start_row: RowBuf) -> Result<(), EditError> {
+        IncompatibleStages::test_err(self.stage, start_row.stage())
+            .map_err(EditError::IncompatibleStages)?;
+        self.get_fragment_mut(frag_idx)?.start_row = Rc::new(start_row);
+        Ok(())
+    }
+
+    /// Makes a [`Fragment`] start `sub_lead_index` rows through the first lead of its first
+    /// [`Method`] (e.g. `1` for a backstroke snap start).  The start [`Row`] and the end of the
+    /// first lead stay where they are, so rows are added to or removed from the start of the
+    /// [`Fragment`].
+    pub fn set_start_sub_lead_index(
+        &mut self,
+        frag_idx: FragIdx,
+        sub_lead_index: usize,
+    ) -> Result<(), EditError> {
+        self.get_fragment_mut(frag_idx)?
+            .set_start_sub_lead_index(frag_idx, sub_lead_index)
+    }
+
+    /// Reverses every [`Fragment`], keeping their start [`Row`]s fixed.  The changes of each
+    /// [`Fragment`] are read backwards, and then the first change (i.e. the last lead end) is
+    /// moved back to the end, so that the [`Fragment`] still starts at a lead head.
+    ///
+    /// The changes of a lead of a symmetric [`Method`] (apart from the lead end) read the same
+    /// backwards, so this reverses the order of the leads and moves every lead end call onto the
+    /// end of the lead before it.  For example, a calling of `c1, c2, ..., cn` becomes
+    /// `c(n-1), ..., c1, cn`.  Calls within a lead (e.g. at the half-lead) stay in their lead.
+    /// Fails if any [`Fragment`] uses an asymmetric [`Method`], doesn't consist of whole leads,
+    /// contains a [`Call`] which doesn't read the same backwards, or splices between [`Method`]s
+    /// with different lead end changes.
+    pub fn reverse(&mut self) -> Result<(), EditError> {
+        for (frag_idx, f) in self.fragments.iter_mut_enumerated() {
+            Rc::make_mut(f).reverse(frag_idx)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces every use of the [`Call`] at `call_a` with the [`Call`] at `call_b`, and vice
+    /// versa.  This is useful for e.g. swapping the bob/single conventions of a composition.
+    pub fn swap_calls(&mut self, call_a: CallIdx, call_b: CallIdx) -> Result<(), EditError> {
+        let call_a = self.get_call(call_a)?.clone();
+        let call_b = self.get_call(call_b)?.clone();
+        for frag in self.fragments.iter_mut() {
+            // Only clone the fragments which actually contain one of the calls
+            let contains_calls = frag
+                .chunks
+                .iter()
+                .any(|c| c.swapped_calls(&call_a, &call_b).is_some());
+            if !contains_calls {
+                continue;
+            }
+            for chunk in Rc::make_mut(frag).chunks.iter_mut() {
+                if let Some(new_chunk) = chunk.swapped_calls(&call_a, &call_b) {
+                    *chunk = Rc::new(new_chunk);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces whatever is rung directly above the [`Row`] at `row_idx` with a [`Call`], or
+    /// with the plain [`Method`] if `call_idx` is `None`.  `row_idx` must be at the [`Call`]'s
+    /// lead location, e.g. a lead head for a lead end call or a half-lead for a half-lead call.
+    pub fn set_call(
+        &mut self,
+        frag_idx: FragIdx,
+        row_idx: isize,
+        call_idx: Option<CallIdx>,
+    ) -> Result<(), EditError> {
+        let call = call_idx
+            .map(|idx| self.get_call(idx).map(Rc::clone))
+            .transpose()?;
+        self.get_fragment_mut(frag_idx)?
+            .set_call(frag_idx, row_idx, call)
+    }
+
+    /// Adds a new [`Method`] to the composition, returning its index.  `ruleoffs_above` are the
+    /// indices within the lead of the [`Row`]s which have rule-offs drawn above them.  Calls can
+    /// be made at any rule-off, so principles can put rule-offs at every division end (e.g. the
+    /// six ends of Stedman).
+    pub fn add_method(
+        &mut self,
+        name: String,
+        shorthand: String,
+        place_notation: String,
+        ruleoffs_above: Vec<usize>,
+    ) -> Result<MethodIdx, EditError> {
+        let method = self.new_method(name, shorthand, place_notation, ruleoffs_above)?;
+        Ok(self.methods.push(Rc::new(method)))
+    }
+
+    /// Replaces the [`Method`] at a given index with a new [`Method`] with the given name,
+    /// shorthand, place notation and rule-offs.  Every [`Chunk`] which used the old [`Method`]
+    /// will use the new one instead.
+    pub fn edit_method(
+        &mut self,
+        method_idx: MethodIdx,
+        name: String,
+        shorthand: String,
+        place_notation: String,
+        ruleoffs_above: Vec<usize>,
+    ) -> Result<(), EditError> {
+        self.get_method(method_idx)?; // Check that the method exists before parsing anything
+        let new_method = self.new_method(name, shorthand, place_notation, ruleoffs_above)?;
+        let new_method = Rc::new(new_method);
+        let old_method = std::mem::replace(&mut self.methods[method_idx], new_method.clone());
+
+        // Replace the method in every chunk that uses it, only cloning the fragments which
+        // actually contain the method
+        for frag in self.fragments.iter_mut() {
+            let contains_method = frag
+                .chunks
+                .iter()
+                .any(|c| c.with_method_replaced(&old_method, &new_method).is_some());
+            if !contains_method {
+                continue;
+            }
+            for chunk in Rc::make_mut(frag).chunks.iter_mut() {
+                if let Some(new_chunk) = chunk.with_method_replaced(&old_method, &new_method) {
+                    *chunk = Rc::new(new_chunk);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a new [`Call`] to the composition, returning its index
+    pub fn add_call(
+        &mut self,
+        symbol: char,
+        place_notation: String,
+        lead_location: String,
+        calling_positions: String,
+    ) -> Result<CallIdx, EditError> {
+        check_calling_positions(&calling_positions, self.stage)?;
+        let call = Call::new(
+            symbol,
+            place_notation,
+            lead_location,
+            calling_positions,
+            self.stage,
+        )
+        .map_err(|e| EditError::PlaceNotation(PnError::Standard(e)))?;
+        Ok(self.calls.push(Rc::new(call)))
+    }
+
+    /// Replaces the [`Call`] at a given index with a new one.  Every [`Chunk`] which used the old
+    /// [`Call`] will use the new one instead.  If the [`Call`] is used anywhere in the
+    /// composition, then its length can't be changed (because this would change the length of
+    /// the [`Fragment`]s using it).
+    pub fn edit_call(
+        &mut self,
+        call_idx: CallIdx,
+        symbol: char,
+        place_notation: String,
+        lead_location: String,
+        calling_positions: String,
+    ) -> Result<(), EditError> {
+        check_calling_positions(&calling_positions, self.stage)?;
+        let old_call = self.get_call(call_idx)?.clone();
+        let new_call = Call::new(
+            symbol,
+            place_notation,
+            lead_location,
+            calling_positions,
+            self.stage,
+        )
+        .map_err(|e| EditError::PlaceNotation(PnError::Standard(e)))?;
+        let new_call = Rc::new(new_call);
+        let is_call_used = self
+            .fragments
+            .iter()
+            .flat_map(|f| f.chunks.iter())
+            .any(|c| c.with_call_replaced(&old_call, &new_call).is_some());
+        if is_call_used && old_call.len() != new_call.len() {
+            return Err(EditError::CallLengthChanged {
+                old_len: old_call.len(),
+                new_len: new_call.len(),
+            });
+        }
+        self.calls[call_idx] = new_call.clone();
+
+        // Replace the call in every chunk that uses it, only cloning the fragments which
+        // actually contain the call
+        for frag in self.fragments.iter_mut() {
+            let contains_call = frag
+                .chunks
+                .iter()
+                .any(|c| c.with_call_replaced(&old_call, &new_call).is_some());
+            if !contains_call {
+                continue;
+            }
+            for chunk in Rc::make_mut(frag).chunks.iter_mut() {
+                if let Some(new_chunk) = chunk.with_call_replaced(&old_call, &new_call) {
+                    *chunk = Rc::new(new_chunk);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a new [`Music::Regex`] (or an empty [`Music::Group`] if `regex` is `None`) as the last
+    /// sub-group of the [`Music::Group`] at `parent`.  An empty `parent` refers to the top level
+    /// of the music tree.  An empty `name` gives a [`Music::Regex`] no name.
+    pub fn add_music(
+        &mut self,
+        parent: &[usize],
+        name: String,
+        regex: Option<&str>,
+        weight: f32,
+        count_wraps: bool,
+        parts: Option<Vec<PartIdx>>,
+    ) -> Result<(), EditError> {
+        let new_music = match regex {
+            Some(regex) => {
+                let name = non_empty(name);
+                Music::parse_regex(name, regex, weight, count_wraps, parts, self.stage)
+                    .map_err(EditError::MusicRegex)?
+            }
+            None => Music::Group {
+                name,
+                sub_groups: Vec::new(),
+                weight,
+                parts,
+            },
+        };
+        music_group_mut(Rc::make_mut(&mut self.music), parent)?.push(new_music);
+        Ok(())
+    }
+
+    /// Removes the [`Music`] at `path`, along with all of its sub-groups
+    pub fn remove_music(&mut self, path: &[usize]) -> Result<(), EditError> {
+        let invalid_path = || EditError::InvalidMusicPath(path.to_vec());
+        let (&idx, parent) = path.split_last().ok_or_else(invalid_path)?;
+        let siblings = music_group_mut(Rc::make_mut(&mut self.music), parent)?;
+        if idx >= siblings.len() {
+            return Err(invalid_path());
+        }
+        siblings.remove(idx);
+        Ok(())
+    }
+
+    /// Renames, re-weights and sets the parts of the [`Music`] at `path`.  If it's a
+    /// [`Music::Regex`], this also replaces its [`Regex`] with `regex` and sets whether or not it
+    /// counts wraps.  `regex` and `count_wraps` are ignored for [`Music::Group`]s.
+    pub fn edit_music(
+        &mut self,
+        path: &[usize],
+        name: String,
+        regex: Option<&str>,
+        weight: f32,
+        count_wraps: bool,
+        parts: Option<Vec<PartIdx>>,
+    ) -> Result<(), EditError> {
+        let stage = self.stage;
+        let music = music_mut(Rc::make_mut(&mut self.music), path)?;
+        match music {
+            Music::Regex {
+                name: old_name,
+                regex: old_regex,
+                weight: old_weight,
+                count_wraps: old_count_wraps,
+                parts: old_parts,
+            } => {
+                if let Some(regex) = regex {
+                    check_regex(regex, stage).map_err(EditError::MusicRegex)?;
+                    *old_regex = Regex::parse(regex);
+                }
+                *old_name = non_empty(name);
+                *old_weight = weight;
+                *old_count_wraps = count_wraps;
+                *old_parts = parts;
+            }
+            Music::Group {
+                name: old_name,
+                weight: old_weight,
+                parts: old_parts,
+                ..
+            } => {
+                *old_name = name;
+                *old_weight = weight;
+                *old_parts = parts;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the [`Music`] at `path` so that it becomes the last sub-group of the
+    /// [`Music::Group`] at `new_parent`.  Does nothing if `new_parent` already contains the
+    /// [`Music`].
+    pub fn move_music(&mut self, path: &[usize], new_parent: &[usize]) -> Result<(), EditError> {
+        let invalid_path = || EditError::InvalidMusicPath(path.to_vec());
+        let (&idx, old_parent) = path.split_last().ok_or_else(invalid_path)?;
+        if new_parent == old_parent {
+            return Ok(());
+        }
+        if new_parent.starts_with(path) {
+            return Err(EditError::MoveMusicIntoItself);
+        }
+        let music_tree = Rc::make_mut(&mut self.music);
+        // Check that both paths are valid before modifying anything
+        music_mut(music_tree, path)?;
+        music_group_mut(music_tree, new_parent)?;
+
+        let music = music_group_mut(music_tree, old_parent)?.remove(idx);
+        // Removing `music` shifts its later siblings (and their descendants) back by one
+        let mut new_parent = new_parent.to_vec();
+        if new_parent.len() > old_parent.len()
+            && new_parent.starts_with(old_parent)
+            && new_parent[old_parent.len()] > idx
+        {
+            new_parent[old_parent.len()] -= 1;
+        }
+        music_group_mut(music_tree, &new_parent)?.push(music);
+        Ok(())
+    }
+
+    /// Creates a [`Chunk`] containing either a plain lead or a plain course of a [`Method`]
+    fn plain_chunk(&self, method_idx: MethodIdx, is_course: bool) -> Result<Chunk, EditError> {
+        let method = self.get_method(method_idx)?;
+        let length = if is_course {
+            method.course_len()
+        } else {
+            method.lead_len()
+        };
+        Ok(Chunk::method(method.clone(), 0, length))
+    }
+
+    /// Creates a new [`Method`] on the [`Stage`] of this `CompSpec`, checking that every rule-off
+    /// is within the lead
+    fn new_method(
+        &self,
+        name: String,
+        shorthand: String,
+        place_notation: String,
+        ruleoffs_above: Vec<usize>,
+    ) -> Result<Method, EditError> {
+        let method = Method::new(
+            name,
+            shorthand,
+            self.stage,
+            place_notation,
+            ruleoffs_above.into_iter().collect(),
+        )
+        .map_err(EditError::PlaceNotation)?;
+        let lead_len = method.lead_len();
+        if let Some(&idx) = method.ruleoffs_above.iter().find(|&&idx| idx >= lead_len) {
+            return Err(EditError::RuleoffOutOfRange { idx, lead_len });
+        }
+        Ok(method)
+    }
+
+    fn get_method(&self, idx: MethodIdx) -> Result<&Rc<Method>, EditError> {
+        self.methods.get(idx).ok_or(EditError::MethodOutOfRange {
+            idx,
+            len: self.methods.len(),
+        })
+    }
+
+    fn get_call(&self, idx: CallIdx) -> Result<&Rc<Call>, EditError> {
+        self.calls.get(idx).ok_or(EditError::CallOutOfRange {
+            idx,
+            len: self.calls.len(),
+        })
+    }
+
     fn get_fragment(&self, idx: FragIdx) -> Result<&Fragment, EditError> {
         self.fragments
             .get(idx)
@@ -245,17 +1009,31 @@ impl CompSpec {
     }
 }
 
+/// Where a [`Fragment`] starts, as returned by [`CompSpec::fragment_start`]
+#[derive(Debug, Clone)]
+pub struct FragStart {
+    /// The first [`Row`] of the [`Fragment`]
+    pub start_row: RowBuf,
+    /// How many rows through a lead of the first [`Method`] the start [`Row`] comes (e.g. `0`
+    /// for a standard lead head start)
+    pub sub_lead_index: usize,
+    /// The lead length of the first [`Method`]
+    pub lead_len: usize,
+}
+
 /// A single `Fragment` of composition.
 #[derive(Debug, Clone)]
 pub struct Fragment {
     /// The on-screen location of the top-left corner of the top row this `Frag`
-    position: Pos2,
+    position: Position,
     start_row: Rc<RowBuf>,
     /// A sequence of [`Chunk`]s that make up this `Fragment`
     chunks: ChunkVec<Rc<Chunk>>,
     /// Set to `false` if this `Fragment` is visible but 'muted' - i.e. visually greyed out and not
     /// included in the proving, ATW calculations, statistics, etc.
     is_proved: bool,
+    /// Set to `true` if this `Fragment` should be drawn with bluelines instead of bell names
+    show_bluelines: bool,
 }
 
 impl Fragment {
@@ -267,6 +1045,23 @@ impl Fragment {
         Ok(())
     }
 
+    /// Returns a copy of this `Fragment` where every [`Row`] is pre-multiplied by
+    /// `transposition`.  [`Chunk`]s only store the transpositions between [`Row`]s, so only the
+    /// start [`Row`] has to change.
+    pub fn transposed(&self, transposition: &Row) -> Result<Fragment, IncompatibleStages> {
+        Ok(Fragment {
+            start_row: Rc::new(transposition.mul_result(&self.start_row)?),
+            ..self.clone()
+        })
+    }
+
+    /// Toggles whether or not this `Fragment` is drawn with bluelines.  Like
+    /// [`Fragment::toggle_mute`], this never fails.
+    pub fn toggle_bluelines(&mut self) -> Result<(), EditError> {
+        self.show_bluelines = !self.show_bluelines;
+        Ok(())
+    }
+
     /// Gets the number of non-leftover [`Row`]s in this [`Fragment`] in one part of the
     /// composition.
     pub(crate) fn len(&self) -> usize {
@@ -279,7 +1074,7 @@ impl Fragment {
         &mut self,
         frag_idx: FragIdx,
         split_idx: isize,
-        new_frag_pos: Pos2,
+        new_frag_pos: Position,
     ) -> Result<Self, EditError> {
         // Compute which chunk contains the split point
         let (chunk_idx, sub_chunk_idx, new_frag_start_row) =
@@ -292,17 +1087,400 @@ impl Fragment {
         // Put the first half of the split chunk back onto `self` (if it's non-empty)
         self.chunks.extend(chunk_before_split);
 
-        // Construct the chunks for the other fragment
-        let mut new_frag_chunks = ChunkVec::with_capacity(other_chunks.len() + 1);
-        new_frag_chunks.extend(chunk_after_split);
-        new_frag_chunks.extend(other_chunks);
-        // Construct and return the fragment containing the part of `self` after the split
-        Ok(Fragment {
-            position: new_frag_pos,
-            start_row: Rc::new(new_frag_start_row),
-            chunks: new_frag_chunks,
-            is_proved: self.is_proved, // Inherit proved-ness from `self`
-        })
+        // Construct the chunks for the other fragment
+        let mut new_frag_chunks = ChunkVec::with_capacity(other_chunks.len() + 1);
+        new_frag_chunks.extend(chunk_after_split);
+        new_frag_chunks.extend(other_chunks);
+        // Construct and return the fragment containing the part of `self` after the split
+        Ok(Fragment {
+            position: new_frag_pos,
+            start_row: Rc::new(new_frag_start_row),
+            chunks: new_frag_chunks,
+            is_proved: self.is_proved, // Inherit proved-ness from `self`
+            show_bluelines: self.show_bluelines,
+        })
+    }
+
+    /// Cyclically rotates this `Fragment` so that the [`Row`] at `row_idx` becomes its first
+    /// [`Row`].  This only makes sense for round blocks (i.e. `Fragment`s whose leftover row is
+    /// the same as their first row), so any other `Fragment` will generate an error.
+    fn rotate(&mut self, frag_idx: FragIdx, row_idx: isize) -> Result<(), EditError> {
+        if !self.is_round_block() {
+            return Err(EditError::NotRoundBlock(frag_idx));
+        }
+        let (chunk_idx, sub_chunk_idx, new_start_row) = self.get_row_data(frag_idx, row_idx)?;
+        // Split the chunk which contains the new start row
+        let chunks_after_split = self.chunks.split_off(chunk_idx + 1);
+        let chunk_being_split = self.chunks.pop().unwrap();
+        let (chunk_before_split, chunk_after_split) = chunk_being_split.split(sub_chunk_idx)?;
+        // Reassemble the chunks so that the rows after the split come first
+        let chunks_before_split = std::mem::take(&mut self.chunks);
+        self.chunks.extend(chunk_after_split);
+        self.chunks.extend(chunks_after_split);
+        self.chunks.extend(chunks_before_split);
+        self.chunks.extend(chunk_before_split);
+        self.start_row = Rc::new(new_start_row);
+        Ok(())
+    }
+
+    /// Replaces the first [`Chunk`] of this `Fragment` so that it starts at `sub_lead_index`
+    /// through a lead of its [`Method`], but still ends at the same place.  The first [`Chunk`]
+    /// must be a [`Chunk::Method`] which reaches past `sub_lead_index`.
+    fn set_start_sub_lead_index(
+        &mut self,
+        frag_idx: FragIdx,
+        sub_lead_index: usize,
+    ) -> Result<(), EditError> {
+        let invalid_start = EditError::InvalidStart {
+            frag_idx,
+            sub_lead_index,
+        };
+        let first_chunk = self
+            .chunks
+            .first_mut()
+            .ok_or_else(|| invalid_start.clone())?;
+        // The index of the end of the first chunk, relative to the start of its first lead
+        let (method, end_idx) = match first_chunk.as_ref() {
+            Chunk::Method {
+                method,
+                start_sub_lead_index,
+                length,
+                ..
+            } => (method.clone(), start_sub_lead_index + length),
+            Chunk::Call { .. } => return Err(invalid_start),
+        };
+        if sub_lead_index >= method.lead_len() || sub_lead_index >= end_idx {
+            return Err(invalid_start);
+        }
+        *first_chunk = Rc::new(Chunk::method(
+            method,
+            sub_lead_index,
+            end_idx - sub_lead_index,
+        ));
+        Ok(())
+    }
+
+    /// Removes the [`Row`]s in `start..end` from this `Fragment`.  If `start` is `0`, then the
+    /// start [`Row`] is moved so that the remaining [`Row`]s are unchanged.
+    fn delete_rows(
+        &mut self,
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+    ) -> Result<(), EditError> {
+        let (start_chunk, end_chunk) = self.split_chunks_around(frag_idx, start, end)?;
+        if start == 0 && end == self.len() {
+            return Err(EditError::DeleteAllRows(frag_idx));
+        }
+        if start == 0 {
+            let mut accum = RowAccumulator::new(self.start_row.as_ref().clone());
+            for chunk in &self.chunks[start_chunk..end_chunk] {
+                accum *= chunk.transposition();
+            }
+            self.start_row = Rc::new(accum.into_total());
+        }
+        self.chunks.drain(start_chunk..end_chunk);
+        self.merge_method_chunks();
+        Ok(())
+    }
+
+    /// Makes the [`Row`]s in `start..end` use a different [`Method`]
+    fn set_method(
+        &mut self,
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+        method: &Rc<Method>,
+    ) -> Result<(), EditError> {
+        let (start_chunk, end_chunk) = self.split_chunks_around(frag_idx, start, end)?;
+        for chunk in &mut self.chunks[start_chunk..end_chunk] {
+            *chunk = Rc::new(chunk.with_method(method));
+        }
+        self.merge_method_chunks();
+        Ok(())
+    }
+
+    /// Replaces whatever is rung directly above the [`Row`] at `row_idx` with a [`Call`], or
+    /// with the plain [`Method`] if `call` is `None`.
+    fn set_call(
+        &mut self,
+        frag_idx: FragIdx,
+        row_idx: isize,
+        call: Option<Rc<Call>>,
+    ) -> Result<(), EditError> {
+        let out_of_range = EditError::RowOutOfRange {
+            frag_idx,
+            row_idx,
+            frag_len: self.len(),
+        };
+        // Unlike most edits, `row_idx` is allowed to point to the leftover row (so that calls can
+        // be placed at the end of a `Fragment`)
+        let row_idx = usize::try_from(row_idx)
+            .ok()
+            .filter(|idx| *idx <= self.len())
+            .ok_or_else(|| out_of_range.clone())?;
+
+        // Replace any existing call with the plain method
+        self.remove_call_ending_at(row_idx);
+        self.merge_method_chunks();
+        // Add the new call (if there is one)
+        if let Some(call) = call {
+            let call_start_idx = row_idx.checked_sub(call.len()).ok_or(out_of_range)?;
+            let first_chunk_idx = self.split_chunks_at(frag_idx, call_start_idx)?;
+            let end_chunk_idx = self.split_chunks_at(frag_idx, row_idx)?;
+            // The call must replace exactly one chunk of a method
+            let (method, start_sub_lead_index) = match (
+                end_chunk_idx.index() - first_chunk_idx.index(),
+                &*self.chunks[first_chunk_idx],
+            ) {
+                (
+                    1,
+                    Chunk::Method {
+                        method,
+                        start_sub_lead_index,
+                        ..
+                    },
+                ) => (method.clone(), *start_sub_lead_index),
+                _ => return Err(EditError::CallOverSplice),
+            };
+            if !call.can_end_at(&method, start_sub_lead_index + call.len()) {
+                return Err(EditError::CallLocation {
+                    symbol: call.symbol(),
+                    lead_location: call.lead_location().to_owned(),
+                });
+            }
+            self.chunks[first_chunk_idx] = Rc::new(Chunk::Call {
+                call,
+                method,
+                start_sub_lead_index,
+            });
+            // Merge any method chunks which were split but didn't get replaced by the call
+            self.merge_method_chunks();
+        }
+        Ok(())
+    }
+
+    /// If there is a [`Chunk::Call`] which ends directly above the [`Row`] at `row_idx`, then
+    /// replace it with the rows of its [`Method`]
+    fn remove_call_ending_at(&mut self, row_idx: usize) {
+        let mut chunk_end_idx = 0;
+        for chunk in self.chunks.iter_mut() {
+            chunk_end_idx += chunk.len();
+            if chunk_end_idx == row_idx {
+                if let Chunk::Call {
+                    call,
+                    method,
+                    start_sub_lead_index,
+                } = chunk.as_ref()
+                {
+                    let plain = Chunk::method(method.clone(), *start_sub_lead_index, call.len());
+                    *chunk = Rc::new(plain);
+                }
+                return;
+            }
+        }
+    }
+
+    /// Makes sure that a [`Chunk`] starts at the [`Row`] at `row_idx`, splitting a [`Chunk`] if
+    /// necessary.  Returns the index of the [`Chunk`] which starts at `row_idx` (which will be
+    /// `self.chunks.len()` if `row_idx` points to the leftover row).
+    fn split_chunks_at(
+        &mut self,
+        frag_idx: FragIdx,
+        row_idx: usize,
+    ) -> Result<ChunkIdx, EditError> {
+        let mut chunk_start_idx = 0;
+        for (chunk_idx, chunk) in self.chunks.iter_enumerated() {
+            if chunk_start_idx == row_idx {
+                return Ok(chunk_idx); // A chunk already starts at `row_idx`
+            }
+            let chunk_end_idx = chunk_start_idx + chunk.len();
+            if row_idx < chunk_end_idx {
+                // `row_idx` is strictly inside this chunk, so split it into two
+                let (chunk_before_split, chunk_after_split) =
+                    chunk.clone().split(row_idx - chunk_start_idx)?;
+                // Unwraps are safe because splitting strictly inside a chunk always creates two
+                // non-empty chunks
+                self.chunks[chunk_idx] = chunk_before_split.unwrap();
+                self.chunks
+                    .insert(chunk_idx + 1, chunk_after_split.unwrap());
+                return Ok(chunk_idx + 1);
+            }
+            chunk_start_idx = chunk_end_idx;
+        }
+        // If no chunks contain `row_idx`, then it must be the leftover row
+        if chunk_start_idx == row_idx {
+            Ok(self.chunks.next_idx())
+        } else {
+            Err(EditError::RowOutOfRange {
+                frag_idx,
+                row_idx: row_idx as isize,
+                frag_len: self.len(),
+            })
+        }
+    }
+
+    /// Splits [`Chunk`]s so that the [`Row`]s in `start..end` are covered by a whole number of
+    /// [`Chunk`]s, returning the indices of the first [`Chunk`] in the range and of the first
+    /// [`Chunk`] after it.
+    fn split_chunks_around(
+        &mut self,
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+    ) -> Result<(ChunkIdx, ChunkIdx), EditError> {
+        if start >= end {
+            return Err(EditError::EmptyRowRange {
+                frag_idx,
+                start,
+                end,
+            });
+        }
+        // Splitting at `end` can't change the chunks before `start`, so `start_chunk` stays valid
+        let start_chunk = self.split_chunks_at(frag_idx, start)?;
+        let end_chunk = self.split_chunks_at(frag_idx, end)?;
+        Ok((start_chunk, end_chunk))
+    }
+
+    /// Merges any adjacent [`Chunk::Method`]s which ring consecutive [`Row`]s of the same
+    /// [`Method`]
+    fn merge_method_chunks(&mut self) {
+        let mut merged_chunks = ChunkVec::<Rc<Chunk>>::with_capacity(self.chunks.len());
+        for chunk in self.chunks.drain(..) {
+            let merged_chunk = merged_chunks
+                .last()
+                .and_then(|last| last.merged_with(&chunk));
+            match merged_chunk {
+                // Unwrap is safe because `merged_chunk` can only be `Some` if `merged_chunks`
+                // has a last element
+                Some(c) => *merged_chunks.last_mut().unwrap() = Rc::new(c),
+                None => {
+                    merged_chunks.push(chunk);
+                }
+            }
+        }
+        self.chunks = merged_chunks;
+    }
+
+    /// Pre-multiplies every [`Row`] in this `Fragment` by a given `transposition`
+    fn pre_transpose(&mut self, transposition: &Row) -> Result<(), IncompatibleStages> {
+        self.start_row = Rc::new(transposition.mul_result(&self.start_row)?);
+        Ok(())
+    }
+
+    /// Reverses the changes of this `Fragment`, keeping its start [`Row`] fixed.  See
+    /// [`CompSpec::reverse`] for more details.
+    fn reverse(&mut self, frag_idx: FragIdx) -> Result<(), EditError> {
+        /// A lead of a [`Fragment`], split into the parts which move differently when reversed
+        struct Lead {
+            method: Rc<Method>,
+            /// The [`Call`] which makes the lead end change, if there is one
+            lead_end_call: Option<Rc<Call>>,
+            /// Every other [`Call`] in the lead, along with the sub-lead index of the [`Row`]
+            /// directly after it **once the lead is reversed**
+            inner_calls: Vec<(Rc<Call>, usize)>,
+        }
+
+        // Split the chunks into leads.  A `Chunk::Method` can cover many leads, but a call is
+        // always within one lead.
+        let mut leads = Vec::<Lead>::new();
+        for chunk in &self.chunks {
+            let (method, start_sub_lead_index) = chunk.method_and_start();
+            if !method.is_symmetric() {
+                return Err(EditError::ReverseAsymmetricMethod(
+                    method.shorthand().to_owned(),
+                ));
+            }
+            let lead_len = method.lead_len();
+            let mut sub_lead_index = start_sub_lead_index;
+            let mut rows_left = chunk.len();
+            while rows_left > 0 {
+                // Every lead must start at the start of a lead, and can't splice mid-lead
+                match leads.last() {
+                    _ if sub_lead_index == 0 => leads.push(Lead {
+                        method: method.clone(),
+                        lead_end_call: None,
+                        inner_calls: Vec::new(),
+                    }),
+                    Some(lead) if Rc::ptr_eq(&lead.method, method) => {}
+                    _ => return Err(EditError::ReversePartialLead(frag_idx)),
+                }
+                let rows_in_lead = rows_left.min(lead_len - sub_lead_index);
+                sub_lead_index = (sub_lead_index + rows_in_lead) % lead_len;
+                rows_left -= rows_in_lead;
+            }
+            // The unwrap is safe because every chunk contains at least one row, so will have
+            // added to or checked the last lead
+            let lead = leads.last_mut().unwrap();
+            if let Chunk::Call { call, .. } = chunk.as_ref() {
+                let end_sub_lead_index = start_sub_lead_index + call.len();
+                if end_sub_lead_index == lead_len && call.len() == 1 {
+                    lead.lead_end_call = Some(call.clone());
+                } else if end_sub_lead_index < lead_len && call.is_symmetric() {
+                    // Change `i` of the lead becomes change `lead_len - 2 - i`, so the call's last
+                    // change becomes the one before its first change
+                    let reversed_end = lead_len - 1 - start_sub_lead_index;
+                    lead.inner_calls.push((call.clone(), reversed_end));
+                } else {
+                    // The call's changes would be split up or change order
+                    return Err(EditError::ReverseCall(call.symbol()));
+                }
+            }
+        }
+        // The last lead must also be complete
+        if let Some(last_chunk) = self.chunks.last() {
+            let (method, start_sub_lead_index) = last_chunk.method_and_start();
+            if (start_sub_lead_index + last_chunk.len()) % method.lead_len() != 0 {
+                return Err(EditError::ReversePartialLead(frag_idx));
+            }
+        }
+
+        // Build plain leads in reverse order, recording where the calls need to go.  Modify a copy
+        // of `self` so that `self` is unchanged if any of the calls can't be made.
+        let mut reversed = self.clone();
+        reversed.chunks.clear();
+        let mut calls = Vec::<(usize, Rc<Call>)>::new(); // (row after the call, call)
+        let mut lead_start = 0;
+        for (lead_idx, lead) in leads.iter().enumerate().rev() {
+            let lead_len = lead.method.lead_len();
+            // Each lead now ends with the lead end change of the lead before it (wrapping round so
+            // that the last lead end stays at the end)
+            let prev_lead = &leads[(lead_idx + leads.len() - 1) % leads.len()];
+            match &prev_lead.lead_end_call {
+                Some(call) => calls.push((lead_start + lead_len, call.clone())),
+                None if prev_lead.method.lead_end_change() == lead.method.lead_end_change() => {}
+                None => return Err(EditError::ReverseSplicedLeadEnds(frag_idx)),
+            }
+            for (call, end_sub_lead_index) in &lead.inner_calls {
+                calls.push((lead_start + end_sub_lead_index, call.clone()));
+            }
+            reversed
+                .chunks
+                .push(Rc::new(Chunk::method(lead.method.clone(), 0, lead_len)));
+            lead_start += lead_len;
+        }
+        reversed.merge_method_chunks();
+        for (row_idx, call) in calls {
+            reversed.set_call(frag_idx, row_idx as isize, Some(call))?;
+        }
+        *self = reversed;
+        Ok(())
+    }
+
+    /// Returns the leftover [`Row`] of this `Fragment` (i.e. the [`Row`] which would be rung
+    /// directly after the end of this `Fragment`).
+    fn leftover_row(&self) -> RowBuf {
+        let mut accum = RowAccumulator::new(self.start_row.as_ref().clone());
+        for chunk in &self.chunks {
+            accum *= chunk.transposition();
+        }
+        accum.into_total()
+    }
+
+    /// Returns `true` if the leftover [`Row`] of this `Fragment` is the same as its first
+    /// [`Row`]
+    fn is_round_block(&self) -> bool {
+        self.leftover_row() == *self.start_row
     }
 
     /// Given a (possibly negative) row index, this returns a tuple of
@@ -459,6 +1637,23 @@ impl Chunk {
         }
     }
 
+    /// Returns the [`Method`] which this `Chunk` is part of, along with the sub-lead index of its
+    /// first [`Row`]
+    fn method_and_start(&self) -> (&Rc<Method>, usize) {
+        match self {
+            Chunk::Method {
+                method,
+                start_sub_lead_index,
+                ..
+            }
+            | Chunk::Call {
+                method,
+                start_sub_lead_index,
+                ..
+            } => (method, *start_sub_lead_index),
+        }
+    }
+
     /// Return the number of [`Row`]s generated by this [`Chunk`]
     fn len(&self) -> usize {
         match self {
@@ -475,6 +1670,116 @@ impl Chunk {
         }
     }
 
+    /// If `self` is a [`Chunk::Call`] of either `call_a` or `call_b`, then this returns a copy of
+    /// `self` using the other [`Call`].  Otherwise, returns `None`.
+    fn swapped_calls(&self, call_a: &Rc<Call>, call_b: &Rc<Call>) -> Option<Chunk> {
+        match self {
+            Chunk::Call {
+                call,
+                method,
+                start_sub_lead_index,
+            } => {
+                let new_call = if Rc::ptr_eq(call, call_a) {
+                    call_b
+                } else if Rc::ptr_eq(call, call_b) {
+                    call_a
+                } else {
+                    return None;
+                };
+                Some(Chunk::Call {
+                    call: new_call.clone(),
+                    method: method.clone(),
+                    start_sub_lead_index: *start_sub_lead_index,
+                })
+            }
+            Chunk::Method { .. } => None,
+        }
+    }
+
+    /// If `self` and `next` are both [`Chunk::Method`]s and `next` directly continues `self`,
+    /// then this returns a single [`Chunk`] covering both.  Otherwise, returns `None`.
+    fn merged_with(&self, next: &Chunk) -> Option<Chunk> {
+        match (self, next) {
+            (
+                Chunk::Method {
+                    method,
+                    start_sub_lead_index,
+                    length,
+                    ..
+                },
+                Chunk::Method {
+                    method: next_method,
+                    start_sub_lead_index: next_start_sub_lead_index,
+                    length: next_length,
+                    ..
+                },
+            ) => {
+                let is_continuation = Rc::ptr_eq(method, next_method)
+                    && (start_sub_lead_index + length) % method.lead_len()
+                        == *next_start_sub_lead_index;
+                is_continuation.then(|| {
+                    Chunk::method(method.clone(), *start_sub_lead_index, length + next_length)
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// If `self` is a [`Chunk::Call`] of `old_call`, then this returns a copy of `self` which uses
+    /// `new_call` instead.  Otherwise, returns `None`.
+    fn with_call_replaced(&self, old_call: &Rc<Call>, new_call: &Rc<Call>) -> Option<Chunk> {
+        match self {
+            Chunk::Call {
+                call,
+                method,
+                start_sub_lead_index,
+            } if Rc::ptr_eq(call, old_call) => Some(Chunk::Call {
+                call: new_call.clone(),
+                method: method.clone(),
+                start_sub_lead_index: *start_sub_lead_index,
+            }),
+            _ => None,
+        }
+    }
+
+    /// If `self` uses `old_method`, then this returns a copy of `self` which uses `new_method`
+    /// instead.  Otherwise, returns `None`.
+    fn with_method_replaced(
+        &self,
+        old_method: &Rc<Method>,
+        new_method: &Rc<Method>,
+    ) -> Option<Chunk> {
+        let method = match self {
+            Chunk::Method { method, .. } | Chunk::Call { method, .. } => method,
+        };
+        Rc::ptr_eq(method, old_method).then(|| self.with_method(new_method))
+    }
+
+    /// Returns a copy of `self` which uses `new_method` instead of its current [`Method`]
+    fn with_method(&self, new_method: &Rc<Method>) -> Chunk {
+        match self {
+            Chunk::Method {
+                method: _,
+                start_sub_lead_index,
+                length,
+                transposition: _,
+            } => {
+                // The transposition has to be recomputed, because the new method may generate
+                // different rows
+                Chunk::method(new_method.clone(), *start_sub_lead_index, *length)
+            }
+            Chunk::Call {
+                call,
+                method: _,
+                start_sub_lead_index,
+            } => Chunk::Call {
+                call: call.clone(),
+                method: new_method.clone(),
+                start_sub_lead_index: *start_sub_lead_index % new_method.lead_len(),
+            },
+        }
+    }
+
     /// Splits `self` into two chunks.  Empty `Chunk`s are returned as `None`
     #[allow(clippy::type_complexity)]
     fn split(
@@ -515,6 +1820,9 @@ impl Chunk {
 #[derive(Debug, Clone)]
 pub(crate) struct Method {
     inner: bellframe::Method,
+    /// The place notation string which generated `inner`.  `bellframe::Method` doesn't store
+    /// this, so we keep it around so that it can be saved and edited.
+    place_notation: String,
     /// The name (not title) of this `Method`.  For example, the method who's title is `"Bristol
     /// Surprise Major"` would have name `"Bristol"`.
     name: RefCell<String>,
@@ -525,25 +1833,46 @@ pub(crate) struct Method {
     shorthand: RefCell<String>,
     /// Which locations in the lead should have lines drawn **above** them
     ruleoffs_above: HashSet<usize>, // TODO: Use a bitmask
+    /// The class of this `Method`, which is used to generate its title
+    class: FullClass,
 }
 
 impl Method {
-    fn with_lead_end_ruleoff(inner: bellframe::Method, name: String, shorthand: String) -> Self {
-        Self::new(inner, name, shorthand, std::iter::once(0).collect())
+    /// Creates a new `Method` by parsing a string of place notation, placing a single rule-off at
+    /// the lead end.
+    fn with_lead_end_ruleoff(
+        name: String,
+        shorthand: String,
+        stage: Stage,
+        place_notation: String,
+    ) -> Result<Self, PnError> {
+        Self::new(
+            name,
+            shorthand,
+            stage,
+            place_notation,
+            std::iter::once(0).collect(),
+        )
     }
 
+    /// Creates a new `Method` by parsing a string of place notation, which may contain jumps
     fn new(
-        inner: bellframe::Method,
         name: String,
         shorthand: String,
+        stage: Stage,
+        place_notation: String,
         ruleoffs: HashSet<usize>,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, PnError> {
+        let inner = place_not::parse_method(&place_notation, stage)?;
+        let class = FullClass::classify(inner.first_lead());
+        Ok(Self {
             inner,
+            place_notation,
             name: RefCell::new(name),
             shorthand: RefCell::new(shorthand),
             ruleoffs_above: ruleoffs,
-        }
+            class,
+        })
     }
 
     #[inline]
@@ -551,14 +1880,87 @@ impl Method {
         self.inner.lead_len()
     }
 
+    /// The lead head of this `Method`, i.e. the [`Row`] reached at the end of a plain lead
+    pub fn lead_head(&self) -> &Row {
+        self.inner.lead_head()
+    }
+
+    /// The [`Row`]s of a plain lead of this `Method`, starting from rounds.  This doesn't include
+    /// the lead head reached at the end of the lead.
+    pub(crate) fn plain_lead(&self) -> impl Iterator<Item = &Row> + '_ {
+        (0..self.lead_len()).map(move |i| self.inner.row_in_plain_lead(i))
+    }
+
+    /// The number of [`Row`]s in a plain course of this `Method`
+    pub fn course_len(&self) -> usize {
+        // The number of leads in a plain course is the order of the lead head
+        self.lead_len() * self.inner.lead_head().closure_from_rounds().len()
+    }
+
     pub fn shorthand(&self) -> Ref<String> {
         self.shorthand.borrow()
     }
 
+    pub fn place_notation(&self) -> &str {
+        &self.place_notation
+    }
+
     pub fn name(&self) -> Ref<String> {
         self.name.borrow()
     }
 
+    pub fn class(&self) -> FullClass {
+        self.class
+    }
+
+    /// The full title of this `Method`, e.g. `"Bristol Surprise Major"`
+    pub fn title(&self) -> String {
+        method_title(&self.name(), self.class, self.inner.stage())
+    }
+
+    /// The standard lead head code of this `Method` (e.g. `"m"` for Bristol Surprise Major), or
+    /// `None` if it doesn't have Plain Bob lead heads
+    pub fn lead_head_code(&self) -> Option<String> {
+        let lead_end = self.inner.row_in_plain_lead(self.lead_len() - 1);
+        lead_head_code(lead_end, self.inner.lead_head())
+    }
+
+    /// The indices within the lead of the [`Row`]s which have rule-offs drawn above them, in
+    /// ascending order
+    pub fn ruleoffs_above(&self) -> Vec<usize> {
+        let mut ruleoffs = self.ruleoffs_above.iter().copied().collect::<Vec<_>>();
+        ruleoffs.sort_unstable();
+        ruleoffs
+    }
+
+    /// The change made at the lead end of a plain lead of this `Method`
+    fn lead_end_change(&self) -> RowBuf {
+        // Unwrap is safe because every row of a lead has the same `Stage`
+        Row::solve_ax_equals_b(
+            self.inner.row_in_plain_lead(self.lead_len() - 1),
+            self.inner.lead_head(),
+        )
+        .unwrap()
+    }
+
+    /// Returns `true` if the changes of a lead of this `Method` (apart from the lead end change)
+    /// read the same backwards, i.e. they are palindromic about the half-lead
+    fn is_symmetric(&self) -> bool {
+        let lead_len = self.lead_len();
+        // The change made between rows `i` and `i + 1` of the lead.  The unwrap is safe because
+        // every row of a lead has the same `Stage`.
+        let change = |i: usize| {
+            Row::solve_ax_equals_b(
+                self.inner.row_in_plain_lead(i),
+                self.inner.row_in_plain_lead(i + 1),
+            )
+            .unwrap()
+        };
+        // Change `lead_len - 1` is the lead end, which isn't part of the palindrome
+        let num_changes = lead_len - 1;
+        (0..num_changes / 2).all(|i| change(i) == change(num_changes - 1 - i))
+    }
+
     pub fn is_ruleoff_below(&self, sub_lead_idx: usize) -> bool {
         // We store which rows have ruleoffs **above** them, so we have to query the row below the
         // one specified by `sub_lead_idx`
@@ -567,9 +1969,159 @@ impl Method {
     }
 }
 
+/// Names of methods whose titles don't include their class (e.g. Grandsire is a Bob method, but
+/// is called 'Grandsire Triples' rather than 'Grandsire Bob Triples')
+const NAMES_WITHOUT_CLASS: [&str; 2] = ["Grandsire", "Union"];
+
+/// Generates the full title of a method with a given `name` and `class`, e.g. `"Bristol Surprise
+/// Major"`.  Unlike [`bellframe::method::generate_title`], this doesn't repeat the class if the user
+/// has already included it in the name (e.g. by naming a method 'Plain Bob' rather than 'Plain'),
+/// and names [`Stage`]s which don't have a standard name.
+fn method_title(name: &str, class: FullClass, stage: Stage) -> String {
+    let mut words = vec![name.to_owned()];
+    if class.is_jump() {
+        words.push("Jump".to_owned());
+    }
+    if class.is_differential() {
+        words.push("Differential".to_owned());
+    }
+    // Principles and Hybrids don't have their class in their titles
+    if let Some(class_name) = class.class().name_in_title() {
+        if class.is_little() {
+            words.push("Little".to_owned());
+        }
+        if !NAMES_WITHOUT_CLASS.contains(&name) && !name.ends_with(class_name) {
+            words.push(class_name.to_owned());
+        }
+    }
+    words.push(match stage.name() {
+        Some(stage_name) => stage_name.to_owned(),
+        None => format!("on {} bells", stage.num_bells()),
+    });
+    words.retain(|w| !w.is_empty());
+    words.join(" ")
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct Call {
     inner: bellframe::Call,
+    /// The place notation string which generated `inner`
+    place_notation: String,
+    /// Where in the lead this `Call` is made, e.g. `"LE"` for a lead end call
+    lead_location: String,
+    /// The name of the calling position for every place that the tenor can be in after this
+    /// `Call` (e.g. `"LIBFVMWH"` for a `14` bob in Major).  This is empty if the calling positions
+    /// haven't been specified.
+    calling_positions: String,
+}
+
+impl Call {
+    /// Creates a new `Call` by parsing a string of place notation
+    fn new(
+        symbol: char,
+        place_notation: String,
+        lead_location: String,
+        calling_positions: String,
+        stage: Stage,
+    ) -> Result<Self, PnBlockParseError> {
+        let pn_block = PnBlock::parse(&place_notation, stage)?;
+        let inner = bellframe::Call::new(
+            symbol,
+            lead_location.clone(),
+            pn_block.len(),
+            pn_block.to_block_from_rounds(),
+        );
+        Ok(Self {
+            inner,
+            place_notation,
+            lead_location,
+            calling_positions,
+        })
+    }
+
+    pub fn symbol(&self) -> char {
+        self.inner.notation()
+    }
+
+    pub fn place_notation(&self) -> &str {
+        &self.place_notation
+    }
+
+    pub fn lead_location(&self) -> &str {
+        &self.lead_location
+    }
+
+    /// The sub-lead index of the [`Row`] directly after this `Call`, when it's made in a lead of
+    /// `lead_len` rows.  `"LE"` calls finish at the lead end, `"HL"` calls at the half-lead, and a
+    /// number gives the sub-lead index directly (e.g. `"3"` and `"9"` for the six ends of a
+    /// Stedman lead).  Any other `lead_location` returns `None`, meaning that this `Call` can be
+    /// made anywhere.
+    pub(crate) fn end_sub_lead_index(&self, lead_len: usize) -> Option<usize> {
+        let location = self.lead_location.trim();
+        if location.eq_ignore_ascii_case("LE") {
+            Some(0)
+        } else if location.eq_ignore_ascii_case("HL") {
+            Some(lead_len / 2)
+        } else {
+            location.parse::<usize>().ok().map(|idx| idx % lead_len)
+        }
+    }
+
+    /// Returns `true` if this `Call` can finish directly above the [`Row`] at `sub_lead_idx` in a
+    /// lead of `method`
+    pub(crate) fn can_end_at(&self, method: &Method, sub_lead_idx: usize) -> bool {
+        let lead_len = method.lead_len();
+        match self.end_sub_lead_index(lead_len) {
+            Some(idx) => idx == sub_lead_idx % lead_len,
+            None => true, // Calls without a recognised lead location can be made anywhere
+        }
+    }
+
+    pub fn calling_positions(&self) -> &str {
+        &self.calling_positions
+    }
+
+    /// The number of [`Row`]s generated by this `Call`
+    pub(crate) fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// The [`Row`]s generated by this `Call`, starting from rounds
+    pub(crate) fn rows(&self) -> impl Iterator<Item = &Row> + '_ {
+        self.inner.block().rows()
+    }
+
+    /// The transposition from the first [`Row`] of this `Call` to the [`Row`] after it
+    pub(crate) fn transposition(&self) -> &Row {
+        self.inner.transposition()
+    }
+
+    /// Returns `true` if the changes made by this `Call` read the same backwards
+    fn is_symmetric(&self) -> bool {
+        // The unwrap is safe because every row of a call has the same `Stage`
+        let changes = self
+            .rows()
+            .chain(std::iter::once(self.transposition()))
+            .tuple_windows()
+            .map(|(a, b)| Row::solve_ax_equals_b(a, b).unwrap())
+            .collect_vec();
+        changes.iter().eq(changes.iter().rev())
+    }
+
+    /// Returns the string used to denote this `Call` in a calling.  This is the `Call`'s calling
+    /// position (i.e. the place of the `observation_bell` in the `lead_head` after the call),
+    /// prefixed by the call's symbol unless the call is a bob.  If there's no calling position,
+    /// then only the symbol is used.
+    pub(crate) fn label(&self, lead_head: Option<&Row>, observation_bell: Bell) -> String {
+        let position = lead_head
+            .and_then(|row| row.place_of(observation_bell))
+            .and_then(|place| self.calling_positions.chars().nth(place));
+        match (self.symbol(), position) {
+            ('-', Some(position)) => position.to_string(),
+            (symbol, Some(position)) => format!("{}{}", symbol, position),
+            (symbol, None) => symbol.to_string(),
+        }
+    }
 }
 
 /// A point where the composition can be folded.  Composition folding is not part of the undo
@@ -579,6 +2131,48 @@ pub(crate) struct Fold {
     is_open: Cell<bool>,
 }
 
+////////////////////
+// LENGTH TARGETS //
+////////////////////
+
+/// An inclusive range of lengths which the composition should have (e.g. `1250..=1299` for a
+/// quarter peal)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LengthTarget {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl LengthTarget {
+    /// Compares the `length` of a composition with `num_parts` parts to this target
+    pub fn status(&self, length: usize, num_parts: usize) -> LengthStatus {
+        // Every part has the same length, so only multiples of `num_parts` can be reached
+        let num_parts = num_parts.max(1);
+        let largest_reachable = self.max / num_parts * num_parts;
+        if largest_reachable < self.min {
+            LengthStatus::Unreachable
+        } else if length < self.min {
+            LengthStatus::Short(self.min - length)
+        } else if length > self.max {
+            LengthStatus::Long(length - self.max)
+        } else {
+            LengthStatus::InRange
+        }
+    }
+}
+
+/// How the length of a composition compares to its [`LengthTarget`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthStatus {
+    InRange,
+    /// The composition needs at least this many more rows to reach the target
+    Short(usize),
+    /// The composition exceeds the target by this many rows
+    Long(usize),
+    /// No multiple of the number of parts is within the target, so it can never be reached
+    Unreachable,
+}
+
 /////////////////
 // ERROR TYPES //
 /////////////////
@@ -595,8 +2189,245 @@ pub enum EditError {
         row_idx: isize, // Can be negative if the user was hovering above the first row
         frag_len: usize,
     },
+    CallOutOfRange {
+        idx: CallIdx,
+        len: usize,
+    },
     // Trying to split the region covered by a call
     SplitCall,
+    // Trying to edit a range of rows which doesn't contain any rows
+    EmptyRowRange {
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+    },
+    // Trying to delete every row of a fragment (the fragment should be deleted instead)
+    DeleteAllRows(FragIdx),
+    // Trying to transpose a fragment by a row of a different stage
+    IncompatibleStages(IncompatibleStages),
+    // Trying to set a length target whose minimum is larger than its maximum
+    InvalidLengthTarget {
+        min: usize,
+        max: usize,
+    },
+    // Trying to start a fragment at a sub-lead index which its first method chunk doesn't cover
+    InvalidStart {
+        frag_idx: FragIdx,
+        sub_lead_index: usize,
+    },
+    // Trying to rotate a fragment which doesn't come round
+    NotRoundBlock(FragIdx),
+    // Trying to join a fragment onto itself
+    JoinFragToItself(FragIdx),
+    // Trying to join two fragments with a touch which doesn't end at the second fragment's start
+    TouchDoesntJoin {
+        frag_a: FragIdx,
+        frag_b: FragIdx,
+    },
+    MethodOutOfRange {
+        idx: MethodIdx,
+        len: usize,
+    },
+    // Trying to give a method a rule-off which isn't within its lead
+    RuleoffOutOfRange {
+        idx: usize,
+        lead_len: usize,
+    },
+    // Trying to give a method or call invalid place notation
+    PlaceNotation(PnError),
+    // Trying to add a call over rows which don't all come from the same method
+    CallOverSplice,
+    // Trying to add a call somewhere other than its lead location (e.g. a lead end call at the
+    // half-lead)
+    CallLocation {
+        symbol: char,
+        lead_location: String,
+    },
+    // Trying to give a call the wrong number of calling positions
+    CallingPositions {
+        expected: usize,
+        found: usize,
+    },
+    // Trying to change the length of a call which is used in the composition
+    CallLengthChanged {
+        old_len: usize,
+        new_len: usize,
+    },
+    // A path through the music tree didn't point to a music group of the right type
+    InvalidMusicPath(Vec<usize>),
+    // Trying to make a music group a sub-group of itself
+    MoveMusicIntoItself,
+    // Trying to add an invalid music regex
+    MusicRegex(RegexError),
+    // Trying to refer to a method by a shorthand which no method has
+    NoMethodWithShorthand(String),
+    // Trying to observe a bell which isn't in the composition's stage
+    BellOutOfStage(Bell),
+    // Trying to reverse a composition containing a method which isn't symmetric
+    ReverseAsymmetricMethod(String),
+    // Trying to reverse a fragment which doesn't consist of whole leads
+    ReversePartialLead(FragIdx),
+    // Trying to reverse a composition containing a call which doesn't read the same backwards
+    ReverseCall(char),
+    // Trying to reverse a fragment which splices between methods with different lead ends
+    ReverseSplicedLeadEnds(FragIdx),
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::FragOutOfRange { idx, .. } => {
+                write!(f, "Fragment {} no longer exists", idx.index())
+            }
+            EditError::RowOutOfRange {
+                frag_idx, row_idx, ..
+            } => write!(f, "Fragment {} has no row {}", frag_idx.index(), row_idx),
+            EditError::CallOutOfRange { idx, .. } => {
+                write!(f, "Call #{} no longer exists", idx.index())
+            }
+            EditError::SplitCall => write!(f, "Cannot split in the middle of a call"),
+            EditError::EmptyRowRange { .. } => write!(f, "No rows are selected"),
+            EditError::DeleteAllRows(frag_idx) => write!(
+                f,
+                "Cannot delete every row of fragment {} (delete the fragment instead)",
+                frag_idx.index()
+            ),
+            EditError::IncompatibleStages(e) => write!(f, "{}", e),
+            EditError::InvalidLengthTarget { min, max } => write!(
+                f,
+                "The minimum length ({}) is longer than the maximum length ({})",
+                min, max
+            ),
+            EditError::InvalidStart {
+                frag_idx,
+                sub_lead_index,
+            } => write!(
+                f,
+                "Fragment {} can't start at row {} of the lead",
+                frag_idx.index(),
+                sub_lead_index
+            ),
+            EditError::NotRoundBlock(frag_idx) => write!(
+                f,
+                "Fragment {} doesn't come round, so can't be rotated",
+                frag_idx.index()
+            ),
+            EditError::JoinFragToItself(frag_idx) => {
+                write!(f, "Cannot join fragment {} onto itself", frag_idx.index())
+            }
+            EditError::TouchDoesntJoin { frag_a, frag_b } => write!(
+                f,
+                "Touch doesn't join fragment {} to fragment {}",
+                frag_a.index(),
+                frag_b.index()
+            ),
+            EditError::MethodOutOfRange { idx, .. } => {
+                write!(f, "Method #{} no longer exists", idx.index())
+            }
+            EditError::RuleoffOutOfRange { idx, lead_len } => write!(
+                f,
+                "Cannot put a rule-off after row {} of a lead of {} rows",
+                idx, lead_len
+            ),
+            EditError::PlaceNotation(e) => write!(f, "Invalid place notation: {}", e),
+            EditError::CallOverSplice => {
+                write!(f, "Calls can't cover rows from more than one method")
+            }
+            EditError::CallLocation {
+                symbol,
+                lead_location,
+            } => write!(
+                f,
+                "'{}' calls can only be made at {}",
+                symbol, lead_location
+            ),
+            EditError::CallingPositions { expected, found } => write!(
+                f,
+                "Expected {} calling positions (one per bell), found {}",
+                expected, found
+            ),
+            EditError::CallLengthChanged { old_len, new_len } => write!(
+                f,
+                "Cannot change a call which is used in the composition from covering {} rows to {}",
+                old_len, new_len
+            ),
+            EditError::InvalidMusicPath(_) => write!(f, "That music group no longer exists"),
+            EditError::MoveMusicIntoItself => {
+                write!(f, "Cannot move a music group into itself")
+            }
+            EditError::MusicRegex(e) => write!(f, "Invalid music: {}", e),
+            EditError::NoMethodWithShorthand(shorthand) => {
+                write!(f, "No method has the shorthand '{}'", shorthand)
+            }
+            EditError::BellOutOfStage(bell) => {
+                write!(f, "Bell {} isn't rung in this composition", bell)
+            }
+            EditError::ReverseAsymmetricMethod(shorthand) => write!(
+                f,
+                "Method '{}' isn't symmetric, so the composition can't be reversed",
+                shorthand
+            ),
+            EditError::ReversePartialLead(frag_idx) => write!(
+                f,
+                "Fragment {} isn't made of whole leads, so can't be reversed",
+                frag_idx.index()
+            ),
+            EditError::ReverseCall(symbol) => write!(
+                f,
+                "'{}' calls don't read the same backwards, so the composition can't be reversed",
+                symbol
+            ),
+            EditError::ReverseSplicedLeadEnds(frag_idx) => write!(
+                f,
+                "Fragment {} splices methods with different lead ends, so can't be reversed",
+                frag_idx.index()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// Returns the [`Music`] at a given `path` through the music tree
+fn music_mut<'m>(music: &'m mut Vec<Music>, path: &[usize]) -> Result<&'m mut Music, EditError> {
+    let invalid_path = || EditError::InvalidMusicPath(path.to_vec());
+    let (&idx, parent) = path.split_last().ok_or_else(invalid_path)?;
+    let siblings = music_group_mut(music, parent)?;
+    siblings.get_mut(idx).ok_or_else(invalid_path)
+}
+
+/// Returns the sub-groups of the [`Music::Group`] at a given `path` through the music tree.  An
+/// empty `path` refers to the top-level groups.
+fn music_group_mut<'m>(
+    music: &'m mut Vec<Music>,
+    path: &[usize],
+) -> Result<&'m mut Vec<Music>, EditError> {
+    let mut group = music;
+    for &idx in path {
+        group = match group.get_mut(idx) {
+            Some(Music::Group { sub_groups, .. }) => sub_groups,
+            _ => return Err(EditError::InvalidMusicPath(path.to_vec())),
+        };
+    }
+    Ok(group)
+}
+
+/// Converts empty names into `None`
+fn non_empty(name: String) -> Option<String> {
+    (!name.is_empty()).then_some(name)
+}
+
+/// Checks that a string of calling positions either contains one position per bell, or is empty
+fn check_calling_positions(calling_positions: &str, stage: Stage) -> Result<(), EditError> {
+    let num_positions = calling_positions.chars().count();
+    if num_positions == 0 || num_positions == stage.num_bells() {
+        Ok(())
+    } else {
+        Err(EditError::CallingPositions {
+            expected: stage.num_bells(),
+            found: num_positions,
+        })
+    }
 }
 
 ///////////////
@@ -604,7 +2435,7 @@ pub enum EditError {
 ///////////////
 
 impl Fragment {
-    fn expand(&self, part_heads: &PartHeads) -> ExpandedFrag {
+    pub(crate) fn expand(&self, part_heads: &Rc<PartHeads>) -> ExpandedFrag {
         let mut rows_in_one_part = AnnotBlock::<()>::empty(self.start_row.stage());
         rows_in_one_part.pre_multiply(&self.start_row).unwrap(); // Set the start row of the first chunk
         let mut row_data = RowVec::<RowData>::with_capacity(self.len() + 1);
@@ -618,11 +2449,12 @@ impl Fragment {
             call_source: None,
             is_proved: false, // leftover rows are never proved
         });
-        // Expand the rows across the part heads, thus generating the rows in each part
+        // The rows in the other parts are generated from these as they're needed
         ExpandedFrag::from_single_part(
             rows_in_one_part.into_row_vec(),
             row_data,
             self.is_proved,
+            self.show_bluelines,
             self.position,
             part_heads,
         )
@@ -679,16 +2511,205 @@ impl Chunk {
             }
             Chunk::Call {
                 call,
-                method: _,
-                start_sub_lead_index: _,
+                method,
+                start_sub_lead_index,
             } => {
                 let block = call.inner.block();
-                // TODO: Extend row data
+                // Extend row data.  The rows of a call replace rows of its method, so we give them
+                // the sub-lead indices of the rows they replace.
+                let lead_len = method.inner.lead_len();
+                row_data.extend((0..block.len()).map(|i| {
+                    let sub_lead_idx = (*start_sub_lead_index + i) % lead_len;
+                    RowData {
+                        method_source: Some((method.clone(), sub_lead_idx)),
+                        call_source: Some((call.clone(), i)),
+                        is_proved,
+                    }
+                }));
                 // Extend rows
                 rows_in_one_part.extend(block).unwrap();
-                // Update the start row of the next chunk
-                todo!() // Decide what lead indices should be given
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMBRIDGE: &str = "-36-14-12-36-14-56,12";
+    const PRIMROSE: &str = "-36-14-12-36-14-56,16";
+
+    /// A plain course of Cambridge Minor, along with a bob, a single and a half-lead call
+    fn cambridge_course() -> (CompSpec, CallIdx, CallIdx, CallIdx) {
+        let mut spec = CompSpec::empty(Stage::MINOR);
+        let method = spec
+            .add_method(
+                "C".to_owned(),
+                "C".to_owned(),
+                CAMBRIDGE.to_owned(),
+                vec![0],
+            )
+            .unwrap();
+        let mut add_call = |symbol: char, pn: &str, location: &str| {
+            spec.add_call(symbol, pn.to_owned(), location.to_owned(), String::new())
+                .unwrap()
+        };
+        let bob = add_call('-', "14", "LE");
+        let single = add_call('s', "1234", "LE");
+        let half_lead = add_call('h', "36", "HL");
+        spec.add_fragment(method, true, Position::new(0.0, 0.0))
+            .unwrap();
+        (spec, bob, single, half_lead)
+    }
+
+    /// The [`Row`]s of a [`Fragment`] in the first part, including its leftover [`Row`]
+    fn rows(spec: &CompSpec, frag_idx: usize) -> Vec<RowBuf> {
+        spec.expand_fragments()[FragIdx::new(frag_idx)]
+            .rows_in_one_part
+            .iter()
+            .map(Row::to_owned)
+            .collect()
+    }
+
+    /// Reverses a block of [`Row`]s change-by-change, then moves the first change (i.e. the last
+    /// lead end) to the end, so that the result starts at a lead head
+    fn brute_force_reverse(rows: &[RowBuf]) -> Vec<RowBuf> {
+        let mut changes = rows
+            .iter()
+            .tuple_windows()
+            .map(|(a, b)| Row::solve_ax_equals_b(a, b).unwrap())
+            .collect_vec();
+        changes.reverse();
+        changes.rotate_left(1);
+        let mut reversed_rows = vec![rows[0].clone()];
+        for change in changes {
+            let next_row = reversed_rows.last().unwrap().mul_result(&change).unwrap();
+            reversed_rows.push(next_row);
+        }
+        reversed_rows
+    }
+
+    #[test]
+    fn reverse_matches_brute_force() {
+        let (mut spec, bob, single, half_lead) = cambridge_course();
+        let frag = FragIdx::new(0);
+        spec.set_call(frag, 24, Some(bob)).unwrap();
+        spec.set_call(frag, 60, Some(half_lead)).unwrap();
+        spec.set_call(frag, 96, Some(single)).unwrap();
+        let original_rows = rows(&spec, 0);
+
+        spec.reverse().unwrap();
+        assert_eq!(rows(&spec, 0), brute_force_reverse(&original_rows));
+        // Reversing twice gives the original composition
+        spec.reverse().unwrap();
+        assert_eq!(rows(&spec, 0), original_rows);
+    }
+
+    #[test]
+    fn reverse_last_lead_end_call() {
+        let (mut spec, bob, ..) = cambridge_course();
+        spec.set_call(FragIdx::new(0), 120, Some(bob)).unwrap();
+        let original_rows = rows(&spec, 0);
+        spec.reverse().unwrap();
+        assert_eq!(rows(&spec, 0), brute_force_reverse(&original_rows));
+    }
+
+    #[test]
+    fn reverse_spliced_with_different_lead_ends() {
+        let mut spec = CompSpec::empty(Stage::MINOR);
+        let cambridge = spec
+            .add_method(
+                "C".to_owned(),
+                "C".to_owned(),
+                CAMBRIDGE.to_owned(),
+                vec![0],
+            )
+            .unwrap();
+        let primrose = spec
+            .add_method("P".to_owned(), "P".to_owned(), PRIMROSE.to_owned(), vec![0])
+            .unwrap();
+        let frag = spec
+            .add_fragment(cambridge, false, Position::new(0.0, 0.0))
+            .unwrap();
+        spec.extend_fragment(frag, primrose, false).unwrap();
+        let original_rows = rows(&spec, 0);
+        assert!(matches!(
+            spec.reverse(),
+            Err(EditError::ReverseSplicedLeadEnds(_))
+        ));
+        assert_eq!(rows(&spec, 0), original_rows);
+    }
+
+    #[test]
+    fn rotate_fragment_shifts_rows() {
+        let (mut spec, _, _, half_lead) = cambridge_course();
+        // Making `36` at every half-lead happens to give a round block
+        for lead in 0..5 {
+            spec.set_call(FragIdx::new(0), lead * 24 + 12, Some(half_lead))
+                .unwrap();
+        }
+        let original_rows = rows(&spec, 0);
+        let len = original_rows.len() - 1;
+        assert_eq!(original_rows[0], original_rows[len]);
+
+        spec.rotate_fragment(FragIdx::new(0), 48).unwrap();
+        let rotated_rows = rows(&spec, 0);
+        for (i, row) in rotated_rows.iter().enumerate() {
+            assert_eq!(row, &original_rows[(i + 48) % len]);
+        }
+        // Rotating backwards by the same amount undoes the rotation
+        spec.rotate_fragment(FragIdx::new(0), -48).unwrap();
+        assert_eq!(rows(&spec, 0), original_rows);
+    }
+
+    #[test]
+    fn rotate_transposes_every_fragment() {
+        let (mut spec, ..) = cambridge_course();
+        spec.add_fragment(MethodIdx::new(0), false, Position::new(0.0, 0.0))
+            .unwrap();
+        let (course_rows, lead_rows) = (rows(&spec, 0), rows(&spec, 1));
+        let len = course_rows.len() - 1;
+
+        spec.rotate(FragIdx::new(0), 24).unwrap();
+        // The composition now starts from rounds at the old second lead head, and every fragment
+        // is transposed by the same amount
+        let transposition = course_rows[24].inv();
+        let rotated_course = rows(&spec, 0);
+        assert!(rotated_course[0].is_rounds());
+        for (i, row) in rotated_course.iter().enumerate() {
+            let expected = transposition
+                .mul_result(&course_rows[(i + 24) % len])
+                .unwrap();
+            assert_eq!(row, &expected);
+        }
+        for (row, original_row) in rows(&spec, 1).iter().zip_eq(&lead_rows) {
+            assert_eq!(row, &transposition.mul_result(original_row).unwrap());
+        }
+    }
+
+    #[test]
+    fn rotate_non_round_block() {
+        let (mut spec, bob, ..) = cambridge_course();
+        // A course of Cambridge Minor with one bob doesn't come round
+        spec.set_call(FragIdx::new(0), 24, Some(bob)).unwrap();
+        assert!(matches!(
+            spec.rotate_fragment(FragIdx::new(0), 24),
+            Err(EditError::NotRoundBlock(_))
+        ));
+    }
+
+    #[test]
+    fn swap_calls() {
+        let (mut spec, bob, single, _) = cambridge_course();
+        let mut swapped_spec = spec.clone();
+        let frag = FragIdx::new(0);
+        spec.set_call(frag, 24, Some(bob)).unwrap();
+        spec.set_call(frag, 72, Some(single)).unwrap();
+        swapped_spec.set_call(frag, 24, Some(single)).unwrap();
+        swapped_spec.set_call(frag, 72, Some(bob)).unwrap();
+
+        spec.swap_calls(bob, single).unwrap();
+        assert_eq!(rows(&spec, 0), rows(&swapped_spec, 0));
+    }
+}
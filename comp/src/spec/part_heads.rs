@@ -6,13 +6,39 @@ use bellframe::{IncompatibleStages, InvalidRowError, Row, RowBuf, Stage};
 use itertools::Itertools;
 use serde::Serialize;
 
+/// The largest number of part heads which can be generated from group syntax (e.g.
+/// `<13452, 15432>`).  Even a few generators can generate huge groups (e.g.
+/// `<23456781, 21345678>` generates all 40320 rows of Major), which would take far too long to
+/// expand and aren't sensible compositions anyway.
+pub const MAX_PARTS: usize = 1000;
+
 /// The possible ways that parsing a part head specification can fail
-pub type ParseError = InvalidRowError;
+#[derive(Debug, Clone)]
+pub enum ParseError {
+    /// One of the generators couldn't be parsed as a [`Row`]
+    Row(InvalidRowError),
+    /// The generators generate more than [`MAX_PARTS`] part heads
+    TooManyParts,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Row(e) => write!(f, "{}", e),
+            ParseError::TooManyParts => {
+                write!(f, "These part heads generate more than {} parts", MAX_PARTS)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
 
 /// A struct that stores a specification for a set of part heads.  This contains the [`String`]
 /// that the user entered into the part head box (which must be valid), as well as the
 /// generated set of part heads.  The following invariants must be upheld:
 /// - There is always at least one part head (0 part compositions can't exist)
+/// - The first part head is always rounds
 /// - All the part_heads have the same [`Stage`]
 #[derive(Debug, Clone, Eq, Serialize)]
 pub struct PartHeads {
@@ -92,23 +118,65 @@ impl PartHeads {
     }
 
     /// Attempt to parse then expand a [`str`]ing as a comma-delimited sequence of [`Row`]s.  This
-    /// returns an [`InvalidRowError`] if any of the [`Row`]s couldn't be parsed.
+    /// returns a [`ParseError`] if any of the [`Row`]s couldn't be parsed, or if group syntax
+    /// would generate more than [`MAX_PARTS`] part heads.
     ///
-    /// Expansion converts a sequence of 'generator' rows to the full sequence of part heads, given
-    /// the generators (e.g. `[1342]` will expand to `[1234, 1342, 1423]`, and `[13452, 15432]`
-    /// will expand to `[12345, 15432, 13452, 12543, ...]`).
-    fn parse_and_expand(s: &str, stage: Stage) -> Result<Vec<RowBuf>, InvalidRowError> {
-        let generators = s
+    /// Expansion converts a sequence of 'generator' rows to the products of the powers of each
+    /// generator (e.g. `1342` will expand to `[1234, 1342, 1423]`, and `13452, 15432` will expand
+    /// to `[12345, 15432, 13452, 12543, ...]`).  If the generators are wrapped in angle brackets
+    /// (e.g. `<13452, 15432>`), then they instead expand to the whole group which they generate.
+    /// This is only different if the generators don't commute.  The products of the powers of
+    /// each generator still come first, so the parts are in the order that they're written.
+    fn parse_and_expand(s: &str, stage: Stage) -> Result<Vec<RowBuf>, ParseError> {
+        let group_generators = s
+            .trim()
+            .strip_prefix('<')
+            .and_then(|inner| inner.strip_suffix('>'));
+        let generators = group_generators
+            .unwrap_or(s)
             .split(',')
             .map(|sub_str| RowBuf::parse_with_stage(sub_str, stage))
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ParseError::Row)?;
         // Get the closure (i.e. every power of) each generator
         let closures = generators
             .iter()
             .map(|r| r.closure_from_rounds())
             .collect_vec();
+        if group_generators.is_none() {
+            // Multiply every set of powers of `generators` to get the part heads
+            return Ok(Row::multi_cartesian_product(&closures).unwrap());
+        }
+
+        // Check the size of the product before computing it, since the closures can be quite
+        // large (e.g. a row of Maximus can have order 60)
+        let product_size = closures
+            .iter()
+            .try_fold(1usize, |size, closure| size.checked_mul(closure.len()));
+        match product_size {
+            Some(size) if size <= MAX_PARTS => {}
+            _ => return Err(ParseError::TooManyParts),
+        }
         // Multiply every set of powers of `generators` to get the part heads
-        let part_heads = Row::multi_cartesian_product(&closures).unwrap();
+        let mut part_heads = Row::multi_cartesian_product(&closures).unwrap();
+
+        // If the generators don't commute, then their products won't form a group.  In that case,
+        // keep multiplying the part heads by the generators until no new rows are generated.
+        let mut set = part_heads.iter().cloned().collect::<HashSet<_>>();
+        let mut idx = 0;
+        while let Some(part_head) = part_heads.get(idx) {
+            let new_rows = generators
+                .iter()
+                // Unwrap is safe because all the generators have the same `Stage`
+                .map(|g| part_head.mul_result(g).unwrap())
+                .filter(|r| set.insert(r.clone()))
+                .collect_vec();
+            part_heads.extend(new_rows);
+            if part_heads.len() > MAX_PARTS {
+                return Err(ParseError::TooManyParts);
+            }
+            idx += 1;
+        }
         Ok(part_heads)
     }
 
@@ -204,3 +272,59 @@ pub enum ReparseOk {
     /// [`PartHeads`] is returned.
     DifferentRows(PartHeads),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Vec<String> {
+        let part_heads = PartHeads::parse(s, Stage::MAJOR).unwrap();
+        part_heads.rows().iter().map(RowBuf::to_string).collect()
+    }
+
+    #[test]
+    fn cyclic_part_heads() {
+        let rows = parse("18234567");
+        assert_eq!(rows.len(), 7);
+        assert_eq!(rows[0], "12345678");
+        assert_eq!(rows[1], "18234567");
+        assert_eq!(rows[2], "17823456");
+    }
+
+    #[test]
+    fn commuting_generators() {
+        // The generators commute, so both syntaxes give the same parts in the same order
+        let rows = parse("13425678,12346758");
+        assert_eq!(rows.len(), 9);
+        assert_eq!(parse("<13425678,12346758>"), rows);
+        assert!(PartHeads::parse("13425678,12346758", Stage::MAJOR)
+            .unwrap()
+            .is_group());
+    }
+
+    #[test]
+    fn non_commuting_generators() {
+        // The products of the powers of 1342 and 12354 aren't closed, but their group (every
+        // permutation of 2345) is
+        let products = PartHeads::parse("13425678,12354678", Stage::MAJOR).unwrap();
+        assert_eq!(products.len(), 6);
+        assert!(!products.is_group());
+        let group = PartHeads::parse("<13425678,12354678>", Stage::MAJOR).unwrap();
+        assert_eq!(group.len(), 24);
+        assert!(group.is_group());
+        // The products of powers still come first
+        assert_eq!(&group.rows()[..6], products.rows());
+    }
+
+    #[test]
+    fn too_many_parts() {
+        let result = PartHeads::parse("<23456781,21345678>", Stage::MAJOR);
+        assert!(matches!(result, Err(ParseError::TooManyParts)));
+    }
+
+    #[test]
+    fn invalid_row() {
+        let result = PartHeads::parse("11234567", Stage::MAJOR);
+        assert!(matches!(result, Err(ParseError::Row(_))));
+    }
+}
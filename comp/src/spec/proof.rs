@@ -0,0 +1,145 @@
+//! Code for per-composition proving settings, used by compositions which deliberately only ring
+//! part of the extent.
+
+use bellframe::{music::Regex, Bell, Row, Stage};
+use itertools::Itertools;
+
+use crate::{check_regex, RegexError};
+
+/// The value used to compare [`Row`]s when proving.  [`Bell`]s which aren't being proved are
+/// replaced with `None`, so two [`Row`]s have the same key exactly when all the proved [`Bell`]s
+/// are in the same places.
+pub(crate) type ProofKey = Vec<Option<Bell>>;
+
+/// Settings which relax how a composition is proved.  By default, every [`Row`] must be unique.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofSettings {
+    /// [`Row`]s which match any of these patterns are allowed to be rung any number of times (e.g.
+    /// `*78` allows courses with the tenors home to repeat)
+    allowed_repeats: Vec<Regex>,
+    /// If set, only the places of these [`Bell`]s are proved, so [`Row`]s which only differ in
+    /// the positions of the other [`Bell`]s count as the same (e.g. for tenors-together proving).
+    /// This is kept sorted and never contains duplicates.
+    proved_bells: Option<Vec<Bell>>,
+}
+
+impl ProofSettings {
+    /// Parses [`ProofSettings`] from the strings typed by the user.  `allowed_repeats` is a list
+    /// of patterns, separated by commas or whitespace.  `proved_bells` is a string of bell names
+    /// (e.g. `123456`), where the empty string means that every [`Bell`] is proved.
+    pub fn parse(
+        allowed_repeats: &str,
+        proved_bells: &str,
+        stage: Stage,
+    ) -> Result<Self, ProofSettingsError> {
+        let allowed_repeats = allowed_repeats
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                check_regex(s, stage)
+                    .map(|()| Regex::parse(s))
+                    .map_err(|e| ProofSettingsError::AllowedRepeat(s.to_owned(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let proved_bells = proved_bells
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| {
+                let bell = Bell::from_name(c).ok_or(ProofSettingsError::InvalidBell(c))?;
+                if bell.index() >= stage.num_bells() {
+                    return Err(ProofSettingsError::BellOutOfStage(bell));
+                }
+                Ok(bell)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let proved_bells = if proved_bells.is_empty() {
+            None
+        } else {
+            let mut sorted_bells = proved_bells.clone();
+            sorted_bells.sort_unstable();
+            if let Some((bell, _)) = sorted_bells.iter().tuple_windows().find(|(a, b)| a == b) {
+                return Err(ProofSettingsError::DuplicateBell(*bell));
+            }
+            Some(sorted_bells)
+        };
+
+        Ok(Self {
+            allowed_repeats,
+            proved_bells,
+        })
+    }
+
+    /// Returns `true` if these settings require every [`Row`] to be unique
+    pub fn is_default(&self) -> bool {
+        self.allowed_repeats.is_empty() && self.proved_bells.is_none()
+    }
+
+    /// The patterns of [`Row`]s which are allowed to repeat
+    pub fn allowed_repeats(&self) -> &[Regex] {
+        &self.allowed_repeats
+    }
+
+    /// The [`Bell`]s whose places are proved, or `None` if every [`Bell`] is proved
+    pub fn proved_bells(&self) -> Option<&[Bell]> {
+        self.proved_bells.as_deref()
+    }
+
+    /// The allowed repeats, formatted so that they can be re-parsed by [`ProofSettings::parse`]
+    pub fn allowed_repeats_string(&self) -> String {
+        self.allowed_repeats.iter().join(", ")
+    }
+
+    /// The proved [`Bell`]s, formatted so that they can be re-parsed by [`ProofSettings::parse`]
+    pub fn proved_bells_string(&self) -> String {
+        self.proved_bells().unwrap_or(&[]).iter().join("")
+    }
+
+    /// Returns the [`ProofKey`] used to compare `row` with other [`Row`]s, or `None` if `row` is
+    /// allowed to repeat (and therefore can never be false)
+    pub(crate) fn proof_key(&self, row: &Row) -> Option<ProofKey> {
+        if self.allowed_repeats.iter().any(|regex| regex.matches(row)) {
+            return None;
+        }
+        let key = match &self.proved_bells {
+            Some(bells) => row
+                .bell_iter()
+                .map(|b| bells.binary_search(&b).ok().map(|_| b))
+                .collect(),
+            None => row.bell_iter().map(Some).collect(),
+        };
+        Some(key)
+    }
+}
+
+/// The possible ways that parsing [`ProofSettings`] can fail
+#[derive(Debug, Clone)]
+pub enum ProofSettingsError {
+    /// One of the allowed repeat patterns was invalid
+    AllowedRepeat(String, RegexError),
+    /// A proved bell wasn't a valid bell name
+    InvalidBell(char),
+    /// A proved bell isn't in the [`Stage`] of the composition
+    BellOutOfStage(Bell),
+    /// A [`Bell`] was given as a proved bell more than once
+    DuplicateBell(Bell),
+}
+
+impl std::fmt::Display for ProofSettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofSettingsError::AllowedRepeat(pattern, e) => {
+                write!(f, "Invalid allowed repeat '{}': {}", pattern, e)
+            }
+            ProofSettingsError::InvalidBell(c) => write!(f, "'{}' isn't a bell name", c),
+            ProofSettingsError::BellOutOfStage(bell) => {
+                write!(f, "Bell {} isn't in the stage", bell)
+            }
+            ProofSettingsError::DuplicateBell(bell) => {
+                write!(f, "Bell {} is proved more than once", bell)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProofSettingsError {}
@@ -0,0 +1,852 @@
+//! Code for saving and loading [`CompSpec`]s to and from JSON (or compressed JSON, when
+//! compositions are shared in URLs).
+//!
+//! [`CompSpec`] uses [`Rc`]s to share [`Method`]s, [`Call`]s and [`Chunk`]s, none of which can be
+//! directly (de)serialised.  Instead, we convert the [`CompSpec`] into a parallel set of 'Ser*'
+//! types where all the references are replaced with indices.  These are then serialised with
+//! `serde_json`.
+
+use std::{collections::HashMap, fmt::Formatter, rc::Rc};
+
+use bellframe::{music::Regex, place_not::PnBlockParseError, Bell, InvalidRowError, RowBuf, Stage};
+use jigsaw_utils::{
+    indexed_vec::{CallIdx, CallVec, ChunkVec, FragIdx, FragVec, MethodIdx, MethodVec, PartIdx},
+    types::Position,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    music::{self, RegexError},
+    place_not::PnError,
+    Music,
+};
+
+use super::{
+    part_heads::PartHeads,
+    proof::{ProofSettings, ProofSettingsError},
+    Call, Chunk, CompSpec, EditError, Fragment, LengthTarget, Method,
+};
+
+impl CompSpec {
+    /// Serialises `self` to a JSON string
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&SerSpec::from(self)).unwrap()
+    }
+
+    /// Attempts to load a [`CompSpec`] from a JSON string generated by [`CompSpec::to_json`]
+    pub fn from_json(json: &str) -> Result<Self, LoadError> {
+        let ser_spec: SerSpec = serde_json::from_str(json).map_err(LoadError::Json)?;
+        ser_spec.to_spec()
+    }
+
+    /// Encodes `self` as a string which can be used as the fragment of a URL (i.e. the part after
+    /// the `#`), so that compositions can be shared by link.  This is compact JSON, compressed
+    /// with DEFLATE and then encoded as URL-safe base64.
+    pub fn to_url_fragment(&self) -> String {
+        let json = serde_json::to_string(&SerSpec::from(self)).unwrap();
+        let compressed = miniz_oxide::deflate::compress_to_vec(json.as_bytes(), 10);
+        base64::encode_config(compressed, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Attempts to load a [`CompSpec`] from a URL fragment generated by
+    /// [`CompSpec::to_url_fragment`]
+    pub fn from_url_fragment(fragment: &str) -> Result<Self, LoadError> {
+        let compressed = base64::decode_config(fragment, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| LoadError::UrlFragment)?;
+        let json_bytes = miniz_oxide::inflate::decompress_to_vec(&compressed)
+            .map_err(|_| LoadError::UrlFragment)?;
+        let json = String::from_utf8(json_bytes).map_err(|_| LoadError::UrlFragment)?;
+        Self::from_json(&json)
+    }
+
+    /// Serialises one [`Fragment`] to a JSON string, so that it can be copied to the clipboard.
+    /// [`Method`]s and [`Call`]s are referred to by index, so the [`Fragment`] can only be pasted
+    /// into a composition with the same [`Method`]s and [`Call`]s.
+    pub fn fragment_to_json(&self, frag_idx: FragIdx) -> Result<String, EditError> {
+        let frag = self.get_fragment(frag_idx)?;
+        let clipboard = SerClipboard {
+            jigsaw_fragment: RcIndices::new(self).ser_frag(frag),
+        };
+        Ok(serde_json::to_string_pretty(&clipboard).unwrap())
+    }
+
+    /// Adds a copy of a [`Fragment`] generated by [`CompSpec::fragment_to_json`] to the
+    /// composition, moving it to a new `position`.  Returns the index of the new [`Fragment`].
+    pub fn paste_fragment(&mut self, json: &str, position: Position) -> Result<FragIdx, LoadError> {
+        let clipboard: SerClipboard = serde_json::from_str(json).map_err(LoadError::Json)?;
+        let frag = clipboard
+            .jigsaw_fragment
+            .to_frag(self.stage, &self.methods, &self.calls)?;
+        check_num_rows(
+            self.fragments.iter().map(Rc::as_ref).chain(Some(&frag)),
+            self.part_heads.len(),
+        )?;
+        Ok(self.fragments.push(Rc::new(Fragment { position, ..frag })))
+    }
+}
+
+/// The possible ways that loading a [`CompSpec`] can fail
+#[derive(Debug)]
+pub enum LoadError {
+    /// The JSON was malformed, or didn't match the expected format
+    Json(serde_json::Error),
+    /// A URL fragment couldn't be decompressed into JSON
+    UrlFragment,
+    /// The stage was `0`, so no bells can be rung
+    ZeroStage,
+    /// The part head specification couldn't be parsed
+    PartHeads(super::part_heads::ParseError),
+    /// A method's place notation couldn't be parsed
+    MethodPlaceNotation { name: String, error: PnError },
+    /// A call's place notation couldn't be parsed
+    CallPlaceNotation {
+        symbol: char,
+        error: PnBlockParseError,
+    },
+    /// The proving settings couldn't be parsed
+    ProofSettings(ProofSettingsError),
+    /// The observation bell wasn't the name of a bell in the composition's stage
+    ObservationBell(String),
+    /// A fragment's start row couldn't be parsed
+    StartRow(InvalidRowError),
+    /// A chunk referred to a method which doesn't exist
+    MethodOutOfRange { idx: usize, len: usize },
+    /// A chunk referred to a call which doesn't exist
+    CallOutOfRange { idx: usize, len: usize },
+    /// A chunk of a method had length `0`
+    ZeroLengthChunk,
+    /// A fragment had no chunks
+    EmptyFragment,
+    /// A chunk started further through a lead than the length of its method's lead
+    SubLeadIndexOutOfRange {
+        method: String,
+        sub_lead_index: usize,
+        lead_len: usize,
+    },
+    /// A call was placed somewhere other than its lead location
+    CallLocation { symbol: char, lead_location: String },
+    /// The composition contained more than [`MAX_ROWS`] rows (counting every part)
+    TooManyRows,
+    /// A music pattern wasn't valid
+    MusicRegex { regex: String, error: RegexError },
+}
+
+/// The most [`Row`](bellframe::Row)s (in all parts) that a loaded composition can contain.
+/// Anything bigger is almost certainly corrupted, and would take so long to expand that the GUI
+/// would freeze.
+pub const MAX_ROWS: usize = 1_000_000;
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Json(e) => write!(f, "Invalid JSON: {}", e),
+            LoadError::UrlFragment => write!(f, "The link doesn't contain a valid composition"),
+            LoadError::ZeroStage => write!(f, "Compositions must have at least one bell"),
+            LoadError::PartHeads(e) => write!(f, "Invalid part heads: {}", e),
+            LoadError::MethodPlaceNotation { name, error } => {
+                write!(f, "Invalid place notation for {}: {}", name, error)
+            }
+            LoadError::CallPlaceNotation { symbol, error } => {
+                write!(f, "Invalid place notation for call '{}': {}", symbol, error)
+            }
+            LoadError::ProofSettings(e) => write!(f, "Invalid proving settings: {}", e),
+            LoadError::ObservationBell(name) => write!(f, "Invalid observation bell '{}'", name),
+            LoadError::StartRow(e) => write!(f, "Invalid start row: {}", e),
+            LoadError::MethodOutOfRange { idx, len } => write!(
+                f,
+                "Method #{} is referenced, but only {} methods are defined",
+                idx, len
+            ),
+            LoadError::CallOutOfRange { idx, len } => write!(
+                f,
+                "Call #{} is referenced, but only {} calls are defined",
+                idx, len
+            ),
+            LoadError::ZeroLengthChunk => write!(f, "Chunks of methods can't be empty"),
+            LoadError::EmptyFragment => write!(f, "Fragments can't be empty"),
+            LoadError::SubLeadIndexOutOfRange {
+                method,
+                sub_lead_index,
+                lead_len,
+            } => write!(
+                f,
+                "A chunk starts at row {} of a lead of {}, but its leads only have {} rows",
+                sub_lead_index, method, lead_len
+            ),
+            LoadError::CallLocation {
+                symbol,
+                lead_location,
+            } => write!(
+                f,
+                "Call '{}' is made away from its lead location ({})",
+                symbol, lead_location
+            ),
+            LoadError::TooManyRows => write!(
+                f,
+                "The composition has more than {} rows, so is too long to load",
+                MAX_ROWS
+            ),
+            LoadError::MusicRegex { regex, error } => {
+                write!(f, "Invalid music pattern '{}': {}", regex, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+///////////////////
+// SERIALISATION //
+///////////////////
+
+/// A serialisable version of a [`CompSpec`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SerSpec {
+    stage: usize,
+    part_heads: String,
+    methods: Vec<SerMethod>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    calls: Vec<SerCall>,
+    fragments: Vec<SerFrag>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    music: Vec<SerMusic>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    length_target: Option<LengthTarget>,
+    /// Patterns of rows which are allowed to repeat, in the format parsed by
+    /// [`ProofSettings::parse`]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    allowed_repeats: String,
+    /// The bells whose places are proved, or empty if all bells are proved
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    proved_bells: String,
+    /// The name of the observation bell, or `None` if the tenor is observed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    observation_bell: Option<String>,
+}
+
+/// A serialisable version of a [`Method`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SerMethod {
+    name: String,
+    shorthand: String,
+    place_notation: String,
+    ruleoffs_above: Vec<usize>,
+}
+
+/// A serialisable version of a [`Call`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SerCall {
+    symbol: char,
+    place_notation: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    lead_location: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    calling_positions: String,
+}
+
+/// A serialisable version of a [`Fragment`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SerFrag {
+    x: f32,
+    y: f32,
+    start_row: String,
+    chunks: Vec<SerChunk>,
+    #[serde(default = "jigsaw_utils::serialisation::get_true")]
+    is_proved: bool,
+    #[serde(default)]
+    show_bluelines: bool,
+}
+
+/// A [`Fragment`] which has been copied to the clipboard.  The fragment is wrapped in a named
+/// field so that the clipboard contents are recognisable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SerClipboard {
+    jigsaw_fragment: SerFrag,
+}
+
+/// A serialisable version of a [`Chunk`], where [`Method`]s and [`Call`]s are referred to by
+/// their indices
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SerChunk {
+    Method {
+        method: usize,
+        start_sub_lead_index: usize,
+        length: usize,
+    },
+    Call {
+        call: usize,
+        method: usize,
+        start_sub_lead_index: usize,
+    },
+}
+
+/// A serialisable version of a [`Music`] group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SerMusic {
+    Regex {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        regex: String,
+        #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+        weight: f32,
+        #[serde(default, skip_serializing_if = "is_false")]
+        count_wraps: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parts: Option<Vec<usize>>,
+    },
+    Group {
+        name: String,
+        sub_groups: Vec<SerMusic>,
+        #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+        weight: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parts: Option<Vec<usize>>,
+    },
+}
+
+fn default_weight() -> f32 {
+    Music::DEFAULT_WEIGHT
+}
+
+fn is_default_weight(weight: &f32) -> bool {
+    *weight == Music::DEFAULT_WEIGHT
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl From<&CompSpec> for SerSpec {
+    fn from(spec: &CompSpec) -> Self {
+        let indices = RcIndices::new(spec);
+        SerSpec {
+            stage: spec.stage.num_bells(),
+            part_heads: spec.part_heads.spec_string(),
+            methods: spec
+                .methods
+                .iter()
+                .map(|m| SerMethod {
+                    name: m.name().to_owned(),
+                    shorthand: m.shorthand().to_owned(),
+                    place_notation: m.place_notation.clone(),
+                    // Sorted, so that the output is deterministic
+                    ruleoffs_above: m.ruleoffs_above(),
+                })
+                .collect(),
+            calls: spec
+                .calls
+                .iter()
+                .map(|c| SerCall {
+                    symbol: c.symbol(),
+                    place_notation: c.place_notation.clone(),
+                    lead_location: c.lead_location.clone(),
+                    calling_positions: c.calling_positions.clone(),
+                })
+                .collect(),
+            fragments: spec.fragments.iter().map(|f| indices.ser_frag(f)).collect(),
+            music: spec.music.iter().map(SerMusic::from).collect(),
+            length_target: spec.length_target,
+            allowed_repeats: spec.proof_settings.allowed_repeats_string(),
+            proved_bells: spec.proof_settings.proved_bells_string(),
+            observation_bell: (spec.observation_bell != Bell::tenor(spec.stage))
+                .then(|| spec.observation_bell.name()),
+        }
+    }
+}
+
+/// Maps the [`Rc`]s of a [`CompSpec`]'s [`Method`]s and [`Call`]s (hashed by their memory
+/// addresses) to their indices, so that chunks can refer to methods and calls by index
+struct RcIndices {
+    methods: HashMap<*const Method, MethodIdx>,
+    calls: HashMap<*const Call, CallIdx>,
+}
+
+impl RcIndices {
+    fn new(spec: &CompSpec) -> Self {
+        Self {
+            methods: spec
+                .methods
+                .iter_enumerated()
+                .map(|(idx, m)| (m.as_ref() as *const Method, idx))
+                .collect(),
+            calls: spec
+                .calls
+                .iter_enumerated()
+                .map(|(idx, c)| (c.as_ref() as *const Call, idx))
+                .collect(),
+        }
+    }
+
+    fn method_idx(&self, m: &Rc<Method>) -> usize {
+        self.methods[&(m.as_ref() as *const Method)].index()
+    }
+
+    fn call_idx(&self, c: &Rc<Call>) -> usize {
+        self.calls[&(c.as_ref() as *const Call)].index()
+    }
+
+    fn ser_frag(&self, f: &Fragment) -> SerFrag {
+        SerFrag {
+            x: f.position.x,
+            y: f.position.y,
+            start_row: f.start_row.to_string(),
+            chunks: f
+                .chunks
+                .iter()
+                .map(|c| match c.as_ref() {
+                    Chunk::Method {
+                        method,
+                        start_sub_lead_index,
+                        length,
+                        transposition: _,
+                    } => SerChunk::Method {
+                        method: self.method_idx(method),
+                        start_sub_lead_index: *start_sub_lead_index,
+                        length: *length,
+                    },
+                    Chunk::Call {
+                        call,
+                        method,
+                        start_sub_lead_index,
+                    } => SerChunk::Call {
+                        call: self.call_idx(call),
+                        method: self.method_idx(method),
+                        start_sub_lead_index: *start_sub_lead_index,
+                    },
+                })
+                .collect(),
+            is_proved: f.is_proved,
+            show_bluelines: f.show_bluelines,
+        }
+    }
+}
+
+impl From<&Music> for SerMusic {
+    fn from(music: &Music) -> Self {
+        match music {
+            Music::Regex {
+                name,
+                regex,
+                weight,
+                count_wraps,
+                parts,
+            } => SerMusic::Regex {
+                name: name.clone(),
+                regex: regex.to_string(),
+                weight: *weight,
+                count_wraps: *count_wraps,
+                parts: ser_parts(parts),
+            },
+            Music::Group {
+                name,
+                sub_groups,
+                weight,
+                parts,
+            } => SerMusic::Group {
+                name: name.clone(),
+                sub_groups: sub_groups.iter().map(SerMusic::from).collect(),
+                weight: *weight,
+                parts: ser_parts(parts),
+            },
+        }
+    }
+}
+
+fn ser_parts(parts: &Option<Vec<PartIdx>>) -> Option<Vec<usize>> {
+    parts
+        .as_ref()
+        .map(|parts| parts.iter().map(|p| p.index()).collect())
+}
+
+/////////////////////
+// DESERIALISATION //
+/////////////////////
+
+impl SerSpec {
+    fn to_spec(&self) -> Result<CompSpec, LoadError> {
+        if self.stage == 0 {
+            return Err(LoadError::ZeroStage);
+        }
+        let stage = Stage::new(self.stage);
+
+        let part_heads = PartHeads::parse(&self.part_heads, stage).map_err(LoadError::PartHeads)?;
+        let methods = self
+            .methods
+            .iter()
+            .map(|m| {
+                Method::new(
+                    m.name.clone(),
+                    m.shorthand.clone(),
+                    stage,
+                    m.place_notation.clone(),
+                    m.ruleoffs_above.iter().copied().collect(),
+                )
+                .map(Rc::new)
+                .map_err(|error| LoadError::MethodPlaceNotation {
+                    name: m.name.clone(),
+                    error,
+                })
+            })
+            .collect::<Result<MethodVec<_>, _>>()?;
+        let calls = self
+            .calls
+            .iter()
+            .map(|c| {
+                Call::new(
+                    c.symbol,
+                    c.place_notation.clone(),
+                    c.lead_location.clone(),
+                    c.calling_positions.clone(),
+                    stage,
+                )
+                .map(Rc::new)
+                .map_err(|error| LoadError::CallPlaceNotation {
+                    symbol: c.symbol,
+                    error,
+                })
+            })
+            .collect::<Result<CallVec<_>, _>>()?;
+        let fragments = self
+            .fragments
+            .iter()
+            .map(|f| f.to_frag(stage, &methods, &calls).map(Rc::new))
+            .collect::<Result<FragVec<_>, _>>()?;
+        check_num_rows(fragments.iter().map(Rc::as_ref), part_heads.len())?;
+        let music = self
+            .music
+            .iter()
+            .map(|m| m.to_music(stage))
+            .collect::<Result<Vec<_>, _>>()?;
+        let proof_settings = ProofSettings::parse(&self.allowed_repeats, &self.proved_bells, stage)
+            .map_err(LoadError::ProofSettings)?;
+        let observation_bell = match &self.observation_bell {
+            Some(name) => {
+                parse_bell(name, stage).ok_or_else(|| LoadError::ObservationBell(name.clone()))?
+            }
+            None => Bell::tenor(stage),
+        };
+
+        Ok(CompSpec {
+            fragments,
+            part_heads: Rc::new(part_heads),
+            methods,
+            calls,
+            music: Rc::new(music),
+            length_target: self.length_target,
+            proof_settings: Rc::new(proof_settings),
+            observation_bell,
+            stage,
+        })
+    }
+}
+
+impl SerFrag {
+    fn to_frag(
+        &self,
+        stage: Stage,
+        methods: &MethodVec<Rc<Method>>,
+        calls: &CallVec<Rc<Call>>,
+    ) -> Result<Fragment, LoadError> {
+        let start_row =
+            RowBuf::parse_with_stage(&self.start_row, stage).map_err(LoadError::StartRow)?;
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|c| c.to_chunk(methods, calls).map(Rc::new))
+            .collect::<Result<ChunkVec<_>, _>>()?;
+        if chunks.is_empty() {
+            return Err(LoadError::EmptyFragment);
+        }
+        Ok(Fragment {
+            position: Position::new(self.x, self.y),
+            start_row: Rc::new(start_row),
+            chunks,
+            is_proved: self.is_proved,
+            show_bluelines: self.show_bluelines,
+        })
+    }
+}
+
+impl SerChunk {
+    fn to_chunk(
+        &self,
+        methods: &MethodVec<Rc<Method>>,
+        calls: &CallVec<Rc<Call>>,
+    ) -> Result<Chunk, LoadError> {
+        Ok(match *self {
+            SerChunk::Method {
+                method,
+                start_sub_lead_index,
+                length,
+            } => {
+                if length == 0 {
+                    return Err(LoadError::ZeroLengthChunk);
+                }
+                // Check the length before using it, since huge lengths can overflow
+                if length > MAX_ROWS {
+                    return Err(LoadError::TooManyRows);
+                }
+                let method = get_method(methods, method)?;
+                check_sub_lead_index(&method, start_sub_lead_index)?;
+                Chunk::method(method, start_sub_lead_index, length)
+            }
+            SerChunk::Call {
+                call,
+                method,
+                start_sub_lead_index,
+            } => {
+                let call = get_call(calls, call)?;
+                let method = get_method(methods, method)?;
+                check_sub_lead_index(&method, start_sub_lead_index)?;
+                if !call.can_end_at(&method, start_sub_lead_index + call.len()) {
+                    return Err(LoadError::CallLocation {
+                        symbol: call.symbol(),
+                        lead_location: call.lead_location().to_owned(),
+                    });
+                }
+                Chunk::Call {
+                    call,
+                    method,
+                    start_sub_lead_index,
+                }
+            }
+        })
+    }
+}
+
+impl SerMusic {
+    fn to_music(&self, stage: Stage) -> Result<Music, LoadError> {
+        Ok(match self {
+            SerMusic::Regex {
+                name,
+                regex,
+                weight,
+                count_wraps,
+                parts,
+            } => {
+                music::check_regex(regex, stage).map_err(|error| LoadError::MusicRegex {
+                    regex: regex.clone(),
+                    error,
+                })?;
+                Music::Regex {
+                    name: name.clone(),
+                    regex: Regex::parse(regex),
+                    weight: *weight,
+                    count_wraps: *count_wraps,
+                    parts: de_parts(parts),
+                }
+            }
+            SerMusic::Group {
+                name,
+                sub_groups,
+                weight,
+                parts,
+            } => Music::Group {
+                name: name.clone(),
+                sub_groups: sub_groups
+                    .iter()
+                    .map(|m| m.to_music(stage))
+                    .collect::<Result<_, _>>()?,
+                weight: *weight,
+                parts: de_parts(parts),
+            },
+        })
+    }
+}
+
+fn de_parts(parts: &Option<Vec<usize>>) -> Option<Vec<PartIdx>> {
+    parts
+        .as_ref()
+        .map(|parts| parts.iter().copied().map(PartIdx::new).collect())
+}
+
+fn get_method(methods: &MethodVec<Rc<Method>>, idx: usize) -> Result<Rc<Method>, LoadError> {
+    methods
+        .get(MethodIdx::new(idx))
+        .ok_or(LoadError::MethodOutOfRange {
+            idx,
+            len: methods.len(),
+        })
+        .map(Rc::clone)
+}
+
+/// Checks that a [`Chunk`] of `method` can start `sub_lead_index` rows into a lead
+fn check_sub_lead_index(method: &Method, sub_lead_index: usize) -> Result<(), LoadError> {
+    if sub_lead_index < method.lead_len() {
+        Ok(())
+    } else {
+        Err(LoadError::SubLeadIndexOutOfRange {
+            method: method.name().to_owned(),
+            sub_lead_index,
+            lead_len: method.lead_len(),
+        })
+    }
+}
+
+/// Checks that `fragments` contain no more than [`MAX_ROWS`] [`Row`](bellframe::Row)s once
+/// they're expanded into every part
+fn check_num_rows<'f>(
+    fragments: impl IntoIterator<Item = &'f Fragment>,
+    num_parts: usize,
+) -> Result<(), LoadError> {
+    let mut rows_per_part = 0usize;
+    for frag in fragments {
+        // Include the leftover row, which is also expanded
+        rows_per_part = rows_per_part.saturating_add(frag.len()).saturating_add(1);
+    }
+    match rows_per_part.checked_mul(num_parts) {
+        Some(num_rows) if num_rows <= MAX_ROWS => Ok(()),
+        _ => Err(LoadError::TooManyRows),
+    }
+}
+
+fn get_call(calls: &CallVec<Rc<Call>>, idx: usize) -> Result<Rc<Call>, LoadError> {
+    calls
+        .get(CallIdx::new(idx))
+        .ok_or(LoadError::CallOutOfRange {
+            idx,
+            len: calls.len(),
+        })
+        .map(Rc::clone)
+}
+
+/// Parses the name of a single [`Bell`], returning `None` if `name` isn't exactly one bell in
+/// `stage`
+fn parse_bell(name: &str, stage: Stage) -> Option<Bell> {
+    let mut chars = name.chars();
+    let bell = Bell::from_name(chars.next()?)?;
+    (chars.next().is_none() && bell.index() < stage.num_bells()).then_some(bell)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example composition, with a call added so that every type of [`Chunk`] is saved
+    fn example_with_call() -> CompSpec {
+        let mut spec = CompSpec::example();
+        // Call a bob at the second lead end of the example touch
+        spec.set_call(FragIdx::new(0), 64, Some(CallIdx::new(0)))
+            .unwrap();
+        spec
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let spec = example_with_call();
+        let json = spec.to_json();
+        let loaded = CompSpec::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json(), json);
+    }
+
+    #[test]
+    fn url_fragment_round_trip() {
+        let spec = example_with_call();
+        let loaded = CompSpec::from_url_fragment(&spec.to_url_fragment()).unwrap();
+        assert_eq!(loaded.to_json(), spec.to_json());
+    }
+
+    #[test]
+    fn fragment_round_trip() {
+        let mut spec = example_with_call();
+        let json = spec.fragment_to_json(FragIdx::new(0)).unwrap();
+        // Paste the copy in the same place, so that it's saved identically
+        let position = spec.fragments[FragIdx::new(0)].position;
+        let new_frag_idx = spec.paste_fragment(&json, position).unwrap();
+        assert_eq!(spec.fragment_to_json(new_frag_idx).unwrap(), json);
+    }
+
+    #[test]
+    fn zero_stage() {
+        let json = CompSpec::example()
+            .to_json()
+            .replace("\"stage\": 8", "\"stage\": 0");
+        assert!(matches!(
+            CompSpec::from_json(&json),
+            Err(LoadError::ZeroStage)
+        ));
+    }
+
+    /// Loads [`example_with_call`] after `edit` has modified its JSON
+    fn load_edited(edit: impl FnOnce(&mut serde_json::Value)) -> Result<CompSpec, LoadError> {
+        let mut json: serde_json::Value =
+            serde_json::from_str(&example_with_call().to_json()).unwrap();
+        edit(&mut json);
+        CompSpec::from_json(&json.to_string())
+    }
+
+    #[test]
+    fn empty_fragment() {
+        let result = load_edited(|json| json["fragments"][0]["chunks"] = serde_json::json!([]));
+        assert!(matches!(result, Err(LoadError::EmptyFragment)));
+        // Pasting shares the same loader
+        let mut spec = example_with_call();
+        let mut frag: serde_json::Value =
+            serde_json::from_str(&spec.fragment_to_json(FragIdx::new(0)).unwrap()).unwrap();
+        frag["jigsaw_fragment"]["chunks"] = serde_json::json!([]);
+        let position = spec.fragments[FragIdx::new(0)].position;
+        assert!(matches!(
+            spec.paste_fragment(&frag.to_string(), position),
+            Err(LoadError::EmptyFragment)
+        ));
+    }
+
+    #[test]
+    fn huge_chunk() {
+        let result = load_edited(|json| {
+            json["fragments"][0]["chunks"][0]["length"] = serde_json::json!(u64::MAX)
+        });
+        assert!(matches!(result, Err(LoadError::TooManyRows)));
+    }
+
+    #[test]
+    fn too_many_rows() {
+        // Each chunk is small enough on its own, but not once expanded into all 7 parts
+        let result = load_edited(|json| {
+            json["fragments"][0]["chunks"][0]["length"] = serde_json::json!(MAX_ROWS / 4)
+        });
+        assert!(matches!(result, Err(LoadError::TooManyRows)));
+    }
+
+    #[test]
+    fn sub_lead_index_out_of_range() {
+        let result = load_edited(|json| {
+            json["fragments"][0]["chunks"][0]["start_sub_lead_index"] = serde_json::json!(32)
+        });
+        assert!(matches!(
+            result,
+            Err(LoadError::SubLeadIndexOutOfRange {
+                sub_lead_index: 32,
+                lead_len: 32,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn call_away_from_lead_end() {
+        let result = load_edited(|json| {
+            json["fragments"][0]["chunks"][2]["start_sub_lead_index"] = serde_json::json!(15)
+        });
+        assert!(matches!(
+            result,
+            Err(LoadError::CallLocation { symbol: '-', .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_music_regex() {
+        let result = load_edited(|json| {
+            json["music"][0]["sub_groups"][0]["regex"] = serde_json::json!("*6579")
+        });
+        assert!(matches!(
+            result,
+            Err(LoadError::MusicRegex {
+                error: RegexError::BellOutOfStage(_),
+                ..
+            })
+        ));
+    }
+}
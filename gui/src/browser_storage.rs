@@ -0,0 +1,89 @@
+//! Persistence of compositions on the web build, where there's no file system.  eframe's web
+//! [`Storage`] is backed by the browser's `localStorage`, so compositions are stored there,
+//! alongside the [`Config`](crate::config::Config).
+//!
+//! Saving and opening use one slot per composition name (i.e. the file path which would be used
+//! on the native build).  Separately, the composition being edited is auto-saved so that
+//! refreshing the page doesn't lose any work.
+
+use std::collections::{HashMap, HashSet};
+
+use eframe::epi::{self, Storage};
+use itertools::Itertools;
+
+/// The key under which the names of every slot are stored
+const SLOT_NAMES_KEY: &str = "composition_slots";
+/// The key under which the composition being edited is auto-saved
+const AUTOSAVE_KEY: &str = "composition_autosave";
+
+/// The storage key of the slot with a given name
+fn slot_key(name: &str) -> String {
+    format!("composition:{}", name)
+}
+
+/// The compositions stored in the browser.  eframe only gives access to [`Storage`] at start-up
+/// and when auto-saving, so every slot is loaded at start-up and changes are written back during
+/// the next auto-save.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BrowserStorage {
+    /// The JSON of the composition stored in each slot
+    slots: HashMap<String, String>,
+    /// The slots which have changed since they were last written to [`Storage`]
+    dirty_slots: HashSet<String>,
+    /// The JSON of the composition which was last auto-saved
+    autosave: Option<String>,
+}
+
+impl BrowserStorage {
+    /// Loads every slot from [`Storage`]
+    pub fn load(storage: &dyn Storage) -> Self {
+        let names: Vec<String> = epi::get_value(storage, SLOT_NAMES_KEY).unwrap_or_default();
+        let slots = names
+            .into_iter()
+            .filter_map(|name| {
+                let json = storage.get_string(&slot_key(&name))?;
+                Some((name, json))
+            })
+            .collect();
+        Self {
+            slots,
+            dirty_slots: HashSet::new(),
+            autosave: storage.get_string(AUTOSAVE_KEY),
+        }
+    }
+
+    /// The JSON of the composition which was auto-saved before the page was last closed
+    pub fn autosave(&self) -> Option<&str> {
+        self.autosave.as_deref()
+    }
+
+    /// The JSON of the composition stored in a given slot
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.slots.get(name).map(String::as_str)
+    }
+
+    /// Stores a composition's JSON in a given slot, overwriting any existing composition
+    pub fn set(&mut self, name: &str, json: String) {
+        self.slots.insert(name.to_owned(), json);
+        self.dirty_slots.insert(name.to_owned());
+    }
+
+    /// Writes any changed slots to [`Storage`], and auto-saves the composition being edited
+    pub fn write(&mut self, storage: &mut dyn Storage, current_json: String) {
+        if !self.dirty_slots.is_empty() {
+            for name in self.dirty_slots.drain() {
+                storage.set_string(&slot_key(&name), self.slots[&name].clone());
+            }
+            epi::set_value(
+                storage,
+                SLOT_NAMES_KEY,
+                &self.slots.keys().sorted().collect_vec(),
+            );
+        }
+        // Only write the auto-save if the composition has changed
+        if self.autosave.as_ref() != Some(&current_json) {
+            storage.set_string(AUTOSAVE_KEY, current_json.clone());
+            self.autosave = Some(current_json);
+        }
+    }
+}
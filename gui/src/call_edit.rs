@@ -0,0 +1,157 @@
+//! Drawing code for the window used to add or edit a [`Call`](full::Call)
+
+use bellframe::{PnBlock, Stage};
+use eframe::egui::{self, Color32};
+use jigsaw_comp::full;
+use jigsaw_utils::indexed_vec::CallIdx;
+
+use crate::{Action, CompAction};
+
+/// The state of a [`Call`](full::Call) which is being added or edited.  Like
+/// [`MethodEdit`](crate::method_edit::MethodEdit), this can diverge from the
+/// [`Call`](full::Call)s stored in the undo history whilst the user is typing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CallEdit {
+    /// The index of the [`Call`](full::Call) being edited, or `None` if a new
+    /// [`Call`](full::Call) is being added
+    pub call_idx: Option<CallIdx>,
+    pub symbol: String,
+    pub place_notation: String,
+    pub lead_location: String,
+    pub calling_positions: String,
+}
+
+impl CallEdit {
+    /// Start editing a [`Call`](full::Call), with all the fields filled in with its current
+    /// values
+    pub(crate) fn new(call_idx: CallIdx, call: &full::Call) -> Self {
+        Self {
+            call_idx: Some(call_idx),
+            symbol: call.symbol().to_string(),
+            place_notation: call.place_notation(),
+            lead_location: call.lead_location(),
+            calling_positions: call.calling_positions(),
+        }
+    }
+
+    /// Start adding a new [`Call`](full::Call).  Most calls are made at the lead end, so the lead
+    /// location is filled in with `"LE"`.
+    pub(crate) fn new_call() -> Self {
+        Self {
+            call_idx: None,
+            symbol: String::new(),
+            place_notation: String::new(),
+            lead_location: "LE".to_owned(),
+            calling_positions: String::new(),
+        }
+    }
+
+    /// Returns the symbol of this call, or `None` if the user hasn't typed exactly one character
+    fn symbol_char(&self) -> Option<char> {
+        let mut chars = self.symbol.trim().chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    edit: &CallEdit,
+    stage: Stage,
+    mut push_action: impl FnMut(Action),
+) {
+    let title = match edit.call_idx {
+        Some(_) => "Edit call",
+        None => "Add call",
+    };
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut new_edit = edit.clone();
+            egui::Grid::new("call_edit_grid").show(ui, |grid_ui| {
+                grid_ui.label("Symbol:");
+                grid_ui.text_edit_singleline(&mut new_edit.symbol);
+                grid_ui.end_row();
+
+                grid_ui.label("Place notation:");
+                grid_ui.text_edit_singleline(&mut new_edit.place_notation);
+                grid_ui.end_row();
+
+                grid_ui.label("Lead location:");
+                grid_ui
+                    .text_edit_singleline(&mut new_edit.lead_location)
+                    .on_hover_text(
+                    "'LE' for lead end calls, 'HL' for half-lead calls, or the index in the lead \
+                         of the row after the call.  Anything else can be called anywhere.",
+                );
+                grid_ui.end_row();
+
+                grid_ui.label("Calling positions:");
+                grid_ui.text_edit_singleline(&mut new_edit.calling_positions);
+                grid_ui.end_row();
+            });
+            // Update the app's `call_edit` before the buttons' actions, so that closing the window
+            // takes precedence
+            if &new_edit != edit {
+                push_action(Action::SetCallEdit(Some(new_edit)));
+            }
+
+            // Check every field each frame, so that the user gets live feedback
+            let mut errors = Vec::new();
+            let symbol = edit.symbol_char();
+            if symbol.is_none() {
+                errors.push("Symbol must be a single character".to_owned());
+            }
+            if let Err(e) = PnBlock::parse(&edit.place_notation, stage) {
+                errors.push(e.to_string());
+            }
+            let num_positions = edit.calling_positions.chars().count();
+            if num_positions != 0 && num_positions != stage.num_bells() {
+                errors.push(format!(
+                    "Expected {} calling positions, found {}",
+                    stage.num_bells(),
+                    num_positions
+                ));
+            }
+            for e in &errors {
+                ui.label(egui::Label::new(e).text_color(Color32::RED));
+            }
+
+            ui.horizontal(|button_ui| {
+                // Only allow the user to apply edits which will succeed
+                if button_ui
+                    .add(egui::Button::new("Apply").enabled(errors.is_empty()))
+                    .clicked()
+                {
+                    // Unwrap is safe because the button is disabled if the symbol is invalid
+                    let symbol = symbol.unwrap();
+                    let place_notation = edit.place_notation.clone();
+                    let lead_location = edit.lead_location.trim().to_owned();
+                    let calling_positions = edit.calling_positions.clone();
+                    let comp_action = match edit.call_idx {
+                        Some(call_idx) => CompAction::EditCall {
+                            call_idx,
+                            symbol,
+                            place_notation,
+                            lead_location,
+                            calling_positions,
+                        },
+                        None => CompAction::AddCall {
+                            symbol,
+                            place_notation,
+                            lead_location,
+                            calling_positions,
+                        },
+                    };
+                    push_action(Action::Comp(comp_action));
+                    push_action(Action::SetCallEdit(None));
+                }
+                if button_ui.button("Cancel").clicked() {
+                    push_action(Action::SetCallEdit(None));
+                }
+            });
+        });
+}
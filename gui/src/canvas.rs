@@ -2,6 +2,7 @@
 
 use std::{
     collections::{HashMap, HashSet},
+    convert::TryFrom,
     sync::Arc,
 };
 
@@ -11,38 +12,82 @@ use eframe::egui::{
     Vec2, Widget,
 };
 use itertools::Itertools;
-use jigsaw_comp::full::{Fragment, FullState, RowDataForOnePart};
+use jigsaw_comp::full::{FragEnd, Fragment, FullState, MusicCounts, RowDataForOnePart};
 use jigsaw_utils::{
-    indexed_vec::{FragIdx, PartIdx},
+    indexed_vec::{FragIdx, PartIdx, RowIdx},
     types::RowSource,
 };
 
-use crate::config::Config;
+use crate::{
+    config::Config,
+    playback::PlaybackCursor,
+    theme::{Hatch, Palette},
+};
+
+/// The [`TextStyle`] of the text drawn on the canvas, whose size is set by [`Config::font_size`].
+/// This is monospace to match the SVG export.
+pub(crate) const TEXT_STYLE: TextStyle = TextStyle::Monospace;
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn draw(
     ctx: &egui::CtxRef,
     full_state: &FullState,
     config: &Config,
     camera_pos: Pos2,
     rows_to_highlight: HashSet<RowSource>,
+    selection: Option<RowSelection>,
     part_being_viewed: PartIdx,
+    handbell_pair: Option<(Bell, Bell)>,
+    highlighted_bell: Option<Bell>,
+    playback_cursor: Option<PlaybackCursor>,
 ) -> CanvasResponse {
     let mut frag_hover = None;
+    let mut cursor_pos = None;
+    // The same as egui's default central panel, but filled with the theme's canvas colour
+    let frame = egui::Frame {
+        margin: Vec2::new(8.0, 8.0),
+        fill: config.palette().canvas_background,
+        ..Default::default()
+    };
     let inner_response = egui::CentralPanel::default()
+        .frame(frame)
         .show(ctx, |ui| {
             ui.add(CanvasWidget {
                 full_state,
                 config,
                 camera_pos,
                 rows_to_highlight,
+                selection,
                 part_being_viewed,
-                frag_hover: &mut frag_hover, // Used to pass values out of `ui.add`
+                handbell_pair,
+                highlighted_bell,
+                playback_cursor,
+                // Used to pass values out of `ui.add`
+                frag_hover: &mut frag_hover,
+                cursor_pos: &mut cursor_pos,
             })
         })
         .inner;
 
+    // Show which music is matched by the row under the cursor
+    if let Some(source) = frag_hover
+        .as_ref()
+        .and_then(|h| h.hovered_row_source(full_state))
+    {
+        let matches = full_state.music.matches_for_row(source);
+        if !matches.is_empty() {
+            egui::show_tooltip(ctx, egui::Id::new("music_tooltip"), |ui| {
+                for m in matches {
+                    let part_name = format!("Part {}", m.part.index() + 1);
+                    ui.label(format!("{}: {}", part_name, m.group_names.join(" > ")));
+                }
+            });
+        }
+    }
+
     CanvasResponse {
         frag_hover,
+        cursor_pos,
         inner: inner_response,
     }
 }
@@ -50,6 +95,9 @@ pub(crate) fn draw(
 #[derive(Debug, Clone)]
 pub(crate) struct CanvasResponse {
     pub frag_hover: Option<FragHover>,
+    /// The location of the cursor in the same coordinates as [`Fragment::position`], or `None` if
+    /// the cursor isn't over the canvas
+    pub cursor_pos: Option<Pos2>,
     pub inner: Response,
 }
 
@@ -63,16 +111,31 @@ struct CanvasWidget<'a> {
     /// Position of the camera
     camera_pos: Pos2,
     rows_to_highlight: HashSet<RowSource>,
+    /// The rows which the user has selected, if any
+    selection: Option<RowSelection>,
     part_being_viewed: PartIdx,
+    /// The bells rung by one handbell ringer, which are highlighted in every row
+    handbell_pair: Option<(Bell, Bell)>,
+    /// The bell whose path is highlighted in every row
+    highlighted_bell: Option<Bell>,
+    /// The row being rung during playback, if the composition is being played
+    playback_cursor: Option<PlaybackCursor>,
     frag_hover: &'a mut Option<FragHover>,
+    cursor_pos: &'a mut Option<Pos2>,
 }
 
 impl<'a> Widget for CanvasWidget<'a> {
-    fn ui(self, ui: &mut Ui) -> eframe::egui::Response {
+    fn ui(mut self, ui: &mut Ui) -> eframe::egui::Response {
         let size = ui.available_size_before_wrap_finite();
         let (rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
 
         let origin = rect.min - self.camera_pos.to_vec2();
+        // Save the cursor's position, so that new fragments can be added under the cursor
+        if let Some(mouse_pos) = ui.ctx().input().pointer.hover_pos() {
+            if rect.contains(mouse_pos) {
+                *self.cursor_pos = Some((mouse_pos - origin).to_pos2());
+            }
+        }
 
         // Generate 'Galley's for every bell before rendering starts, placing them in a lookup
         // table when rendering.  This way, the text layout only gets calculated once which
@@ -81,86 +144,232 @@ impl<'a> Widget for CanvasWidget<'a> {
             .full_state
             .stage
             .bells()
-            .map(|bell| ui.fonts().layout_single_line(TextStyle::Body, bell.name()))
+            .map(|bell| ui.fonts().layout_single_line(TEXT_STYLE, bell.name()))
             .collect_vec();
 
+        // Decide which parts to draw, and in which columns
+        let parts_to_draw = if self.config.show_all_parts {
+            (0..self.full_state.part_heads.len())
+                .map(PartIdx::new)
+                .collect_vec()
+        } else {
+            vec![self.part_being_viewed]
+        };
+        let part_col_width = self.config.part_col_width(self.full_state.stage);
+
+        // Find the fragment under the cursor before anything is drawn, so that hovering a false
+        // row can highlight every row which it's false against
+        self.update_frag_hover(ui, origin, parts_to_draw.len(), part_col_width);
+        let false_rows = self.hovered_false_rows();
+        self.rows_to_highlight.extend(false_rows.iter().copied());
+
         for (frag_idx, frag) in self.full_state.fragments.iter_enumerated() {
-            /* Compute bboxes */
-
-            // The unpadded rectangle containing all the rows
-            let row_bbox = Rect::from_min_size(
-                origin + frag.position.to_vec2(),
-                Vec2::new(
-                    self.config.col_width * self.full_state.stage.num_bells() as f32,
-                    // TODO: This doesn't take row folding into account - once row folding is
-                    // implemented, this will become incorrect
-                    self.config.row_height * frag.num_rows() as f32,
-                ),
-            );
-            // The bounding box of the fragment **after** padding has been added.  This is used for
-            // detecting mouse input and is used to draw the backing rectangle
-            let padded_bbox = row_bbox.expand2(self.config.frag_padding_vec());
+            for (col_idx, &part) in parts_to_draw.iter().enumerate() {
+                /* Compute bboxes */
+
+                let row_bbox = self.row_bbox(origin, frag, col_idx, part_col_width);
+                // The bounding box of the fragment **after** padding has been added.  This is
+                // used for detecting mouse input and is used to draw the backing rectangle
+                let padded_bbox = row_bbox.expand2(self.config.frag_padding_vec());
+
+                /* Draw fragment */
+
+                // When the parts are side-by-side, only annotate the outside edges of the parts
+                // so that the annotations don't overlap the neighbouring parts
+                let annotations = Annotations {
+                    calls: col_idx == 0,
+                    methods: col_idx == parts_to_draw.len() - 1,
+                };
+                self.draw_frag(
+                    ui,
+                    frag_idx,
+                    frag,
+                    part,
+                    annotations,
+                    row_bbox,
+                    padded_bbox,
+                    &bell_name_galleys,
+                );
+            }
+        }
+        self.draw_off_screen_markers(ui, rect, origin, &false_rows);
 
-            /* Draw fragment */
+        response
+    }
+}
 
-            self.draw_frag(
-                ui,
-                frag_idx,
-                frag,
-                row_bbox,
-                padded_bbox,
-                &bell_name_galleys,
-            );
+impl<'a> CanvasWidget<'a> {
+    /// The unpadded screen-space rectangle containing all the rows of one part of a [`Fragment`],
+    /// where that part is drawn in the `col_idx`th column
+    fn row_bbox(&self, origin: Pos2, frag: &Fragment, col_idx: usize, part_col_width: f32) -> Rect {
+        // In the course view, each course takes up one line
+        let num_lines = if self.config.course_view {
+            frag.courses.len().max(1)
+        } else {
+            frag.num_rows()
+        };
+        Rect::from_min_size(
+            origin
+                + Pos2::from(frag.position).to_vec2()
+                + Vec2::new(col_idx as f32 * part_col_width, 0.0),
+            Vec2::new(
+                self.config.col_width * self.full_state.stage.num_bells() as f32,
+                // TODO: This doesn't take row folding into account - once row folding is
+                // implemented, this will become incorrect
+                self.config.row_height * num_lines as f32,
+            ),
+        )
+    }
 
-            // If the cursor is hovering this fragment, then save its position.  When the user
-            // presses a key, this position is used by the input handling code to determine which
-            // fragment/row should receive the input.
-            if let Some(mouse_pos) = ui.ctx().input().pointer.hover_pos() {
-                if padded_bbox.contains(mouse_pos) {
-                    let mouse_indices_float =
+    /// If the cursor is hovering a fragment (in any part), then save its position.  When the user
+    /// presses a key, this position is used by the input handling code to determine which
+    /// fragment/row should receive the input.
+    fn update_frag_hover(&mut self, ui: &Ui, origin: Pos2, num_cols: usize, part_col_width: f32) {
+        let mouse_pos = match ui.ctx().input().pointer.hover_pos() {
+            Some(pos) => pos,
+            None => return,
+        };
+        for (frag_idx, frag) in self.full_state.fragments.iter_enumerated() {
+            for col_idx in 0..num_cols {
+                let row_bbox = self.row_bbox(origin, frag, col_idx, part_col_width);
+                if row_bbox
+                    .expand2(self.config.frag_padding_vec())
+                    .contains(mouse_pos)
+                {
+                    let mut mouse_indices_float =
                         (mouse_pos - row_bbox.min) / self.config.bell_box_size();
+                    if self.config.course_view {
+                        mouse_indices_float.y = course_line_to_row_idx(frag, mouse_indices_float.y);
+                    }
                     // Overwrite the `frag_hover` with this fragment.  This way, the top-most
                     // fragment will take any user input
                     *self.frag_hover = Some(FragHover::new(frag_idx, mouse_indices_float));
                 }
             }
         }
+    }
 
-        response
+    /// If the cursor is over a false row, returns every row which is false against it (including
+    /// the hovered row itself).  Otherwise, returns an empty set.
+    fn hovered_false_rows(&self) -> HashSet<RowSource> {
+        let mut false_rows = HashSet::new();
+        let hovered_row = self
+            .frag_hover
+            .as_ref()
+            .and_then(|h| h.hovered_row_source(self.full_state));
+        if let Some(source) = hovered_row {
+            let falseness = &self.full_state.falseness;
+            for &group_idx in falseness.groups_for_row(source) {
+                falseness.groups[group_idx].add_row_sources(&mut false_rows);
+            }
+        }
+        false_rows
+    }
+
+    /// Draws a marker on the edge of the canvas for every row in `rows` which is off-screen, so
+    /// that the user can see which direction to scroll to find it
+    fn draw_off_screen_markers(
+        &self,
+        ui: &mut Ui,
+        canvas_rect: Rect,
+        origin: Pos2,
+        rows: &HashSet<RowSource>,
+    ) {
+        // Rows are summarised in the course view, so don't have their own positions
+        if self.config.course_view {
+            return;
+        }
+        let radius = self.config.off_screen_marker_radius;
+        let marker_area = canvas_rect.shrink(radius);
+        for source in rows {
+            let frag = &self.full_state.fragments[source.frag_index];
+            let row_centre = origin
+                + Pos2::from(frag.position).to_vec2()
+                + Vec2::new(
+                    self.config.col_width * self.full_state.stage.num_bells() as f32 / 2.0,
+                    self.config.row_height * (source.row_index.index() as f32 + 0.5),
+                );
+            if !canvas_rect.contains(row_centre) {
+                ui.painter().add(Shape::circle_filled(
+                    marker_area.clamp(row_centre),
+                    radius,
+                    self.config.off_screen_marker_color,
+                ));
+            }
+        }
     }
-}
 
-impl<'a> CanvasWidget<'a> {
     /// Draw a [`Fragment`] to the display, returning the bounding [`Rect`] of this [`Fragment`]
     /// **in screen space**.
+    #[allow(clippy::too_many_arguments)]
     fn draw_frag(
         &self,
         ui: &mut Ui,
         frag_index: FragIdx,
         frag: &Fragment,
+        part: PartIdx,
+        annotations: Annotations,
         rows_bbox: Rect,   // The bbox containing the rows of this fragment
         padded_bbox: Rect, // The bbox which adds padding round the rows
         bell_name_galleys: &[Arc<Galley>],
     ) {
+        // When drawing bluelines, only the blueline bells are drawn (as lines) and all the other
+        // bells are hidden
+        let show_bluelines = self.config.show_bluelines || frag.show_bluelines;
+        let bell_lines = if show_bluelines {
+            &self.config.blueline_bells
+        } else {
+            &self.config.bell_lines
+        };
         // Create empty line paths for each bell which should be drawn as lines.  These will be
         // extended during row drawing, and then all rendered at the end.
-        let mut lines: HashMap<_, _> = self
-            .config
-            .bell_lines
+        let mut lines: HashMap<_, _> = bell_lines
             .iter()
             .map(|(&bell, &(width, color))| (bell, (width, color, Vec::<Pos2>::new())))
             .collect();
+        // The highlighted bell's line is drawn over any line it would usually have
+        if let (true, Some(bell)) = (self.config.highlighted_bell_line, self.highlighted_bell) {
+            let (width, color) = self.config.highlighted_bell_line_style;
+            lines.insert(bell, (width, color, Vec::new()));
+        }
 
         // Draw the background rect
         ui.painter().add(Shape::Rect {
             rect: padded_bbox,
             corner_radius: 0.0,
-            fill: Color32::BLACK,
+            fill: self.config.palette().frag_background,
             stroke: Stroke::none(),
         });
 
+        // Draw coloured markers on the ends of any fragments which link to other fragments, so that
+        // round blocks can be spotted
+        let link_markers = [
+            (frag.link_groups.top, padded_bbox.min.y),
+            (frag.link_groups.bottom, padded_bbox.max.y),
+        ];
+        for (group, y) in link_markers.iter() {
+            if let Some(group) = group {
+                ui.painter().add(Shape::LineSegment {
+                    points: [
+                        Pos2::new(padded_bbox.min.x, *y),
+                        Pos2::new(padded_bbox.max.x, *y),
+                    ],
+                    stroke: Stroke {
+                        width: self.config.link_line_width,
+                        color: self.config.link_color(*group),
+                    },
+                });
+            }
+        }
+
+        // In the course view, the rows are summarised rather than drawn
+        if self.config.course_view {
+            self.draw_courses(ui, frag, part, annotations, rows_bbox, bell_name_galleys);
+            return;
+        }
+
         // Draw the rows
-        for (row_index, data) in frag.rows_in_part(self.part_being_viewed) {
+        for (row_index, data) in frag.rows_in_part(part) {
             let row_source = RowSource {
                 frag_index,
                 row_index,
@@ -170,6 +379,10 @@ impl<'a> CanvasWidget<'a> {
                 rows_bbox,
                 row_source,
                 data,
+                frag.call_label(part, row_index),
+                frag.place_bell(part, row_index),
+                annotations,
+                show_bluelines,
                 bell_name_galleys,
                 &mut lines,
             );
@@ -190,6 +403,144 @@ impl<'a> CanvasWidget<'a> {
                 },
             });
         }
+
+        self.draw_frag_end(ui, frag, annotations, rows_bbox);
+
+        // Draw the playback cursor over everything else
+        if let Some(cursor) = self.playback_cursor {
+            let loc = cursor.location;
+            if loc.frag_index == frag_index && loc.part_index == part {
+                self.draw_playback_cursor(ui, rows_bbox, loc.row_index, cursor.place);
+            }
+        }
+    }
+
+    /// Mark the leftover row of a fragment if the composition comes round there, or if nothing
+    /// follows it.  Leftover rows which link onto other fragments already have link markers.
+    fn draw_frag_end(
+        &self,
+        ui: &mut Ui,
+        frag: &Fragment,
+        annotations: Annotations,
+        rows_bbox: Rect,
+    ) {
+        let palette = self.config.palette();
+        let (color, label) = match frag.end {
+            FragEnd::ComesRound => (palette.comes_round, "Comes round"),
+            FragEnd::Open => (palette.unfinished, "Unfinished"),
+            FragEnd::Linked => return,
+        };
+        let y_coord = rows_bbox.min.y + (frag.num_rows() - 1) as f32 * self.config.row_height;
+        let rect = Rect::from_min_size(
+            Pos2::new(rows_bbox.min.x, y_coord),
+            Vec2::new(rows_bbox.width(), self.config.row_height),
+        );
+        ui.painter().add(Shape::Rect {
+            rect,
+            corner_radius: 0.0,
+            fill: Color32::TRANSPARENT,
+            stroke: Stroke::new(self.config.frag_end_line_width, color),
+        });
+        if annotations.methods {
+            let text_y_coord = y_coord + self.config.row_height * self.config.text_pos_y;
+            ui.painter().add(Shape::Text {
+                pos: Pos2::new(
+                    rows_bbox.max.x + self.config.method_name_offset(),
+                    text_y_coord,
+                ),
+                galley: ui.fonts().layout_single_line(TEXT_STYLE, label.to_owned()),
+                color,
+                fake_italics: false,
+            });
+        }
+    }
+
+    /// Draw the cursor round the row which is being rung during playback, filling in the bell
+    /// which was just struck
+    fn draw_playback_cursor(
+        &self,
+        ui: &mut Ui,
+        rows_bbox: Rect,
+        row_index: RowIdx,
+        place: Option<usize>,
+    ) {
+        let y_coord = rows_bbox.min.y + row_index.index() as f32 * self.config.row_height;
+        let color = self.config.playback_cursor_color;
+        if let Some(place) = place {
+            let x_coord = rows_bbox.min.x + place as f32 * self.config.col_width;
+            ui.painter().add(Shape::Rect {
+                rect: Rect::from_min_size(Pos2::new(x_coord, y_coord), self.config.bell_box_size()),
+                corner_radius: 0.0,
+                fill: color.linear_multiply(0.4),
+                stroke: Stroke::none(),
+            });
+        }
+        ui.painter().add(Shape::Rect {
+            rect: Rect::from_min_size(
+                Pos2::new(rows_bbox.min.x, y_coord),
+                Vec2::new(rows_bbox.width(), self.config.row_height),
+            ),
+            corner_radius: 0.0,
+            fill: Color32::TRANSPARENT,
+            stroke: Stroke::new(2.0, color),
+        });
+    }
+
+    /// Draw a [`Fragment`] in the course view, where each course is summarised on one line by its
+    /// course end and calling
+    fn draw_courses(
+        &self,
+        ui: &mut Ui,
+        frag: &Fragment,
+        part: PartIdx,
+        annotations: Annotations,
+        rows_bbox: Rect,
+        bell_name_galleys: &[Arc<Galley>],
+    ) {
+        let foreground_color = self.config.palette().foreground;
+        for (line_idx, course) in frag.courses.iter().enumerate() {
+            let y_coord = rows_bbox.min.y + line_idx as f32 * self.config.row_height;
+            let text_y_coord = y_coord + self.config.row_height * self.config.text_pos_y;
+
+            // Course end
+            let course_end = frag.row_in_part(part, course.end);
+            for (col_idx, bell) in course_end.bell_iter().enumerate() {
+                ui.painter().add(Shape::Text {
+                    pos: Pos2::new(
+                        rows_bbox.min.x
+                            + self.config.col_width * (col_idx as f32 + self.config.text_pos_x),
+                        text_y_coord,
+                    ),
+                    galley: bell_name_galleys[bell.index()].clone(),
+                    color: foreground_color,
+                    fake_italics: false,
+                });
+            }
+            // Calling, drawn where the method names would usually go
+            if annotations.methods && !course.calling.is_empty() {
+                ui.painter().add(Shape::Text {
+                    pos: Pos2::new(rows_bbox.max.x + self.config.col_width, text_y_coord),
+                    galley: ui
+                        .fonts()
+                        .layout_single_line(TEXT_STYLE, course.calling.clone()),
+                    color: foreground_color,
+                    fake_italics: false,
+                });
+            }
+            // Rule-off between courses
+            if line_idx > 0 {
+                ui.painter().add(Shape::LineSegment {
+                    points: [
+                        Pos2::new(rows_bbox.min.x, y_coord),
+                        Pos2::new(rows_bbox.max.x, y_coord),
+                    ],
+                    stroke: Stroke {
+                        width: self.config.ruleoff_line_width,
+                        color: foreground_color,
+                    },
+                });
+            }
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -199,6 +550,12 @@ impl<'a> CanvasWidget<'a> {
         rows_bbox: Rect,
         source: RowSource,
         data: RowDataForOnePart,
+        // The label of the call starting at this row in this part (e.g. `"sH"`), if any
+        call_label: Option<String>,
+        // The place bell which the observation bell becomes, if a lead starts at this row
+        place_bell: Option<usize>,
+        annotations: Annotations,
+        show_bluelines: bool,
         bell_name_galleys: &[Arc<Galley>],
         lines: &mut HashMap<Bell, (f32, Color32, Vec<Pos2>)>,
     ) {
@@ -218,7 +575,40 @@ impl<'a> CanvasWidget<'a> {
         if !data.is_proved {
             opacity *= 0.5; // Also fade out non-proved rows
         }
-        let foreground_color: Color32 = Rgba::WHITE.multiply(opacity).into();
+        let palette = self.config.palette();
+        let foreground_color: Color32 = Rgba::from(palette.foreground).multiply(opacity).into();
+
+        /* DRAW FALSENESS */
+
+        if data.false_group.is_some() {
+            let rect = Rect::from_min_size(
+                Pos2::new(rows_bbox.min.x, y_coord),
+                Vec2::new(rows_bbox.width(), self.config.row_height),
+            );
+            ui.painter().add(Shape::Rect {
+                rect,
+                corner_radius: 0.0,
+                fill: palette.false_row,
+                stroke: Stroke::none(),
+            });
+            self.draw_hatch(ui, rect, FALSE_ROW_HATCH, &palette);
+        }
+
+        /* DRAW SELECTION */
+
+        if let Some(selection) = self.selection {
+            if selection.contains(source) {
+                ui.painter().add(Shape::Rect {
+                    rect: Rect::from_min_size(
+                        Pos2::new(rows_bbox.min.x, y_coord),
+                        Vec2::new(rows_bbox.width(), self.config.row_height),
+                    ),
+                    corner_radius: 0.0,
+                    fill: palette.selection,
+                    stroke: Stroke::none(),
+                });
+            }
+        }
 
         /* DRAW BELLS/LINES */
 
@@ -232,20 +622,45 @@ impl<'a> CanvasWidget<'a> {
                     ),
                 self.config.bell_box_size(),
             );
+            // Draw handbell pair highlight
+            let is_handbell = match self.handbell_pair {
+                Some((bell_a, bell_b)) => bell == bell_a || bell == bell_b,
+                None => false,
+            };
+            if is_handbell {
+                ui.painter().add(Shape::Rect {
+                    rect,
+                    corner_radius: 0.0,
+                    fill: palette.handbell_pair,
+                    stroke: Stroke::none(),
+                });
+            }
+            // Draw the highlighted bell's path
+            if self.highlighted_bell == Some(bell) {
+                ui.painter().add(Shape::Rect {
+                    rect,
+                    corner_radius: 0.0,
+                    fill: palette.highlighted_bell,
+                    stroke: Stroke::none(),
+                });
+            }
             // Draw music highlight
-            if data.music_counts[col_idx] > 0 {
+            if let Some((fill, hatch)) = music_highlight(&palette, &data.music_counts, col_idx) {
                 ui.painter().add(Shape::Rect {
                     rect,
                     corner_radius: 0.0,
-                    fill: Color32::from_rgb(50, 100, 0),
+                    fill,
                     stroke: Stroke::none(),
                 });
+                if let Some(hatch) = hatch {
+                    self.draw_hatch(ui, rect, hatch, &palette);
+                }
             }
             // Draw text or add point to line
             if let Some((_, _, points)) = lines.get_mut(&bell) {
                 // If this bell is part of a line, then add this location to the line path
                 points.push(rect.center());
-            } else {
+            } else if !show_bluelines {
                 // If this bell isn't part of a line, then render it as text
                 ui.painter().add(Shape::Text {
                     pos: Pos2::new(
@@ -259,14 +674,41 @@ impl<'a> CanvasWidget<'a> {
             }
         }
 
+        /* DRAW PLACE BELL */
+
+        if let (true, Some(place)) = (self.config.show_place_bells, place_bell) {
+            ui.painter().add(Shape::Text {
+                pos: Pos2::new(rows_bbox.max.x + self.config.col_width * 0.5, text_y_coord),
+                galley: ui
+                    .fonts()
+                    .layout_single_line(TEXT_STYLE, place_bell_label(place)),
+                color: self.config.place_bell_color,
+                fake_italics: false,
+            });
+        }
+
         /* DRAW METHOD NAME */
 
-        if let Some(method_name) = &data.method_annotation {
+        if let (true, Some(method_name)) = (annotations.methods, &data.method_annotation) {
             ui.painter().add(Shape::Text {
-                pos: Pos2::new(rows_bbox.max.x + self.config.col_width, text_y_coord),
+                pos: Pos2::new(
+                    rows_bbox.max.x + self.config.method_name_offset(),
+                    text_y_coord,
+                ),
                 galley: ui
                     .fonts()
-                    .layout_single_line(TextStyle::Body, method_name.name()),
+                    .layout_single_line(TEXT_STYLE, method_name.name()),
+                color: foreground_color,
+                fake_italics: false,
+            });
+        }
+
+        /* DRAW CALL LABEL */
+
+        if let (true, Some(label)) = (annotations.calls, call_label) {
+            ui.painter().add(Shape::Text {
+                pos: Pos2::new(rows_bbox.min.x - self.config.col_width * 2.0, text_y_coord),
+                galley: ui.fonts().layout_single_line(TEXT_STYLE, label),
                 color: foreground_color,
                 fake_italics: false,
             });
@@ -287,6 +729,103 @@ impl<'a> CanvasWidget<'a> {
             });
         }
     }
+
+    /// Draws a [`Hatch`] pattern over a highlighted [`Rect`], if the user has turned them on
+    fn draw_hatch(&self, ui: &mut Ui, rect: Rect, hatch: Hatch, palette: &Palette) {
+        if !self.config.hatch_highlights {
+            return;
+        }
+        let stroke = Stroke::new(1.0, palette.hatch_color());
+        for points in hatch.lines(rect, self.config.hatch_spacing()) {
+            ui.painter().add(Shape::LineSegment { points, stroke });
+        }
+    }
+}
+
+/// The [`Hatch`] pattern drawn over false rows
+pub(crate) const FALSE_ROW_HATCH: Hatch = Hatch::Backward;
+
+/// The colour (and [`Hatch`] pattern) used to highlight the bell in a given place of a row, or
+/// `None` if that bell isn't part of any music.  Anti-music takes precedence, since those are the
+/// rows which the composer is trying to avoid.
+pub(crate) fn music_highlight(
+    palette: &Palette,
+    counts: &MusicCounts,
+    place: usize,
+) -> Option<(Color32, Option<Hatch>)> {
+    if counts.anti_music[place] > 0 {
+        Some((palette.anti_music, Some(Hatch::Cross)))
+    } else if counts.music[place] > 0 {
+        Some((palette.music, None))
+    } else if counts.wraps[place] > 0 {
+        Some((palette.wraps, Some(Hatch::Forward)))
+    } else {
+        None
+    }
+}
+
+/// The region of the canvas covered by a [`Fragment`] (including its padding and every part
+/// which is drawn), in the same coordinates as [`Fragment::position`]
+pub(crate) fn frag_rect(full_state: &FullState, config: &Config, frag: &Fragment) -> Rect {
+    let num_cols = if config.show_all_parts {
+        full_state.part_heads.len()
+    } else {
+        1
+    };
+    let num_lines = if config.course_view {
+        frag.courses.len().max(1)
+    } else {
+        frag.num_rows()
+    };
+    let size = Vec2::new(
+        config.part_col_width(full_state.stage) * (num_cols - 1) as f32
+            + config.col_width * full_state.stage.num_bells() as f32,
+        config.row_height * num_lines as f32,
+    );
+    Rect::from_min_size(Pos2::from(frag.position), size).expand2(config.frag_padding_vec())
+}
+
+/// Converts a fractional line index in the course view into the fractional index of the
+/// corresponding row, so that the cursor still points to the right rows when courses are collapsed
+fn course_line_to_row_idx(frag: &Fragment, line_idx_float: f32) -> f32 {
+    let line_idx = line_idx_float.floor();
+    let course = usize::try_from(line_idx as isize)
+        .ok()
+        .and_then(|idx| frag.courses.get(idx));
+    match course {
+        Some(course) => {
+            let course_len = (course.end.index() - course.start.index()) as f32;
+            course.start.index() as f32 + (line_idx_float - line_idx) * course_len
+        }
+        // Lines below the last course point to the leftover row and beyond
+        None if line_idx >= 0.0 => {
+            (frag.num_rows() - 1) as f32 + (line_idx_float - frag.courses.len() as f32)
+        }
+        None => line_idx_float,
+    }
+}
+
+/// Which annotations should be drawn next to the [`Row`](bellframe::Row)s of one part of a
+/// [`Fragment`]
+#[derive(Debug, Clone, Copy)]
+struct Annotations {
+    /// Draw call symbols to the left of the rows
+    calls: bool,
+    /// Draw method names to the right of the rows
+    methods: bool,
+}
+
+/// Returns the name of the place bell which starts a lead in a given `place` (counting from 0),
+/// e.g. `"2nds"` or `"5ths"`
+pub(crate) fn place_bell_label(place: usize) -> String {
+    let place_num = place + 1;
+    let suffix = match place_num {
+        1 => "st",
+        2 => "nds",
+        3 => "rds",
+        _ => "ths",
+    };
+    format!("{}{}", place_num, suffix)
 }
 
 /// The location of a mouse hovering within a [`Fragment`]
@@ -313,13 +852,65 @@ impl FragHover {
     }
 
     /// The integer index of the row that's being hovered (which may be negative)
-    #[allow(dead_code)]
     pub fn hovered_row_idx(&self) -> isize {
         self.row_idx_float.floor() as isize
     }
 
+    /// The [`RowSource`] of the row under the cursor, or `None` if the cursor isn't directly over
+    /// a row (e.g. if it's over the fragment's padding)
+    fn hovered_row_source(&self, full_state: &FullState) -> Option<RowSource> {
+        let num_rows = full_state.fragments[self.frag_idx].num_rows();
+        let num_bells = full_state.stage.num_bells();
+        let row_idx = usize::try_from(self.hovered_row_idx()).ok()?;
+        let is_over_row = row_idx < num_rows
+            && self.place_idx_float >= 0.0
+            && self.place_idx_float < num_bells as f32;
+        is_over_row.then(|| RowSource {
+            frag_index: self.frag_idx,
+            row_index: RowIdx::new(row_idx),
+        })
+    }
+
     /// The integer index of the row **below** the nearest row boundary to the cursor
     pub fn nearest_row_boundary(&self) -> isize {
         self.row_idx_float.round() as isize
     }
 }
+
+/// A contiguous range of (non-leftover) rows in one [`Fragment`], which the user has selected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RowSelection {
+    pub frag_idx: FragIdx,
+    /// The row where the user started the selection
+    pub anchor: usize,
+    /// The row which the selection has been extended to.  This can be either above or below
+    /// `anchor`.
+    pub head: usize,
+}
+
+impl RowSelection {
+    /// Creates a `RowSelection` containing only one row
+    pub fn new(frag_idx: FragIdx, row_idx: usize) -> Self {
+        Self {
+            frag_idx,
+            anchor: row_idx,
+            head: row_idx,
+        }
+    }
+
+    /// The index of the first selected row
+    pub fn start(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    /// The index of the row directly **after** the last selected row
+    pub fn end(&self) -> usize {
+        self.anchor.max(self.head) + 1
+    }
+
+    /// Returns `true` if the row at `source` is selected
+    fn contains(&self, source: RowSource) -> bool {
+        let row_idx = source.row_index.index();
+        source.frag_index == self.frag_idx && self.start() <= row_idx && row_idx < self.end()
+    }
+}
@@ -0,0 +1,635 @@
+//! Real-time collaboration, where several copies of Jigsaw edit the same composition over the
+//! network.  One copy **hosts** the session, and the others **join** it using a session code (the
+//! host's address followed by a random token, e.g. `192.168.0.4:7878/3f09c1...`).  The host only
+//! lets in collaborators who send the token, so knowing the host's address isn't enough to edit
+//! the composition.
+//!
+//! Every successful edit is sent as the composition it produces (since [`CompAction`]s refer to
+//! indices which only make sense in the composition they were made in).  The host decides the
+//! order of all the edits: each edit says which version of the composition it was made from, and
+//! an edit made from anything other than the host's latest version conflicts with an edit which
+//! the host has already accepted.  Conflicting edits are rejected and overwritten by the host's
+//! version, just like a failed edit.  Edits from collaborators are added to the undo history like
+//! any other edit, so they can be undone (which sends the undo to everyone else).
+//!
+//! Messages are sent over plain TCP as lines of JSON.  The web build can't open TCP connections,
+//! so can't collaborate.
+//!
+//! [`CompAction`]: crate::CompAction
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use eframe::egui::{self, Color32};
+use jigsaw_comp::spec::CompSpec;
+use serde::{Deserialize, Serialize};
+
+use crate::Action;
+
+/// The port used to host sessions, unless the user chooses another one
+pub(crate) const DEFAULT_PORT: u16 = 7878;
+/// How long to wait for a connection to be set up (either connecting to a host, or a new
+/// collaborator sending the session's token) before giving up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// The ID of the host.  Clients are numbered from 1.
+const HOST_ID: u64 = 0;
+/// How many bytes can be waiting to be sent to a collaborator before we give up on them (e.g.
+/// because they've stopped reading)
+const MAX_UNSENT_BYTES: usize = 16 * 1024 * 1024;
+/// The longest message we'll receive.  Anyone who sends a longer message is disconnected, rather
+/// than being allowed to fill up our memory.
+const MAX_MESSAGE_LEN: usize = 8 * 1024 * 1024;
+
+/////////////
+// SESSION //
+/////////////
+
+/// A collaboration session which this copy of Jigsaw is either hosting or has joined
+#[derive(Debug)]
+pub(crate) struct Session {
+    role: Role,
+    /// The latest version of the composition which we know about (including our own edits which
+    /// the host hasn't confirmed yet)
+    version: Version,
+    /// Our ID within the session.  This is [`HOST_ID`] for the host, or `None` if we're a client
+    /// which hasn't been welcomed by the host yet.
+    id: Option<u64>,
+    /// The code which others can use to join this session
+    code: String,
+}
+
+#[derive(Debug)]
+enum Role {
+    Host {
+        listener: TcpListener,
+        /// The token which collaborators have to send before they can join
+        token: String,
+        /// Connections which haven't sent the token yet, along with when they connected
+        pending: Vec<(Connection, Instant)>,
+        clients: Vec<Connection>,
+        next_client_id: u64,
+    },
+    Client {
+        host: Connection,
+    },
+}
+
+/// A version of the composition: the number of edits made since the session started, along with
+/// the ID of whoever made the last edit.  The ID distinguishes between different edits made from
+/// the same version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Version {
+    num_edits: u64,
+    author: u64,
+}
+
+/// Something which happened in a [`Session`], which the app has to respond to
+#[derive(Debug)]
+pub(crate) enum Event {
+    /// The composition was changed by a collaborator (or we joined a session, and received the
+    /// host's composition)
+    RemoteEdit(CompSpec),
+    /// One of our edits conflicted with a collaborator's, and has been overwritten
+    EditRejected,
+    /// A collaborator joined the session we're hosting
+    Joined(String),
+    /// A collaborator left the session we're hosting
+    Left(String),
+    /// Someone connected to the session we're hosting without sending the right token
+    Refused(String),
+    /// We were disconnected from the host, so the session is over
+    Disconnected(String),
+    /// A collaborator sent something which couldn't be understood
+    InvalidMessage(String),
+}
+
+impl Session {
+    /// Starts hosting a new session on a given `port`.  The composition is sent to collaborators
+    /// as they join.
+    pub fn host(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let token = random_token();
+        let code = format!("{}:{}/{}", local_ip(), listener.local_addr()?.port(), token);
+        Ok(Self {
+            role: Role::Host {
+                listener,
+                token,
+                pending: Vec::new(),
+                clients: Vec::new(),
+                next_client_id: HOST_ID + 1,
+            },
+            version: Version {
+                num_edits: 0,
+                author: HOST_ID,
+            },
+            id: Some(HOST_ID),
+            code,
+        })
+    }
+
+    /// Joins the session with a given `code`.  The composition will be replaced by the host's
+    /// composition once the host has welcomed us.
+    pub fn join(code: &str) -> io::Result<Self> {
+        let (addr, token) = code.trim().rsplit_once('/').ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "session code has no token")
+        })?;
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "session code has no address")
+        })?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        let mut host = Connection::new(stream, HOST_ID)?;
+        host.send(&Message::Hello {
+            token: token.to_owned(),
+        })?;
+        Ok(Self {
+            role: Role::Client { host },
+            version: Version {
+                num_edits: 0,
+                author: HOST_ID,
+            },
+            id: None,
+            code: code.trim().to_owned(),
+        })
+    }
+
+    /// A short description of this session, e.g. `Hosting 192.168.0.4:7878/3f09c1... (2 collaborators)`
+    pub fn status(&self) -> String {
+        match &self.role {
+            Role::Host { clients, .. } => {
+                format!("Hosting {} ({} collaborators)", self.code, clients.len())
+            }
+            Role::Client { .. } if self.id.is_none() => format!("Joining {}...", self.code),
+            Role::Client { .. } => format!("Joined {}", self.code),
+        }
+    }
+
+    /// Sends an edit which we made (resulting in `spec`) to the rest of the session
+    pub fn send_edit(&mut self, spec: &CompSpec) {
+        let comp = spec.to_json();
+        match &mut self.role {
+            Role::Host { clients, .. } => {
+                // The host's edits are always accepted
+                self.version = Version {
+                    num_edits: self.version.num_edits + 1,
+                    author: HOST_ID,
+                };
+                let message = Message::State {
+                    version: self.version,
+                    comp,
+                };
+                // Clients which can't be sent to will be removed on the next poll
+                for client in clients {
+                    let _ = client.send(&message);
+                }
+            }
+            Role::Client { host } => {
+                let my_id = match self.id {
+                    Some(id) => id,
+                    None => return, // Edits made before joining are overwritten by the host's
+                };
+                let message = Message::Edit {
+                    base: self.version,
+                    comp,
+                };
+                // If the host can't be reached, the next poll will report the disconnection
+                let _ = host.send(&message);
+                // Assume that the host will accept the edit.  If it doesn't, then it will send
+                // us its version before rejecting the edit.
+                self.version = Version {
+                    num_edits: self.version.num_edits + 1,
+                    author: my_id,
+                };
+            }
+        }
+    }
+
+    /// Handles any new connections and messages, returning what happened.  `spec` is the current
+    /// composition, which is sent to anyone who joins.
+    pub fn poll(&mut self, spec: &CompSpec) -> Vec<Event> {
+        let mut events = Vec::new();
+        match &mut self.role {
+            Role::Host {
+                listener,
+                token,
+                pending,
+                clients,
+                next_client_id,
+            } => {
+                // Accept new connections, which can't join until they've sent the token
+                while let Ok((stream, _addr)) = listener.accept() {
+                    if let Ok(connection) = Connection::new(stream, *next_client_id) {
+                        pending.push((connection, Instant::now()));
+                        *next_client_id += 1;
+                    }
+                }
+                // Welcome new collaborators who sent the right token, and refuse anyone else
+                let mut pending_idx = 0;
+                while pending_idx < pending.len() {
+                    let (connection, connected_at) = &mut pending[pending_idx];
+                    let first_message = connection
+                        .receive()
+                        .map(|messages| messages.into_iter().next());
+                    match first_message {
+                        Ok(None) if connected_at.elapsed() < CONNECT_TIMEOUT => pending_idx += 1,
+                        Ok(Some(Message::Hello { token: sent_token })) if sent_token == *token => {
+                            let (mut connection, _) = pending.remove(pending_idx);
+                            let welcome = Message::Welcome {
+                                client_id: connection.id,
+                                version: self.version,
+                                comp: spec.to_json(),
+                            };
+                            if connection.send(&welcome).is_ok() {
+                                events.push(Event::Joined(connection.peer_name()));
+                                clients.push(connection);
+                            }
+                        }
+                        _ => {
+                            let (connection, _) = pending.remove(pending_idx);
+                            events.push(Event::Refused(connection.peer_name()));
+                        }
+                    }
+                }
+                // Receive edits, in the order that they arrive
+                let mut client_idx = 0;
+                while client_idx < clients.len() {
+                    let client = &mut clients[client_idx];
+                    let messages = match client.flush().and_then(|()| client.receive()) {
+                        Ok(messages) => messages,
+                        Err(e) => {
+                            let client = clients.remove(client_idx);
+                            events.push(Event::Left(format!("{} ({})", client.peer_name(), e)));
+                            continue;
+                        }
+                    };
+                    for message in messages {
+                        let (base, comp) = match message {
+                            Message::Edit { base, comp } => (base, comp),
+                            _ => continue, // Clients only send edits
+                        };
+                        let author = clients[client_idx].id;
+                        if base != self.version {
+                            // The edit conflicts with an edit we've already accepted.  The client
+                            // has already been sent our version, so just tell it what happened.
+                            let _ = clients[client_idx].send(&Message::Rejected);
+                            continue;
+                        }
+                        let spec = match CompSpec::from_json(&comp) {
+                            Ok(spec) => spec,
+                            Err(e) => {
+                                events.push(Event::InvalidMessage(e.to_string()));
+                                let _ = clients[client_idx].send(&Message::Rejected);
+                                continue;
+                            }
+                        };
+                        self.version = Version {
+                            num_edits: base.num_edits + 1,
+                            author,
+                        };
+                        let state = Message::State {
+                            version: self.version,
+                            comp,
+                        };
+                        for client in clients.iter_mut().filter(|c| c.id != author) {
+                            let _ = client.send(&state);
+                        }
+                        events.push(Event::RemoteEdit(spec));
+                    }
+                    client_idx += 1;
+                }
+            }
+            Role::Client { host } => {
+                let messages = match host.flush().and_then(|()| host.receive()) {
+                    Ok(messages) => messages,
+                    Err(e) => {
+                        events.push(Event::Disconnected(e.to_string()));
+                        return events;
+                    }
+                };
+                for message in messages {
+                    let (version, comp) = match message {
+                        Message::Welcome {
+                            client_id,
+                            version,
+                            comp,
+                        } => {
+                            self.id = Some(client_id);
+                            (version, comp)
+                        }
+                        Message::State { version, comp } => (version, comp),
+                        Message::Rejected => {
+                            events.push(Event::EditRejected);
+                            continue;
+                        }
+                        // Only the host receives these
+                        Message::Edit { .. } | Message::Hello { .. } => continue,
+                    };
+                    self.version = version;
+                    match CompSpec::from_json(&comp) {
+                        Ok(spec) => events.push(Event::RemoteEdit(spec)),
+                        Err(e) => events.push(Event::InvalidMessage(e.to_string())),
+                    }
+                }
+            }
+        }
+        events
+    }
+}
+
+/// The messages sent between the host and the clients
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// Sent by a client as soon as it connects, to show that it was given the session code
+    Hello { token: String },
+    /// Sent by the host to a client when it joins
+    Welcome {
+        client_id: u64,
+        version: Version,
+        comp: String,
+    },
+    /// Sent by the host whenever the composition is changed by someone other than the receiver
+    State { version: Version, comp: String },
+    /// Sent by a client whenever it edits the composition, starting from version `base`
+    Edit { base: Version, comp: String },
+    /// Sent by the host when a client's edit conflicts with an edit which was already accepted
+    Rejected,
+}
+
+/// A connection to another copy of Jigsaw, which sends and receives lines of JSON
+#[derive(Debug)]
+struct Connection {
+    stream: TcpStream,
+    /// The ID of the other end of this connection
+    id: u64,
+    /// Bytes which have been received but don't yet make up a full line
+    received: Vec<u8>,
+    /// Bytes of messages which haven't been sent yet, because the socket wasn't ready for them
+    unsent: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream, id: u64) -> io::Result<Self> {
+        // Reading and writing mustn't block the GUI
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            id,
+            received: Vec::new(),
+            unsent: Vec::new(),
+        })
+    }
+
+    fn peer_name(&self) -> String {
+        match self.stream.peer_addr() {
+            Ok(addr) => addr.to_string(),
+            Err(_) => format!("collaborator #{}", self.id),
+        }
+    }
+
+    /// Queues a message to be sent, and sends as much as possible without blocking.  Anything
+    /// left over is sent by later calls to [`Connection::flush`].
+    fn send(&mut self, message: &Message) -> io::Result<()> {
+        serde_json::to_writer(&mut self.unsent, message).map_err(io::Error::from)?;
+        self.unsent.push(b'\n');
+        self.flush()
+    }
+
+    /// Sends as much of the queued data as the socket will take without blocking.  Returns an
+    /// error if the connection has been closed, or if the other end has stopped reading for so
+    /// long that too much data has been queued.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.unsent.is_empty() {
+            match self.stream.write(&self.unsent) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(num_bytes) => {
+                    self.unsent.drain(..num_bytes);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        if self.unsent.len() > MAX_UNSENT_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "stopped receiving edits",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns every complete message which has arrived since the last call, or an error if the
+    /// connection has been closed or the other end sent something invalid
+    fn receive(&mut self) -> io::Result<Vec<Message>> {
+        let mut buf = [0; 4096];
+        let mut messages = Vec::new();
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(num_bytes) => {
+                    let new_bytes = &buf[..num_bytes];
+                    self.received.extend_from_slice(new_bytes);
+                    // Parse messages as soon as they're complete, so that `received` only ever
+                    // holds the start of one message
+                    if new_bytes.contains(&b'\n') {
+                        while let Some(newline_idx) = self.received.iter().position(|&b| b == b'\n')
+                        {
+                            let line = self.received.drain(..=newline_idx).collect::<Vec<u8>>();
+                            let message = serde_json::from_slice(&line).map_err(io::Error::from)?;
+                            messages.push(message);
+                        }
+                    }
+                    if self.received.len() > MAX_MESSAGE_LEN {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "message is too long",
+                        ));
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(messages)
+    }
+}
+
+/// Generates a random token, which collaborators have to know to join a session.  [`RandomState`]
+/// is seeded from the OS's random number generator, so the hashes it creates are unpredictable.
+fn random_token() -> String {
+    let random_u64 = || RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", random_u64(), random_u64())
+}
+
+/// Finds the IP address of this machine on the local network, which is the address other machines
+/// should connect to.  This 'connects' a UDP socket to a public address (which doesn't send
+/// anything) to see which local address the OS would use.
+fn local_ip() -> String {
+    let ip = UdpSocket::bind(("0.0.0.0", 0))
+        .and_then(|socket| {
+            socket.connect(("8.8.8.8", 80))?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip().to_string());
+    ip.unwrap_or_else(|_| "127.0.0.1".to_owned())
+}
+
+////////////
+// WINDOW //
+////////////
+
+/// The state of the window used to host or join sessions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CollabWindow {
+    /// The port to host sessions on
+    pub port: String,
+    /// The code of the session to join
+    pub join_code: String,
+}
+
+impl Default for CollabWindow {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT.to_string(),
+            join_code: String::new(),
+        }
+    }
+}
+
+/// Draw the collaboration window.  `session_status` is the status of the current session, or
+/// `None` if we're not in a session.
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    window: &CollabWindow,
+    session_status: Option<String>,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::Window::new("Collaborate")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut new_window = window.clone();
+            if let Some(status) = session_status {
+                ui.label(status);
+                if ui.button("Leave session").clicked() {
+                    push_action(Action::LeaveSession);
+                }
+            } else {
+                egui::Grid::new("collab_grid").show(ui, |grid_ui| {
+                    let port = window.port.trim().parse::<u16>();
+                    grid_ui.label("Port:");
+                    grid_ui.text_edit_singleline(&mut new_window.port);
+                    if grid_ui
+                        .add(egui::Button::new("Host session").enabled(port.is_ok()))
+                        .clicked()
+                    {
+                        // Unwrap is safe because the button is disabled if the port is invalid
+                        push_action(Action::HostSession(port.unwrap()));
+                    }
+                    grid_ui.end_row();
+
+                    grid_ui.label("Session code:");
+                    grid_ui.text_edit_singleline(&mut new_window.join_code);
+                    let can_join = !window.join_code.trim().is_empty();
+                    if grid_ui
+                        .add(egui::Button::new("Join session").enabled(can_join))
+                        .clicked()
+                    {
+                        push_action(Action::JoinSession(window.join_code.clone()));
+                    }
+                    grid_ui.end_row();
+                });
+                if window.port.trim().parse::<u16>().is_err() {
+                    let text = format!("Invalid port '{}'", window.port);
+                    ui.label(egui::Label::new(text).text_color(Color32::RED));
+                }
+                ui.label("Joining a session replaces this composition with the host's.");
+            }
+            if ui.button("Close").clicked() {
+                push_action(Action::SetCollabWindow(None));
+            }
+            if &new_window != window {
+                push_action(Action::SetCollabWindow(Some(new_window)));
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::TcpStream, thread, time::Instant};
+
+    use bellframe::Stage;
+    use jigsaw_comp::spec::CompSpec;
+
+    use super::{Event, Session, CONNECT_TIMEOUT, MAX_MESSAGE_LEN};
+
+    /// Hosts a session, returning it along with its session code pointing at this machine
+    fn host() -> (Session, String) {
+        let session = Session::host(0).unwrap();
+        let (addr, token) = session.code.rsplit_once('/').unwrap();
+        let port = addr.rsplit_once(':').unwrap().1;
+        let code = format!("127.0.0.1:{}/{}", port, token);
+        (session, code)
+    }
+
+    /// Polls `host` until it reports an event about a new connection
+    fn next_connection_event(host: &mut Session, spec: &CompSpec) -> Event {
+        for _ in 0..200 {
+            let events = host.poll(spec);
+            let event = events
+                .into_iter()
+                .find(|e| matches!(e, Event::Joined(_) | Event::Refused(_)));
+            if let Some(event) = event {
+                return event;
+            }
+            thread::sleep(CONNECT_TIMEOUT / 100);
+        }
+        panic!("host never accepted or refused the connection");
+    }
+
+    #[test]
+    fn only_collaborators_with_the_token_can_join() {
+        let spec = CompSpec::empty(Stage::MINOR);
+        let (mut host, code) = host();
+
+        let _client = Session::join(&code).unwrap();
+        assert!(matches!(
+            next_connection_event(&mut host, &spec),
+            Event::Joined(_)
+        ));
+
+        let (addr, _token) = code.rsplit_once('/').unwrap();
+        let _client = Session::join(&format!("{}/wrong-token", addr)).unwrap();
+        assert!(matches!(
+            next_connection_event(&mut host, &spec),
+            Event::Refused(_)
+        ));
+        assert!(Session::join(addr).is_err());
+    }
+
+    #[test]
+    fn long_messages_are_refused() {
+        let spec = CompSpec::empty(Stage::MINOR);
+        let (mut host, code) = host();
+
+        let (addr, _token) = code.rsplit_once('/').unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let junk = vec![b'x'; MAX_MESSAGE_LEN + 1];
+        thread::spawn(move || stream.write_all(&junk));
+        let start = Instant::now();
+        assert!(matches!(
+            next_connection_event(&mut host, &spec),
+            Event::Refused(_)
+        ));
+        // The connection should be dropped for its message, not for not sending the token in time
+        assert!(start.elapsed() < CONNECT_TIMEOUT);
+    }
+}
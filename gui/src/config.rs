@@ -2,13 +2,35 @@ use std::collections::HashMap;
 
 use bellframe::{Bell, Stage};
 use eframe::egui::{Color32, Vec2};
+use jigsaw_comp::{HistoryConfig, Pace};
+use serde::{Deserialize, Serialize};
 
-/// Configuration settings for Jigsaw's GUI
-#[derive(Debug, Clone)]
+use crate::{
+    keymap::Keymap,
+    playback::SoundOutput,
+    theme::{HighlightColors, Palette, Theme},
+};
+
+/// Configuration settings for Jigsaw's GUI.  These are saved between sessions, and any settings
+/// which aren't in the saved config (e.g. because they were added in a newer version of Jigsaw)
+/// are given their default values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /* Display */
-    pub(crate) col_width: f32,  // points
+    /// The colour scheme of both the canvas and the rest of the GUI
+    pub(crate) theme: Theme,
+    /// The colours used to highlight falseness and music
+    pub(crate) highlight_colors: HighlightColors,
+    /// If `true`, falseness and music highlights are also drawn with
+    /// [`Hatch`](crate::theme::Hatch) patterns, so that they can be told apart without relying on
+    /// colour
+    pub(crate) hatch_highlights: bool,
+
+    /// These three are set together by [`Config::set_row_density`]
+    pub(crate) col_width: f32, // points
     pub(crate) row_height: f32, // points
+    pub(crate) font_size: f32,  // points
 
     pub(crate) ruleoff_line_width: f32, // points
 
@@ -18,8 +40,57 @@ pub struct Config {
     pub(crate) frag_padding_x: f32, // multiple of `col_width`
     pub(crate) frag_padding_y: f32, // multiple of `row_height`
 
+    /// If `true`, every [`Fragment`](jigsaw_comp::full::Fragment) is drawn once per part, with the
+    /// parts in adjacent columns.  Otherwise, only the part being viewed is drawn.
+    pub(crate) show_all_parts: bool,
+    /// If `true`, every course is collapsed into one line showing its calling and course end
+    /// (like a printed composition).  Otherwise, every row is drawn.
+    pub(crate) course_view: bool,
+    /// If `true`, every lead head is labelled with the place bell which the observation bell
+    /// becomes for that lead
+    pub(crate) show_place_bells: bool,
+    pub(crate) place_bell_color: Color32,
+    /// If `true`, an overview of the whole composition is drawn in the corner of the canvas
+    pub(crate) show_minimap: bool,
+    pub(crate) minimap_size: Vec2, // points
+    pub(crate) minimap_background_color: Color32,
+    /// The horizontal gap between adjacent parts when all the parts are drawn
+    pub(crate) part_gap: f32, // multiple of `col_width`
+
     /// Widths are multiples of `self.col_width`
+    #[serde(
+        serialize_with = "jigsaw_utils::serialisation::ser_bell_map",
+        deserialize_with = "jigsaw_utils::serialisation::de_bell_map"
+    )]
     pub(crate) bell_lines: HashMap<Bell, (f32, Color32)>,
+    /// If `true`, every [`Fragment`](jigsaw_comp::full::Fragment) is drawn with bluelines.
+    /// Otherwise, only the fragments which have bluelines turned on are drawn with bluelines.
+    pub(crate) show_bluelines: bool,
+    /// The bells which are drawn as lines when a fragment is drawn with bluelines.  All other
+    /// bells are hidden.  Widths are multiples of `self.col_width`.
+    #[serde(
+        serialize_with = "jigsaw_utils::serialisation::ser_bell_map",
+        deserialize_with = "jigsaw_utils::serialisation::de_bell_map"
+    )]
+    pub(crate) blueline_bells: HashMap<Bell, (f32, Color32)>,
+    /// If `true`, a line is also drawn through the path of the highlighted bell
+    pub(crate) highlighted_bell_line: bool,
+    /// The width and colour of the line drawn through the highlighted bell.  The width is a
+    /// multiple of `self.col_width`.
+    pub(crate) highlighted_bell_line_style: (f32, Color32),
+    /// The colours of the markers drawn at the ends of linked fragments.  Link groups cycle
+    /// through these colours.
+    pub(crate) link_colors: Vec<Color32>,
+    pub(crate) link_line_width: f32, // points
+    /// The width of the markers drawn round the leftover rows of fragments which come round or
+    /// need continuing
+    pub(crate) frag_end_line_width: f32, // points
+    /// The colour of the markers drawn on the edge of the canvas to point to highlighted rows
+    /// which are off-screen
+    pub(crate) off_screen_marker_color: Color32,
+    pub(crate) off_screen_marker_radius: f32, // points
+    /// The colour of the cursor drawn round the row being rung during playback
+    pub(crate) playback_cursor_color: Color32,
 
     /* User interaction */
     /// When splitting a fragment at a rule-off, the cursor must be less than this many rows away
@@ -27,13 +98,88 @@ pub struct Config {
     pub(crate) ruleoff_snap_distance: f32, // rows
     /// When a fragment is split, how far away is the 2nd fragment?
     pub(crate) split_height: f32, // multiples of `row_height`
+    /// When a fragment is dragged so that its first row is less than this distance from the
+    /// leftover row of a fragment which it links onto, it snaps underneath that fragment
+    pub(crate) frag_snap_distance: f32, // points
+    /// Pressing a fragment for this long without moving picks it up, like dragging it with the
+    /// secondary mouse button.  This way, fragments can be moved on touchscreens.
+    pub(crate) long_press_time: f64, // seconds
+    /// If `true`, typing a method's shorthand whilst hovering over the leftover row of a fragment
+    /// appends a lead of that method to the fragment
+    pub(crate) compose_by_keyboard: bool,
+    /// Which keys trigger which keyboard shortcuts
+    pub(crate) keymap: Keymap,
+
+    /* Problems */
+    /// If `true`, methods can be spliced anywhere in a lead without being reported as a
+    /// [`Problem`](jigsaw_comp::full::Problem).  This is useful when composing link methods.
+    pub(crate) allow_mid_lead_splices: bool,
+
+    /* Playback */
+    /// The speed and rhythm of playback, also used when exporting the times of each row
+    pub(crate) pace: Pace,
+    /// How the bells are sounded during playback
+    pub(crate) sound_output: SoundOutput,
+
+    /* Undo history */
+    /// How much undo history is kept, and how rapid edits are grouped into undo steps
+    pub(crate) history: HistoryConfig,
 }
 
 impl Config {
+    /// The sizes which control how densely rows are drawn on the canvas
+    pub(crate) fn row_density(&self) -> RowDensity {
+        RowDensity {
+            col_width: self.col_width,
+            row_height: self.row_height,
+            font_size: self.font_size,
+        }
+    }
+
+    pub(crate) fn set_row_density(&mut self, density: RowDensity) {
+        self.col_width = density.col_width;
+        self.row_height = density.row_height;
+        self.font_size = density.font_size;
+    }
+
     pub(crate) fn bell_box_size(&self) -> Vec2 {
         Vec2::new(self.col_width, self.row_height)
     }
 
+    /// The distance between the lines of the [`Hatch`](crate::theme::Hatch) patterns drawn over
+    /// highlights
+    pub(crate) fn hatch_spacing(&self) -> f32 {
+        self.col_width * 0.6
+    }
+
+    /// The horizontal distance between the left edges of adjacent parts when all the parts are
+    /// drawn
+    pub(crate) fn part_col_width(&self, stage: Stage) -> f32 {
+        self.col_width * (stage.num_bells() as f32 + self.part_gap)
+    }
+
+    /// The horizontal distance from the right-hand edge of a fragment's rows to the method names
+    /// drawn next to them.  When place bells are shown, they are drawn in this gap.
+    pub(crate) fn method_name_offset(&self) -> f32 {
+        let num_cols = if self.show_place_bells { 4.0 } else { 1.0 };
+        self.col_width * num_cols
+    }
+
+    /// The colour of the markers drawn at the ends of [`Fragment`](jigsaw_comp::full::Fragment)s
+    /// in a given link group
+    pub(crate) fn link_color(&self, group: usize) -> Color32 {
+        if self.link_colors.is_empty() {
+            Color32::WHITE
+        } else {
+            self.link_colors[group % self.link_colors.len()]
+        }
+    }
+
+    /// The colours used to draw the canvas in the current [`Theme`]
+    pub(crate) fn palette(&self) -> Palette {
+        self.theme.palette(self.highlight_colors)
+    }
+
     /// Returns the [`Vec2`] representing the size of the padding round a fragment, in (virtual)
     /// pixels.
     pub(crate) fn frag_padding_vec(&self) -> Vec2 {
@@ -47,8 +193,13 @@ impl Config {
 impl Default for Config {
     fn default() -> Self {
         Self {
-            col_width: 10.0,
-            row_height: 16.0,
+            theme: Theme::default(),
+            highlight_colors: HighlightColors::default(),
+            hatch_highlights: false,
+
+            col_width: RowDensity::DEFAULT.col_width,
+            row_height: RowDensity::DEFAULT.row_height,
+            font_size: RowDensity::DEFAULT.font_size,
 
             ruleoff_line_width: 1.0,
 
@@ -58,8 +209,28 @@ impl Default for Config {
             frag_padding_x: 0.5,
             frag_padding_y: 0.3,
 
+            show_all_parts: false,
+            course_view: false,
+            show_place_bells: false,
+            place_bell_color: Color32::LIGHT_BLUE,
+            show_minimap: true,
+            minimap_size: Vec2::new(200.0, 150.0), // points
+            minimap_background_color: Color32::from_black_alpha(200),
+            part_gap: 3.0,
+
             ruleoff_snap_distance: 3.0, // rows
             split_height: 2.0,
+            frag_snap_distance: 15.0, // points
+            long_press_time: 0.5,     // seconds
+            compose_by_keyboard: false,
+            keymap: Keymap::default(),
+
+            allow_mid_lead_splices: false,
+
+            pace: Pace::default(),
+            sound_output: SoundOutput::Bells,
+
+            history: HistoryConfig::default(),
 
             bell_lines: {
                 let mut map = HashMap::new();
@@ -67,6 +238,68 @@ impl Default for Config {
                 map.insert(Bell::tenor(Stage::MAJOR), (0.2, Color32::LIGHT_BLUE));
                 map
             },
+            show_bluelines: false,
+            blueline_bells: {
+                let mut map = HashMap::new();
+                map.insert(Bell::TREBLE, (0.1, Color32::RED));
+                map.insert(Bell::from_index(1), (0.2, Color32::LIGHT_BLUE));
+                map
+            },
+            highlighted_bell_line: false,
+            highlighted_bell_line_style: (0.15, Color32::from_rgb(60, 220, 140)),
+            link_colors: vec![
+                Color32::from_rgb(230, 80, 80),
+                Color32::from_rgb(80, 200, 80),
+                Color32::from_rgb(80, 140, 255),
+                Color32::from_rgb(240, 200, 50),
+                Color32::from_rgb(200, 90, 230),
+                Color32::from_rgb(60, 210, 210),
+            ],
+            link_line_width: 3.0,
+            frag_end_line_width: 1.5,
+            off_screen_marker_color: Color32::from_rgb(230, 60, 60),
+            off_screen_marker_radius: 5.0, // points
+            playback_cursor_color: Color32::from_rgb(255, 200, 0),
+        }
+    }
+}
+
+/// The sizes which control how densely rows are drawn on the canvas, which can be zoomed together
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct RowDensity {
+    pub col_width: f32,  // points
+    pub row_height: f32, // points
+    /// The size of the bell names and annotations
+    pub font_size: f32, // points
+}
+
+impl RowDensity {
+    pub const DEFAULT: Self = Self {
+        col_width: 10.0,
+        row_height: 16.0,
+        font_size: 14.0,
+    };
+    /// Smaller rows, so that more of the composition fits on small screens (e.g. laptops)
+    pub const COMPACT: Self = Self {
+        col_width: 8.0,
+        row_height: 12.0,
+        font_size: 11.0,
+    };
+
+    /// The smallest and largest font sizes which zooming can reach
+    const MIN_FONT_SIZE: f32 = 6.0;
+    const MAX_FONT_SIZE: f32 = 40.0;
+
+    /// Scales every size by the same `factor`, as long as the font size stays within a sensible
+    /// range
+    pub fn zoomed(self, factor: f32) -> Self {
+        let new_font_size =
+            (self.font_size * factor).clamp(Self::MIN_FONT_SIZE, Self::MAX_FONT_SIZE);
+        let factor = new_font_size / self.font_size;
+        Self {
+            col_width: self.col_width * factor,
+            row_height: self.row_height * factor,
+            font_size: new_font_size,
         }
     }
 }
@@ -0,0 +1,67 @@
+//! Drawing code for the window used to export the composition's rows to a file
+
+use eframe::egui;
+use jigsaw_comp::full::{ExportFormat, ExportOptions};
+use jigsaw_utils::indexed_vec::PartIdx;
+
+use crate::Action;
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    options: &ExportOptions,
+    part_being_viewed: PartIdx,
+    file_path: &str,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::Window::new("Export rows")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut new_options = *options;
+            ui.horizontal(|ui| {
+                ui.label("Format:");
+                ui.radio_value(
+                    &mut new_options.format,
+                    ExportFormat::PlainText,
+                    "Plain text",
+                );
+                ui.radio_value(&mut new_options.format, ExportFormat::Csv, "CSV");
+            });
+            // Formats which ringing simulators (Abel, Beltower, Mobel, etc.) can load
+            ui.horizontal(|ui| {
+                ui.label("For simulators:");
+                ui.radio_value(&mut new_options.format, ExportFormat::SimulatorRows, "Rows");
+                ui.radio_value(
+                    &mut new_options.format,
+                    ExportFormat::PlaceNotation,
+                    "Place notation",
+                );
+            });
+            let mut only_viewed_part = options.part.is_some();
+            ui.checkbox(&mut only_viewed_part, "Only the part being viewed");
+            new_options.part = only_viewed_part.then(|| part_being_viewed);
+            // Simulators can't read annotations, so they're only written in the other formats
+            let is_annotated = matches!(
+                new_options.format,
+                ExportFormat::PlainText | ExportFormat::Csv
+            );
+            if is_annotated {
+                ui.checkbox(&mut new_options.annotations, "Method and call names");
+                ui.checkbox(&mut new_options.proof_flags, "Proved and false flags");
+            }
+            if new_options != *options {
+                push_action(Action::SetExportRows(Some(new_options)));
+            }
+
+            ui.label(format!("The rows will be written to '{}'", file_path));
+            ui.horizontal(|ui| {
+                if ui.button("Export").clicked() {
+                    push_action(Action::ExportRows(new_options));
+                    push_action(Action::SetExportRows(None));
+                }
+                if ui.button("Cancel").clicked() {
+                    push_action(Action::SetExportRows(None));
+                }
+            });
+        });
+}
@@ -0,0 +1,33 @@
+//! Native file dialogs, so that the user can choose files with the OS's file picker instead of
+//! typing their paths.  These need GTK on Linux, so are only compiled with the `file_dialogs`
+//! feature (which the web build, having no file system, never enables).
+
+use std::path::Path;
+
+use rfd::FileDialog;
+
+/// Asks the user to choose an existing file, returning its path (or `None` if they cancelled)
+pub(crate) fn pick_file_to_open(current_path: &str) -> Option<String> {
+    let path = dialog(current_path).set_title("Open").pick_file()?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+/// Asks the user to choose where to write a file, returning its path (or `None` if they
+/// cancelled).  The file doesn't have to exist.
+pub(crate) fn pick_file_to_save(current_path: &str) -> Option<String> {
+    let path = dialog(current_path).set_title("Save as").save_file()?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+/// Creates a [`FileDialog`] which starts at the file currently being used
+fn dialog(current_path: &str) -> FileDialog {
+    let path = Path::new(current_path);
+    let mut dialog = FileDialog::new();
+    if let Some(file_name) = path.file_name() {
+        dialog = dialog.set_file_name(&file_name.to_string_lossy());
+    }
+    if let Some(dir) = path.parent().filter(|dir| dir.is_dir()) {
+        dialog = dialog.set_directory(dir);
+    }
+    dialog
+}
@@ -0,0 +1,337 @@
+//! A deterministic fuzzer which applies random sequences of [`CompAction`]s to a [`JigsawApp`]
+//! (without running the GUI) and checks that the app's state stays consistent after every one.
+//! Every run is determined by its seed, so the fuzzer is run as a test over a fixed set of seeds
+//! and any failure can be reproduced exactly.
+
+use bellframe::RowBuf;
+use eframe::egui::{Pos2, Vec2};
+use itertools::Itertools;
+use jigsaw_comp::{
+    full::FullState,
+    report::Report,
+    spec::{part_heads::PartHeads, CompSpec},
+};
+use jigsaw_utils::indexed_vec::{CallIdx, FragIdx, MethodIdx, PartIdx, RowIdx};
+
+use crate::{CompAction, HistoryDirection, JigsawApp, DEFAULT_FILE_PATH};
+
+/// The seeds which are fuzzed by [`fixed_seeds`]
+const SEEDS: std::ops::Range<u64> = 0..4;
+/// The number of random edits made by each fuzzing run.  Every edit is checked against a
+/// [`FullState`] built from scratch (which rebuilds the falseness table), so this is kept small
+/// enough for the test to run in about a minute.
+const NUM_ACTIONS: usize = 25;
+
+#[test]
+fn fixed_seeds() {
+    let failures = SEEDS
+        .filter_map(|seed| fuzz(seed, NUM_ACTIONS).err())
+        .map(|failure| failure.to_string())
+        .collect_vec();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// An invariant which was broken during a fuzzing run
+#[derive(Debug, Clone)]
+struct FuzzFailure {
+    seed: u64,
+    /// The index of the action after which the invariant was broken
+    step: usize,
+    /// The [`Debug`] representation of the action which broke the invariant
+    action: String,
+    reason: String,
+}
+
+impl std::fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "seed {}, step {}: {} (after {})",
+            self.seed, self.step, self.reason, self.action
+        )
+    }
+}
+
+/// Applies `num_actions` random [`CompAction`]s to the example composition, returning the first
+/// broken invariant (if any).  The actions are generated from the state of the composition, so
+/// most of them are valid edits, but some deliberately use out-of-range indices to check that
+/// failed edits leave the composition unchanged.
+fn fuzz(seed: u64, num_actions: usize) -> Result<(), FuzzFailure> {
+    let mut app = JigsawApp::new(CompSpec::example(), DEFAULT_FILE_PATH.to_owned());
+    let mut rng = Rng::new(seed);
+    // Leave longer than the grouping timeout between edits, so that every successful edit
+    // creates its own undo step
+    let time_between_edits = app.config.history.group_timeout * 2.0;
+    for step in 0..num_actions {
+        let action = random_action(&app, &mut rng);
+        let fail = |reason: String| FuzzFailure {
+            seed,
+            step,
+            action: format!("{:?}", action),
+            reason,
+        };
+        let time = step as f64 * time_between_edits;
+        let is_history_move = matches!(action, CompAction::UndoRedo(_) | CompAction::JumpToStep(_));
+
+        let json_before = app.history.comp_spec().to_json();
+        let result = app.apply_comp_action(action.clone(), time);
+        let json_after = app.history.comp_spec().to_json();
+        if result.is_err() && json_before != json_after {
+            return Err(fail("failed edit changed the composition".to_owned()));
+        }
+        // Undoing then redoing a successful edit should return to the same composition
+        if result.is_ok() && !is_history_move {
+            undo_redo(&mut app, time, &json_before, &json_after).map_err(fail)?;
+        }
+        check_invariants(&app).map_err(fail)?;
+    }
+    Ok(())
+}
+
+/// Undoes and then redoes the last edit, checking that the composition goes back to `json_before`
+/// and then forward to `json_after`
+fn undo_redo(
+    app: &mut JigsawApp,
+    time: f64,
+    json_before: &str,
+    json_after: &str,
+) -> Result<(), String> {
+    for &(direction, expected_json) in &[
+        (HistoryDirection::Undo, json_before),
+        (HistoryDirection::Redo, json_after),
+    ] {
+        app.apply_comp_action(CompAction::UndoRedo(direction), time)
+            .map_err(|e| format!("couldn't {:?} the edit: {}", direction, e))?;
+        if app.history.comp_spec().to_json() != expected_json {
+            return Err(format!("{:?} didn't restore the composition", direction));
+        }
+        check_invariants(app).map_err(|e| format!("after {:?}, {}", direction, e))?;
+    }
+    Ok(())
+}
+
+/// Checks that the app's cached [`FullState`] is consistent, and is the same as one built from
+/// scratch
+fn check_invariants(app: &JigsawApp) -> Result<(), String> {
+    let cached = &app.full_state;
+    let fresh = FullState::new(app.history.comp_spec());
+
+    // Stage consistency
+    if cached.stage != fresh.stage {
+        return Err(format!("stage is {}, not {}", cached.stage, fresh.stage));
+    }
+    if cached.part_heads.stage() != cached.stage {
+        return Err("part heads have the wrong stage".to_owned());
+    }
+    for (frag_idx, frag) in cached.fragments.iter_enumerated() {
+        for part_idx in 0..cached.part_heads.len() {
+            let part = PartIdx::new(part_idx);
+            // Fragment lengths should be the same in every part, and always include the leftover
+            // row
+            let rows = frag
+                .rows_in_part(part)
+                .map(|(_, data)| data.row)
+                .collect_vec();
+            if rows.len() != frag.num_rows() || rows.is_empty() {
+                return Err(format!(
+                    "fragment {} has {} rows in part {}, but num_rows is {}",
+                    frag_idx.index(),
+                    rows.len(),
+                    part_idx,
+                    frag.num_rows()
+                ));
+            }
+            if rows.iter().any(|r| r.stage() != cached.stage) {
+                return Err(format!(
+                    "fragment {} has rows of the wrong stage in part {}",
+                    frag_idx.index(),
+                    part_idx
+                ));
+            }
+        }
+    }
+
+    // The incrementally updated `FullState` should match one built from scratch
+    if cached.fragments.len() != fresh.fragments.len() {
+        return Err(format!(
+            "cached state has {} fragments, not {}",
+            cached.fragments.len(),
+            fresh.fragments.len()
+        ));
+    }
+    for ((frag_idx, cached_frag), fresh_frag) in
+        cached.fragments.iter_enumerated().zip(&fresh.fragments)
+    {
+        let frag_rows = |frag: &jigsaw_comp::full::Fragment| {
+            (0..cached.part_heads.len())
+                .flat_map(|part| frag.rows_in_part(PartIdx::new(part)))
+                .map(|(_, data)| (data.row.to_owned(), data.false_group, data.is_proved))
+                .collect_vec()
+        };
+        if cached_frag.position != fresh_frag.position
+            || frag_rows(cached_frag) != frag_rows(fresh_frag)
+        {
+            return Err(format!("cached fragment {} is stale", frag_idx.index()));
+        }
+    }
+    if Report::new(cached).to_json() != Report::new(&fresh).to_json() {
+        return Err("cached stats are stale".to_owned());
+    }
+    Ok(())
+}
+
+/// Generates a random [`CompAction`] which could be applied to the current state of `app`
+fn random_action(app: &JigsawApp, rng: &mut Rng) -> CompAction {
+    let full_state = &app.full_state;
+    let frag_idx = random_frag(full_state, rng);
+    // The number of rows in `frag_idx`, not including the leftover row
+    let frag_len = full_state
+        .fragments
+        .get(frag_idx)
+        .map_or(0, |f| f.num_rows() - 1);
+    let row_idx = rng.below(frag_len + 2);
+    let (start, end) = {
+        let a = rng.below(frag_len + 1);
+        let b = rng.below(frag_len + 1);
+        (a.min(b), a.max(b))
+    };
+    let method_idx = MethodIdx::new(rng.below(full_state.methods.len() + 1));
+    let position = Pos2::new(rng.below(1000) as f32, rng.below(1000) as f32);
+
+    match rng.below(22) {
+        0 => CompAction::UndoRedo(HistoryDirection::Undo),
+        1 => CompAction::UndoRedo(HistoryDirection::Redo),
+        2 => CompAction::JumpToStep(rng.below(app.history.step_descriptions().count() + 1)),
+        3 => CompAction::MuteFragment(frag_idx),
+        4 => CompAction::SoloFragment(frag_idx),
+        5 => CompAction::ToggleBluelines(frag_idx),
+        6 => CompAction::AddFragment {
+            method_idx,
+            is_course: rng.below(4) == 0,
+            position,
+        },
+        7 => CompAction::ExtendFragment {
+            frag_idx,
+            method_idx,
+            by_course: rng.below(4) == 0,
+        },
+        8 => CompAction::AppendLead {
+            frag_idx,
+            shorthand: full_state
+                .methods
+                .get(method_idx)
+                .map_or_else(|| "?".to_owned(), |m| m.shorthand()),
+        },
+        9 => CompAction::MoveFragment { frag_idx, position },
+        10 => CompAction::DuplicateTransposed {
+            frag_idx,
+            transposition: random_row(full_state, rng),
+            position,
+        },
+        11 => CompAction::DeleteFragment(frag_idx),
+        12 => CompAction::SplitFragment {
+            frag_idx,
+            split_index: row_idx as isize,
+            pos_of_new_frag: position,
+        },
+        13 => CompAction::DeleteRows {
+            frag_idx,
+            start,
+            end,
+        },
+        14 => CompAction::IsolateRows {
+            frag_idx,
+            start,
+            end,
+            pos_of_rows: position,
+            pos_of_rows_after: position + Vec2::DOWN * 100.0,
+        },
+        15 => CompAction::SetRowsMethod {
+            frag_idx,
+            start,
+            end,
+            method_idx,
+        },
+        16 => CompAction::JoinFragments {
+            frag_a: frag_idx,
+            frag_b: random_frag(full_state, rng),
+        },
+        17 => CompAction::RotateComp {
+            frag_idx,
+            row_idx: row_idx as isize,
+        },
+        18 => CompAction::RotateFragment {
+            frag_idx,
+            by_rows: row_idx as isize,
+        },
+        19 => CompAction::SetCall {
+            frag_idx,
+            row_idx: row_idx as isize,
+            call_idx: match rng.below(full_state.calls.len() + 1) {
+                0 => None,
+                i => Some(CallIdx::new(i - 1)),
+            },
+        },
+        20 => CompAction::ReverseComp,
+        _ => {
+            // Use a row of the composition as the part head, so that it has the right stage.  If
+            // it can't be parsed, fall back on a one-part composition
+            let row = random_row(full_state, rng);
+            let part_heads = PartHeads::parse(&row.to_string(), full_state.stage)
+                .unwrap_or_else(|_| PartHeads::one_part(full_state.stage));
+            CompAction::SetPartHeads(part_heads)
+        }
+    }
+}
+
+/// Picks a random [`FragIdx`], which is out of range roughly once in every ten picks
+fn random_frag(full_state: &FullState, rng: &mut Rng) -> FragIdx {
+    let num_frags = full_state.fragments.len();
+    if num_frags == 0 || rng.below(10) == 0 {
+        FragIdx::new(num_frags)
+    } else {
+        FragIdx::new(rng.below(num_frags))
+    }
+}
+
+/// Picks a random [`Row`](bellframe::Row) from the first part of the composition, or rounds if
+/// there are no fragments
+fn random_row(full_state: &FullState, rng: &mut Rng) -> RowBuf {
+    let num_frags = full_state.fragments.len();
+    if num_frags == 0 {
+        return RowBuf::rounds(full_state.stage);
+    }
+    let frag = &full_state.fragments[FragIdx::new(rng.below(num_frags))];
+    let row_idx = RowIdx::new(rng.below(frag.num_rows()));
+    frag.row_in_part(PartIdx::new(0), row_idx).to_owned()
+}
+
+/// A small pseudo-random number generator (SplitMix64).  This is used instead of an external
+/// crate, so that the same seed always generates the same actions.
+#[derive(Debug, Clone)]
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`, or `0` if `n` is `0`
+    fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            0
+        } else {
+            (self.next_u64() % n as u64) as usize
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Drawing code for the window used to export the composition as a printable HTML page
+
+use eframe::egui;
+
+use crate::Action;
+
+/// The state of the window used to export the composition as a printable HTML page.  The title and
+/// composer aren't part of the composition, so are only stored here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HtmlExport {
+    pub title: String,
+    pub composer: String,
+}
+
+impl Default for HtmlExport {
+    fn default() -> Self {
+        Self {
+            title: "Untitled".to_owned(),
+            composer: String::new(),
+        }
+    }
+}
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    export: &HtmlExport,
+    file_path: &str,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::Window::new("Export printable composition")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut new_export = export.clone();
+            egui::Grid::new("html_export_grid").show(ui, |grid_ui| {
+                grid_ui.label("Title:");
+                grid_ui.text_edit_singleline(&mut new_export.title);
+                grid_ui.end_row();
+
+                grid_ui.label("Composer:");
+                grid_ui.text_edit_singleline(&mut new_export.composer);
+                grid_ui.end_row();
+            });
+            if &new_export != export {
+                push_action(Action::SetHtmlExport(Some(new_export.clone())));
+            }
+
+            ui.label(format!("The HTML page will be written to '{}'", file_path));
+            ui.horizontal(|ui| {
+                if ui.button("Export").clicked() {
+                    push_action(Action::ExportHtml(new_export));
+                    push_action(Action::SetHtmlExport(None));
+                }
+                if ui.button("Cancel").clicked() {
+                    push_action(Action::SetHtmlExport(None));
+                }
+            });
+        });
+}
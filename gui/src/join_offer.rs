@@ -0,0 +1,39 @@
+//! Drawing code for the window which offers to join a fragment after it snaps onto another
+
+use eframe::egui;
+use jigsaw_utils::indexed_vec::FragIdx;
+
+use crate::{touch_search::TouchSearch, Action, CompAction};
+
+/// Draw a window offering to join `frag_b` onto the end of `frag_a`
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    frag_a: FragIdx,
+    frag_b: FragIdx,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::Window::new("Join fragments?")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "Join fragment {} onto the end of fragment {}?",
+                frag_b.index(),
+                frag_a.index()
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Join").clicked() {
+                    push_action(Action::Comp(CompAction::JoinFragments { frag_a, frag_b }));
+                    push_action(Action::SetJoinOffer(None));
+                }
+                if ui.button("Fill in the gap...").clicked() {
+                    let search = TouchSearch::joining(frag_a, frag_b);
+                    push_action(Action::SetTouchSearch(Some(search)));
+                    push_action(Action::SetJoinOffer(None));
+                }
+                if ui.button("Keep separate").clicked() {
+                    push_action(Action::SetJoinOffer(None));
+                }
+            });
+        });
+}
@@ -0,0 +1,282 @@
+//! Code for the customisable mapping between key chords and the [`Shortcut`]s they trigger
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use eframe::egui::{Key, Modifiers};
+use serde::{Deserialize, Serialize};
+
+/// An operation which can be triggered by pressing a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub(crate) enum Shortcut {
+    Undo,
+    Redo,
+    ReverseComp,
+    AddLead,
+    AddCourse,
+    DeleteFragment,
+    SplitAtRuleoff,
+    SplitAtCursor,
+    MuteFragment,
+    SoloFragment,
+    ToggleBluelines,
+    ExtendByLead,
+    ExtendByCourse,
+    JoinFragments,
+    RotateComp,
+    ToggleBob,
+    ToggleSingle,
+    MakePlain,
+    PreviousPart,
+    NextPart,
+    ClearSelection,
+    DeleteRows,
+    IsolateRows,
+    SetRowsMethod,
+    DuplicateIntoPart,
+    RotateFragment,
+    EditFragmentStart,
+    NextFragment,
+    PreviousFragment,
+    ViewFirstFragment,
+    ViewAllFragments,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    FindRow,
+}
+
+impl Shortcut {
+    /// Every [`Shortcut`], in the order that they're displayed to the user
+    pub(crate) const ALL: [Shortcut; 35] = [
+        Shortcut::Undo,
+        Shortcut::Redo,
+        Shortcut::ReverseComp,
+        Shortcut::AddLead,
+        Shortcut::AddCourse,
+        Shortcut::DeleteFragment,
+        Shortcut::SplitAtRuleoff,
+        Shortcut::SplitAtCursor,
+        Shortcut::MuteFragment,
+        Shortcut::SoloFragment,
+        Shortcut::ToggleBluelines,
+        Shortcut::ExtendByLead,
+        Shortcut::ExtendByCourse,
+        Shortcut::JoinFragments,
+        Shortcut::RotateComp,
+        Shortcut::RotateFragment,
+        Shortcut::EditFragmentStart,
+        Shortcut::ToggleBob,
+        Shortcut::ToggleSingle,
+        Shortcut::MakePlain,
+        Shortcut::PreviousPart,
+        Shortcut::NextPart,
+        Shortcut::ClearSelection,
+        Shortcut::DeleteRows,
+        Shortcut::IsolateRows,
+        Shortcut::SetRowsMethod,
+        Shortcut::DuplicateIntoPart,
+        Shortcut::NextFragment,
+        Shortcut::PreviousFragment,
+        Shortcut::ViewFirstFragment,
+        Shortcut::ViewAllFragments,
+        Shortcut::ZoomIn,
+        Shortcut::ZoomOut,
+        Shortcut::ResetZoom,
+        Shortcut::FindRow,
+    ];
+
+    /// A human-readable description of what this [`Shortcut`] does
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Shortcut::Undo => "Undo",
+            Shortcut::Redo => "Redo",
+            Shortcut::ReverseComp => "Reverse composition",
+            Shortcut::AddLead => "Add a plain lead at the cursor",
+            Shortcut::AddCourse => "Add a plain course at the cursor",
+            Shortcut::DeleteFragment => "Delete fragment",
+            Shortcut::SplitAtRuleoff => "Split fragment at the nearest rule-off",
+            Shortcut::SplitAtCursor => "Split fragment at the cursor",
+            Shortcut::MuteFragment => "Mute/unmute fragment",
+            Shortcut::SoloFragment => "Solo fragment",
+            Shortcut::ToggleBluelines => "Toggle bluelines on fragment",
+            Shortcut::ExtendByLead => "Extend fragment by a plain lead",
+            Shortcut::ExtendByCourse => "Extend fragment by a plain course",
+            Shortcut::JoinFragments => "Join fragment onto the nearest fragment",
+            Shortcut::RotateComp => "Rotate composition to the nearest rule-off",
+            Shortcut::RotateFragment => "Rotate fragment to start at the nearest rule-off",
+            Shortcut::EditFragmentStart => "Change the start row or snap start of fragment",
+            Shortcut::ToggleBob => "Toggle a bob",
+            Shortcut::ToggleSingle => "Toggle a single",
+            Shortcut::MakePlain => "Make a lead end plain",
+            Shortcut::PreviousPart => "View the previous part",
+            Shortcut::NextPart => "View the next part",
+            Shortcut::ClearSelection => "Clear the selected rows",
+            Shortcut::DeleteRows => "Delete the selected rows",
+            Shortcut::IsolateRows => "Split the selected rows into their own fragment",
+            Shortcut::SetRowsMethod => "Change the selected rows to the selected method",
+            Shortcut::DuplicateIntoPart => "Duplicate fragment, transposed into the viewed part",
+            Shortcut::NextFragment => "Move the view to the next fragment",
+            Shortcut::PreviousFragment => "Move the view to the previous fragment",
+            Shortcut::ViewFirstFragment => "Move the view to the first fragment",
+            Shortcut::ViewAllFragments => "Move the view to the centre of all the fragments",
+            Shortcut::ZoomIn => "Make the rows bigger",
+            Shortcut::ZoomOut => "Make the rows smaller",
+            Shortcut::ResetZoom => "Reset the rows to their default size",
+            Shortcut::FindRow => "Find every row matching a row or music pattern",
+        }
+    }
+
+    /// The [`KeyChord`]s which trigger this [`Shortcut`] unless the user rebinds it
+    pub(crate) fn default_chords(self) -> Vec<KeyChord> {
+        use Key::*;
+
+        let plain = KeyChord::plain;
+        let shift = KeyChord::shift;
+        match self {
+            Shortcut::Undo => vec![plain(Z), KeyChord::command(Z)],
+            Shortcut::Redo => vec![
+                shift(Z),
+                plain(Y),
+                shift(Y),
+                KeyChord {
+                    key: Z,
+                    shift: true,
+                    command: true,
+                },
+                KeyChord::command(Y),
+            ],
+            Shortcut::ReverseComp => vec![shift(R)],
+            Shortcut::AddLead => vec![plain(A)],
+            Shortcut::AddCourse => vec![shift(A)],
+            Shortcut::DeleteFragment => vec![plain(D), shift(D)],
+            Shortcut::SplitAtRuleoff => vec![plain(X)],
+            Shortcut::SplitAtCursor => vec![shift(X)],
+            Shortcut::MuteFragment => vec![plain(S)],
+            Shortcut::SoloFragment => vec![shift(S)],
+            Shortcut::ToggleBluelines => vec![plain(L)],
+            Shortcut::ExtendByLead => vec![plain(E)],
+            Shortcut::ExtendByCourse => vec![shift(E)],
+            Shortcut::JoinFragments => vec![plain(J), shift(J)],
+            Shortcut::RotateComp => vec![plain(R)],
+            Shortcut::RotateFragment => vec![plain(O)],
+            Shortcut::EditFragmentStart => vec![plain(F)],
+            Shortcut::ToggleBob => vec![plain(B)],
+            Shortcut::ToggleSingle => vec![shift(B)],
+            Shortcut::MakePlain => vec![plain(P), shift(P)],
+            Shortcut::PreviousPart => vec![plain(ArrowLeft)],
+            Shortcut::NextPart => vec![plain(ArrowRight)],
+            Shortcut::ClearSelection => vec![plain(Escape)],
+            Shortcut::DeleteRows => vec![plain(Delete), plain(Backspace)],
+            Shortcut::IsolateRows => vec![plain(I)],
+            Shortcut::SetRowsMethod => vec![plain(M)],
+            Shortcut::DuplicateIntoPart => vec![plain(T)],
+            Shortcut::NextFragment => vec![plain(Tab)],
+            Shortcut::PreviousFragment => vec![shift(Tab)],
+            Shortcut::ViewFirstFragment => vec![plain(Home)],
+            Shortcut::ViewAllFragments => vec![shift(Home)],
+            Shortcut::ZoomIn => vec![KeyChord::command(PageUp)],
+            Shortcut::ZoomOut => vec![KeyChord::command(PageDown)],
+            Shortcut::ResetZoom => vec![KeyChord::command(Num0)],
+            Shortcut::FindRow => vec![KeyChord::command(F)],
+        }
+    }
+}
+
+/// A [`Key`], along with the modifiers which have to be held down for it to trigger a
+/// [`Shortcut`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct KeyChord {
+    pub key: Key,
+    pub shift: bool,
+    /// Ctrl on Windows and Linux, or ⌘ Command on Mac
+    pub command: bool,
+}
+
+impl KeyChord {
+    fn plain(key: Key) -> Self {
+        Self {
+            key,
+            shift: false,
+            command: false,
+        }
+    }
+
+    fn shift(key: Key) -> Self {
+        Self {
+            key,
+            shift: true,
+            command: false,
+        }
+    }
+
+    fn command(key: Key) -> Self {
+        Self {
+            key,
+            shift: false,
+            command: true,
+        }
+    }
+
+    /// Creates the [`KeyChord`] made by pressing a [`Key`] whilst holding some [`Modifiers`]
+    pub(crate) fn from_key_press(key: Key, modifiers: Modifiers) -> Self {
+        Self {
+            key,
+            shift: modifiers.shift,
+            command: modifiers.command,
+        }
+    }
+}
+
+impl Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+/// A mapping from [`Shortcut`]s to the [`KeyChord`]s which trigger them.  Only the bindings which
+/// the user has changed are stored, so any [`Shortcut`]s added in future versions of Jigsaw will
+/// still get their default bindings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Keymap {
+    rebound: BTreeMap<Shortcut, Vec<KeyChord>>,
+}
+
+impl Keymap {
+    /// Returns the [`KeyChord`]s which trigger a given [`Shortcut`]
+    pub(crate) fn chords(&self, shortcut: Shortcut) -> Vec<KeyChord> {
+        match self.rebound.get(&shortcut) {
+            Some(chords) => chords.clone(),
+            None => shortcut.default_chords(),
+        }
+    }
+
+    /// Returns `true` if the user has changed the [`KeyChord`]s of a given [`Shortcut`]
+    pub(crate) fn is_rebound(&self, shortcut: Shortcut) -> bool {
+        self.rebound.contains_key(&shortcut)
+    }
+
+    /// Returns every [`Shortcut`] triggered by a given [`KeyChord`], in the order of
+    /// [`Shortcut::ALL`]
+    pub(crate) fn shortcuts_for(&self, chord: KeyChord) -> Vec<Shortcut> {
+        Shortcut::ALL
+            .iter()
+            .copied()
+            .filter(|s| self.chords(*s).contains(&chord))
+            .collect()
+    }
+
+    /// Change the [`KeyChord`]s which trigger a [`Shortcut`], or reset them to the default if
+    /// `chords` is `None`
+    pub(crate) fn rebind(&mut self, shortcut: Shortcut, chords: Option<Vec<KeyChord>>) {
+        match chords {
+            Some(chords) => self.rebound.insert(shortcut, chords),
+            None => self.rebound.remove(&shortcut),
+        };
+    }
+}
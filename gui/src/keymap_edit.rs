@@ -0,0 +1,76 @@
+//! Drawing code for the window used to rebind keyboard [`Shortcut`]s
+
+use eframe::egui;
+use itertools::Itertools;
+
+use crate::{
+    keymap::{Keymap, Shortcut},
+    Action,
+};
+
+/// The state of the window used to rebind keyboard [`Shortcut`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct KeymapEdit {
+    /// The [`Shortcut`] which the next key press will be bound to (if any), and whether that key
+    /// press should replace all the [`Shortcut`]'s existing key chords (rather than being added
+    /// alongside them)
+    pub rebinding: Option<(Shortcut, bool)>,
+}
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    edit: &KeymapEdit,
+    keymap: &Keymap,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::Window::new("Keyboard shortcuts")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("keymap_edit_grid").show(ui, |grid_ui| {
+                for &shortcut in Shortcut::ALL.iter() {
+                    grid_ui.label(shortcut.description());
+                    let chords_text = match edit.rebinding {
+                        Some((s, _)) if s == shortcut => {
+                            "Press a key (or Escape to cancel)".to_owned()
+                        }
+                        _ => {
+                            let chords = keymap.chords(shortcut);
+                            if chords.is_empty() {
+                                "(none)".to_owned()
+                            } else {
+                                chords.iter().join(", ")
+                            }
+                        }
+                    };
+                    grid_ui.label(chords_text);
+
+                    // Rebinding waits for the user's next key press, which is handled along with
+                    // the rest of the keyboard input
+                    let mut start_rebinding = |replace: bool| {
+                        let new_edit = KeymapEdit {
+                            rebinding: Some((shortcut, replace)),
+                        };
+                        push_action(Action::SetKeymapEdit(Some(new_edit)));
+                    };
+                    if grid_ui.button("Rebind").clicked() {
+                        start_rebinding(true);
+                    }
+                    if grid_ui.button("Add").clicked() {
+                        start_rebinding(false);
+                    }
+                    if grid_ui
+                        .add(egui::Button::new("Reset").enabled(keymap.is_rebound(shortcut)))
+                        .clicked()
+                    {
+                        push_action(Action::RebindShortcut(shortcut, None));
+                    }
+                    grid_ui.end_row();
+                }
+            });
+
+            if ui.button("Close").clicked() {
+                push_action(Action::SetKeymapEdit(None));
+            }
+        });
+}
@@ -1,23 +1,88 @@
 //! Top-level code for Jigsaw's GUI
 
-use canvas::{CanvasResponse, FragHover};
+use std::{collections::HashSet, convert::TryFrom, rc::Rc, time::Duration};
+
+use canvas::{CanvasResponse, FragHover, RowSelection};
 use eframe::{
     egui::{self, PointerButton, Pos2, Vec2},
     epi,
 };
 
 use jigsaw_comp::{
-    full::FullState,
-    spec::{self, part_heads::PartHeads, CompSpec},
-    History,
+    analysis::MusicAnalyzer,
+    full::{ExportOptions, FullState},
+    method_lib::{self, MethodLib},
+    search::{Search, SearchParams, Touch},
+    spec::{
+        self, import, part_heads::PartHeads, proof::ProofSettings, save_load, CompSpec,
+        LengthTarget,
+    },
+    History, Pace,
+};
+use jigsaw_utils::{
+    indexed_vec::{CallIdx, FragIdx, MethodIdx, PartIdx},
+    types::{RowLocation, RowSource},
+};
+
+use self::{
+    call_edit::CallEdit,
+    collab::{CollabWindow, Session},
+    config::{Config, RowDensity},
+    html_export::HtmlExport,
+    keymap::{KeyChord, Shortcut},
+    keymap_edit::KeymapEdit,
+    method_edit::MethodEdit,
+    music_edit::MusicEdit,
+    new_comp::NewComp,
+    playback::Playback,
+    proof_edit::ProofEdit,
+    row_search::RowSearch,
+    start_edit::StartEdit,
+    theme::{HighlightColors, Theme},
+    toasts::Toasts,
+    touch_search::TouchSearch,
 };
-use jigsaw_utils::indexed_vec::{FragIdx, PartIdx};
 
-use self::config::Config;
+#[cfg(feature = "audio")]
+use self::playback::SoundOutput;
 
+#[cfg(target_arch = "wasm32")]
+mod browser_storage;
+mod call_edit;
 mod canvas;
+mod collab;
 mod config;
+mod export_rows;
+#[cfg(feature = "file_dialogs")]
+mod file_dialog;
+#[cfg(test)]
+mod fuzz;
+mod html_export;
+mod join_offer;
+mod keymap;
+mod keymap_edit;
+mod menu_bar;
+mod method_edit;
+mod method_grid;
+mod minimap;
+mod music_edit;
+mod new_comp;
+mod playback;
+mod proof_edit;
+mod row_search;
+mod script;
 mod side_panel;
+#[cfg(feature = "audio")]
+mod sound;
+mod start_edit;
+mod svg_export;
+mod theme;
+mod toasts;
+mod touch_search;
+
+use bellframe::{Bell, RowBuf};
+
+pub use script::{apply_script, ScriptAction, ScriptError};
 
 // Imports only used for doc comments
 #[allow(unused_imports)]
@@ -39,33 +104,239 @@ pub struct JigsawApp {
     /// The text currently in the part head UI box.  Whilst the user is typing, this can become
     /// invalid, and therefore must be able to diverge from `self.history`
     part_head_str: String,
+    /// The path of the file which the composition will be saved to or opened from
+    file_path: String,
+    /// The [`Method`](jigsaw_comp::full::Method) currently being added or edited, if any
+    method_edit: Option<MethodEdit>,
+    /// The library used to add methods by name, if one has been loaded
+    method_lib: Option<MethodLib>,
+    /// The [`Call`](jigsaw_comp::full::Call) currently being added or edited, if any
+    call_edit: Option<CallEdit>,
+    /// The [`Music`](jigsaw_comp::Music) group currently being added or edited, if any
+    music_edit: Option<MusicEdit>,
+    /// The state of the keyboard shortcut window, if it's open
+    keymap_edit: Option<KeymapEdit>,
+    /// The start of a fragment which is being edited, if any
+    start_edit: Option<StartEdit>,
+    /// The proving settings which are being edited, if any
+    proof_edit: Option<ProofEdit>,
+    /// The state of the window used to start a new composition, if it's open
+    new_comp: Option<NewComp>,
+    /// The options of the window used to export the composition's rows, if it's open
+    export_rows: Option<ExportOptions>,
+    /// The state of the window used to export a printable composition, if it's open
+    html_export: Option<HtmlExport>,
+    /// The state of the window used to search for touches, if it's open
+    touch_search: Option<TouchSearch>,
+    /// The state of the window used to host or join collaboration sessions, if it's open
+    collab_window: Option<CollabWindow>,
+    /// The collaboration session which this app is hosting or has joined, if any
+    session: Option<Session>,
+    /// The touch search which is currently running, if any.  This is stepped a few leads every
+    /// frame, so that the GUI stays responsive during long searches.
+    running_search: Option<Search>,
+    /// The playback of the composition, if the playback window is open
+    playback: Option<Playback>,
+    /// The state of the row search window, if it's open
+    row_search: Option<RowSearch>,
+    /// Set when the composition is edited, and cleared when it is saved or replaced
+    has_unsaved_changes: bool,
+    /// Notifications about errors and large operations, shown over the corner of the screen
+    toasts: Toasts,
+    /// The [`Method`](jigsaw_comp::full::Method) used when adding new leads or courses
+    selected_method: MethodIdx,
+    /// The range of rows which the user has selected, if any
+    selection: Option<RowSelection>,
+    /// The fragment currently being dragged by the user, if any
+    frag_drag: Option<FragDrag>,
+    /// The time when the pointer was last pressed, if it's still held down.  egui doesn't record
+    /// this, but it's needed to detect long presses.
+    press_start_time: Option<f64>,
+    /// A pair of fragments `(frag_a, frag_b)` where `frag_b` has just been snapped underneath
+    /// `frag_a`, and the user is being offered the chance to join them
+    join_offer: Option<(FragIdx, FragIdx)>,
+    /// The part which is being displayed on the canvas.  This may be out of range if the number
+    /// of parts has decreased, so should be accessed through [`JigsawApp::part_being_viewed`].
+    part_being_viewed: PartIdx,
+    /// The pair of bells rung by one handbell ringer which is highlighted on the canvas, if any
+    handbell_pair: Option<(Bell, Bell)>,
+    /// The bell whose path is highlighted in every row on the canvas, if any
+    highlighted_bell: Option<Bell>,
+    /// If `true`, the falseness panel only lists the false course heads which keep the tenors
+    /// together
+    tenors_together_fchs: bool,
+    /// Custom analyses registered with [`JigsawApp::add_analyzer`], each shown in its own panel
+    analyzers: Vec<Box<dyn MusicAnalyzer>>,
     camera_pos: Pos2,
+    /// The size of the canvas in the last frame, used to centre the view on rows outside of input
+    /// handling (e.g. when a problem is clicked in the side panel)
+    canvas_size: Vec2,
+    /// The fragment which the view was last moved to by keyboard navigation, if any.  This may be
+    /// out of range if fragments have been deleted.
+    focused_frag: Option<FragIdx>,
+    /// If `true`, the composition can be viewed but not edited, and none of the editing UI is
+    /// drawn.  This is used to embed compositions in other web pages.
+    is_readonly: bool,
+    /// The URL of the page running Jigsaw (without any fragment), used to generate links which
+    /// share the composition.  This is only set on the web build.
+    page_url: Option<String>,
+    /// The compositions stored in the browser, used instead of files on the web build
+    #[cfg(target_arch = "wasm32")]
+    browser_storage: browser_storage::BrowserStorage,
 }
 
 impl JigsawApp {
     /// Load an example composition
     pub fn example() -> Self {
-        let spec = CompSpec::example();
+        Self::new(CompSpec::example(), DEFAULT_FILE_PATH.to_owned())
+    }
+
+    /// Start editing a given [`CompSpec`], which will be saved to `file_path`
+    pub fn new(spec: CompSpec, file_path: String) -> Self {
         let full_state = FullState::new(&spec);
         let part_head_str = full_state.part_heads.spec_string();
+        let config = Config::default();
 
         Self {
-            config: Config::default(),
+            history: History::with_config(spec, config.history),
+            config,
 
-            history: History::new(spec),
             full_state,
 
             part_head_str,
+            file_path,
+            method_edit: None,
+            method_lib: None,
+            call_edit: None,
+            music_edit: None,
+            keymap_edit: None,
+            start_edit: None,
+            proof_edit: None,
+            new_comp: None,
+            export_rows: None,
+            html_export: None,
+            touch_search: None,
+            running_search: None,
+            playback: None,
+            row_search: None,
+            collab_window: None,
+            session: None,
+            has_unsaved_changes: false,
+            toasts: Toasts::default(),
+            selected_method: MethodIdx::new(0),
+            selection: None,
+            frag_drag: None,
+            press_start_time: None,
+            join_offer: None,
+            part_being_viewed: PartIdx::new(0),
+            handbell_pair: None,
+            highlighted_bell: None,
+            tenors_together_fchs: true,
+            analyzers: Vec::new(),
             camera_pos: Pos2::ZERO,
+            canvas_size: Vec2::ZERO,
+            focused_frag: None,
+            is_readonly: false,
+            page_url: None,
+            #[cfg(target_arch = "wasm32")]
+            browser_storage: Default::default(),
         }
     }
+
+    /// View a given [`CompSpec`] without being able to edit it.  The canvas can be moved around,
+    /// but none of the editing UI is drawn, and nothing is saved between sessions.
+    pub fn readonly(spec: CompSpec) -> Self {
+        Self {
+            is_readonly: true,
+            ..Self::new(spec, DEFAULT_FILE_PATH.to_owned())
+        }
+    }
+
+    /// Replace the composition with one shared through a URL fragment generated by
+    /// [`CompSpec::to_url_fragment`].  This resets the undo history.
+    pub fn load_url_fragment(&mut self, fragment: &str) -> Result<(), save_load::LoadError> {
+        let spec = CompSpec::from_url_fragment(fragment)?;
+        self.load_spec(spec);
+        // The shared composition isn't stored anywhere yet
+        self.has_unsaved_changes = true;
+        Ok(())
+    }
+
+    /// Register a custom analysis of the composition's rows, which will be shown in its own panel
+    pub fn add_analyzer(&mut self, analyzer: Box<dyn MusicAnalyzer>) {
+        self.analyzers.push(analyzer);
+    }
+
+    /// Set the URL of the page running Jigsaw, allowing the user to copy links which share the
+    /// composition
+    pub fn set_page_url(&mut self, page_url: String) {
+        self.page_url = Some(page_url);
+    }
 }
 
+/// The file path used for saving/opening compositions, until the user changes it
+const DEFAULT_FILE_PATH: &str = "composition.json";
+/// The key under which the GUI's [`Config`] is persisted between sessions
+const CONFIG_KEY: &str = "config";
+/// The range of scales which the interface can be pinch-zoomed to
+const MIN_PIXELS_PER_POINT: f32 = 0.5;
+const MAX_PIXELS_PER_POINT: f32 = 3.0;
+/// How much each zoom shortcut scales the rows on the canvas
+const ZOOM_FACTOR: f32 = 1.1;
+/// The furthest the pointer can move whilst still counting as a long press, in points
+const LONG_PRESS_MAX_DISTANCE: f32 = 6.0;
+/// The number of leads tried by a running touch search every frame
+const SEARCH_LEADS_PER_FRAME: usize = 5_000;
+
 impl epi::App for JigsawApp {
     fn name(&self) -> &str {
         "Jigsaw"
     }
 
+    fn setup(
+        &mut self,
+        _ctx: &egui::CtxRef,
+        _frame: &mut epi::Frame<'_>,
+        storage: Option<&dyn epi::Storage>,
+    ) {
+        // Read-only views are independent of whatever the user has been editing
+        if self.is_readonly {
+            return;
+        }
+        // Load the config saved by the previous session, if it exists.  The undo history is
+        // still empty, so it can be safely recreated to use the loaded history config.
+        if let Some(config) = storage.and_then(|s| epi::get_value::<Config>(s, CONFIG_KEY)) {
+            self.history = History::with_config(self.history.comp_spec().clone(), config.history);
+            self.config = config;
+        }
+        // On the web, restore the composition which was being edited before the page was
+        // refreshed (unless a composition has already been loaded from a share link)
+        #[cfg(target_arch = "wasm32")]
+        if let Some(storage) = storage {
+            self.browser_storage = browser_storage::BrowserStorage::load(storage);
+            let autosave = self.browser_storage.autosave().map(CompSpec::from_json);
+            if let (false, Some(Ok(spec))) = (self.has_unsaved_changes, autosave) {
+                self.load_spec(spec);
+            }
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn epi::Storage) {
+        if self.is_readonly {
+            return;
+        }
+        epi::set_value(storage, CONFIG_KEY, &self.config);
+        #[cfg(target_arch = "wasm32")]
+        self.browser_storage
+            .write(storage, self.history.comp_spec().to_json());
+    }
+
+    fn auto_save_interval(&self) -> Duration {
+        // Save often, so that changes to the config are saved almost as soon as they're made (and
+        // aren't lost if Jigsaw doesn't shut down cleanly).  The config is small, so this is cheap.
+        Duration::from_secs(1)
+    }
+
     fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
         // To prevent bugs (and appease the borrow checker), Jigsaw's app is **immutable** during
         // both drawing and input gathering.  When the GUI wants to make changes to the app's state
@@ -74,14 +345,29 @@ impl epi::App for JigsawApp {
         // to a list of `actions` which will all be applied at the end of the frame.
         let mut actions = Vec::<Action>::new(); // These all take effect at the end of the frame
 
+        self.toasts.remove_expired(ctx.input().time);
+        self.update_press_start_time(ctx);
+        self.step_running_search(ctx);
+        self.poll_session(ctx);
+        self.step_playback(ctx);
+        // Cloning the visuals is cheap, and setting them every frame means that the theme can't
+        // get out of sync with the config
+        ctx.set_visuals(self.config.theme.visuals());
+        self.update_font_size(ctx);
         let gui_response = self.draw_gui(ctx, |a| actions.push(a));
+        self.canvas_size = gui_response.inner.rect.size();
 
         // PERF: Handling inputs **before** rendering the GUI would save a frame of latency
         self.handle_input(ctx, gui_response, |action| actions.push(action));
 
-        // Apply all actions
+        // Apply all actions.  The time is used to group rapid edits into single undo steps
+        let time = ctx.input().time;
         for action in actions {
-            self.apply_action(action);
+            // Read-only views can be looked around, but not changed
+            if self.is_readonly && !action.is_view_only() {
+                continue;
+            }
+            self.apply_action(action, time);
         }
     }
 
@@ -96,19 +382,170 @@ impl JigsawApp {
     // DRAW GUI //
     //////////////
 
-    fn draw_gui(&self, ctx: &egui::CtxRef, push_action: impl FnMut(Action)) -> CanvasResponse {
+    fn draw_gui(&self, ctx: &egui::CtxRef, mut push_action: impl FnMut(Action)) -> CanvasResponse {
+        // Read-only views only show the canvas
+        if self.is_readonly {
+            let response = self.draw_canvas(ctx, HashSet::new(), &mut push_action);
+            self.toasts.draw(ctx);
+            return response;
+        }
+
+        // Draw the menu bar along the top of the screen
+        menu_bar::draw(
+            ctx,
+            &self.file_path,
+            &self.config,
+            self.history.comp_spec(),
+            self.page_url.as_deref(),
+            &mut push_action,
+        );
+        // Draw the method editing window, if a method is being edited
+        if let Some(edit) = &self.method_edit {
+            method_edit::draw(
+                ctx,
+                edit,
+                self.method_lib.as_ref(),
+                self.full_state.stage,
+                &mut push_action,
+            );
+        }
+        // Draw the call editing window, if a call is being edited
+        if let Some(edit) = &self.call_edit {
+            call_edit::draw(ctx, edit, self.full_state.stage, &mut push_action);
+        }
+        // Draw the music editing window, if a music group is being edited
+        if let Some(edit) = &self.music_edit {
+            music_edit::draw(
+                ctx,
+                edit,
+                self.full_state.music_definitions(),
+                self.full_state.stage,
+                &mut push_action,
+            );
+        }
+        // Offer to join a fragment which has just been snapped onto another
+        if let Some((frag_a, frag_b)) = self.join_offer {
+            join_offer::draw(ctx, frag_a, frag_b, &mut push_action);
+        }
+        // Draw the fragment start window, if the start of a fragment is being edited
+        if let Some(edit) = &self.start_edit {
+            // The fragment may have been deleted since the window was opened
+            if let Ok(frag_start) = self.history.comp_spec().fragment_start(edit.frag_idx) {
+                let stage = self.full_state.stage;
+                start_edit::draw(ctx, edit, &frag_start, stage, &mut push_action);
+            }
+        }
+        // Draw the proving settings window, if it's open
+        if let Some(edit) = &self.proof_edit {
+            let settings = self.full_state.proof_settings();
+            proof_edit::draw(ctx, edit, settings, self.full_state.stage, &mut push_action);
+        }
+        // Draw the touch search window, if it's open
+        if let Some(search) = &self.touch_search {
+            let camera_pos = self.camera_pos;
+            let leads_tried = self.running_search.as_ref().map(Search::leads_tried);
+            touch_search::draw(
+                ctx,
+                search,
+                leads_tried,
+                &self.full_state,
+                camera_pos,
+                &mut push_action,
+            );
+        }
+        // Draw the collaboration window, if it's open
+        if let Some(window) = &self.collab_window {
+            let status = self.session.as_ref().map(Session::status);
+            collab::draw(ctx, window, status, &mut push_action);
+        }
+        // Draw the playback window, if it's open
+        if let Some(playback) = &self.playback {
+            playback::draw(ctx, playback, &self.full_state, &mut push_action);
+        }
+        // Draw the row search window, if it's open
+        if let Some(search) = &self.row_search {
+            row_search::draw(ctx, search, &self.full_state, &mut push_action);
+        }
+        // Draw the new composition window, if it's open
+        if let Some(new_comp) = &self.new_comp {
+            new_comp::draw(ctx, new_comp, self.has_unsaved_changes, &mut push_action);
+        }
+        // Draw the row export window, if it's open
+        if let Some(options) = &self.export_rows {
+            let part = self.part_being_viewed();
+            export_rows::draw(ctx, options, part, &self.file_path, &mut push_action);
+        }
+        // Draw the printable composition export window, if it's open
+        if let Some(export) = &self.html_export {
+            html_export::draw(ctx, export, &self.file_path, &mut push_action);
+        }
+        // Draw the keyboard shortcut window, if it's open
+        if let Some(edit) = &self.keymap_edit {
+            keymap_edit::draw(ctx, edit, &self.config.keymap, &mut push_action);
+        }
         // Draw right-hand panel, and decide which rows should be highlighted
-        let rows_to_highlight =
-            side_panel::draw(ctx, &self.full_state, &self.part_head_str, push_action);
-        // Draw the main canvas
-        canvas::draw(
+        let mut rows_to_highlight = side_panel::draw(
+            ctx,
+            &self.full_state,
+            &self.history,
+            &self.part_head_str,
+            self.selected_method,
+            self.part_being_viewed(),
+            self.handbell_pair,
+            self.highlighted_bell,
+            self.tenors_together_fchs,
+            &self.analyzers,
+            &self.config,
+            &mut push_action,
+        );
+        // Every occurrence of the row being searched for is highlighted
+        if let Some(search) = &self.row_search {
+            let matches = search.matches().iter().map(RowLocation::as_source);
+            rows_to_highlight.extend(matches);
+        }
+        let canvas_response = self.draw_canvas(ctx, rows_to_highlight, &mut push_action);
+        self.toasts.draw(ctx);
+        canvas_response
+    }
+
+    /// Draw the main canvas, along with the minimap over its corner
+    fn draw_canvas(
+        &self,
+        ctx: &egui::CtxRef,
+        rows_to_highlight: HashSet<RowSource>,
+        push_action: impl FnMut(Action),
+    ) -> CanvasResponse {
+        let canvas_response = canvas::draw(
             ctx,
             &self.full_state,
             &self.config,
             self.camera_pos,
             rows_to_highlight,
-            PartIdx::new(0), // Always display the first part until we can change this
-        )
+            self.selection,
+            self.part_being_viewed(),
+            self.handbell_pair,
+            self.highlighted_bell,
+            self.playback.as_ref().and_then(Playback::cursor),
+        );
+        // Draw the minimap over the corner of the canvas
+        if self.config.show_minimap {
+            minimap::draw(
+                ctx,
+                &self.full_state,
+                &self.config,
+                canvas_response.inner.rect,
+                self.camera_pos,
+                push_action,
+            );
+        }
+        canvas_response
+    }
+
+    /// The part which is being displayed on the canvas, clamped to the last part if the number of
+    /// parts has decreased since it was chosen
+    fn part_being_viewed(&self) -> PartIdx {
+        let last_part = PartIdx::new(self.full_state.part_heads.len() - 1);
+        self.part_being_viewed.min(last_part)
     }
 
     ////////////////////
@@ -122,6 +559,70 @@ impl JigsawApp {
         canvas_response: CanvasResponse,
         mut push_action: impl FnMut(Action),
     ) {
+        // Pan the canvas
+        if canvas_response.inner.dragged_by(PointerButton::Middle)
+            || self.is_primary_pan(ctx, &canvas_response)
+        {
+            push_action(Action::PanView(-canvas_response.inner.drag_delta()));
+        }
+        Self::pinch_zoom(ctx);
+        // Drag fragments with the secondary mouse button
+        self.drag_fragment(ctx, &canvas_response, &mut push_action);
+        // Select rows with the primary mouse button
+        if let Some(action) = self.update_selection(ctx, &canvas_response) {
+            push_action(action);
+        }
+
+        // If the user is rebinding a shortcut, then the next key press becomes one of that
+        // shortcut's key chords (and doesn't trigger any shortcuts itself)
+        if let Some((shortcut, replace)) = self.keymap_edit.and_then(|edit| edit.rebinding) {
+            self.rebind_shortcut(ctx, shortcut, replace, &mut push_action);
+            return;
+        }
+
+        // In compose-by-keyboard mode, typing a method's shorthand whilst hovering a fragment's
+        // leftover row appends a lead of that method to the fragment
+        let mut appended_lead = false;
+        if let Some(frag_hover) = canvas_response.frag_hover.as_ref() {
+            if self.config.compose_by_keyboard
+                && !ctx.wants_keyboard_input()
+                && self.is_leftover_row(frag_hover)
+            {
+                for evt in &ctx.input().events {
+                    if let egui::Event::Text(text) = evt {
+                        if let Some(comp_action) = self.append_lead(frag_hover, text) {
+                            push_action(Action::Comp(comp_action));
+                            appended_lead = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Copy the hovered fragment to the clipboard, or paste a copied fragment under the cursor
+        if !ctx.wants_keyboard_input() {
+            for evt in &ctx.input().events {
+                match evt {
+                    egui::Event::Copy => {
+                        if let Some(frag_hover) = canvas_response.frag_hover.as_ref() {
+                            self.copy_fragment(ctx, frag_hover.frag_idx, &mut push_action);
+                        }
+                    }
+                    // Typing only generates single characters, so any longer text must have been
+                    // pasted
+                    egui::Event::Text(text) if text.chars().count() > 1 => {
+                        if let Some(position) = canvas_response.cursor_pos {
+                            push_action(Action::Comp(CompAction::PasteFragment {
+                                json: text.clone(),
+                                position,
+                            }));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
         // Keyboard events
         for evt in &ctx.input().events {
             if let egui::Event::Key {
@@ -130,66 +631,665 @@ impl JigsawApp {
                 modifiers,
             } = *evt
             {
-                if !ctx.wants_keyboard_input() && pressed {
-                    if let Some(comp_action) =
-                        self.handle_key_press(key, modifiers, canvas_response.frag_hover.as_ref())
-                    {
-                        push_action(Action::Comp(comp_action));
+                // If the key press typed a method's shorthand, then it shouldn't also be used as a
+                // shortcut
+                if !ctx.wants_keyboard_input() && pressed && !appended_lead {
+                    if let Some(part_idx) = self.part_shortcut(key, modifiers) {
+                        push_action(Action::SetViewedPart(part_idx));
+                    }
+                    let action = self.handle_key_press(
+                        key,
+                        modifiers,
+                        canvas_response.frag_hover.as_ref(),
+                        canvas_response.cursor_pos,
+                        canvas_response.inner.rect.size(),
+                    );
+                    // Bell names only highlight bells if they aren't used by any shortcuts (e.g.
+                    // 'E' usually extends the fragment under the cursor)
+                    if let Some(action) = action.or_else(|| self.bell_shortcut(key, modifiers)) {
+                        push_action(action);
                     }
                 }
             }
         }
+    }
 
-        // Pan the canvas
-        if canvas_response.inner.dragged_by(PointerButton::Middle) {
-            push_action(Action::PanView(-canvas_response.inner.drag_delta()));
+    /// Move fragments which are dragged with the secondary mouse button.  A dragged fragment whose
+    /// first row gets close to the leftover row of a fragment which it links onto snaps into
+    /// place underneath it, and when the drag ends the user is offered the chance to join them.
+    fn drag_fragment(
+        &self,
+        ctx: &egui::CtxRef,
+        canvas_response: &CanvasResponse,
+        mut push_action: impl FnMut(Action),
+    ) {
+        let response = &canvas_response.inner;
+        // Fragments can be picked up by dragging with the secondary button or, since touchscreens
+        // don't have a secondary button, by a long press
+        let is_long_press = self.frag_drag.is_none() && self.is_long_press(ctx, canvas_response);
+        if (response.drag_started() && ctx.input().pointer.secondary_down()) || is_long_press {
+            if let Some(frag_hover) = &canvas_response.frag_hover {
+                let frag_idx = frag_hover.frag_idx;
+                push_action(Action::SetFragDrag(Some(FragDrag {
+                    frag_idx,
+                    unsnapped_pos: Pos2::from(self.full_state.fragments[frag_idx].position),
+                })));
+                push_action(Action::SetJoinOffer(None));
+                // The press will have selected rows, but the user wanted to move the fragment
+                if is_long_press {
+                    push_action(Action::SetSelection(None));
+                }
+            }
+            return;
+        }
+
+        let drag = match self.frag_drag {
+            Some(drag) => drag,
+            None => return,
+        };
+        // The position that the fragment would be at without snapping, kept separately so that
+        // the fragment can be dragged away again after it snaps
+        let unsnapped_pos = drag.unsnapped_pos + response.drag_delta();
+        let snap_target = self.snap_target(drag.frag_idx, unsnapped_pos);
+        if response.dragged() {
+            push_action(Action::SetFragDrag(Some(FragDrag {
+                unsnapped_pos,
+                ..drag
+            })));
+            push_action(Action::Comp(CompAction::MoveFragment {
+                frag_idx: drag.frag_idx,
+                position: snap_target.map_or(unsnapped_pos, |(_, pos)| pos),
+            }));
+        } else {
+            // The drag has finished
+            push_action(Action::SetFragDrag(None));
+            if let Some((frag_a, _)) = snap_target {
+                push_action(Action::SetJoinOffer(Some((frag_a, drag.frag_idx))));
+            }
         }
     }
 
-    /// Handle a keyboard key being pressed down
+    /// Records when the pointer is pressed, so that [`Self::is_long_press`] can tell how long it
+    /// has been held down for
+    fn update_press_start_time(&mut self, ctx: &egui::CtxRef) {
+        let input = ctx.input();
+        if input.pointer.any_pressed() {
+            self.press_start_time = Some(input.time);
+        } else if !input.pointer.any_down() {
+            self.press_start_time = None;
+        }
+    }
+
+    /// Returns `true` if the primary pointer has been held down over a fragment for at least
+    /// [`Config::long_press_time`], without moving
+    fn is_long_press(&self, ctx: &egui::CtxRef, canvas_response: &CanvasResponse) -> bool {
+        let pointer = &ctx.input().pointer;
+        if !pointer.primary_down() || canvas_response.frag_hover.is_none() {
+            return false;
+        }
+        let (press_origin, press_start_time, pos) = match (
+            pointer.press_origin(),
+            self.press_start_time,
+            pointer.interact_pos(),
+        ) {
+            (Some(origin), Some(start_time), Some(pos)) => (origin, start_time, pos),
+            _ => return false,
+        };
+        if (pos - press_origin).length() > LONG_PRESS_MAX_DISTANCE {
+            return false;
+        }
+        let time_held = ctx.input().time - press_start_time;
+        if time_held < self.config.long_press_time {
+            // egui only repaints when there's input, so keep repainting until the press is long
+            // enough to count
+            ctx.request_repaint();
+            return false;
+        }
+        true
+    }
+
+    /// Returns `true` if the canvas is being panned by dragging the primary pointer over empty
+    /// space (e.g. with one finger on a touchscreen).  Drags which select rows or move fragments
+    /// don't pan the canvas.
+    fn is_primary_pan(&self, ctx: &egui::CtxRef, canvas_response: &CanvasResponse) -> bool {
+        let response = &canvas_response.inner;
+        response.dragged_by(PointerButton::Primary)
+            && !response.drag_started()
+            && self.selection.is_none()
+            && self.frag_drag.is_none()
+            // Pinches are also reported as primary drags
+            && ctx.input().multi_touch().is_none()
+    }
+
+    /// Pinching on a touchscreen scales the whole interface, since the canvas has no zoom level of
+    /// its own
+    fn pinch_zoom(ctx: &egui::CtxRef) {
+        let zoom_delta = match ctx.input().multi_touch() {
+            Some(touch) => touch.zoom_delta,
+            None => return,
+        };
+        let pixels_per_point =
+            (ctx.pixels_per_point() * zoom_delta).clamp(MIN_PIXELS_PER_POINT, MAX_PIXELS_PER_POINT);
+        ctx.set_pixels_per_point(pixels_per_point);
+    }
+
+    /// Returns the fragment that `frag_idx` would snap underneath if its first row was at
+    /// `position`, along with the position it would snap to.  Fragments only snap onto fragments
+    /// which they [link](jigsaw_comp::full::FragLink) onto.
+    fn snap_target(&self, frag_idx: FragIdx, position: Pos2) -> Option<(FragIdx, Pos2)> {
+        let mut nearest_target = None;
+        for link in &self.full_state.frag_links {
+            if link.to != frag_idx || link.from == frag_idx {
+                continue;
+            }
+            let frag_a = &self.full_state.fragments[link.from];
+            // `num_rows` includes the leftover row, so the leftover row is at `num_rows - 1`
+            let leftover_row_y = self.config.row_height * (frag_a.num_rows() - 1) as f32;
+            let snap_pos = Pos2::from(frag_a.position) + Vec2::new(0.0, leftover_row_y);
+            let dist = (snap_pos - position).length();
+            if dist >= self.config.frag_snap_distance {
+                continue;
+            }
+            match nearest_target {
+                Some((_, _, best_dist)) if best_dist <= dist => {}
+                _ => nearest_target = Some((link.from, snap_pos, dist)),
+            }
+        }
+        nearest_target.map(|(frag_a, snap_pos, _dist)| (frag_a, snap_pos))
+    }
+
+    /// Returns the [`Action`] which changes the selected rows in response to the mouse (if the
+    /// selection has changed).  Pressing the mouse over a row selects that row, and dragging
+    /// extends the selection to the row under the cursor.  Shift-clicking extends the existing
+    /// selection to the clicked row, and clicking away from the rows clears the selection.
+    fn update_selection(
+        &self,
+        ctx: &egui::CtxRef,
+        canvas_response: &CanvasResponse,
+    ) -> Option<Action> {
+        let response = &canvas_response.inner;
+        let frag_hover = canvas_response.frag_hover.as_ref();
+        // Long presses move fragments rather than selecting rows
+        if self.frag_drag.is_some() {
+            return None;
+        }
+
+        if response.drag_started() && ctx.input().pointer.primary_down() {
+            let hovered_row = frag_hover.and_then(|h| self.selectable_row(h));
+            let new_selection = match (hovered_row, self.selection) {
+                (Some((frag_idx, row_idx)), Some(selection))
+                    if ctx.input().modifiers.shift && selection.frag_idx == frag_idx =>
+                {
+                    Some(RowSelection {
+                        head: row_idx,
+                        ..selection
+                    })
+                }
+                (Some((frag_idx, row_idx)), _) => Some(RowSelection::new(frag_idx, row_idx)),
+                (None, _) => None,
+            };
+            return Some(Action::SetSelection(new_selection));
+        }
+
+        if response.dragged_by(PointerButton::Primary) {
+            // Selections can't be dragged between fragments
+            let selection = self.selection?;
+            let frag_hover = frag_hover.filter(|h| h.frag_idx == selection.frag_idx)?;
+            // `num_rows` includes the leftover row, which can't be selected
+            let last_row = self.full_state.fragments[selection.frag_idx]
+                .num_rows()
+                .checked_sub(2)?;
+            let head = frag_hover.hovered_row_idx().clamp(0, last_row as isize) as usize;
+            if head != selection.head {
+                return Some(Action::SetSelection(Some(RowSelection {
+                    head,
+                    ..selection
+                })));
+            }
+        }
+        None
+    }
+
+    /// Returns the fragment and index of the hovered row, or `None` if the cursor isn't over a
+    /// row which can be selected (i.e. any non-leftover row)
+    fn selectable_row(&self, frag_hover: &FragHover) -> Option<(FragIdx, usize)> {
+        let fragment = &self.full_state.fragments[frag_hover.frag_idx];
+        let row_idx = usize::try_from(frag_hover.hovered_row_idx()).ok()?;
+        // `num_rows` includes the leftover row, so the leftover row is at `num_rows - 1`
+        (row_idx + 1 < fragment.num_rows()).then(|| (frag_hover.frag_idx, row_idx))
+    }
+
+    /// Returns the part which should be viewed after a given key is pressed (if that key changes
+    /// the part).  Shift and the number keys jump directly to a part (without shift, the number
+    /// keys highlight bells).
+    fn part_shortcut(&self, key: egui::Key, modifiers: egui::Modifiers) -> Option<PartIdx> {
+        use egui::Key::*;
+
+        if !modifiers.shift {
+            return None;
+        }
+        let number_keys = [Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9];
+        let part = number_keys.iter().position(|k| *k == key)?;
+        // Ignore number keys which don't correspond to a part
+        (part < self.full_state.part_heads.len()).then(|| PartIdx::new(part))
+    }
+
+    /// Returns the [`Action`] triggered by typing a bell's name (`1-9`, `0`, `E` or `T`), which
+    /// toggles the highlighting of that bell's path
+    fn bell_shortcut(&self, key: egui::Key, modifiers: egui::Modifiers) -> Option<Action> {
+        use egui::Key::*;
+
+        if modifiers.shift || modifiers.command || modifiers.alt {
+            return None;
+        }
+        let bell_keys = [
+            Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0, E, T,
+        ];
+        let bell_idx = bell_keys.iter().position(|k| *k == key)?;
+        // Ignore bells which aren't in the composition
+        if bell_idx >= self.full_state.stage.num_bells() {
+            return None;
+        }
+        let bell = Bell::from_index(bell_idx);
+        let new_bell = (self.highlighted_bell != Some(bell)).then(|| bell);
+        Some(Action::SetHighlightedBell(new_bell))
+    }
+
+    /// Returns the part after (or before, if `forwards` is `false`) the part being viewed, wrapping
+    /// round at either end
+    fn cycle_part(&self, forwards: bool) -> PartIdx {
+        let num_parts = self.full_state.part_heads.len();
+        let current_part = self.part_being_viewed().index();
+        let new_part = if forwards {
+            (current_part + 1) % num_parts
+        } else {
+            (current_part + num_parts - 1) % num_parts
+        };
+        PartIdx::new(new_part)
+    }
+
+    /// Returns the fragment after (or before, if `forwards` is `false`) the fragment which the
+    /// view was last moved to, wrapping round at either end.  Returns `None` if there are no
+    /// fragments.
+    fn cycle_frag(&self, forwards: bool) -> Option<FragIdx> {
+        let num_frags = self.full_state.fragments.len();
+        if num_frags == 0 {
+            return None;
+        }
+        let focused_frag = self.focused_frag.filter(|f| f.index() < num_frags);
+        let new_frag = match focused_frag {
+            Some(f) if forwards => (f.index() + 1) % num_frags,
+            Some(f) => (f.index() + num_frags - 1) % num_frags,
+            None if forwards => 0,
+            None => num_frags - 1,
+        };
+        Some(FragIdx::new(new_frag))
+    }
+
+    /// Returns the [`Action`] which moves the view to centre on a fragment, or `None` if that
+    /// fragment doesn't exist.  If the fragment is taller than the view, then its top is put at
+    /// the top of the view.
+    fn focus_fragment(&self, frag_idx: FragIdx, view_size: Vec2) -> Option<Action> {
+        let frag = self.full_state.fragments.get(frag_idx)?;
+        let frag_rect = canvas::frag_rect(&self.full_state, &self.config, frag);
+        let mut camera_pos = frag_rect.center() - view_size / 2.0;
+        camera_pos.y = camera_pos.y.min(frag_rect.min.y);
+        Some(Action::SetView {
+            camera_pos,
+            focused_frag: Some(frag_idx),
+        })
+    }
+
+    /// Move the camera so that a given row is in the centre of the canvas, viewing the part which
+    /// contains that row
+    fn go_to_row(&mut self, location: RowLocation) {
+        let frag = match self.full_state.fragments.get(location.frag_index) {
+            Some(frag) => frag,
+            None => return, // The fragment may have been deleted since the row was chosen
+        };
+        self.part_being_viewed = location.part_index;
+        // When all the parts are drawn, each part is drawn in its own column
+        let part_col = if self.config.show_all_parts {
+            location.part_index.index()
+        } else {
+            0
+        };
+        let row_offset = Vec2::new(
+            part_col as f32 * self.config.part_col_width(self.full_state.stage),
+            (location.row_index.index() as f32 + 0.5) * self.config.row_height,
+        );
+        self.camera_pos = Pos2::from(frag.position) + row_offset - self.canvas_size / 2.0;
+        self.focused_frag = Some(location.frag_index);
+    }
+
+    /// Returns the [`Action`] which moves the view to the centre of the bounding box of every
+    /// fragment, or `None` if there are no fragments
+    fn view_all_fragments(&self, view_size: Vec2) -> Option<Action> {
+        let bbox = self
+            .full_state
+            .fragments
+            .iter()
+            .map(|frag| canvas::frag_rect(&self.full_state, &self.config, frag))
+            .reduce(egui::Rect::union)?;
+        Some(Action::SetView {
+            camera_pos: bbox.center() - view_size / 2.0,
+            focused_frag: None,
+        })
+    }
+
+    /// Handle a keyboard key being pressed down, returning the [`Action`] of the [`Shortcut`]
+    /// which it triggers (if any)
     #[must_use]
     fn handle_key_press(
         &self,
         key: egui::Key,
         modifiers: egui::Modifiers,
         frag_hover: Option<&FragHover>,
-    ) -> Option<CompAction> {
-        use egui::Key::*;
+        cursor_pos: Option<Pos2>,
+        view_size: Vec2,
+    ) -> Option<Action> {
+        let chord = KeyChord::from_key_press(key, modifiers);
+        // If several shortcuts share the same key chord, then the first one which can be applied
+        // is used
+        self.config
+            .keymap
+            .shortcuts_for(chord)
+            .into_iter()
+            .find_map(|shortcut| self.apply_shortcut(shortcut, frag_hover, cursor_pos, view_size))
+    }
 
-        // z with any set of modifiers is undo
-        if key == Z && !modifiers.shift {
-            return Some(CompAction::UndoRedo(HistoryDirection::Undo));
-        }
-        // Z, y or Y with any set of modifiers is redo
-        if (key == Z && modifiers.shift) || key == Y {
-            return Some(CompAction::UndoRedo(HistoryDirection::Redo));
-        }
-
-        // Actions which apply to a fragment under the cursor
-        if let Some(frag_hover) = frag_hover {
-            let action = match (key, modifiers.shift) {
-                // d or D to delete the fragment under the cursor
-                (D, _) => Some(CompAction::DeleteFragment(frag_hover.frag_idx)),
-                // x to split the fragment at the nearest rule-off
-                (X, false) => self.split_fragment(frag_hover, FragSplitLocation::NearestRuleoff),
-                // X to split the hovered fragment at the cursor
-                (X, true) => self.split_fragment(frag_hover, FragSplitLocation::NearestRow),
-                // s to mute/unmute the fragment under the cursor
-                (S, false) => Some(CompAction::MuteFragment(frag_hover.frag_idx)),
-                // S to solo the fragment under the cursor
-                (S, true) => Some(CompAction::SoloFragment(frag_hover.frag_idx)),
-
-                // All other key presses are ignored
-                _ => None,
-            };
-            // Return if this keyboard shortcut corresponds to an action (this is basically the
-            // reverse of the `?` sigil).
-            if let Some(action) = action {
-                return Some(action);
+    /// Returns the [`Action`] triggered by a [`Shortcut`], or `None` if that [`Shortcut`] can't
+    /// be applied (e.g. because it requires a fragment but the cursor isn't over one).  `view_size`
+    /// is the size of the canvas, used to centre the view on fragments.
+    fn apply_shortcut(
+        &self,
+        shortcut: Shortcut,
+        frag_hover: Option<&FragHover>,
+        cursor_pos: Option<Pos2>,
+        view_size: Vec2,
+    ) -> Option<Action> {
+        let comp_action = match shortcut {
+            Shortcut::Undo => CompAction::UndoRedo(HistoryDirection::Undo),
+            Shortcut::Redo => CompAction::UndoRedo(HistoryDirection::Redo),
+            Shortcut::ReverseComp => CompAction::ReverseComp,
+            // Add a plain lead or course of the selected method under the cursor
+            Shortcut::AddLead | Shortcut::AddCourse => CompAction::AddFragment {
+                method_idx: self.selected_method,
+                is_course: shortcut == Shortcut::AddCourse,
+                position: cursor_pos?,
+            },
+            // Changing parts doesn't modify the composition
+            Shortcut::PreviousPart => return Some(Action::SetViewedPart(self.cycle_part(false))),
+            Shortcut::NextPart => return Some(Action::SetViewedPart(self.cycle_part(true))),
+            // Nor does moving the view
+            Shortcut::NextFragment => {
+                return self.focus_fragment(self.cycle_frag(true)?, view_size)
+            }
+            Shortcut::PreviousFragment => {
+                return self.focus_fragment(self.cycle_frag(false)?, view_size)
+            }
+            Shortcut::ViewFirstFragment => return self.focus_fragment(FragIdx::new(0), view_size),
+            Shortcut::ViewAllFragments => return self.view_all_fragments(view_size),
+            // Zooming only changes the config
+            Shortcut::ZoomIn | Shortcut::ZoomOut => {
+                let factor = if shortcut == Shortcut::ZoomIn {
+                    ZOOM_FACTOR
+                } else {
+                    1.0 / ZOOM_FACTOR
+                };
+                let density = self.config.row_density().zoomed(factor);
+                return Some(Action::SetRowDensity(density));
+            }
+            Shortcut::ResetZoom => return Some(Action::SetRowDensity(RowDensity::DEFAULT)),
+            Shortcut::FindRow => {
+                let search = self.row_search.clone().unwrap_or_default();
+                return Some(Action::SetRowSearch(Some(search)));
+            }
+
+            // Actions which apply to the selected rows
+            Shortcut::ClearSelection => return self.selection.map(|_| Action::SetSelection(None)),
+            Shortcut::DeleteRows => {
+                let selection = self.selection?;
+                CompAction::DeleteRows {
+                    frag_idx: selection.frag_idx,
+                    start: selection.start(),
+                    end: selection.end(),
+                }
+            }
+            Shortcut::IsolateRows => self.isolate_rows(self.selection?),
+            Shortcut::SetRowsMethod => {
+                let selection = self.selection?;
+                CompAction::SetRowsMethod {
+                    frag_idx: selection.frag_idx,
+                    start: selection.start(),
+                    end: selection.end(),
+                    method_idx: self.selected_method,
+                }
+            }
+
+            // Actions which apply to a fragment under the cursor
+            Shortcut::DeleteFragment => CompAction::DeleteFragment(frag_hover?.frag_idx),
+            Shortcut::SplitAtRuleoff => {
+                self.split_fragment(frag_hover?, FragSplitLocation::NearestRuleoff)?
+            }
+            Shortcut::SplitAtCursor => {
+                self.split_fragment(frag_hover?, FragSplitLocation::NearestRow)?
+            }
+            Shortcut::MuteFragment => CompAction::MuteFragment(frag_hover?.frag_idx),
+            Shortcut::SoloFragment => CompAction::SoloFragment(frag_hover?.frag_idx),
+            Shortcut::ToggleBluelines => CompAction::ToggleBluelines(frag_hover?.frag_idx),
+            // Extend the fragment under the cursor by a plain lead or course of the selected
+            // method
+            Shortcut::ExtendByLead | Shortcut::ExtendByCourse => CompAction::ExtendFragment {
+                frag_idx: frag_hover?.frag_idx,
+                method_idx: self.selected_method,
+                by_course: shortcut == Shortcut::ExtendByCourse,
+            },
+            Shortcut::DuplicateIntoPart => self.duplicate_into_part(frag_hover?),
+            Shortcut::JoinFragments => self.join_fragments(frag_hover?)?,
+            Shortcut::RotateComp => self.rotate_comp(frag_hover?)?,
+            Shortcut::RotateFragment => self.rotate_fragment(frag_hover?)?,
+            Shortcut::EditFragmentStart => {
+                let frag_idx = frag_hover?.frag_idx;
+                let frag_start = self.history.comp_spec().fragment_start(frag_idx).ok()?;
+                return Some(Action::SetStartEdit(Some(StartEdit::new(
+                    frag_idx,
+                    &frag_start,
+                ))));
+            }
+            Shortcut::ToggleBob => self.set_call(frag_hover?, Some('-'))?,
+            Shortcut::ToggleSingle => self.set_call(frag_hover?, Some('s'))?,
+            Shortcut::MakePlain => self.set_call(frag_hover?, None)?,
+        };
+        Some(Action::Comp(comp_action))
+    }
+
+    /// Bind the first key pressed this frame (if any) to a [`Shortcut`].  Pressing escape cancels
+    /// the rebinding.
+    fn rebind_shortcut(
+        &self,
+        ctx: &egui::CtxRef,
+        shortcut: Shortcut,
+        replace: bool,
+        mut push_action: impl FnMut(Action),
+    ) {
+        for evt in &ctx.input().events {
+            if let egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+            } = *evt
+            {
+                if key != egui::Key::Escape {
+                    let chord = KeyChord::from_key_press(key, modifiers);
+                    let mut chords = if replace {
+                        Vec::new()
+                    } else {
+                        self.config.keymap.chords(shortcut)
+                    };
+                    if !chords.contains(&chord) {
+                        chords.push(chord);
+                    }
+                    push_action(Action::RebindShortcut(shortcut, Some(chords)));
+                }
+                push_action(Action::SetKeymapEdit(Some(KeymapEdit::default())));
+                return;
             }
         }
+    }
 
-        None
+    /// Copy a fragment to the clipboard, so that it can be pasted with
+    /// [`CompAction::PasteFragment`]
+    fn copy_fragment(
+        &self,
+        ctx: &egui::CtxRef,
+        frag_idx: FragIdx,
+        mut push_action: impl FnMut(Action),
+    ) {
+        match self.history.comp_spec().fragment_to_json(frag_idx) {
+            Ok(json) => ctx.output().copied_text = json,
+            Err(e) => push_action(Action::ShowError(format!("Couldn't copy: {}", e))),
+        }
+    }
+
+    /// Returns `true` if the cursor is over the leftover row of the hovered fragment
+    fn is_leftover_row(&self, frag_hover: &FragHover) -> bool {
+        let fragment = &self.full_state.fragments[frag_hover.frag_idx];
+        // `num_rows` includes the leftover row, so the leftover row is at `num_rows - 1`
+        frag_hover.hovered_row_idx() == fragment.num_rows() as isize - 1
+    }
+
+    /// Append a lead of the method whose shorthand is `text` to the hovered fragment.  Text
+    /// which isn't the shorthand of any method is ignored.
+    fn append_lead(&self, frag_hover: &FragHover, text: &str) -> Option<CompAction> {
+        let is_shorthand = self
+            .full_state
+            .methods
+            .iter()
+            .any(|m| m.shorthand() == text);
+        is_shorthand.then(|| CompAction::AppendLead {
+            frag_idx: frag_hover.frag_idx,
+            shorthand: text.to_owned(),
+        })
+    }
+
+    fn rotate_comp(&self, frag_hover: &FragHover) -> Option<CompAction> {
+        let fragment = &self.full_state.fragments[frag_hover.frag_idx];
+        let (row_idx, _dist) = fragment
+            .nearest_ruleoff_to(frag_hover.row_idx_float)
+            .filter(|(_idx, dist)| *dist < self.config.ruleoff_snap_distance)?;
+        Some(CompAction::RotateComp {
+            frag_idx: frag_hover.frag_idx,
+            row_idx: row_idx.index() as isize,
+        })
+    }
+
+    /// Rotate the hovered fragment so that it starts at the nearest rule-off to the cursor
+    fn rotate_fragment(&self, frag_hover: &FragHover) -> Option<CompAction> {
+        let fragment = &self.full_state.fragments[frag_hover.frag_idx];
+        let (row_idx, _dist) = fragment
+            .nearest_ruleoff_to(frag_hover.row_idx_float)
+            .filter(|(_idx, dist)| *dist < self.config.ruleoff_snap_distance)?;
+        Some(CompAction::RotateFragment {
+            frag_idx: frag_hover.frag_idx,
+            by_rows: row_idx.index() as isize,
+        })
+    }
+
+    /// Duplicate the hovered fragment, transposing the copy by the part head of the part being
+    /// viewed.  The copy is placed to the right of the original, clear of any other parts which
+    /// are drawn alongside it.
+    fn duplicate_into_part(&self, frag_hover: &FragHover) -> CompAction {
+        let fragment = &self.full_state.fragments[frag_hover.frag_idx];
+        let part_head = &self.full_state.part_heads.rows()[self.part_being_viewed().index()];
+        let num_cols_drawn = if self.config.show_all_parts {
+            self.full_state.part_heads.len()
+        } else {
+            1
+        };
+        let col_width = self.config.part_col_width(self.full_state.stage);
+        CompAction::DuplicateTransposed {
+            frag_idx: frag_hover.frag_idx,
+            transposition: part_head.clone(),
+            position: Pos2::from(fragment.position)
+                + Vec2::RIGHT * col_width * num_cols_drawn as f32,
+        }
+    }
+
+    /// Join the hovered fragment onto the end of the fragment whose leftover row is closest to
+    /// the hovered fragment's first row
+    fn join_fragments(&self, frag_hover: &FragHover) -> Option<CompAction> {
+        let frag_b = frag_hover.frag_idx;
+        let frag_b_pos = Pos2::from(self.full_state.fragments[frag_b].position);
+
+        let mut nearest_frag = None;
+        for (frag_idx, frag) in self.full_state.fragments.iter_enumerated() {
+            if frag_idx == frag_b {
+                continue; // Fragments can't be joined to themselves
+            }
+            // `num_rows` includes the leftover row, so the leftover row is at `num_rows - 1`
+            let leftover_row_y = self.config.row_height * (frag.num_rows() - 1) as f32;
+            let leftover_row_pos = Pos2::from(frag.position) + Vec2::new(0.0, leftover_row_y);
+            let dist = (leftover_row_pos - frag_b_pos).length();
+            match nearest_frag {
+                Some((_, best_dist)) if best_dist <= dist => {}
+                _ => nearest_frag = Some((frag_idx, dist)),
+            }
+        }
+        let (frag_a, _dist) = nearest_frag?;
+        Some(CompAction::JoinFragments { frag_a, frag_b })
+    }
+
+    /// Set the call at the nearest rule-off to the cursor to the
+    /// [`Call`](jigsaw_comp::full::Call) with a given `symbol` (or make it plain if `symbol` is
+    /// `None`).  Setting a call which is already there will remove it, so the same key press can
+    /// be used to toggle a call.
+    fn set_call(&self, frag_hover: &FragHover, symbol: Option<char>) -> Option<CompAction> {
+        let fragment = &self.full_state.fragments[frag_hover.frag_idx];
+        let (row_idx, _dist) = fragment
+            .nearest_ruleoff_to(frag_hover.row_idx_float)
+            .filter(|(_idx, dist)| *dist < self.config.ruleoff_snap_distance)?;
+        let call_idx = match symbol {
+            Some(symbol) => {
+                // Ignore the key press if no calls with this symbol can be made here.  Calls made
+                // at different places in the lead (e.g. lead end and half-lead bobs) can share a
+                // symbol, since only one of them will fit.
+                let (call_idx, call) =
+                    self.full_state.calls.iter_enumerated().find(|(_, call)| {
+                        call.symbol() == symbol && fragment.can_end_call_at(call, row_idx)
+                    })?;
+                let is_already_called = fragment
+                    .call_ending_at(row_idx)
+                    .is_some_and(|c| Rc::ptr_eq(c, call));
+                (!is_already_called).then(|| call_idx)
+            }
+            None => None,
+        };
+        Some(CompAction::SetCall {
+            frag_idx: frag_hover.frag_idx,
+            row_idx: row_idx.index() as isize,
+            call_idx,
+        })
+    }
+
+    /// Split the selected rows into a fragment of their own, by splitting their fragment at both
+    /// ends of the selection
+    fn isolate_rows(&self, selection: RowSelection) -> CompAction {
+        let fragment = &self.full_state.fragments[selection.frag_idx];
+        let (start, end) = (selection.start(), selection.end());
+        // Leave a gap of `split_height` rows either side of the selected rows
+        let split_height = self.config.split_height;
+        let pos_of_rows = Pos2::from(fragment.position)
+            + Vec2::DOWN * self.config.row_height * (start as f32 + split_height);
+        let pos_of_rows_after = Pos2::from(fragment.position)
+            + Vec2::DOWN * self.config.row_height * (end as f32 + split_height * 2.0);
+        CompAction::IsolateRows {
+            frag_idx: selection.frag_idx,
+            start,
+            end,
+            pos_of_rows,
+            pos_of_rows_after,
+        }
     }
 
     fn split_fragment(
@@ -211,7 +1311,7 @@ impl JigsawApp {
             FragSplitLocation::NearestRow => frag_hover.nearest_row_boundary(),
         };
         // Compute the position of the new fragment
-        let pos_of_new_frag = fragment.position
+        let pos_of_new_frag = Pos2::from(fragment.position)
             + Vec2::DOWN * self.config.row_height * (split_index as f32 + self.config.split_height);
         Some(CompAction::SplitFragment {
             frag_idx: frag_hover.frag_idx,
@@ -226,19 +1326,456 @@ impl JigsawApp {
 /////////////
 
 impl JigsawApp {
-    fn apply_action(&mut self, action: Action) {
+    fn apply_action(&mut self, action: Action, time: f64) {
         match action {
             Action::PanView(delta) => self.camera_pos += delta,
+            Action::SetView {
+                camera_pos,
+                focused_frag,
+            } => {
+                self.camera_pos = camera_pos;
+                self.focused_frag = focused_frag;
+            }
+            Action::GoToRow(location) => self.go_to_row(location),
             Action::SetPartHeadString(new_part_head_str) => self.part_head_str = new_part_head_str,
-            Action::Comp(comp_action) => {
-                if let Err(e) = self.apply_comp_action(comp_action) {
-                    println!("EDIT ERROR: {:?}", e);
+            Action::SetFilePath(new_file_path) => self.file_path = new_file_path,
+            Action::SetMethodEdit(new_method_edit) => self.method_edit = new_method_edit,
+            Action::SetCallEdit(new_call_edit) => self.call_edit = new_call_edit,
+            Action::SetMusicEdit(new_music_edit) => self.music_edit = new_music_edit,
+            Action::SetKeymapEdit(new_keymap_edit) => self.keymap_edit = new_keymap_edit,
+            Action::SetStartEdit(new_start_edit) => self.start_edit = new_start_edit,
+            Action::SetProofEdit(new_proof_edit) => self.proof_edit = new_proof_edit,
+            Action::RebindShortcut(shortcut, chords) => self.config.keymap.rebind(shortcut, chords),
+            Action::SelectMethod(method_idx) => self.selected_method = method_idx,
+            Action::SetSelection(selection) => self.selection = selection,
+            Action::SetFragDrag(frag_drag) => self.frag_drag = frag_drag,
+            Action::SetJoinOffer(join_offer) => self.join_offer = join_offer,
+            Action::SetViewedPart(part_idx) => self.part_being_viewed = part_idx,
+            Action::SetHandbellPair(pair) => self.handbell_pair = pair,
+            Action::SetHighlightedBell(bell) => self.highlighted_bell = bell,
+            Action::SetHighlightedBellLine(draw_line) => {
+                self.config.highlighted_bell_line = draw_line
+            }
+            Action::SetTheme(theme) => self.config.theme = theme,
+            Action::SetRowDensity(density) => self.config.set_row_density(density),
+            Action::SetHighlightColors(colors) => self.config.highlight_colors = colors,
+            Action::SetHatchHighlights(hatch) => self.config.hatch_highlights = hatch,
+            Action::SetAllowMidLeadSplices(allow) => self.config.allow_mid_lead_splices = allow,
+            Action::SetTenorsTogetherFchs(tenors_together) => {
+                self.tenors_together_fchs = tenors_together
+            }
+            Action::SetShowAllParts(show_all_parts) => self.config.show_all_parts = show_all_parts,
+            Action::SetShowBluelines(show_bluelines) => self.config.show_bluelines = show_bluelines,
+            Action::SetCourseView(course_view) => self.config.course_view = course_view,
+            Action::SetShowPlaceBells(show_place_bells) => {
+                self.config.show_place_bells = show_place_bells
+            }
+            Action::SetShowMinimap(show_minimap) => self.config.show_minimap = show_minimap,
+            Action::SetComposeByKeyboard(compose_by_keyboard) => {
+                self.config.compose_by_keyboard = compose_by_keyboard
+            }
+            Action::SetNewComp(new_comp) => self.new_comp = new_comp,
+            Action::SetTouchSearch(search) => {
+                // Closing the window (or switching between searching for touches and joins) also
+                // stops its search
+                let join = |s: &Option<TouchSearch>| s.as_ref().map(|s| s.join);
+                if join(&search) != join(&self.touch_search) {
+                    self.running_search = None;
+                }
+                self.touch_search = search;
+            }
+            Action::SearchTouches { params, join } => {
+                let spec = self.history.comp_spec();
+                let search = match join {
+                    Some((frag_a, frag_b)) => spec.start_join_search(frag_a, frag_b, params),
+                    None => spec.start_search(params),
+                };
+                match search {
+                    Ok(search) => {
+                        if let Some(touch_search) = &mut self.touch_search {
+                            touch_search.results = Some(Vec::new());
+                        }
+                        self.running_search = Some(search);
+                    }
+                    Err(e) => self.toasts.error(format!("Couldn't search: {}", e), time),
+                }
+            }
+            Action::CancelSearch => self.running_search = None,
+            Action::SetCollabWindow(window) => self.collab_window = window,
+            Action::HostSession(port) => match Session::host(port) {
+                Ok(session) => {
+                    self.toasts.success(session.status(), time);
+                    self.session = Some(session);
+                }
+                Err(e) => self
+                    .toasts
+                    .error(format!("Couldn't host session: {}", e), time),
+            },
+            Action::JoinSession(code) => match Session::join(&code) {
+                Ok(session) => self.session = Some(session),
+                Err(e) => self
+                    .toasts
+                    .error(format!("Couldn't join session: {}", e), time),
+            },
+            Action::LeaveSession => self.session = None,
+            Action::SetPlaybackOpen(is_open) => {
+                self.playback = is_open.then(|| {
+                    // Start by playing the part being viewed, so that the cursor is visible
+                    let part = self.part_being_viewed();
+                    let part = (self.full_state.part_heads.len() > 1).then(|| part);
+                    Playback::new(&self.full_state, part, self.config.pace)
+                })
+            }
+            Action::SetRowSearch(search) => self.row_search = search,
+            Action::ShowError(text) => self.toasts.error(text, time),
+            Action::SetPlaying(is_playing) => {
+                if let Some(playback) = &mut self.playback {
+                    if let Err(e) = playback.set_playing(is_playing, &self.config) {
+                        self.toasts.error(e, time);
+                    }
+                }
+            }
+            Action::SeekPlayback(row) => {
+                if let Some(playback) = &mut self.playback {
+                    playback.seek(row);
+                }
+            }
+            Action::SetPlaybackPart(part) => {
+                if let Some(playback) = &mut self.playback {
+                    playback.set_part(part, &self.full_state);
+                }
+                if let Some(part) = part {
+                    self.part_being_viewed = part;
                 }
             }
+            Action::SetPace(pace) => {
+                self.config.pace = pace;
+                if let Some(playback) = &mut self.playback {
+                    playback.set_pace(pace);
+                }
+            }
+            #[cfg(feature = "audio")]
+            Action::SetSoundOutput(output) => {
+                self.config.sound_output = output;
+                // Switch the output of any running playback
+                if let Some(playback) = &mut self.playback {
+                    if playback.is_playing() {
+                        if let Err(e) = playback.set_playing(true, &self.config) {
+                            self.toasts.error(e, time);
+                        }
+                    }
+                }
+            }
+            Action::NewComp(stage) => self.load_spec(CompSpec::empty(stage)),
+            Action::Save => match self.save_file() {
+                Ok(()) => {
+                    self.has_unsaved_changes = false;
+                    let text = format!("Saved to {}", self.file_path);
+                    self.toasts.success(text, time);
+                }
+                Err(e) => self.toasts.error(format!("Couldn't save: {}", e), time),
+            },
+            Action::Open => {
+                if let Err(e) = self.open_file() {
+                    let text = format!("Couldn't open {}: {}", self.file_path, e);
+                    self.toasts.error(text, time);
+                }
+            }
+            Action::ImportRows => match self.import_rows() {
+                Ok(()) => {
+                    let text = format!("Imported rows from {}", self.file_path);
+                    self.toasts.success(text, time);
+                }
+                Err(e) => self
+                    .toasts
+                    .error(format!("Couldn't import rows: {}", e), time),
+            },
+            Action::SetExportRows(options) => self.export_rows = options,
+            Action::ExportRows(options) => {
+                let rows = self.full_state.export_rows(&options);
+                self.write_export(rows, time);
+            }
+            Action::SetHtmlExport(export) => self.html_export = export,
+            Action::ExportHtml(export) => {
+                let html = self.full_state.export_html(&export.title, &export.composer);
+                self.write_export(html, time);
+            }
+            Action::ExportRowTimings => {
+                let part = self.playback.as_ref().and_then(Playback::part);
+                let timings = self.full_state.export_row_timings(part, &self.config.pace);
+                self.write_export(timings, time);
+            }
+            Action::ExportSvg => {
+                let part = self.part_being_viewed();
+                let svg = svg_export::render(&self.full_state, &self.config, part);
+                self.write_export(svg, time);
+            }
+            Action::LoadMethodLib => match self.load_method_lib() {
+                Ok(num_methods) => {
+                    let text = format!("Loaded {} methods from {}", num_methods, self.file_path);
+                    self.toasts.success(text, time);
+                }
+                Err(e) => {
+                    let text = format!("Couldn't load method library: {}", e);
+                    self.toasts.error(text, time);
+                }
+            },
+            Action::ExtendComp(stage) => match self.extend_comp(stage) {
+                Ok(()) => {
+                    let text = format!("Extended to {} bells", stage.num_bells());
+                    self.toasts.success(text, time);
+                }
+                Err(problems) => {
+                    for problem in problems {
+                        let text = format!("Couldn't extend composition: {}", problem);
+                        self.toasts.error(text, time);
+                    }
+                }
+            },
+            Action::Comp(comp_action) => match self.apply_comp_action(comp_action, time) {
+                Ok(()) => {
+                    self.has_unsaved_changes = true;
+                    if let Some(session) = &mut self.session {
+                        session.send_edit(self.history.comp_spec());
+                    }
+                }
+                Err(e) => self.toasts.error(e.to_string(), time),
+            },
         }
     }
 
-    fn apply_comp_action(&mut self, action: CompAction) -> Result<(), ActionError> {
+    /// Save the current composition to `self.file_path`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_file(&mut self) -> std::io::Result<()> {
+        std::fs::write(&self.file_path, self.history.comp_spec().to_json())
+    }
+
+    /// Save the current composition to the browser's storage, in the slot named by
+    /// `self.file_path`
+    #[cfg(target_arch = "wasm32")]
+    fn save_file(&mut self) -> std::io::Result<()> {
+        let json = self.history.comp_spec().to_json();
+        self.browser_storage.set(&self.file_path, json);
+        Ok(())
+    }
+
+    /// Reads the JSON of the composition stored at `self.file_path`.  On the web build, this reads
+    /// from the browser's storage instead of the file system.
+    fn read_comp_json(&self) -> Result<String, FileError> {
+        #[cfg(target_arch = "wasm32")]
+        let json = match self.browser_storage.get(&self.file_path) {
+            Some(json) => Ok(json.to_owned()),
+            None => Err(std::io::Error::from(std::io::ErrorKind::NotFound)),
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let json = std::fs::read_to_string(&self.file_path);
+        json.map_err(FileError::Io)
+    }
+
+    /// Replace the current composition with one loaded from `self.file_path`.  This resets the
+    /// undo history.
+    fn open_file(&mut self) -> Result<(), FileError> {
+        let json = self.read_comp_json()?;
+        let spec = CompSpec::from_json(&json).map_err(FileError::Load)?;
+        self.load_spec(spec);
+        Ok(())
+    }
+
+    /// Replace the current composition with one generated from the list of [`Row`]s stored in
+    /// `self.file_path`.  The [`Row`]s are matched against the methods of the current
+    /// composition.  This resets the undo history.
+    fn import_rows(&mut self) -> Result<(), FileError> {
+        let rows = std::fs::read_to_string(&self.file_path).map_err(FileError::Io)?;
+        let spec = self
+            .history
+            .comp_spec()
+            .import_rows(&rows)
+            .map_err(FileError::Import)?;
+        self.load_spec(spec);
+        Ok(())
+    }
+
+    /// Try a few more leads of the running touch search (if any), copying the touches found so far
+    /// into the touch search window
+    fn step_running_search(&mut self, ctx: &egui::CtxRef) {
+        let search = match &mut self.running_search {
+            Some(search) => search,
+            None => return,
+        };
+        let is_finished = search.step(SEARCH_LEADS_PER_FRAME);
+        if let Some(touch_search) = &mut self.touch_search {
+            touch_search.results = Some(search.touches().to_vec());
+        }
+        if is_finished {
+            let text = format!("Found {} touches", search.touches().len());
+            self.toasts.success(text, ctx.input().time);
+            self.running_search = None;
+        } else {
+            // Keep drawing frames until the search finishes, even if the user isn't doing anything
+            ctx.request_repaint();
+        }
+    }
+
+    /// Set the size of the canvas's text to the size in the config.  Changing the fonts makes
+    /// egui rebuild its font atlas, so this is only done when the size actually changes.
+    fn update_font_size(&self, ctx: &egui::CtxRef) {
+        let current_size = ctx
+            .fonts()
+            .definitions()
+            .family_and_size
+            .get(&canvas::TEXT_STYLE)
+            .map(|(_family, size)| *size);
+        if current_size == Some(self.config.font_size) {
+            return;
+        }
+        let mut fonts = ctx.fonts().definitions().clone();
+        if let Some((_family, size)) = fonts.family_and_size.get_mut(&canvas::TEXT_STYLE) {
+            *size = self.config.font_size;
+        }
+        ctx.set_fonts(fonts);
+    }
+
+    /// Move playback (if any) forward to the current time, sounding the bells which are passed
+    fn step_playback(&mut self, ctx: &egui::CtxRef) {
+        if let Some(playback) = &mut self.playback {
+            if playback.is_playing() {
+                let dt = ctx.input().unstable_dt as f64;
+                playback.step(dt, &self.full_state);
+                // Keep drawing frames so that the bells are struck on time
+                ctx.request_repaint();
+            }
+        }
+    }
+
+    /// Handle any edits or other events from the collaboration session (if any)
+    fn poll_session(&mut self, ctx: &egui::CtxRef) {
+        let session = match &mut self.session {
+            Some(session) => session,
+            None => return,
+        };
+        let time = ctx.input().time;
+        for event in session.poll(self.history.comp_spec()) {
+            match event {
+                collab::Event::RemoteEdit(spec) => {
+                    let action = CompAction::RemoteEdit(Box::new(spec));
+                    match self.apply_comp_action(action, time) {
+                        Ok(()) => self.has_unsaved_changes = true,
+                        Err(e) => self.toasts.error(e.to_string(), time),
+                    }
+                }
+                collab::Event::EditRejected => self.toasts.error(
+                    "Your edit conflicted with a collaborator's, and was undone".to_owned(),
+                    time,
+                ),
+                collab::Event::Joined(name) => {
+                    self.toasts.success(format!("{} joined", name), time)
+                }
+                collab::Event::Left(name) => self.toasts.error(format!("{} left", name), time),
+                collab::Event::Refused(name) => self.toasts.error(
+                    format!("Refused {}, who didn't give the session code", name),
+                    time,
+                ),
+                collab::Event::Disconnected(e) => {
+                    self.toasts
+                        .error(format!("Disconnected from session: {}", e), time);
+                    self.session = None;
+                    break;
+                }
+                collab::Event::InvalidMessage(e) => self
+                    .toasts
+                    .error(format!("Invalid edit from collaborator: {}", e), time),
+            }
+        }
+        // Keep checking for edits, even if the user isn't doing anything
+        if self.session.is_some() {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Write the contents of an export to `self.file_path`, telling the user whether or not it
+    /// succeeded
+    fn write_export(&mut self, contents: String, time: f64) {
+        match std::fs::write(&self.file_path, contents) {
+            Ok(()) => {
+                let text = format!("Exported to {}", self.file_path);
+                self.toasts.success(text, time);
+            }
+            Err(e) => self.toasts.error(format!("Couldn't export: {}", e), time),
+        }
+    }
+
+    /// Load a [`MethodLib`] from `self.file_path`, returning the number of methods loaded.  Files
+    /// ending in `.xml` are parsed as CCCBR method collections, and all other files are parsed as
+    /// MicroSIRIL libraries (where every method is assumed to have the same stage as the
+    /// composition).
+    fn load_method_lib(&mut self) -> Result<usize, FileError> {
+        let text = std::fs::read_to_string(&self.file_path).map_err(FileError::Io)?;
+        let lib = if self.file_path.to_lowercase().ends_with(".xml") {
+            MethodLib::from_cccbr_xml(&text)
+        } else {
+            MethodLib::from_microsiril(&text, self.full_state.stage)
+        };
+        let lib = lib.map_err(FileError::MethodLib)?;
+        let num_methods = lib.len();
+        self.method_lib = Some(lib);
+        Ok(num_methods)
+    }
+
+    /// Replace the current composition with its extension to a larger
+    /// [`Stage`](bellframe::Stage), using the loaded method library.  This resets the undo
+    /// history.
+    fn extend_comp(&mut self, stage: bellframe::Stage) -> Result<(), Vec<String>> {
+        let method_lib = match &self.method_lib {
+            Some(lib) => lib,
+            None => return Err(vec!["Load a method library first".to_owned()]),
+        };
+        match self
+            .history
+            .comp_spec()
+            .extended_to_stage(stage, method_lib)
+        {
+            Ok(spec) => {
+                self.load_spec(spec);
+                Ok(())
+            }
+            Err(problems) => Err(problems.iter().map(ToString::to_string).collect()),
+        }
+    }
+
+    /// Replace the current composition with a new [`CompSpec`], resetting the undo history
+    fn load_spec(&mut self, spec: CompSpec) {
+        self.full_state = FullState::new(&spec);
+        self.part_head_str = self.full_state.part_heads.spec_string();
+        self.history = History::with_config(spec, self.config.history);
+        self.has_unsaved_changes = false;
+        // Any state referring to the old composition's fragments or methods is now meaningless
+        self.selected_method = MethodIdx::new(0);
+        self.selection = None;
+        self.start_edit = None;
+        self.proof_edit = None;
+        self.touch_search = None;
+        self.running_search = None;
+        self.playback = None;
+        self.frag_drag = None;
+        if let Some(search) = &mut self.row_search {
+            search.update_matches(&self.full_state);
+        }
+        self.join_offer = None;
+        self.part_being_viewed = PartIdx::new(0);
+        // Share the new composition with any collaborators
+        if let Some(session) = &mut self.session {
+            session.send_edit(self.history.comp_spec());
+        }
+    }
+
+    /// Apply a [`CompAction`] which was generated at a given `time` (in seconds)
+    fn apply_comp_action(&mut self, action: CompAction, time: f64) -> Result<(), ActionError> {
+        let desc = self.describe_comp_action(&action);
+        // Any edit except the fragment moves which are part of a drag makes the join offer stale
+        let is_move = matches!(action, CompAction::MoveFragment { .. });
+        // Set to `true` if we moved through the undo history, and therefore might have changed
+        // the part heads
+        let mut moved_through_history = false;
         match action {
             CompAction::UndoRedo(direction) => {
                 let was_successful = match direction {
@@ -249,64 +1786,712 @@ impl JigsawApp {
                     // Abort with an error if the undo wasn't possible
                     return Err(ActionError::NoSteps(direction));
                 }
-                // Update the part head box, since we have potentially changed the part heads.  If
-                // we don't do this, then the code will notice that the contents of the part head
-                // box is different to the current part heads, and promptly creates a new undo step
-                // to change them.
-                //
-                // TODO: Don't update the box if the user is part-way through editing it?
-                self.part_head_str = self.full_state.part_heads.spec_string();
+                moved_through_history = true;
+            }
+            CompAction::JumpToStep(index) => {
+                if !self.history.jump_to(index) {
+                    return Err(ActionError::InvalidStep(index));
+                }
+                moved_through_history = true;
             }
             CompAction::SetPartHeads(new_part_heads) => {
+                // Every keystroke in the part head box can change the part heads, so group them
+                // together to avoid filling the undo history with every intermediate value
                 self.history
-                    .apply_infallible_edit(|spec| spec.set_part_heads(new_part_heads));
+                    .apply_grouped_edit("part heads", time, desc, |spec| {
+                        spec.set_part_heads(new_part_heads);
+                        Ok::<_, ActionError>(())
+                    })?;
             }
-            CompAction::SoloFragment(frag_idx) => {
-                self.history.apply_edit(|spec| spec.solo_frag(frag_idx))?
+            CompAction::SetProofSettings(settings) => self
+                .history
+                .apply_infallible_edit(desc, |spec| spec.set_proof_settings(settings)),
+            CompAction::SetObservationBell(bell) => self
+                .history
+                .apply_edit(desc, |spec| spec.set_observation_bell(bell))?,
+            CompAction::SetLengthTarget(target) => {
+                // Dragging the bounds changes the target every frame, so group these edits
+                self.history
+                    .apply_grouped_edit("length target", time, desc, |spec| {
+                        spec.set_length_target(target)
+                    })?;
             }
-            CompAction::MuteFragment(frag_idx) => self
+            CompAction::SoloFragment(frag_idx) => self
                 .history
-                .apply_frag_edit(frag_idx, |frag| frag.toggle_mute())?,
+                .apply_edit(desc, |spec| spec.solo_frag(frag_idx))?,
+            CompAction::MuteFragment(frag_idx) => {
+                self.history
+                    .apply_frag_edit(desc, frag_idx, |frag| frag.toggle_mute())?
+            }
+            CompAction::ToggleBluelines(frag_idx) => {
+                self.history
+                    .apply_frag_edit(desc, frag_idx, |frag| frag.toggle_bluelines())?
+            }
+            CompAction::AddFragment {
+                method_idx,
+                is_course,
+                position,
+            } => {
+                self.history.apply_edit(desc, |spec| {
+                    spec.add_fragment(method_idx, is_course, position.into())
+                })?;
+            }
+            CompAction::RemoteEdit(spec) => {
+                self.history.apply_edit(desc, |s| {
+                    *s = *spec;
+                    Ok::<_, ActionError>(())
+                })?;
+                // Collaborators can change anything, including the part heads
+                moved_through_history = true;
+            }
+            CompAction::AddTouch { touch, position } => {
+                self.history
+                    .apply_edit(desc, |spec| spec.add_touch(&touch, position.into()))?;
+            }
+            CompAction::JoinWithTouch {
+                frag_a,
+                frag_b,
+                touch,
+            } => self
+                .history
+                .apply_edit(desc, |spec| spec.join_with_touch(frag_a, frag_b, &touch))?,
+            CompAction::ExtendFragment {
+                frag_idx,
+                method_idx,
+                by_course,
+            } => self.history.apply_edit(desc, |spec| {
+                spec.extend_fragment(frag_idx, method_idx, by_course)
+            })?,
+            CompAction::AppendLead {
+                frag_idx,
+                shorthand,
+            } => self
+                .history
+                .apply_edit(desc, |spec| spec.append_lead(frag_idx, &shorthand))?,
+            CompAction::MoveFragment { frag_idx, position } => {
+                // Every frame of a drag moves the fragment, so group them together to give one
+                // undo step per drag
+                self.history
+                    .apply_grouped_edit("move fragment", time, desc, |spec| {
+                        spec.move_fragment(frag_idx, position.into())
+                    })?;
+            }
+            CompAction::PasteFragment { json, position } => {
+                self.history.apply_edit(desc, |spec| {
+                    spec.paste_fragment(&json, position.into())
+                        .map_err(ActionError::Paste)
+                })?;
+            }
+            CompAction::DuplicateTransposed {
+                frag_idx,
+                transposition,
+                position,
+            } => {
+                self.history.apply_edit(desc, |spec| {
+                    spec.duplicate_transposed(frag_idx, &transposition, position.into())
+                })?;
+            }
             CompAction::DeleteFragment(frag_idx) => self
                 .history
-                .apply_edit(|spec| spec.delete_fragment(frag_idx))?,
+                .apply_edit(desc, |spec| spec.delete_fragment(frag_idx))?,
             CompAction::SplitFragment {
                 frag_idx,
                 split_index,
                 pos_of_new_frag,
+            } => self.history.apply_edit(desc, |spec| {
+                spec.split_fragment(frag_idx, split_index, pos_of_new_frag.into())
+            })?,
+            CompAction::DeleteRows {
+                frag_idx,
+                start,
+                end,
+            } => {
+                self.history
+                    .apply_edit(desc, |spec| spec.delete_rows(frag_idx, start, end))?;
+                // The selected rows no longer exist
+                self.selection = None;
+            }
+            CompAction::IsolateRows {
+                frag_idx,
+                start,
+                end,
+                pos_of_rows,
+                pos_of_rows_after,
+            } => {
+                // `num_rows` includes the leftover row
+                let frag_len = self
+                    .full_state
+                    .fragments
+                    .get(frag_idx)
+                    .map_or(0, |f| f.num_rows() - 1);
+                self.history.apply_edit(desc, |spec| {
+                    // Split off the rows after the selection first, so that `start` still refers
+                    // to the same row when the second split is made
+                    if end < frag_len {
+                        spec.split_fragment(frag_idx, end as isize, pos_of_rows_after.into())?;
+                    }
+                    if start > 0 {
+                        spec.split_fragment(frag_idx, start as isize, pos_of_rows.into())?;
+                    }
+                    Ok::<_, ActionError>(())
+                })?;
+                // The selected rows may now be in a different fragment
+                self.selection = None;
+            }
+            CompAction::SetRowsMethod {
+                frag_idx,
+                start,
+                end,
+                method_idx,
+            } => self.history.apply_edit(desc, |spec| {
+                spec.set_method(frag_idx, start, end, method_idx)
+            })?,
+            CompAction::JoinFragments { frag_a, frag_b } => self
+                .history
+                .apply_edit(desc, |spec| spec.join_fragments(frag_a, frag_b))?,
+            CompAction::RotateComp { frag_idx, row_idx } => self
+                .history
+                .apply_edit(desc, |spec| spec.rotate(frag_idx, row_idx))?,
+            CompAction::RotateFragment { frag_idx, by_rows } => self
+                .history
+                .apply_edit(desc, |spec| spec.rotate_fragment(frag_idx, by_rows))?,
+            CompAction::SetStartRow {
+                frag_idx,
+                start_row,
+            } => self
+                .history
+                .apply_edit(desc, |spec| spec.set_start_row(frag_idx, start_row))?,
+            CompAction::SetStartSubLeadIndex {
+                frag_idx,
+                sub_lead_index,
+            } => self.history.apply_edit(desc, |spec| {
+                spec.set_start_sub_lead_index(frag_idx, sub_lead_index)
+            })?,
+            CompAction::ReverseComp => self.history.apply_edit(desc, CompSpec::reverse)?,
+            CompAction::SwapCalls(call_a, call_b) => self
+                .history
+                .apply_edit(desc, |spec| spec.swap_calls(call_a, call_b))?,
+            CompAction::AddMethod {
+                name,
+                shorthand,
+                place_notation,
+                ruleoffs_above,
+            } => {
+                self.history.apply_edit(desc, |spec| {
+                    spec.add_method(name, shorthand, place_notation, ruleoffs_above)
+                })?;
+            }
+            CompAction::AddCall {
+                symbol,
+                place_notation,
+                lead_location,
+                calling_positions,
+            } => {
+                self.history.apply_edit(desc, |spec| {
+                    spec.add_call(symbol, place_notation, lead_location, calling_positions)
+                })?;
+            }
+            CompAction::EditCall {
+                call_idx,
+                symbol,
+                place_notation,
+                lead_location,
+                calling_positions,
+            } => self.history.apply_edit(desc, |spec| {
+                spec.edit_call(
+                    call_idx,
+                    symbol,
+                    place_notation,
+                    lead_location,
+                    calling_positions,
+                )
+            })?,
+            CompAction::AddMusic {
+                parent,
+                name,
+                regex,
+                weight,
+                count_wraps,
+                parts,
+            } => self.history.apply_edit(desc, |spec| {
+                spec.add_music(&parent, name, regex.as_deref(), weight, count_wraps, parts)
+            })?,
+            CompAction::EditMusic {
+                path,
+                name,
+                regex,
+                weight,
+                count_wraps,
+                parts,
+                parent,
+            } => self.history.apply_edit(desc, |spec| {
+                spec.edit_music(&path, name, regex.as_deref(), weight, count_wraps, parts)?;
+                spec.move_music(&path, &parent)
+            })?,
+            CompAction::RemoveMusic(path) => self
+                .history
+                .apply_edit(desc, |spec| spec.remove_music(&path))?,
+            CompAction::SetCall {
+                frag_idx,
+                row_idx,
+                call_idx,
             } => self
                 .history
-                .apply_edit(|spec| spec.split_fragment(frag_idx, split_index, pos_of_new_frag))?,
+                .apply_edit(desc, |spec| spec.set_call(frag_idx, row_idx, call_idx))?,
+            CompAction::EditMethod {
+                method_idx,
+                name,
+                shorthand,
+                place_notation,
+                ruleoffs_above,
+            } => self.history.apply_edit(desc, |spec| {
+                spec.edit_method(method_idx, name, shorthand, place_notation, ruleoffs_above)
+            })?,
         }
         // If the edit succeeded, rebuild `self.full_state` so that the new changes are rendered
         self.full_state.update(self.history.comp_spec());
+        if let Some(playback) = &mut self.playback {
+            playback.update_rows(&self.full_state);
+        }
+        if let Some(search) = &mut self.row_search {
+            search.update_matches(&self.full_state);
+        }
+        if !is_move {
+            self.join_offer = None;
+        }
+        // Clear the selection if its rows no longer exist
+        let full_state = &self.full_state;
+        self.selection = self.selection.filter(|s| {
+            full_state
+                .fragments
+                .get(s.frag_idx)
+                .is_some_and(|f| s.end() < f.num_rows())
+        });
+        if moved_through_history {
+            // Update the part head box, since we have potentially changed the part heads.  If
+            // we don't do this, then the code will notice that the contents of the part head
+            // box is different to the current part heads, and promptly creates a new undo step
+            // to change them.
+            //
+            // TODO: Don't update the box if the user is part-way through editing it?
+            self.part_head_str = self.full_state.part_heads.spec_string();
+        }
         Ok(())
     }
+
+    /// Generate a human-readable description of a [`CompAction`], to be displayed in the undo
+    /// history
+    fn describe_comp_action(&self, action: &CompAction) -> String {
+        let method_name = |idx: MethodIdx| {
+            self.full_state
+                .methods
+                .get(idx)
+                .map_or_else(|| format!("method #{}", idx.index()), |m| m.name())
+        };
+        match action {
+            CompAction::SetPartHeads(part_heads) => {
+                format!("Changed part heads to {}", part_heads.spec_string())
+            }
+            CompAction::SetLengthTarget(Some(target)) => {
+                format!("Set target length to {}-{}", target.min, target.max)
+            }
+            CompAction::SetLengthTarget(None) => "Removed target length".to_owned(),
+            CompAction::SetProofSettings(settings) if settings.is_default() => {
+                "Proved every row".to_owned()
+            }
+            CompAction::SetProofSettings(_) => "Changed proving settings".to_owned(),
+            CompAction::SetObservationBell(bell) => format!("Set observation bell to {}", bell),
+            CompAction::UndoRedo(HistoryDirection::Undo) => "Undo".to_owned(),
+            CompAction::UndoRedo(HistoryDirection::Redo) => "Redo".to_owned(),
+            CompAction::JumpToStep(index) => format!("Jumped to step {}", index),
+            CompAction::MuteFragment(frag_idx) => {
+                format!("Toggled mute on fragment {}", frag_idx.index())
+            }
+            CompAction::SoloFragment(frag_idx) => format!("Soloed fragment {}", frag_idx.index()),
+            CompAction::ToggleBluelines(frag_idx) => {
+                format!("Toggled bluelines on fragment {}", frag_idx.index())
+            }
+            CompAction::AddFragment {
+                method_idx,
+                is_course,
+                ..
+            } => format!(
+                "Added a plain {} of {}",
+                if *is_course { "course" } else { "lead" },
+                method_name(*method_idx)
+            ),
+            CompAction::RemoteEdit(_) => "Edit by a collaborator".to_owned(),
+            CompAction::AddTouch { touch, .. } => format!(
+                "Added {}",
+                touch_search::touch_description(&self.full_state, touch)
+            ),
+            CompAction::JoinWithTouch {
+                frag_a,
+                frag_b,
+                touch,
+            } => format!(
+                "Joined fragment {} onto fragment {} with {}",
+                frag_b.index(),
+                frag_a.index(),
+                touch_search::touch_description(&self.full_state, touch)
+            ),
+            CompAction::ExtendFragment {
+                frag_idx,
+                method_idx,
+                by_course,
+            } => format!(
+                "Extended fragment {} by a plain {} of {}",
+                frag_idx.index(),
+                if *by_course { "course" } else { "lead" },
+                method_name(*method_idx)
+            ),
+            CompAction::AppendLead {
+                frag_idx,
+                shorthand,
+            } => format!(
+                "Appended a lead of '{}' to fragment {}",
+                shorthand,
+                frag_idx.index()
+            ),
+            CompAction::MoveFragment { frag_idx, .. } => {
+                format!("Moved fragment {}", frag_idx.index())
+            }
+            CompAction::PasteFragment { .. } => "Pasted a fragment".to_owned(),
+            CompAction::DuplicateTransposed {
+                frag_idx,
+                transposition,
+                ..
+            } => format!(
+                "Duplicated fragment {}, transposed by {}",
+                frag_idx.index(),
+                transposition
+            ),
+            CompAction::DeleteFragment(frag_idx) => {
+                format!("Deleted fragment {}", frag_idx.index())
+            }
+            CompAction::SplitFragment { frag_idx, .. } => {
+                format!("Split fragment {}", frag_idx.index())
+            }
+            CompAction::DeleteRows {
+                frag_idx,
+                start,
+                end,
+            } => format!(
+                "Deleted {} rows from fragment {}",
+                end - start,
+                frag_idx.index()
+            ),
+            CompAction::IsolateRows {
+                frag_idx,
+                start,
+                end,
+                ..
+            } => format!(
+                "Split {} rows out of fragment {}",
+                end - start,
+                frag_idx.index()
+            ),
+            CompAction::SetRowsMethod {
+                frag_idx,
+                start,
+                end,
+                method_idx,
+            } => format!(
+                "Changed {} rows of fragment {} to {}",
+                end - start,
+                frag_idx.index(),
+                method_name(*method_idx)
+            ),
+            CompAction::JoinFragments { frag_a, frag_b } => format!(
+                "Joined fragment {} onto fragment {}",
+                frag_b.index(),
+                frag_a.index()
+            ),
+            CompAction::RotateComp { .. } => "Rotated composition".to_owned(),
+            CompAction::RotateFragment { frag_idx, by_rows } => {
+                format!("Rotated fragment {} by {} rows", frag_idx.index(), by_rows)
+            }
+            CompAction::SetStartRow {
+                frag_idx,
+                start_row,
+            } => format!("Started fragment {} from {}", frag_idx.index(), start_row),
+            CompAction::SetStartSubLeadIndex {
+                frag_idx,
+                sub_lead_index,
+            } => format!(
+                "Started fragment {} at row {} of the lead",
+                frag_idx.index(),
+                sub_lead_index
+            ),
+            CompAction::ReverseComp => "Reversed composition".to_owned(),
+            CompAction::SwapCalls(call_a, call_b) => {
+                let symbol = |idx: &CallIdx| self.full_state.calls.get(*idx).map(|c| c.symbol());
+                match (symbol(call_a), symbol(call_b)) {
+                    (Some(a), Some(b)) => format!("Swapped calls '{}' and '{}'", a, b),
+                    _ => "Swapped calls".to_owned(),
+                }
+            }
+            CompAction::AddCall { symbol, .. } => format!("Added call '{}'", symbol),
+            CompAction::EditCall { symbol, .. } => format!("Edited call '{}'", symbol),
+            CompAction::AddMusic { name, .. } => format!("Added music '{}'", name),
+            CompAction::EditMusic { name, .. } => format!("Edited music '{}'", name),
+            CompAction::RemoveMusic(_) => "Removed music".to_owned(),
+            CompAction::SetCall {
+                frag_idx, call_idx, ..
+            } => match call_idx.and_then(|idx| self.full_state.calls.get(idx)) {
+                Some(call) => format!(
+                    "Set call '{}' in fragment {}",
+                    call.symbol(),
+                    frag_idx.index()
+                ),
+                None => format!("Removed call in fragment {}", frag_idx.index()),
+            },
+            CompAction::AddMethod { name, .. } => format!("Added method {}", name),
+            CompAction::EditMethod { method_idx, .. } => {
+                format!("Edited method {}", method_name(*method_idx))
+            }
+        }
+    }
 }
 
-/// The possible ways that the state of `JigsawApp` can be mutated.  These can be randomly
-/// generated to test the app without the overhead of running a full GUI.
+/// The possible ways that the state of `JigsawApp` can be mutated.  [`CompAction`]s are randomly
+/// generated by the fuzzing tests to test the app without the overhead of running a full GUI.
 #[derive(Debug, Clone)]
 pub(crate) enum Action {
     /// Pan the canvas view.  Note that this refers to the position of the 'camera', not the
     /// positions of the canvas (so increasing both axis corresponds to the fragments moving
     /// up and left).
     PanView(Vec2),
+    /// Move the camera to a given position, and set the fragment which the next keyboard
+    /// navigation will start from
+    SetView {
+        camera_pos: Pos2,
+        focused_frag: Option<FragIdx>,
+    },
+    /// Move the camera so that a given row is in the centre of the canvas, viewing the part which
+    /// contains it
+    GoToRow(RowLocation),
     /// Update the 'Part Heads' box to some new value
     SetPartHeadString(String),
+    /// Update the path of the file used for saving/opening
+    SetFilePath(String),
+    /// Start, update or (if `None`) stop adding or editing a
+    /// [`Method`](jigsaw_comp::full::Method)
+    SetMethodEdit(Option<MethodEdit>),
+    /// Start, update or (if `None`) stop adding or editing a [`Call`](jigsaw_comp::full::Call)
+    SetCallEdit(Option<CallEdit>),
+    /// Start, update or (if `None`) stop adding or editing a [`Music`](jigsaw_comp::Music) group
+    SetMusicEdit(Option<MusicEdit>),
+    /// Open, update or (if `None`) close the keyboard shortcut window
+    SetKeymapEdit(Option<KeymapEdit>),
+    /// Start, update or (if `None`) stop editing the start of a fragment
+    SetStartEdit(Option<StartEdit>),
+    /// Start, update or (if `None`) stop editing the composition's proving settings
+    SetProofEdit(Option<ProofEdit>),
+    /// Change the key chords which trigger a [`Shortcut`], or reset them to the defaults if
+    /// `None`
+    RebindShortcut(Shortcut, Option<Vec<KeyChord>>),
+    /// Choose the [`Method`](jigsaw_comp::full::Method) used when adding new leads or courses
+    SelectMethod(MethodIdx),
+    /// Change (or, if `None`, clear) the range of rows which the user has selected
+    SetSelection(Option<RowSelection>),
+    /// Start, update or (if `None`) stop dragging a fragment
+    SetFragDrag(Option<FragDrag>),
+    /// Offer (or, if `None`, stop offering) to join one fragment onto the end of another
+    SetJoinOffer(Option<(FragIdx, FragIdx)>),
+    /// Change which part is displayed on the canvas
+    SetViewedPart(PartIdx),
+    /// Highlight (or, if `None`, stop highlighting) the bells rung by one handbell ringer
+    SetHandbellPair(Option<(Bell, Bell)>),
+    /// Highlight (or, if `None`, stop highlighting) the path of one bell in every row
+    SetHighlightedBell(Option<Bell>),
+    /// Choose whether or not a line is drawn through the path of the highlighted bell
+    SetHighlightedBellLine(bool),
+    /// Change the colour scheme of the GUI and canvas
+    SetTheme(Theme),
+    /// Change the size of the rows and text drawn on the canvas
+    SetRowDensity(RowDensity),
+    /// Change the colours used to highlight falseness and music
+    SetHighlightColors(HighlightColors),
+    /// Choose whether or not patterns are drawn over falseness and music highlights
+    SetHatchHighlights(bool),
+    /// Choose whether or not methods being spliced mid-lead is reported as a problem
+    SetAllowMidLeadSplices(bool),
+    /// Switch between listing every false course head and only the tenors-together ones
+    SetTenorsTogetherFchs(bool),
+    /// Switch between drawing every part side-by-side and only drawing the part being viewed
+    SetShowAllParts(bool),
+    /// Switch between drawing every fragment with bluelines and only drawing the fragments which
+    /// have bluelines turned on
+    SetShowBluelines(bool),
+    /// Switch between drawing every row and summarising each course on one line
+    SetCourseView(bool),
+    /// Show or hide the place bells which the observation bell becomes at each lead head
+    SetShowPlaceBells(bool),
+    /// Show or hide the minimap in the corner of the canvas
+    SetShowMinimap(bool),
+    /// Turn on or off the mode where typing a method's shorthand appends a lead of that method
+    SetComposeByKeyboard(bool),
+    /// Open, update or (if `None`) close the window used to start a new composition
+    SetNewComp(Option<NewComp>),
+    /// Open, update or (if `None`) close the window used to search for touches
+    SetTouchSearch(Option<TouchSearch>),
+    /// Start searching for touches, showing the results in the touch search window.  If `join`
+    /// is `Some((frag_a, frag_b))`, the touches join `frag_b` onto the end of `frag_a`.
+    SearchTouches {
+        params: SearchParams,
+        join: Option<(FragIdx, FragIdx)>,
+    },
+    /// Stop the touch search which is currently running, keeping the touches found so far
+    CancelSearch,
+    /// Open, update or (if `None`) close the window used to host or join collaboration sessions
+    SetCollabWindow(Option<CollabWindow>),
+    /// Start hosting a collaboration session on a given port
+    HostSession(u16),
+    /// Join the collaboration session with a given code
+    JoinSession(String),
+    /// Leave (or stop hosting) the current collaboration session
+    LeaveSession,
+    /// Open or close the playback window.  Closing the window stops playback.
+    SetPlaybackOpen(bool),
+    /// Start or pause playback
+    SetPlaying(bool),
+    /// Move playback to the start of a given row (counting from the first row being played)
+    SeekPlayback(usize),
+    /// Choose which part is played, or play every part if `None`
+    SetPlaybackPart(Option<PartIdx>),
+    /// Set the speed and rhythm of playback (and of exported row timings)
+    SetPace(Pace),
+    /// Choose how the bells are sounded during playback
+    #[cfg(feature = "audio")]
+    SetSoundOutput(SoundOutput),
+    /// Open, update or (if `None`) close the window used to find a row in the composition
+    SetRowSearch(Option<RowSearch>),
+    /// Show an error which happened whilst handling input (when the app can't be changed) as a
+    /// toast
+    ShowError(String),
+    /// Replace the composition with an empty one of a given [`Stage`](bellframe::Stage).  This
+    /// resets the undo history.
+    NewComp(bellframe::Stage),
+    /// Save the composition to the current file path
+    Save,
+    /// Replace the composition with the one stored at the current file path
+    Open,
+    /// Replace the composition with one reconstructed from the rows stored at the current file
+    /// path
+    ImportRows,
+    /// Open, update or (if `None`) close the window used to export the composition's rows
+    SetExportRows(Option<ExportOptions>),
+    /// Write the composition's rows to the current file path
+    ExportRows(ExportOptions),
+    /// Write an SVG image of the canvas to the current file path
+    ExportSvg,
+    /// Write the time at which each row starts (when rung at the [`Pace`] in the [`Config`]) to
+    /// the current file path.  If the playback window is open, only the part being played is
+    /// written.
+    ExportRowTimings,
+    /// Open, update or (if `None`) close the window used to export a printable composition
+    SetHtmlExport(Option<HtmlExport>),
+    /// Write the composition, formatted like a printed composition, to the current file path as
+    /// an HTML page
+    ExportHtml(HtmlExport),
+    /// Load a method library from the current file path
+    LoadMethodLib,
+    /// Replace the composition with its extension to a larger [`Stage`](bellframe::Stage), using
+    /// the loaded method library to find the extensions of the methods
+    ExtendComp(bellframe::Stage),
     /// Make an edit to the composition
     Comp(CompAction),
 }
 
+impl Action {
+    /// Returns `true` if this `Action` only changes how the composition is viewed, and so can be
+    /// applied to read-only views
+    fn is_view_only(&self) -> bool {
+        matches!(
+            self,
+            Action::PanView(_)
+                | Action::SetView { .. }
+                | Action::GoToRow(_)
+                | Action::SetRowSearch(_)
+                | Action::ShowError(_)
+                | Action::SetSelection(_)
+                | Action::SetViewedPart(_)
+                | Action::SetHandbellPair(_)
+                | Action::SetHighlightedBell(_)
+                | Action::SetRowDensity(_)
+        )
+    }
+}
+
 /// Actions which modify the composition
 #[derive(Debug, Clone)]
 pub(crate) enum CompAction {
     /// Updates the [`PartHeads`] of the current [`CompSpec`]
     SetPartHeads(PartHeads),
+    /// Set (or remove) the range of lengths which the composition should have
+    SetLengthTarget(Option<LengthTarget>),
+    /// Change which rows are compared when proving the composition
+    SetProofSettings(ProofSettings),
+    /// Change which bell's position names the calls and course ends
+    SetObservationBell(Bell),
     /// Undo or redo (which are similar enough to be handled as one case)
     UndoRedo(HistoryDirection),
+    /// Jump directly to a step in the undo history, where `0` is the oldest step
+    JumpToStep(usize),
     MuteFragment(FragIdx),
     SoloFragment(FragIdx),
+    /// Toggle whether or not a fragment is drawn with bluelines
+    ToggleBluelines(FragIdx),
+    /// Add a new fragment containing a plain lead (or course) of a method
+    AddFragment {
+        method_idx: MethodIdx,
+        is_course: bool,
+        position: Pos2,
+    },
+    /// Replace the composition with one edited by a collaborator
+    RemoteEdit(Box<CompSpec>),
+    /// Add a new fragment containing a touch found by the touch search
+    AddTouch {
+        touch: Touch,
+        position: Pos2,
+    },
+    /// Join `frag_b` onto the end of `frag_a`, with a touch (found by the touch search) rung in
+    /// between them
+    JoinWithTouch {
+        frag_a: FragIdx,
+        frag_b: FragIdx,
+        touch: Touch,
+    },
+    /// Extend the end of a fragment by a plain lead (or course) of a method
+    ExtendFragment {
+        frag_idx: FragIdx,
+        method_idx: MethodIdx,
+        by_course: bool,
+    },
+    /// Append a plain lead of the method with a given shorthand to the end of a fragment
+    AppendLead {
+        frag_idx: FragIdx,
+        shorthand: String,
+    },
+    /// Move a fragment to a new position on the canvas
+    MoveFragment {
+        frag_idx: FragIdx,
+        position: Pos2,
+    },
+    /// Add a copy of a fragment which was copied to the clipboard (as JSON), placing it at a
+    /// given position
+    PasteFragment {
+        json: String,
+        position: Pos2,
+    },
+    /// Add a copy of a fragment where every row is pre-multiplied by `transposition` (usually a
+    /// part head)
+    DuplicateTransposed {
+        frag_idx: FragIdx,
+        transposition: RowBuf,
+        position: Pos2,
+    },
     /// Delete a fragment
     DeleteFragment(FragIdx),
     /// Split a fragment at a given row
@@ -315,16 +2500,153 @@ pub(crate) enum CompAction {
         split_index: isize,
         pos_of_new_frag: Pos2,
     },
+    /// Delete the rows in `start..end` of a fragment
+    DeleteRows {
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+    },
+    /// Split a fragment at both ends of the rows in `start..end`, so that those rows become a
+    /// fragment of their own
+    IsolateRows {
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+        pos_of_rows: Pos2,
+        pos_of_rows_after: Pos2,
+    },
+    /// Change the rows in `start..end` of a fragment to use a different method
+    SetRowsMethod {
+        frag_idx: FragIdx,
+        start: usize,
+        end: usize,
+        method_idx: MethodIdx,
+    },
+    /// Join `frag_b` onto the end of `frag_a`
+    JoinFragments {
+        frag_a: FragIdx,
+        frag_b: FragIdx,
+    },
+    /// Rotate the composition to start at a given row (which must be in a round block)
+    RotateComp {
+        frag_idx: FragIdx,
+        row_idx: isize,
+    },
+    /// Cyclically shift which row of a fragment comes first, leaving every other fragment alone
+    RotateFragment {
+        frag_idx: FragIdx,
+        by_rows: isize,
+    },
+    /// Change the first row of a fragment, transposing the rest of the fragment with it
+    SetStartRow {
+        frag_idx: FragIdx,
+        start_row: RowBuf,
+    },
+    /// Make a fragment start partway through a lead (e.g. for snap starts)
+    SetStartSubLeadIndex {
+        frag_idx: FragIdx,
+        sub_lead_index: usize,
+    },
+    /// Reverse every fragment, reading its changes backwards (see [`CompSpec::reverse`])
+    ReverseComp,
+    /// Replace every use of one call with the other, and vice versa
+    SwapCalls(CallIdx, CallIdx),
+    /// Add a new call to the composition
+    AddCall {
+        symbol: char,
+        place_notation: String,
+        lead_location: String,
+        calling_positions: String,
+    },
+    /// Replace a call with a new symbol, place notation, lead location and calling positions
+    EditCall {
+        call_idx: CallIdx,
+        symbol: char,
+        place_notation: String,
+        lead_location: String,
+        calling_positions: String,
+    },
+    /// Add a new music group (or a regex if `regex` is `Some`) to the end of the group at `parent`
+    AddMusic {
+        parent: Vec<usize>,
+        name: String,
+        regex: Option<String>,
+        weight: f32,
+        count_wraps: bool,
+        /// The parts in which the music is counted, or `None` for every part
+        parts: Option<Vec<PartIdx>>,
+    },
+    /// Rename and re-weight a music group (and change its regex, if it has one), moving it to the
+    /// end of the group at `parent` if that's not the group which currently contains it
+    EditMusic {
+        path: Vec<usize>,
+        name: String,
+        regex: Option<String>,
+        weight: f32,
+        count_wraps: bool,
+        parts: Option<Vec<PartIdx>>,
+        parent: Vec<usize>,
+    },
+    /// Remove a music group and all its sub-groups
+    RemoveMusic(Vec<usize>),
+    /// Replace the rows directly above a given row with a call (or with the plain method if
+    /// `call_idx` is `None`)
+    SetCall {
+        frag_idx: FragIdx,
+        row_idx: isize,
+        call_idx: Option<CallIdx>,
+    },
+    /// Add a new method to the composition
+    AddMethod {
+        name: String,
+        shorthand: String,
+        place_notation: String,
+        ruleoffs_above: Vec<usize>,
+    },
+    /// Replace a method with a new name, shorthand, place notation and rule-offs
+    EditMethod {
+        method_idx: MethodIdx,
+        name: String,
+        shorthand: String,
+        place_notation: String,
+        ruleoffs_above: Vec<usize>,
+    },
 }
 
-#[derive(Debug, Clone)]
+/// The state of a fragment being dragged across the canvas
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FragDrag {
+    frag_idx: FragIdx,
+    /// Where the fragment would be if it hadn't snapped onto another fragment
+    unsnapped_pos: Pos2,
+}
+
+#[derive(Debug)]
 pub(crate) enum ActionError {
     /// The user tried to undo/redo when there were no steps in that direction
     NoSteps(HistoryDirection),
+    /// The user tried to jump to a step which isn't in the undo history
+    InvalidStep(usize),
     /// There was an error whilst modifying the [`CompSpec`]
     EditError(spec::EditError),
+    /// The pasted text wasn't a fragment which could be added to the [`CompSpec`]
+    Paste(save_load::LoadError),
 }
 
+impl std::fmt::Display for ActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionError::NoSteps(HistoryDirection::Undo) => write!(f, "There's nothing to undo"),
+            ActionError::NoSteps(HistoryDirection::Redo) => write!(f, "There's nothing to redo"),
+            ActionError::InvalidStep(step) => write!(f, "Step {} isn't in the undo history", step),
+            ActionError::EditError(e) => write!(f, "{}", e),
+            ActionError::Paste(e) => write!(f, "Couldn't paste fragment: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
 /// Allow `?` to implicitly wrap [`spec::EditError`]s into [`ActionError`]s
 impl From<spec::EditError> for ActionError {
     fn from(e: spec::EditError) -> Self {
@@ -332,6 +2654,26 @@ impl From<spec::EditError> for ActionError {
     }
 }
 
+/// The possible ways that opening or importing a file can fail
+#[derive(Debug)]
+pub(crate) enum FileError {
+    Io(std::io::Error),
+    Load(save_load::LoadError),
+    Import(import::ImportError),
+    MethodLib(method_lib::LibError),
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileError::Io(e) => write!(f, "{}", e),
+            FileError::Load(e) => write!(f, "{}", e),
+            FileError::Import(e) => write!(f, "{}", e),
+            FileError::MethodLib(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub(crate) enum HistoryDirection {
     Undo,
@@ -0,0 +1,169 @@
+//! Drawing code for the menu bar at the top of the screen
+
+use bellframe::Stage;
+use eframe::egui::{self, Ui};
+use jigsaw_comp::{full::ExportOptions, spec::CompSpec};
+
+use crate::{
+    config::Config, html_export::HtmlExport, keymap_edit::KeymapEdit, new_comp::NewComp,
+    row_search::RowSearch, touch_search::TouchSearch, Action,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::collab::CollabWindow;
+#[cfg(feature = "file_dialogs")]
+use crate::file_dialog;
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    file_path: &str,
+    config: &Config,
+    spec: &CompSpec,
+    page_url: Option<&str>,
+    push_action: impl FnMut(Action),
+) {
+    egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+        egui::menu::bar(ui, |bar_ui| {
+            draw_bar_contents(bar_ui, file_path, config, spec, page_url, push_action)
+        });
+    });
+}
+
+fn draw_bar_contents(
+    ui: &mut Ui,
+    file_path: &str,
+    config: &Config,
+    spec: &CompSpec,
+    page_url: Option<&str>,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::menu::menu(ui, "File", |menu_ui| {
+        if menu_ui.button("New composition").clicked() {
+            push_action(Action::SetNewComp(Some(NewComp::default())));
+        }
+        if menu_ui.button("Open").clicked() {
+            push_action(Action::Open);
+        }
+        if menu_ui.button("Save").clicked() {
+            push_action(Action::Save);
+        }
+        #[cfg(feature = "file_dialogs")]
+        {
+            if menu_ui.button("Open file...").clicked() {
+                if let Some(path) = file_dialog::pick_file_to_open(file_path) {
+                    push_action(Action::SetFilePath(path));
+                    push_action(Action::Open);
+                }
+            }
+            if menu_ui.button("Save as...").clicked() {
+                if let Some(path) = file_dialog::pick_file_to_save(file_path) {
+                    push_action(Action::SetFilePath(path));
+                    push_action(Action::Save);
+                }
+            }
+        }
+        if menu_ui.button("Import rows").clicked() {
+            push_action(Action::ImportRows);
+        }
+        if menu_ui.button("Export rows").clicked() {
+            push_action(Action::SetExportRows(Some(ExportOptions::default())));
+        }
+        if menu_ui.button("Export SVG").clicked() {
+            push_action(Action::ExportSvg);
+        }
+        if menu_ui.button("Export printable composition").clicked() {
+            push_action(Action::SetHtmlExport(Some(HtmlExport::default())));
+        }
+        if menu_ui.button("Load method library").clicked() {
+            push_action(Action::LoadMethodLib);
+        }
+        // The web build can't open network connections
+        #[cfg(not(target_arch = "wasm32"))]
+        if menu_ui.button("Collaborate...").clicked() {
+            push_action(Action::SetCollabWindow(Some(CollabWindow::default())));
+        }
+        // Extending to the next two stages with the same parity keeps the tenors together
+        for num_extra_bells in [2, 4] {
+            let stage = Stage::new(spec.stage().num_bells() + num_extra_bells);
+            let stage_name = match stage.name() {
+                Some(name) => name.to_owned(),
+                None => format!("{} bells", stage.num_bells()),
+            };
+            if menu_ui
+                .button(format!("Extend to {}", stage_name))
+                .clicked()
+            {
+                push_action(Action::ExtendComp(stage));
+            }
+        }
+        // Links can only be shared on the web build, where we know the page's URL
+        if let Some(page_url) = page_url {
+            if menu_ui.button("Copy share link").clicked() {
+                let link = format!("{}#{}", page_url, spec.to_url_fragment());
+                menu_ui.ctx().output().copied_text = link;
+            }
+        }
+    });
+    egui::menu::menu(ui, "Edit", |menu_ui| {
+        let mut compose_by_keyboard = config.compose_by_keyboard;
+        menu_ui.checkbox(&mut compose_by_keyboard, "Compose by keyboard");
+        if compose_by_keyboard != config.compose_by_keyboard {
+            push_action(Action::SetComposeByKeyboard(compose_by_keyboard));
+        }
+        if menu_ui.button("Search for touches").clicked() {
+            push_action(Action::SetTouchSearch(Some(TouchSearch::default())));
+        }
+        if menu_ui.button("Keyboard shortcuts").clicked() {
+            push_action(Action::SetKeymapEdit(Some(KeymapEdit::default())));
+        }
+    });
+    egui::menu::menu(ui, "View", |menu_ui| {
+        let mut show_all_parts = config.show_all_parts;
+        menu_ui.checkbox(&mut show_all_parts, "Show all parts");
+        if show_all_parts != config.show_all_parts {
+            push_action(Action::SetShowAllParts(show_all_parts));
+        }
+        let mut show_bluelines = config.show_bluelines;
+        menu_ui.checkbox(&mut show_bluelines, "Show bluelines");
+        if show_bluelines != config.show_bluelines {
+            push_action(Action::SetShowBluelines(show_bluelines));
+        }
+        let mut course_view = config.course_view;
+        menu_ui.checkbox(&mut course_view, "Course view");
+        if course_view != config.course_view {
+            push_action(Action::SetCourseView(course_view));
+        }
+        let mut show_place_bells = config.show_place_bells;
+        menu_ui.checkbox(&mut show_place_bells, "Show place bells");
+        if show_place_bells != config.show_place_bells {
+            push_action(Action::SetShowPlaceBells(show_place_bells));
+        }
+        let mut show_minimap = config.show_minimap;
+        menu_ui.checkbox(&mut show_minimap, "Show minimap");
+        if show_minimap != config.show_minimap {
+            push_action(Action::SetShowMinimap(show_minimap));
+        }
+        if menu_ui.button("Playback").clicked() {
+            push_action(Action::SetPlaybackOpen(true));
+        }
+        if menu_ui.button("Find row").clicked() {
+            push_action(Action::SetRowSearch(Some(RowSearch::default())));
+        }
+    });
+
+    // The path of the file being edited.  This is used by every item in the 'File' menu
+    ui.label("File:");
+    let mut file_path_mut = file_path.to_owned();
+    ui.text_edit_singleline(&mut file_path_mut);
+    // Add an action to update the app's `file_path` if the user changed the string
+    if file_path_mut != file_path {
+        push_action(Action::SetFilePath(file_path_mut));
+    }
+    // Choosing a path with the OS's file picker is useful for the exports, which write to this path
+    #[cfg(feature = "file_dialogs")]
+    if ui.button("Browse...").clicked() {
+        if let Some(path) = file_dialog::pick_file_to_save(file_path) {
+            push_action(Action::SetFilePath(path));
+        }
+    }
+}
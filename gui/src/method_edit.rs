@@ -0,0 +1,192 @@
+//! Drawing code for the window used to add or edit a [`Method`](full::Method)
+
+use bellframe::Stage;
+use eframe::egui::{self, Color32};
+use itertools::Itertools;
+use jigsaw_comp::{full, method_lib::MethodLib, place_not};
+use jigsaw_utils::indexed_vec::MethodIdx;
+
+use crate::{Action, CompAction};
+
+/// The state of a [`Method`](full::Method) which is being added or edited.  Whilst the user is
+/// typing, the place notation can become invalid, so this must be able to diverge from the
+/// [`Method`](full::Method)s stored in the undo history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MethodEdit {
+    /// The index of the [`Method`](full::Method) being edited, or `None` if a new
+    /// [`Method`](full::Method) is being added
+    pub method_idx: Option<MethodIdx>,
+    pub name: String,
+    pub shorthand: String,
+    pub place_notation: String,
+    /// A comma-separated list of the indices within the lead of the rows which have rule-offs
+    /// above them.  Calls can be made at any rule-off, so principles like Stedman put a rule-off
+    /// at every six end.
+    pub ruleoffs: String,
+    /// The text in the box used to search the [`MethodLib`]
+    pub lib_search: String,
+}
+
+impl MethodEdit {
+    /// Start editing a [`Method`](full::Method), with all the fields filled in with its current
+    /// values
+    pub(crate) fn new(method_idx: MethodIdx, method: &full::Method) -> Self {
+        Self {
+            method_idx: Some(method_idx),
+            name: method.name(),
+            shorthand: method.shorthand(),
+            place_notation: method.place_notation(),
+            ruleoffs: method.ruleoffs_above().iter().join(", "),
+            lib_search: String::new(),
+        }
+    }
+
+    /// Start adding a new [`Method`](full::Method).  Most methods are only ruled off at the lead
+    /// end, so the rule-offs are filled in with `"0"` and all other fields are left empty.
+    pub(crate) fn new_method() -> Self {
+        Self {
+            method_idx: None,
+            name: String::new(),
+            shorthand: String::new(),
+            place_notation: String::new(),
+            ruleoffs: LEAD_END_RULEOFF.to_owned(),
+            lib_search: String::new(),
+        }
+    }
+
+    /// Parses the comma-separated list of rule-offs, returning `None` if any of them aren't
+    /// numbers
+    fn parse_ruleoffs(&self) -> Option<Vec<usize>> {
+        self.ruleoffs
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<usize>().ok())
+            .collect()
+    }
+}
+
+/// The rule-offs of a method which is only ruled off at the lead end
+const LEAD_END_RULEOFF: &str = "0";
+/// Explanation of the rule-offs box, shown when the user hovers over it
+const RULEOFFS_HELP: &str = "The rows of the lead which have rule-offs above them.  Calls can be \
+made at any rule-off, so principles should be ruled off at every division end (e.g. \"3, 9\" for \
+the six ends of Stedman).";
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    edit: &MethodEdit,
+    method_lib: Option<&MethodLib>,
+    stage: Stage,
+    mut push_action: impl FnMut(Action),
+) {
+    /// The maximum number of search results shown from the [`MethodLib`]
+    const MAX_SEARCH_RESULTS: usize = 10;
+
+    let title = match edit.method_idx {
+        Some(_) => "Edit method",
+        None => "Add method",
+    };
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut new_edit = edit.clone();
+
+            // When adding a method, let the user fill in the fields from the method library
+            if let (None, Some(lib)) = (edit.method_idx, method_lib) {
+                ui.horizontal(|search_ui| {
+                    search_ui.label("Search library:");
+                    search_ui.text_edit_singleline(&mut new_edit.lib_search);
+                });
+                if !edit.lib_search.trim().is_empty() {
+                    for lib_method in lib.search(&edit.lib_search, stage).take(MAX_SEARCH_RESULTS) {
+                        if ui.button(&lib_method.title).clicked() {
+                            new_edit.name = lib_method.name.clone();
+                            new_edit.shorthand = lib_method.default_shorthand();
+                            new_edit.place_notation = lib_method.place_notation.clone();
+                            new_edit.ruleoffs = LEAD_END_RULEOFF.to_owned();
+                        }
+                    }
+                }
+                ui.separator();
+            }
+
+            egui::Grid::new("method_edit_grid").show(ui, |grid_ui| {
+                grid_ui.label("Name:");
+                grid_ui.text_edit_singleline(&mut new_edit.name);
+                grid_ui.end_row();
+
+                grid_ui.label("Shorthand:");
+                grid_ui.text_edit_singleline(&mut new_edit.shorthand);
+                grid_ui.end_row();
+
+                grid_ui.label("Place notation:");
+                grid_ui.text_edit_singleline(&mut new_edit.place_notation);
+                grid_ui.end_row();
+
+                grid_ui.label("Rule-offs:");
+                grid_ui
+                    .text_edit_singleline(&mut new_edit.ruleoffs)
+                    .on_hover_text(RULEOFFS_HELP);
+                grid_ui.end_row();
+            });
+            // Add an action to update the app's `method_edit` if the user changed any of the text.
+            // This is pushed before the buttons' actions, so that closing the window takes
+            // precedence.
+            if &new_edit != edit {
+                push_action(Action::SetMethodEdit(Some(new_edit)));
+            }
+
+            // Parse the place notation every frame, so that the user gets live feedback.  This
+            // also accepts jump changes, like `(13)`.
+            let parse_result = place_not::parse_method(&edit.place_notation, stage);
+            if let Err(e) = &parse_result {
+                let err_label = egui::Label::new(e.to_string()).text_color(Color32::RED);
+                ui.label(err_label);
+            }
+            let ruleoffs = edit.parse_ruleoffs();
+            if ruleoffs.is_none() {
+                let err_label = egui::Label::new("Rule-offs must be a list of row numbers")
+                    .text_color(Color32::RED);
+                ui.label(err_label);
+            }
+
+            ui.horizontal(|button_ui| {
+                // Only allow the user to apply edits which will succeed
+                if button_ui
+                    .add(
+                        egui::Button::new("Apply")
+                            .enabled(parse_result.is_ok() && ruleoffs.is_some()),
+                    )
+                    .clicked()
+                {
+                    // Unwrap is safe because the button is only enabled if the rule-offs parsed
+                    let ruleoffs_above = ruleoffs.clone().unwrap();
+                    let name = edit.name.clone();
+                    let shorthand = edit.shorthand.clone();
+                    let place_notation = edit.place_notation.clone();
+                    let comp_action = match edit.method_idx {
+                        Some(method_idx) => CompAction::EditMethod {
+                            method_idx,
+                            name,
+                            shorthand,
+                            place_notation,
+                            ruleoffs_above,
+                        },
+                        None => CompAction::AddMethod {
+                            name,
+                            shorthand,
+                            place_notation,
+                            ruleoffs_above,
+                        },
+                    };
+                    push_action(Action::Comp(comp_action));
+                    push_action(Action::SetMethodEdit(None));
+                }
+                if button_ui.button("Cancel").clicked() {
+                    push_action(Action::SetMethodEdit(None));
+                }
+            });
+        });
+}
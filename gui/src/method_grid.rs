@@ -0,0 +1,54 @@
+//! Drawing code for the miniature grids shown in the methods panel.  These draw one plain lead of
+//! a [`Method`], so that the user can check that they typed the right place notation.  They are
+//! drawn independently of the canvas, so aren't affected by the canvas's [`Config`] settings.
+//!
+//! [`Config`]: crate::config::Config
+
+use bellframe::Bell;
+use eframe::egui::{Color32, Pos2, Sense, Shape, Stroke, Ui, Vec2};
+use jigsaw_comp::full::Method;
+
+/// The horizontal distance between adjacent places, in points
+const COL_WIDTH: f32 = 8.0;
+/// The vertical distance between adjacent rows, in points
+const ROW_HEIGHT: f32 = 4.0;
+/// The lines followed by every bell other than the treble
+const WORKING_BELL_STROKE: Stroke = Stroke {
+    width: 1.0,
+    color: Color32::GRAY,
+};
+/// The line followed by the treble, drawn over the other bells' lines
+const TREBLE_STROKE: Stroke = Stroke {
+    width: 2.0,
+    color: Color32::RED,
+};
+
+/// Draw a grid of one plain lead of `method`, with the treble's path highlighted and the place
+/// notation written underneath
+pub(crate) fn draw(ui: &mut Ui, method: &Method) {
+    let rows = method.plain_lead().collect::<Vec<_>>();
+    let num_bells = rows[0].stage().num_bells();
+    let size = Vec2::new(
+        COL_WIDTH * num_bells as f32,
+        ROW_HEIGHT * (rows.len() - 1) as f32,
+    );
+    let (rect, _response) = ui.allocate_exact_size(size, Sense::hover());
+
+    // Trace the path of every bell through the lead
+    let mut paths = vec![Vec::<Pos2>::with_capacity(rows.len()); num_bells];
+    for (row_idx, row) in rows.iter().enumerate() {
+        for (place, bell) in row.bell_iter().enumerate() {
+            paths[bell.index()].push(Pos2::new(
+                rect.min.x + COL_WIDTH * (place as f32 + 0.5),
+                rect.min.y + ROW_HEIGHT * row_idx as f32,
+            ));
+        }
+    }
+    let treble_path = paths.remove(Bell::TREBLE.index());
+    for path in paths {
+        ui.painter().add(Shape::line(path, WORKING_BELL_STROKE));
+    }
+    ui.painter().add(Shape::line(treble_path, TREBLE_STROKE));
+
+    ui.label(method.place_notation());
+}
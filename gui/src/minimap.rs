@@ -0,0 +1,82 @@
+//! Drawing code for the minimap, which shows an overview of the whole composition in a corner of
+//! the canvas.  Clicking or dragging on the minimap moves the view to that location.
+
+use eframe::egui::{self, Color32, Order, Pos2, Rect, Sense, Shape, Stroke, Vec2};
+use jigsaw_comp::full::FullState;
+
+use crate::{canvas::frag_rect, config::Config, Action};
+
+/// The gap between the minimap and the corner of the canvas, in points
+const MARGIN: f32 = 10.0;
+
+/// Draw the minimap in the bottom-right corner of `canvas_rect` (in screen space), where the
+/// top-left corner of the canvas shows the point `camera_pos`
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    full_state: &FullState,
+    config: &Config,
+    canvas_rect: Rect,
+    camera_pos: Pos2,
+    mut push_action: impl FnMut(Action),
+) {
+    // The region of the canvas which is currently on the screen
+    let viewport = Rect::from_min_size(camera_pos, canvas_rect.size());
+    // The region of the canvas shown by the minimap, which is always big enough to contain the
+    // viewport so that the user can't get lost
+    let world_rect = full_state
+        .fragments
+        .iter()
+        .map(|frag| frag_rect(full_state, config, frag))
+        .fold(viewport, Rect::union);
+
+    let minimap_size = config.minimap_size;
+    let minimap_pos = canvas_rect.max - minimap_size - Vec2::splat(MARGIN);
+    egui::Area::new("minimap")
+        .order(Order::Foreground)
+        .fixed_pos(minimap_pos)
+        .show(ctx, |ui| {
+            let (rect, response) = ui.allocate_exact_size(minimap_size, Sense::click_and_drag());
+            // Scale the canvas uniformly so that all of `world_rect` fits in the minimap, and
+            // centre it
+            let scale =
+                (rect.width() / world_rect.width()).min(rect.height() / world_rect.height());
+            let offset = rect.center() - world_rect.center().to_vec2() * scale;
+            let to_minimap = |r: Rect| {
+                Rect::from_min_max(
+                    offset + r.min.to_vec2() * scale,
+                    offset + r.max.to_vec2() * scale,
+                )
+            };
+
+            let painter = ui.painter();
+            painter.add(Shape::Rect {
+                rect,
+                corner_radius: 0.0,
+                fill: config.minimap_background_color,
+                stroke: Stroke::new(1.0, Color32::GRAY),
+            });
+            for frag in &full_state.fragments {
+                painter.add(Shape::Rect {
+                    rect: to_minimap(frag_rect(full_state, config, frag)),
+                    corner_radius: 0.0,
+                    fill: Color32::GRAY,
+                    stroke: Stroke::none(),
+                });
+            }
+            painter.add(Shape::Rect {
+                rect: to_minimap(viewport),
+                corner_radius: 0.0,
+                fill: Color32::TRANSPARENT,
+                stroke: Stroke::new(1.0, config.palette().foreground),
+            });
+
+            // Clicking or dragging centres the view on the cursor
+            if response.clicked() || response.dragged() {
+                if let Some(mouse_pos) = response.interact_pointer_pos() {
+                    let world_pos = ((mouse_pos - offset) / scale).to_pos2();
+                    let new_camera_pos = world_pos - canvas_rect.size() / 2.0;
+                    push_action(Action::PanView(new_camera_pos - camera_pos));
+                }
+            }
+        });
+}
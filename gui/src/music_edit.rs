@@ -0,0 +1,286 @@
+//! Drawing code for the window used to add or edit a group of [`Music`]
+
+use bellframe::Stage;
+use eframe::egui::{self, Color32};
+use itertools::Itertools;
+use jigsaw_comp::{check_regex, Music};
+use jigsaw_utils::indexed_vec::PartIdx;
+
+use crate::{Action, CompAction};
+
+/// The state of a [`Music`] group which is being added or edited.  [`Music`] groups are identified
+/// by their path through the music tree (i.e. the index of each group which contains them,
+/// starting from the top level).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MusicEdit {
+    /// The path of the [`Music`] being edited, or `None` if a new [`Music`] is being added
+    pub path: Option<Vec<usize>>,
+    /// The path of the [`Music::Group`] which contains (or will contain) this [`Music`]
+    pub parent: Vec<usize>,
+    pub name: String,
+    /// The text of the [`Regex`](bellframe::music::Regex), or `None` if this is a
+    /// [`Music::Group`]
+    pub regex: Option<String>,
+    /// The weight which every match (or, for a [`Music::Group`], the score of every sub-group)
+    /// is multiplied by
+    pub weight: f32,
+    /// Whether or not matches which wrap between rows are counted.  Ignored for
+    /// [`Music::Group`]s.
+    pub count_wraps: bool,
+    /// The (1-indexed) numbers of the parts in which this is counted, or empty for every part
+    pub parts: String,
+}
+
+impl MusicEdit {
+    /// Start editing the [`Music`] at `path`, with all the fields filled in with its current
+    /// values
+    pub(crate) fn new(path: Vec<usize>, music: &Music) -> Self {
+        let (regex, count_wraps) = match music {
+            Music::Regex {
+                regex, count_wraps, ..
+            } => (Some(regex.to_string()), *count_wraps),
+            Music::Group { .. } => (None, false),
+        };
+        Self {
+            parent: path[..path.len() - 1].to_vec(),
+            path: Some(path),
+            name: music.name().unwrap_or("").to_owned(),
+            regex,
+            weight: music.weight(),
+            count_wraps,
+            parts: format_parts(music.parts()),
+        }
+    }
+
+    /// Start adding a new [`Music::Regex`] to the group at `parent`
+    pub(crate) fn new_regex(parent: Vec<usize>) -> Self {
+        Self {
+            path: None,
+            parent,
+            name: String::new(),
+            regex: Some(String::new()),
+            weight: Music::DEFAULT_WEIGHT,
+            count_wraps: false,
+            parts: String::new(),
+        }
+    }
+
+    /// Start adding a new [`Music::Regex`] with a negative weight (i.e. anti-music, such as
+    /// crunches) to the group at `parent`
+    pub(crate) fn new_anti_music(parent: Vec<usize>) -> Self {
+        Self {
+            weight: -Music::DEFAULT_WEIGHT,
+            ..Self::new_regex(parent)
+        }
+    }
+
+    /// Start adding a new (empty) [`Music::Group`] to the group at `parent`
+    pub(crate) fn new_group(parent: Vec<usize>) -> Self {
+        Self {
+            path: None,
+            parent,
+            name: String::new(),
+            regex: None,
+            weight: Music::DEFAULT_WEIGHT,
+            count_wraps: false,
+            parts: String::new(),
+        }
+    }
+}
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    edit: &MusicEdit,
+    music_defs: &[Music],
+    stage: Stage,
+    mut push_action: impl FnMut(Action),
+) {
+    let title = match (&edit.path, &edit.regex) {
+        (Some(_), Some(_)) => "Edit music",
+        (Some(_), None) => "Edit music group",
+        (None, Some(_)) => "Add music",
+        (None, None) => "Add music group",
+    };
+    // Music can be moved to any group, except itself or its own sub-groups
+    let possible_parents = group_paths(music_defs)
+        .into_iter()
+        .filter(|(path, _)| match &edit.path {
+            Some(own_path) => !path.starts_with(own_path),
+            None => true,
+        })
+        .collect_vec();
+
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut new_edit = edit.clone();
+            egui::Grid::new("music_edit_grid").show(ui, |grid_ui| {
+                grid_ui.label("Name:");
+                grid_ui.text_edit_singleline(&mut new_edit.name);
+                grid_ui.end_row();
+
+                if let Some(regex) = &mut new_edit.regex {
+                    grid_ui.label("Pattern:");
+                    grid_ui.text_edit_singleline(regex);
+                    grid_ui.end_row();
+                }
+
+                grid_ui.label("Weight:");
+                grid_ui.add(egui::DragValue::new(&mut new_edit.weight).speed(0.1));
+                grid_ui.end_row();
+
+                grid_ui
+                    .label("Parts:")
+                    .on_hover_text("Only count this in these parts (empty for every part)");
+                grid_ui.text_edit_singleline(&mut new_edit.parts);
+                grid_ui.end_row();
+
+                if new_edit.regex.is_some() {
+                    grid_ui.label("Wraps:");
+                    grid_ui
+                        .checkbox(&mut new_edit.count_wraps, "Count wraps between rows")
+                        .on_hover_text("e.g. rounds spanning the end of one row and the next");
+                    grid_ui.end_row();
+                }
+
+                grid_ui.label("Group:");
+                let parent_label = |parent: &[usize]| {
+                    possible_parents
+                        .iter()
+                        .find(|(path, _)| path == parent)
+                        .map_or("", |(_, label)| label.as_str())
+                        .to_owned()
+                };
+                egui::ComboBox::from_id_source("music_edit_parent")
+                    .selected_text(parent_label(&edit.parent))
+                    .show_ui(grid_ui, |combo_ui| {
+                        for (path, label) in &possible_parents {
+                            combo_ui.selectable_value(&mut new_edit.parent, path.clone(), label);
+                        }
+                    });
+                grid_ui.end_row();
+            });
+            // Update the app's `music_edit` before the buttons' actions, so that closing the
+            // window takes precedence
+            if &new_edit != edit {
+                push_action(Action::SetMusicEdit(Some(new_edit)));
+            }
+
+            // Check the regex and parts every frame, so that the user gets live feedback
+            let regex_result = match &edit.regex {
+                Some(regex) => check_regex(regex, stage).map_err(|e| e.to_string()),
+                None => Ok(()),
+            };
+            let parts_result = parse_parts(&edit.parts);
+            for e in regex_result
+                .as_ref()
+                .err()
+                .into_iter()
+                .chain(parts_result.as_ref().err())
+            {
+                let err_label = egui::Label::new(e).text_color(Color32::RED);
+                ui.label(err_label);
+            }
+            let is_valid = regex_result.is_ok() && parts_result.is_ok();
+
+            ui.horizontal(|button_ui| {
+                // Only allow the user to apply edits which will succeed
+                if button_ui
+                    .add(egui::Button::new("Apply").enabled(is_valid))
+                    .clicked()
+                {
+                    let name = edit.name.trim().to_owned();
+                    let regex = edit.regex.clone();
+                    let parent = edit.parent.clone();
+                    let weight = edit.weight;
+                    let count_wraps = edit.count_wraps;
+                    // Unwrap is safe because the button is disabled if the parts are invalid
+                    let parts = parts_result.unwrap();
+                    let comp_action = match &edit.path {
+                        Some(path) => CompAction::EditMusic {
+                            path: path.clone(),
+                            name,
+                            regex,
+                            weight,
+                            count_wraps,
+                            parts,
+                            parent,
+                        },
+                        None => CompAction::AddMusic {
+                            parent,
+                            name,
+                            regex,
+                            weight,
+                            count_wraps,
+                            parts,
+                        },
+                    };
+                    push_action(Action::Comp(comp_action));
+                    push_action(Action::SetMusicEdit(None));
+                }
+                if let Some(path) = &edit.path {
+                    if button_ui.button("Delete").clicked() {
+                        push_action(Action::Comp(CompAction::RemoveMusic(path.clone())));
+                        push_action(Action::SetMusicEdit(None));
+                    }
+                }
+                if button_ui.button("Cancel").clicked() {
+                    push_action(Action::SetMusicEdit(None));
+                }
+            });
+        });
+}
+
+/// Formats a set of parts as a list of 1-indexed part numbers (e.g. `"1, 3"`), the format parsed
+/// by [`parse_parts`].  `None` (i.e. every part) is formatted as the empty string.
+pub(crate) fn format_parts(parts: Option<&[PartIdx]>) -> String {
+    parts
+        .unwrap_or(&[])
+        .iter()
+        .map(|part| part.index() + 1)
+        .join(", ")
+}
+
+/// Parses a list of 1-indexed part numbers, separated by commas or whitespace.  The empty string
+/// refers to every part, and so is parsed as `None`.
+fn parse_parts(s: &str) -> Result<Option<Vec<PartIdx>>, String> {
+    let parts = s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .map(|n| match n.parse::<usize>() {
+            Ok(part_num) if part_num > 0 => Ok(PartIdx::new(part_num - 1)),
+            _ => Err(format!("'{}' isn't a part number", n)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((!parts.is_empty()).then(|| parts))
+}
+
+/// Returns the path and a label for every [`Music::Group`] in `music` (including the top level,
+/// which has an empty path)
+fn group_paths(music: &[Music]) -> Vec<(Vec<usize>, String)> {
+    let mut paths = vec![(Vec::new(), "(top level)".to_owned())];
+    add_group_paths(music, &[], "", &mut paths);
+    paths
+}
+
+/// Recursively adds the path and label of every [`Music::Group`] in `music`
+fn add_group_paths(
+    music: &[Music],
+    path: &[usize],
+    label_prefix: &str,
+    out: &mut Vec<(Vec<usize>, String)>,
+) {
+    for (idx, m) in music.iter().enumerate() {
+        if let Music::Group {
+            name, sub_groups, ..
+        } = m
+        {
+            let mut sub_path = path.to_vec();
+            sub_path.push(idx);
+            let label = format!("{}{}", label_prefix, name);
+            out.push((sub_path.clone(), label.clone()));
+            add_group_paths(sub_groups, &sub_path, &format!("{} > ", label), out);
+        }
+    }
+}
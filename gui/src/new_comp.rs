@@ -0,0 +1,93 @@
+//! Drawing code for the window used to start a new, empty composition
+
+use bellframe::Stage;
+use eframe::egui::{self, Color32};
+
+use crate::Action;
+
+/// The [`Stage`]s which a new composition can be created on, along with their names
+const STAGES: [(usize, &str); 9] = [
+    (4, "Minimus"),
+    (5, "Doubles"),
+    (6, "Minor"),
+    (7, "Triples"),
+    (8, "Major"),
+    (9, "Caters"),
+    (10, "Royal"),
+    (11, "Cinques"),
+    (12, "Maximus"),
+];
+
+/// The state of the window used to start a new composition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct NewComp {
+    /// The [`Stage`] of the composition which will be created
+    pub stage: Stage,
+}
+
+impl Default for NewComp {
+    fn default() -> Self {
+        Self {
+            stage: Stage::MAJOR,
+        }
+    }
+}
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    new_comp: &NewComp,
+    has_unsaved_changes: bool,
+    mut push_action: impl FnMut(Action),
+) {
+    let stage_name = |stage: Stage| {
+        STAGES
+            .iter()
+            .find(|(num_bells, _)| *num_bells == stage.num_bells())
+            .map_or_else(
+                || format!("{} bells", stage.num_bells()),
+                |(_, n)| n.to_string(),
+            )
+    };
+
+    egui::Window::new("New composition")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label("Stage")
+                .selected_text(stage_name(new_comp.stage))
+                .show_ui(ui, |combo_ui| {
+                    for &(num_bells, name) in STAGES.iter() {
+                        let stage = Stage::new(num_bells);
+                        if combo_ui
+                            .selectable_label(stage == new_comp.stage, name)
+                            .clicked()
+                        {
+                            push_action(Action::SetNewComp(Some(NewComp { stage })));
+                        }
+                    }
+                });
+
+            // Starting a new composition replaces the current one (and its undo history), so
+            // make sure that the user knows what they're about to lose
+            if has_unsaved_changes {
+                ui.label(
+                    egui::Label::new("The current composition has unsaved changes.")
+                        .text_color(Color32::RED),
+                );
+            }
+            ui.horizontal(|ui| {
+                let create_text = if has_unsaved_changes {
+                    "Discard changes and create"
+                } else {
+                    "Create"
+                };
+                if ui.button(create_text).clicked() {
+                    push_action(Action::NewComp(new_comp.stage));
+                    push_action(Action::SetNewComp(None));
+                }
+                if ui.button("Cancel").clicked() {
+                    push_action(Action::SetNewComp(None));
+                }
+            });
+        });
+}
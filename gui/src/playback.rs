@@ -0,0 +1,277 @@
+//! Playback of the composition's rows as the sound of bells, with a cursor on the canvas showing
+//! which row is being rung
+
+use bellframe::{Bell, Stage};
+use eframe::egui;
+use jigsaw_comp::{full::FullState, Pace};
+use jigsaw_utils::{indexed_vec::PartIdx, types::RowLocation};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, Action};
+
+#[cfg(feature = "audio")]
+use crate::sound::Sound;
+
+/// The longest time (in seconds) which playback will move forward in one step.  egui doesn't draw
+/// frames when nothing is happening, so the first frame after playback starts can come long after
+/// the previous frame (and we don't want to strike all the bells in that time at once).
+const MAX_STEP: f64 = 0.1;
+
+/// How the bells are sounded during playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum SoundOutput {
+    /// Synthesised bells, played through the default audio device
+    Bells,
+    /// MIDI notes, sent to the first MIDI output port
+    Midi,
+}
+
+/// The row which is currently being rung, drawn on the canvas as a cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PlaybackCursor {
+    pub location: RowLocation,
+    /// The place of the bell which was struck most recently, or `None` during the handstroke gap
+    pub place: Option<usize>,
+}
+
+/// The state of the composition's playback.  This exists whenever the playback window is open.
+#[derive(Debug)]
+pub(crate) struct Playback {
+    /// The part being played, or `None` to play every part one after the other
+    part: Option<PartIdx>,
+    /// The proved rows of the composition, in the order in which they are rung
+    rows: Vec<RowLocation>,
+    stage: Stage,
+    /// The speed and rhythm of playback
+    pace: Pace,
+    /// How far playback has got through `rows`, measured in blows (see [`Pace`])
+    position: f64,
+    is_playing: bool,
+    /// The device used to sound the bells, created when playback first starts.  Without the
+    /// `audio` feature (e.g. on the web build), playback only shows the cursor.
+    #[cfg(feature = "audio")]
+    sound: Option<Sound>,
+}
+
+impl Playback {
+    pub fn new(full_state: &FullState, part: Option<PartIdx>, pace: Pace) -> Self {
+        let mut playback = Self {
+            part,
+            rows: Vec::new(),
+            stage: full_state.stage,
+            pace,
+            position: 0.0,
+            is_playing: false,
+            #[cfg(feature = "audio")]
+            sound: None,
+        };
+        playback.update_rows(full_state);
+        playback
+    }
+
+    /// Rebuild the list of rows to play, e.g. after the composition has been edited.  Playback
+    /// continues from the same row (if it still exists).
+    pub fn update_rows(&mut self, full_state: &FullState) {
+        let current_row = self.current_row();
+        self.rows = full_state.ringing_order(self.part);
+        self.stage = full_state.stage;
+        self.seek(current_row);
+        if self.position >= self.num_blows() {
+            self.is_playing = false;
+        }
+    }
+
+    /* Getters */
+
+    pub fn part(&self) -> Option<PartIdx> {
+        self.part
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// The index (within the rows being played) of the row currently being rung
+    pub fn current_row(&self) -> usize {
+        let (row, _) = self.pace.row_at(self.position, self.stage);
+        row
+    }
+
+    /// The time since the start of playback, in seconds
+    pub fn elapsed_time(&self) -> f64 {
+        self.position * self.pace.blow_duration(self.stage)
+    }
+
+    /// The cursor showing the current row on the canvas, or `None` if there are no rows to play
+    pub fn cursor(&self) -> Option<PlaybackCursor> {
+        let (row, place) = self.pace.row_at(self.position, self.stage);
+        let location = *self.rows.get(row)?;
+        Some(PlaybackCursor { location, place })
+    }
+
+    /* Controls */
+
+    pub fn set_part(&mut self, part: Option<PartIdx>, full_state: &FullState) {
+        self.part = part;
+        self.position = 0.0;
+        self.update_rows(full_state);
+    }
+
+    /// Start or pause playback.  Starting playback at the end of the composition starts again
+    /// from the first row.  Returns an error if the bells can't be sounded (in which case the
+    /// cursor still moves).
+    pub fn set_playing(&mut self, is_playing: bool, config: &Config) -> Result<(), String> {
+        if is_playing && self.position >= self.num_blows() {
+            self.position = 0.0;
+        }
+        self.is_playing = is_playing;
+        #[cfg(feature = "audio")]
+        if is_playing && self.sound.as_ref().map(Sound::output) != Some(config.sound_output) {
+            self.sound = None; // Drop the old output before creating the new one
+            self.sound = Some(Sound::new(config.sound_output)?);
+        }
+        #[cfg(not(feature = "audio"))]
+        let _ = config;
+        Ok(())
+    }
+
+    /// Change the speed and rhythm of playback, continuing from the start of the current row
+    pub fn set_pace(&mut self, pace: Pace) {
+        let current_row = self.current_row();
+        self.pace = pace;
+        self.seek(current_row);
+    }
+
+    /// Move playback to the start of a given row
+    pub fn seek(&mut self, row: usize) {
+        self.position = self.pace.row_start(row.min(self.rows.len()), self.stage);
+        if self.position >= self.num_blows() {
+            self.is_playing = false;
+        }
+    }
+
+    /// Move playback forwards by `dt` seconds, striking every bell which is passed.  This should
+    /// be called every frame.
+    pub fn step(&mut self, dt: f64, full_state: &FullState) {
+        if !self.is_playing {
+            return;
+        }
+        let end = self.num_blows();
+        let dt = dt.min(MAX_STEP);
+        let new_position = (self.position + dt / self.pace.blow_duration(self.stage)).min(end);
+        // Strike every blow which happens in `self.position..new_position`
+        let (first_row, _) = self.pace.row_at(self.position, self.stage);
+        let (last_row, _) = self.pace.row_at(new_position, self.stage);
+        for row in first_row..=last_row {
+            let loc = match self.rows.get(row) {
+                Some(loc) => *loc,
+                None => break,
+            };
+            let row_start = self.pace.row_start(row, self.stage);
+            let frag = &full_state.fragments[loc.frag_index];
+            for (place, bell) in frag
+                .row_in_part(loc.part_index, loc.row_index)
+                .bell_iter()
+                .enumerate()
+            {
+                let blow_time = row_start + place as f64;
+                if (self.position..new_position).contains(&blow_time) {
+                    self.strike(bell);
+                }
+            }
+        }
+        self.position = new_position;
+        if self.position >= end {
+            self.is_playing = false;
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    fn strike(&mut self, bell: Bell) {
+        if let Some(sound) = &mut self.sound {
+            sound.strike(bell, self.stage);
+        }
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn strike(&mut self, _bell: Bell) {}
+
+    /// The number of blows needed to ring every row
+    fn num_blows(&self) -> f64 {
+        self.pace.row_start(self.rows.len(), self.stage)
+    }
+}
+
+/// Draw the window which controls playback
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    playback: &Playback,
+    full_state: &FullState,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::Window::new("Playback")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let play_text = if playback.is_playing() {
+                    "Pause"
+                } else {
+                    "Play"
+                };
+                if ui.button(play_text).clicked() {
+                    push_action(Action::SetPlaying(!playback.is_playing()));
+                }
+                if ui.button("Back to start").clicked() {
+                    push_action(Action::SeekPlayback(0));
+                }
+            });
+
+            // Seek bar
+            let num_rows = playback.num_rows();
+            if num_rows == 0 {
+                ui.label("There are no proved rows to play");
+            } else {
+                let mut row = playback.current_row().min(num_rows - 1);
+                let slider = egui::Slider::new(&mut row, 0..=num_rows - 1).text("Row");
+                if ui.add(slider).changed() {
+                    push_action(Action::SeekPlayback(row));
+                }
+            }
+            let elapsed_mins = (playback.elapsed_time() / 60.0) as usize;
+            ui.label(format!(
+                "{}h {:02}m elapsed",
+                elapsed_mins / 60,
+                elapsed_mins % 60
+            ));
+
+            // Part selection
+            let part_label = |part: Option<PartIdx>| match part {
+                Some(part_idx) => format!("Part {}", part_idx.index() + 1),
+                None => "All parts".to_owned(),
+            };
+            let num_parts = full_state.part_heads.len();
+            egui::ComboBox::from_label("Part to play")
+                .selected_text(part_label(playback.part()))
+                .show_ui(ui, |combo_ui| {
+                    let parts = (0..num_parts).map(|idx| Some(PartIdx::new(idx)));
+                    for part in std::iter::once(None).chain(parts) {
+                        let is_selected = part == playback.part();
+                        if combo_ui
+                            .selectable_label(is_selected, part_label(part))
+                            .clicked()
+                        {
+                            push_action(Action::SetPlaybackPart(part));
+                        }
+                    }
+                });
+
+            if ui.button("Close").clicked() {
+                push_action(Action::SetPlaybackOpen(false));
+            }
+        });
+}
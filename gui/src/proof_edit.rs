@@ -0,0 +1,88 @@
+//! Drawing code for the window used to change how the composition is proved
+
+use bellframe::Stage;
+use eframe::egui::{self, Color32};
+use jigsaw_comp::spec::proof::ProofSettings;
+
+use crate::{Action, CompAction};
+
+/// The state of the window used to edit the composition's [`ProofSettings`].  Like
+/// [`CallEdit`](crate::call_edit::CallEdit), this can diverge from the undo history whilst the
+/// user is typing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ProofEdit {
+    pub allowed_repeats: String,
+    pub proved_bells: String,
+}
+
+impl ProofEdit {
+    /// Start editing some [`ProofSettings`], with all the fields filled in with their current
+    /// values
+    pub(crate) fn new(settings: &ProofSettings) -> Self {
+        Self {
+            allowed_repeats: settings.allowed_repeats_string(),
+            proved_bells: settings.proved_bells_string(),
+        }
+    }
+}
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    edit: &ProofEdit,
+    current_settings: &ProofSettings,
+    stage: Stage,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::Window::new("Proving settings")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut new_edit = edit.clone();
+            egui::Grid::new("proof_edit_grid").show(ui, |grid_ui| {
+                grid_ui
+                    .label("Allowed repeats:")
+                    .on_hover_text("Rows matching these patterns (e.g. '*78') can be repeated");
+                grid_ui.text_edit_singleline(&mut new_edit.allowed_repeats);
+                grid_ui.end_row();
+
+                grid_ui
+                    .label("Proved bells:")
+                    .on_hover_text("Only prove the places of these bells (empty for all bells)");
+                grid_ui.text_edit_singleline(&mut new_edit.proved_bells);
+                grid_ui.end_row();
+            });
+            // Update the app's `proof_edit` before the buttons' actions, so that closing the
+            // window takes precedence
+            if &new_edit != edit {
+                push_action(Action::SetProofEdit(Some(new_edit)));
+            }
+
+            // Parse the settings every frame, so that the user gets live feedback
+            let settings = ProofSettings::parse(&edit.allowed_repeats, &edit.proved_bells, stage);
+            if let Err(e) = &settings {
+                ui.label(egui::Label::new(e.to_string()).text_color(Color32::RED));
+            }
+
+            ui.horizontal(|button_ui| {
+                let is_changed = settings.as_ref().is_ok_and(|s| s != current_settings);
+                // Only allow the user to apply edits which will succeed
+                if button_ui
+                    .add(egui::Button::new("Apply").enabled(is_changed))
+                    .clicked()
+                {
+                    // Unwrap is safe because the button is disabled if the settings are invalid
+                    let settings = settings.unwrap();
+                    push_action(Action::Comp(CompAction::SetProofSettings(settings)));
+                    push_action(Action::SetProofEdit(None));
+                }
+                if button_ui.button("Prove every row").clicked() {
+                    push_action(Action::SetProofEdit(Some(ProofEdit::new(
+                        &ProofSettings::default(),
+                    ))));
+                }
+                if button_ui.button("Cancel").clicked() {
+                    push_action(Action::SetProofEdit(None));
+                }
+            });
+        });
+}
@@ -0,0 +1,150 @@
+//! Drawing code for the window used to find every occurrence of a [`Row`] (or every [`Row`]
+//! matching a music pattern) in the composition
+
+use bellframe::{music::Regex, RowBuf};
+use eframe::egui::{self, Color32, Key};
+use jigsaw_comp::{check_regex, full::FullState};
+use jigsaw_utils::types::RowLocation;
+
+use crate::Action;
+
+// Imports only used for doc comments
+#[allow(unused_imports)]
+use bellframe::Row;
+
+/// The state of the row search window.  This exists whenever the window is open.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct RowSearch {
+    /// The [`Row`] or pattern being searched for, as typed by the user
+    text: String,
+    /// The index of the match which the view was last moved to, or `None` if the user hasn't
+    /// moved to a match since changing `text`
+    current_match: Option<usize>,
+    /// Every location which matches `text`.  These are cached because searching for a pattern
+    /// has to check every [`Row`] in the composition.
+    matches: Vec<RowLocation>,
+    /// Why `text` can't be searched for, if it can't
+    error: Option<String>,
+}
+
+impl RowSearch {
+    pub fn new(text: String, full_state: &FullState) -> Self {
+        let mut search = Self {
+            text,
+            current_match: None,
+            matches: Vec::new(),
+            error: None,
+        };
+        search.update_matches(full_state);
+        search
+    }
+
+    /// Every location which matches the [`Row`] or pattern being searched for
+    pub fn matches(&self) -> &[RowLocation] {
+        &self.matches
+    }
+
+    /// Search the composition again, e.g. after it has been edited.  Text containing `*`s is
+    /// searched for as a music pattern, and anything else as a single [`Row`].
+    pub fn update_matches(&mut self, full_state: &FullState) {
+        let text = self.text.trim();
+        let stage = full_state.stage;
+        let matches = if text.is_empty() {
+            Ok(Vec::new())
+        } else if text.contains('*') {
+            check_regex(text, stage)
+                .map(|()| full_state.find_rows_matching(&Regex::parse(text)))
+                .map_err(|e| e.to_string())
+        } else {
+            RowBuf::parse_with_stage(text, stage)
+                .map(|row| full_state.find_row(&row).to_vec())
+                .map_err(|e| e.to_string())
+        };
+        match matches {
+            Ok(matches) => {
+                self.matches = matches;
+                self.error = None;
+            }
+            Err(e) => {
+                self.matches.clear();
+                self.error = Some(e);
+            }
+        }
+        // The edit may have removed the match which the view was on
+        self.current_match = self.current_match.filter(|idx| *idx < self.matches.len());
+    }
+
+    /// The index of the match after the current one, wrapping round to the first match
+    fn next_match(&self) -> usize {
+        self.current_match
+            .map_or(0, |idx| (idx + 1) % self.matches.len())
+    }
+}
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    search: &RowSearch,
+    full_state: &FullState,
+    mut push_action: impl FnMut(Action),
+) {
+    egui::Window::new("Find row")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let matches = search.matches();
+            let mut go_to_next_match = false;
+
+            ui.horizontal(|ui| {
+                ui.label("Row or pattern:");
+                let mut text = search.text.clone();
+                let response = ui.text_edit_singleline(&mut text);
+                // Matches are highlighted as the user types
+                if text != search.text {
+                    push_action(Action::SetRowSearch(Some(RowSearch::new(text, full_state))));
+                }
+                // Pressing enter moves the focus away from the text box, so give it back to let
+                // the user cycle through the matches by pressing enter repeatedly
+                if response.lost_focus() && ui.input().key_pressed(Key::Enter) {
+                    go_to_next_match = true;
+                    response.request_focus();
+                }
+                if ui.button("Rounds").clicked() {
+                    let rounds = RowBuf::rounds(full_state.stage).to_string();
+                    push_action(Action::SetRowSearch(Some(RowSearch::new(
+                        rounds, full_state,
+                    ))));
+                }
+            });
+
+            if let Some(e) = &search.error {
+                ui.label(egui::Label::new(e).text_color(Color32::RED));
+            } else if search.text.trim().is_empty() {
+                ui.label("Type a row, or a pattern like '*5678'");
+            } else if matches.is_empty() {
+                ui.label("No rows in the composition match");
+            } else {
+                let summary = match search.current_match {
+                    Some(idx) => format!("Match {} of {}", idx + 1, matches.len()),
+                    None if matches.len() == 1 => "1 match".to_owned(),
+                    None => format!("{} matches", matches.len()),
+                };
+                ui.label(summary);
+                if ui.button("Next match").clicked() {
+                    go_to_next_match = true;
+                }
+            }
+
+            if go_to_next_match && !matches.is_empty() {
+                let idx = search.next_match();
+                push_action(Action::SetRowSearch(Some(RowSearch {
+                    current_match: Some(idx),
+                    ..search.clone()
+                })));
+                push_action(Action::GoToRow(matches[idx]));
+            }
+
+            if ui.button("Close").clicked() {
+                push_action(Action::SetRowSearch(None));
+            }
+        });
+}
@@ -0,0 +1,285 @@
+//! A stable, serialisable form of the edits which can be made to a composition, so that external
+//! tools can drive Jigsaw by writing lists of edits to JSON files.  [`CompAction`] itself isn't
+//! exposed, since it changes whenever the GUI changes and refers to GUI types (like [`Pos2`]).
+//!
+//! A script is a JSON array of [`ScriptAction`]s, each tagged with its `action`.  For example:
+//! ```json
+//! [
+//!     { "action": "add_fragment", "method": 0, "is_course": false, "position": [0, 0] },
+//!     { "action": "append_lead", "frag": 0, "shorthand": "B" },
+//!     { "action": "set_call", "frag": 0, "row": 32, "call": 0 }
+//! ]
+//! ```
+
+use bellframe::{Bell, RowBuf};
+use eframe::egui::Pos2;
+use jigsaw_comp::spec::{part_heads::PartHeads, CompSpec};
+use jigsaw_utils::indexed_vec::{CallIdx, FragIdx, MethodIdx};
+use serde::Deserialize;
+
+use crate::{CompAction, HistoryDirection, JigsawApp, DEFAULT_FILE_PATH};
+
+/// A single edit made by a script.  Fragments, methods and calls are referred to by their
+/// indices, and positions are given as `[x, y]` (in the same units as the composition files).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ScriptAction {
+    Undo,
+    Redo,
+    SetPartHeads {
+        part_heads: String,
+    },
+    SetObservationBell {
+        bell: char,
+    },
+    MuteFragment {
+        frag: usize,
+    },
+    SoloFragment {
+        frag: usize,
+    },
+    AddFragment {
+        method: usize,
+        #[serde(default)]
+        is_course: bool,
+        #[serde(default)]
+        position: [f32; 2],
+    },
+    ExtendFragment {
+        frag: usize,
+        method: usize,
+        #[serde(default)]
+        by_course: bool,
+    },
+    AppendLead {
+        frag: usize,
+        shorthand: String,
+    },
+    MoveFragment {
+        frag: usize,
+        position: [f32; 2],
+    },
+    DuplicateTransposed {
+        frag: usize,
+        transposition: String,
+        position: [f32; 2],
+    },
+    DeleteFragment {
+        frag: usize,
+    },
+    SplitFragment {
+        frag: usize,
+        split_index: isize,
+        new_position: [f32; 2],
+    },
+    DeleteRows {
+        frag: usize,
+        start: usize,
+        end: usize,
+    },
+    SetRowsMethod {
+        frag: usize,
+        start: usize,
+        end: usize,
+        method: usize,
+    },
+    JoinFragments {
+        frag_a: usize,
+        frag_b: usize,
+    },
+    RotateComp {
+        frag: usize,
+        row: isize,
+    },
+    SetStartRow {
+        frag: usize,
+        start_row: String,
+    },
+    ReverseComp,
+    SetCall {
+        frag: usize,
+        row: isize,
+        /// The call to make, or `None` to make the lead plain
+        call: Option<usize>,
+    },
+    AddCall {
+        symbol: char,
+        place_notation: String,
+        #[serde(default)]
+        lead_location: String,
+        #[serde(default)]
+        calling_positions: String,
+    },
+    AddMethod {
+        name: String,
+        shorthand: String,
+        place_notation: String,
+        #[serde(default)]
+        ruleoffs_above: Vec<usize>,
+    },
+}
+
+/// The reasons why a script couldn't be applied to a composition
+#[derive(Debug)]
+pub enum ScriptError {
+    /// The script wasn't a valid JSON list of [`ScriptAction`]s
+    Parse(serde_json::Error),
+    /// The action at `index` (counting from 0) couldn't be applied
+    Action { index: usize, message: String },
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::Parse(e) => write!(f, "Invalid script: {}", e),
+            ScriptError::Action { index, message } => write!(f, "Action #{}: {}", index, message),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Applies the actions in a JSON script to `spec`, returning the edited composition.  The actions
+/// are applied in order, stopping at the first action which fails.
+pub fn apply_script(spec: CompSpec, script: &str) -> Result<CompSpec, ScriptError> {
+    let actions: Vec<ScriptAction> = serde_json::from_str(script).map_err(ScriptError::Parse)?;
+    let mut app = JigsawApp::new(spec, DEFAULT_FILE_PATH.to_owned());
+    // Leave longer than the grouping timeout between edits, so that every edit creates its own
+    // undo step (and so `undo` and `redo` undo and redo single actions)
+    let time_between_edits = app.config.history.group_timeout * 2.0;
+    for (index, action) in actions.into_iter().enumerate() {
+        let fail = |message: String| ScriptError::Action { index, message };
+        let comp_action = action.to_comp_action(&app).map_err(fail)?;
+        let time = index as f64 * time_between_edits;
+        app.apply_comp_action(comp_action, time)
+            .map_err(|e| fail(e.to_string()))?;
+    }
+    Ok(app.history.comp_spec().clone())
+}
+
+impl ScriptAction {
+    /// Converts this into the [`CompAction`] which makes the same edit, parsing any rows or bells
+    fn to_comp_action(&self, app: &JigsawApp) -> Result<CompAction, String> {
+        let stage = app.full_state.stage;
+        let pos = |[x, y]: [f32; 2]| Pos2::new(x, y);
+        Ok(match self.clone() {
+            ScriptAction::Undo => CompAction::UndoRedo(HistoryDirection::Undo),
+            ScriptAction::Redo => CompAction::UndoRedo(HistoryDirection::Redo),
+            ScriptAction::SetPartHeads { part_heads } => CompAction::SetPartHeads(
+                PartHeads::parse(&part_heads, stage).map_err(|e| e.to_string())?,
+            ),
+            ScriptAction::SetObservationBell { bell } => CompAction::SetObservationBell(
+                Bell::from_name(bell).ok_or_else(|| format!("'{}' isn't a bell", bell))?,
+            ),
+            ScriptAction::MuteFragment { frag } => CompAction::MuteFragment(FragIdx::new(frag)),
+            ScriptAction::SoloFragment { frag } => CompAction::SoloFragment(FragIdx::new(frag)),
+            ScriptAction::AddFragment {
+                method,
+                is_course,
+                position,
+            } => CompAction::AddFragment {
+                method_idx: MethodIdx::new(method),
+                is_course,
+                position: pos(position),
+            },
+            ScriptAction::ExtendFragment {
+                frag,
+                method,
+                by_course,
+            } => CompAction::ExtendFragment {
+                frag_idx: FragIdx::new(frag),
+                method_idx: MethodIdx::new(method),
+                by_course,
+            },
+            ScriptAction::AppendLead { frag, shorthand } => CompAction::AppendLead {
+                frag_idx: FragIdx::new(frag),
+                shorthand,
+            },
+            ScriptAction::MoveFragment { frag, position } => CompAction::MoveFragment {
+                frag_idx: FragIdx::new(frag),
+                position: pos(position),
+            },
+            ScriptAction::DuplicateTransposed {
+                frag,
+                transposition,
+                position,
+            } => CompAction::DuplicateTransposed {
+                frag_idx: FragIdx::new(frag),
+                transposition: parse_row(&transposition, app)?,
+                position: pos(position),
+            },
+            ScriptAction::DeleteFragment { frag } => CompAction::DeleteFragment(FragIdx::new(frag)),
+            ScriptAction::SplitFragment {
+                frag,
+                split_index,
+                new_position,
+            } => CompAction::SplitFragment {
+                frag_idx: FragIdx::new(frag),
+                split_index,
+                pos_of_new_frag: pos(new_position),
+            },
+            ScriptAction::DeleteRows { frag, start, end } => CompAction::DeleteRows {
+                frag_idx: FragIdx::new(frag),
+                start,
+                end,
+            },
+            ScriptAction::SetRowsMethod {
+                frag,
+                start,
+                end,
+                method,
+            } => CompAction::SetRowsMethod {
+                frag_idx: FragIdx::new(frag),
+                start,
+                end,
+                method_idx: MethodIdx::new(method),
+            },
+            ScriptAction::JoinFragments { frag_a, frag_b } => CompAction::JoinFragments {
+                frag_a: FragIdx::new(frag_a),
+                frag_b: FragIdx::new(frag_b),
+            },
+            ScriptAction::RotateComp { frag, row } => CompAction::RotateComp {
+                frag_idx: FragIdx::new(frag),
+                row_idx: row,
+            },
+            ScriptAction::SetStartRow { frag, start_row } => CompAction::SetStartRow {
+                frag_idx: FragIdx::new(frag),
+                start_row: parse_row(&start_row, app)?,
+            },
+            ScriptAction::ReverseComp => CompAction::ReverseComp,
+            ScriptAction::SetCall { frag, row, call } => CompAction::SetCall {
+                frag_idx: FragIdx::new(frag),
+                row_idx: row,
+                call_idx: call.map(CallIdx::new),
+            },
+            ScriptAction::AddCall {
+                symbol,
+                place_notation,
+                lead_location,
+                calling_positions,
+            } => CompAction::AddCall {
+                symbol,
+                place_notation,
+                lead_location,
+                calling_positions,
+            },
+            ScriptAction::AddMethod {
+                name,
+                shorthand,
+                place_notation,
+                ruleoffs_above,
+            } => CompAction::AddMethod {
+                name,
+                shorthand,
+                place_notation,
+                ruleoffs_above,
+            },
+        })
+    }
+}
+
+/// Parses a [`RowBuf`] on the composition's [`Stage`](bellframe::Stage), e.g. for a start row
+fn parse_row(s: &str, app: &JigsawApp) -> Result<RowBuf, String> {
+    RowBuf::parse_with_stage(s.trim(), app.full_state.stage)
+        .map_err(|e| format!("Invalid row '{}': {}", s, e))
+}
@@ -2,33 +2,83 @@
 
 use std::{collections::HashSet, rc::Rc};
 
+use bellframe::Bell;
 use eframe::egui::{self, Color32, Ui};
+use itertools::Itertools;
 use jigsaw_comp::{
-    full::{self, FullState, MusicGroupInner},
-    spec::part_heads,
+    analysis::MusicAnalyzer,
+    full::{self, FullState, MusicGroupInner, PlainLead},
+    spec::{part_heads, LengthStatus, LengthTarget},
+    History, Music,
+};
+use jigsaw_utils::{
+    indexed_vec::{MethodIdx, PartIdx},
+    types::{RowLocation, RowSource},
+};
+
+use crate::{
+    call_edit::CallEdit,
+    config::{Config, RowDensity},
+    method_edit::MethodEdit,
+    method_grid,
+    music_edit::{self, MusicEdit},
+    proof_edit::ProofEdit,
+    theme::{HighlightColors, Theme},
+    Action, CompAction,
 };
-use jigsaw_utils::types::RowSource;
 
-use crate::{Action, CompAction};
+#[cfg(feature = "audio")]
+use crate::playback::SoundOutput;
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn draw(
     ctx: &egui::CtxRef,
     state: &FullState,
+    history: &History,
     part_head_str: &str,
+    selected_method: MethodIdx,
+    part_being_viewed: PartIdx,
+    handbell_pair: Option<(Bell, Bell)>,
+    highlighted_bell: Option<Bell>,
+    tenors_together_fchs: bool,
+    analyzers: &[Box<dyn MusicAnalyzer>],
+    config: &Config,
     push_action: impl FnMut(Action),
 ) -> HashSet<RowSource> {
     egui::SidePanel::right("side_panel")
         .show(ctx, |ui| {
-            draw_panel_contents(ui, state, part_head_str, push_action)
+            draw_panel_contents(
+                ui,
+                state,
+                history,
+                part_head_str,
+                selected_method,
+                part_being_viewed,
+                handbell_pair,
+                highlighted_bell,
+                tenors_together_fchs,
+                analyzers,
+                config,
+                push_action,
+            )
         })
         .inner
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_panel_contents(
     ui: &mut Ui,
     full_state: &FullState,
+    history: &History,
     part_head_str: &str,
-    push_action: impl FnMut(Action),
+    selected_method: MethodIdx,
+    part_being_viewed: PartIdx,
+    handbell_pair: Option<(Bell, Bell)>,
+    highlighted_bell: Option<Bell>,
+    tenors_together_fchs: bool,
+    analyzers: &[Box<dyn MusicAnalyzer>],
+    config: &Config,
+    mut push_action: impl FnMut(Action),
 ) -> HashSet<RowSource> {
     const PANEL_SPACE: f32 = 5.0; // points
 
@@ -45,17 +95,99 @@ fn draw_panel_contents(
         num_parts,
         part_len * num_parts
     ));
+    draw_length_target(
+        ui,
+        history,
+        part_len * num_parts,
+        num_parts,
+        &mut push_action,
+    );
+    // Truth
+    let falseness = &full_state.falseness;
+    if falseness.is_true() {
+        ui.label("True");
+    } else {
+        let label_text = format!(
+            "False: {} rows in {} groups",
+            falseness.num_false_rows(),
+            falseness.groups.len()
+        );
+        ui.label(egui::Label::new(label_text).text_color(Color32::RED));
+    }
+    // Part selector
+    let part_label = |part_idx: PartIdx| {
+        let part_head = &full_state.part_heads.rows()[part_idx.index()];
+        format!("#{}: {}", part_idx.index() + 1, part_head)
+    };
+    egui::ComboBox::from_label("Viewing part")
+        .selected_text(part_label(part_being_viewed))
+        .show_ui(ui, |combo_ui| {
+            for part_idx in (0..num_parts).map(PartIdx::new) {
+                let is_selected = part_idx == part_being_viewed;
+                if combo_ui
+                    .selectable_label(is_selected, part_label(part_idx))
+                    .clicked()
+                {
+                    push_action(Action::SetViewedPart(part_idx));
+                }
+            }
+        });
 
     ui.add_space(PANEL_SPACE);
 
     // Create a scrollable panel for the rest of the dropdowns
     egui::ScrollArea::auto_sized().show(ui, |panels_ui| {
+        // Problems panel
+        let problems = full_state
+            .problems
+            .iter()
+            .filter(|p| {
+                config.allow_mid_lead_splices || !matches!(p, full::Problem::MidLeadSplice { .. })
+            })
+            .collect::<Vec<_>>();
+        let problems_panel_title = format!("Problems ({})", problems.len());
+        let r = egui::CollapsingHeader::new(problems_panel_title)
+            .id_source("Problems")
+            .show(panels_ui, |ui| {
+                draw_problems_panel(
+                    ui,
+                    &problems,
+                    config,
+                    &mut rows_to_highlight,
+                    &mut push_action,
+                )
+            });
+        // Add space only when the panel is open
+        if r.body_response.is_some() {
+            panels_ui.add_space(PANEL_SPACE);
+        }
+
+        // Statistics panel
+        let r = egui::CollapsingHeader::new("Statistics")
+            .id_source("Statistics")
+            .show(panels_ui, |ui| draw_stats_panel(ui, full_state));
+        // Add space only when the panel is open
+        if r.body_response.is_some() {
+            panels_ui.add_space(PANEL_SPACE);
+        }
+
+        // Work panel
+        let r = egui::CollapsingHeader::new("Work")
+            .id_source("Work")
+            .show(panels_ui, |ui| {
+                draw_work_panel(ui, full_state, highlighted_bell, config, &mut push_action)
+            });
+        // Add space only when the panel is open
+        if r.body_response.is_some() {
+            panels_ui.add_space(PANEL_SPACE);
+        }
+
         // Parts panel
         let part_panel_title = format!("Parts ({})", full_state.part_heads.len());
         let r = egui::CollapsingHeader::new(part_panel_title)
             .id_source("Parts")
             .show(panels_ui, |ui| {
-                draw_parts_panel(ui, full_state, part_head_str, push_action)
+                draw_parts_panel(ui, full_state, part_head_str, &mut push_action)
             });
         // Add space only when the panel is open
         if r.body_response.is_some() {
@@ -66,17 +198,85 @@ fn draw_panel_contents(
         let method_panel_title = format!("Methods ({})", full_state.methods.len());
         let r = egui::CollapsingHeader::new(method_panel_title)
             .id_source("Methods")
-            .show(panels_ui, |ui| draw_method_panel(ui, full_state));
+            .show(panels_ui, |ui| {
+                draw_method_panel(ui, full_state, selected_method, &mut push_action)
+            });
         // Add space only when the panel is open
         if r.body_response.is_some() {
             panels_ui.add_space(PANEL_SPACE);
         }
 
         // Calls panel
-        let r = panels_ui.collapsing("Calls", |ui| {
-            ui.label("14 LE -");
-            ui.label("1234 LE s");
-        });
+        let call_panel_title = format!("Calls ({})", full_state.calls.len());
+        let r = egui::CollapsingHeader::new(call_panel_title)
+            .id_source("Calls")
+            .show(panels_ui, |ui| {
+                draw_call_panel(ui, full_state, &mut push_action)
+            });
+        // Add space only when the panel is open
+        if r.body_response.is_some() {
+            panels_ui.add_space(PANEL_SPACE);
+        }
+
+        // Courses panel
+        let num_course_ends = full_state
+            .lead_heads
+            .iter()
+            .flatten()
+            .filter(|lh| lh.is_course_end)
+            .count();
+        let r = egui::CollapsingHeader::new(format!("Courses ({})", num_course_ends))
+            .id_source("Courses")
+            .show(panels_ui, |ui| {
+                draw_courses_panel(ui, full_state, &mut rows_to_highlight)
+            });
+        // Add space only when the panel is open
+        if r.body_response.is_some() {
+            panels_ui.add_space(PANEL_SPACE);
+        }
+
+        // Handbells panel
+        let r = egui::CollapsingHeader::new("Handbells")
+            .id_source("Handbells")
+            .show(panels_ui, |ui| {
+                draw_handbells_panel(
+                    ui,
+                    full_state,
+                    handbell_pair,
+                    &mut rows_to_highlight,
+                    &mut push_action,
+                )
+            });
+        // Add space only when the panel is open
+        if r.body_response.is_some() {
+            panels_ui.add_space(PANEL_SPACE);
+        }
+
+        // Playback panel
+        let r = egui::CollapsingHeader::new("Playback")
+            .id_source("Playback")
+            .show(panels_ui, |ui| {
+                draw_playback_panel(ui, config, &mut push_action)
+            });
+        // Add space only when the panel is open
+        if r.body_response.is_some() {
+            panels_ui.add_space(PANEL_SPACE);
+        }
+
+        // Falseness panel
+        let falseness = &full_state.falseness;
+        let falseness_panel_title = format!("Falseness ({})", falseness.groups.len());
+        let r = egui::CollapsingHeader::new(falseness_panel_title)
+            .id_source("Falseness")
+            .show(panels_ui, |ui| {
+                draw_falseness_panel(
+                    ui,
+                    full_state,
+                    tenors_together_fchs,
+                    &mut rows_to_highlight,
+                    &mut push_action,
+                )
+            });
         // Add space only when the panel is open
         if r.body_response.is_some() {
             panels_ui.add_space(PANEL_SPACE);
@@ -84,17 +284,378 @@ fn draw_panel_contents(
 
         // Music panel
         let music = &full_state.music;
-        let label = format!("Music ({}/{})", music.total_count(), music.max_count());
+        let label = format!(
+            "Music ({}/{}, score {})",
+            music.total_count(),
+            music.max_count(),
+            music.total_score()
+        );
         egui::CollapsingHeader::new(label)
             .id_source("Music")
             .show(panels_ui, |ui| {
-                draw_music_ui(ui, music.groups(), &mut rows_to_highlight);
+                draw_music_ui(
+                    ui,
+                    music.groups(),
+                    full_state.music_definitions(),
+                    &[],
+                    &mut rows_to_highlight,
+                    &mut push_action,
+                );
+            });
+
+        // Panels for custom analyses
+        for (idx, analyzer) in analyzers.iter().enumerate() {
+            egui::CollapsingHeader::new(analyzer.name())
+                .id_source(("Analysis", idx))
+                .show(panels_ui, |ui| {
+                    draw_analysis_panel(ui, full_state, analyzer.as_ref(), idx)
+                });
+        }
+
+        // Settings panel
+        let r = egui::CollapsingHeader::new("Settings")
+            .id_source("Settings")
+            .show(panels_ui, |ui| {
+                draw_settings_panel(ui, config, &mut push_action)
+            });
+        // Add space only when the panel is open
+        if r.body_response.is_some() {
+            panels_ui.add_space(PANEL_SPACE);
+        }
+
+        // History panel
+        let history_panel_title = format!(
+            "History ({}/{})",
+            history.current_step(),
+            history.step_descriptions().count() - 1
+        );
+        egui::CollapsingHeader::new(history_panel_title)
+            .id_source("History")
+            .show(panels_ui, |ui| {
+                draw_history_panel(ui, history, &mut push_action)
             });
     });
 
     rows_to_highlight
 }
 
+/// The target shown when the user first enables a target length (i.e. a quarter peal)
+const DEFAULT_LENGTH_TARGET: LengthTarget = LengthTarget {
+    min: 1250,
+    max: 1299,
+};
+
+/// Draws the controls for the composition's target length, along with how the composition's
+/// current `length` compares to it
+fn draw_length_target(
+    ui: &mut Ui,
+    history: &History,
+    length: usize,
+    num_parts: usize,
+    mut push_action: impl FnMut(Action),
+) {
+    let target = history.comp_spec().length_target();
+    let mut has_target = target.is_some();
+    let mut new_target = target.unwrap_or(DEFAULT_LENGTH_TARGET);
+    let mut min_changed = false;
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut has_target, "Target length");
+        if has_target {
+            min_changed = ui.add(egui::DragValue::new(&mut new_target.min)).changed();
+            ui.label("to");
+            ui.add(egui::DragValue::new(&mut new_target.max));
+        }
+    });
+    // Keep the range non-empty by dragging the other bound along with the one being edited
+    if new_target.min > new_target.max {
+        if min_changed {
+            new_target.max = new_target.min;
+        } else {
+            new_target.min = new_target.max;
+        }
+    }
+    let new_target = has_target.then(|| new_target);
+    if new_target != target {
+        push_action(Action::Comp(CompAction::SetLengthTarget(new_target)));
+    }
+
+    // Show how the current length compares to the target
+    if let Some(target) = target {
+        let (text, color) = match target.status(length, num_parts) {
+            LengthStatus::InRange => ("Within target length".to_owned(), Color32::GREEN),
+            LengthStatus::Short(rows) => {
+                (format!("{} rows short of target", rows), Color32::YELLOW)
+            }
+            LengthStatus::Long(rows) => (format!("Exceeds target by {} rows", rows), Color32::RED),
+            LengthStatus::Unreachable => (
+                format!("No multiple of {} parts is within the target", num_parts),
+                Color32::RED,
+            ),
+        };
+        ui.label(egui::Label::new(text).text_color(color));
+    }
+}
+
+/// Draw a table of the work done by each bell (summed over every part).  Clicking on a bell
+/// highlights its path through the composition.
+fn draw_work_panel(
+    ui: &mut Ui,
+    full_state: &FullState,
+    highlighted_bell: Option<Bell>,
+    config: &Config,
+    mut push_action: impl FnMut(Action),
+) {
+    let mut draw_line = config.highlighted_bell_line;
+    ui.checkbox(&mut draw_line, "Draw a line through the highlighted bell");
+    if draw_line != config.highlighted_bell_line {
+        push_action(Action::SetHighlightedBellLine(draw_line));
+    }
+
+    let work_per_bell = &full_state.stats.work_per_bell;
+    if work_per_bell.iter().all(|w| w.num_changes == 0) {
+        ui.label("No rows are proved");
+        return;
+    }
+    egui::Grid::new("work_grid")
+        .show(ui, |grid_ui| {
+            for heading in ["Bell", "Hunting", "Dodges", "Places", "Long rests"] {
+                grid_ui.label(heading);
+            }
+            grid_ui.end_row();
+            for (bell, work) in full_state.stage.bells().zip_eq(work_per_bell) {
+                let hunting = work.hunting as f32 / work.num_changes.max(1) as f32;
+                let is_highlighted = highlighted_bell == Some(bell);
+                if grid_ui
+                    .selectable_label(is_highlighted, bell.name())
+                    .clicked()
+                {
+                    let new_bell = (!is_highlighted).then(|| bell);
+                    push_action(Action::SetHighlightedBell(new_bell));
+                }
+                grid_ui.label(format!("{:.0}%", hunting * 100.0));
+                grid_ui.label(work.dodges.to_string());
+                grid_ui.label(work.places.to_string());
+                grid_ui.label(work.long_rests.to_string());
+                grid_ui.end_row();
+            }
+        })
+        .response
+        .on_hover_text(format!(
+            "Long rests are {} or more blows in the same place",
+            full::LONG_REST_LENGTH
+        ));
+}
+
+/// Lists every [`Problem`](full::Problem) with the composition.  Problems caused by specific rows
+/// highlight those rows when hovered, and move the view to the first of them when clicked.
+fn draw_problems_panel(
+    ui: &mut Ui,
+    problems: &[&full::Problem],
+    config: &Config,
+    rows_to_highlight: &mut HashSet<RowSource>,
+    mut push_action: impl FnMut(Action),
+) {
+    if problems.is_empty() {
+        ui.label("No problems found");
+    }
+    for problem in problems {
+        let label = egui::Label::new(problem.to_string()).text_color(Color32::YELLOW);
+        let rows = problem.rows();
+        let response = match rows.first() {
+            Some(_) => ui
+                .add(label)
+                .interact(egui::Sense::click())
+                .on_hover_text("Click to go to the first row"),
+            None => ui.add(label),
+        };
+        if response.hovered() {
+            rows_to_highlight.extend(rows.iter().map(RowLocation::as_source));
+        }
+        if let (true, Some(&first_row)) = (response.clicked(), rows.first()) {
+            push_action(Action::GoToRow(first_row));
+        }
+    }
+
+    ui.separator();
+    let mut allow_splices = config.allow_mid_lead_splices;
+    ui.checkbox(&mut allow_splices, "Allow splices mid-lead")
+        .on_hover_text("Useful when composing with link methods");
+    if allow_splices != config.allow_mid_lead_splices {
+        push_action(Action::SetAllowMidLeadSplices(allow_splices));
+    }
+}
+
+fn draw_stats_panel(ui: &mut Ui, full_state: &FullState) {
+    let stats = &full_state.stats;
+    match stats.comes_round_after {
+        Some(num_changes) => ui.label(format!("Comes round after {} changes", num_changes)),
+        None => ui
+            .label(egui::Label::new("Doesn't come round").text_color(Color32::YELLOW))
+            .on_hover_text("The fragments linked on from rounds never reach rounds again"),
+    };
+    // The rest of the stats are given per part, like a printed composition
+    ui.label(format!("{} changes of method", stats.num_coms));
+    if let Some(balance) = stats.spliced_balance() {
+        let color = if balance.is_balanced() {
+            Color32::GREEN
+        } else {
+            Color32::YELLOW
+        };
+        ui.label(egui::Label::new(balance.to_string()).text_color(color));
+    }
+    match stats.longest_run {
+        Some((method_idx, run_len)) => ui.label(format!(
+            "Longest run: {} rows of {}",
+            run_len,
+            full_state.methods[method_idx].name()
+        )),
+        None => ui.label("Longest run: none"),
+    };
+
+    egui::Grid::new("stats_grid").show(ui, |grid_ui| {
+        let method_stats = stats
+            .changes_per_method
+            .iter()
+            .zip_eq(&stats.leads_per_method)
+            .zip_eq(&stats.atw_per_method);
+        for (method, ((num_changes, num_leads), atw)) in
+            full_state.methods.iter().zip_eq(method_stats)
+        {
+            grid_ui.label(method.name());
+            grid_ui.label(format!("{} changes", num_changes));
+            grid_ui.label(format!("{} leads", num_leads));
+            grid_ui.label(format!("{:.0}% ATW", atw * 100.0));
+            grid_ui.end_row();
+        }
+        for (call, num_uses) in full_state.calls.iter().zip_eq(&stats.uses_per_call) {
+            grid_ui.label(format!("'{}' ({})", call.symbol(), call.place_notation()));
+            grid_ui.label(format!("{} calls", num_uses));
+            grid_ui.end_row();
+        }
+    });
+}
+
+/// Draws the results of a custom [`MusicAnalyzer`].  These are only computed whilst the panel is
+/// open, so closed panels don't slow down the GUI.
+fn draw_analysis_panel(
+    ui: &mut Ui,
+    full_state: &FullState,
+    analyzer: &dyn MusicAnalyzer,
+    idx: usize,
+) {
+    // PERF: Cache the results until the composition changes
+    let lines = analyzer.analyse(&full_state.proved_blocks());
+    if lines.is_empty() {
+        ui.label("Nothing to show");
+        return;
+    }
+    egui::Grid::new(("analysis_grid", idx)).show(ui, |grid_ui| {
+        for line in lines {
+            grid_ui.label(line.label);
+            grid_ui.label(line.value);
+            grid_ui.end_row();
+        }
+    });
+}
+
+/// Draw the settings used for playback and for exporting the times of each row
+fn draw_playback_panel(ui: &mut Ui, config: &Config, mut push_action: impl FnMut(Action)) {
+    let mut pace = config.pace;
+    egui::Grid::new("pace_grid").show(ui, |grid_ui| {
+        grid_ui.label("Peal time (minutes):");
+        grid_ui.add(egui::DragValue::new(&mut pace.peal_time).clamp_range(30.0..=600.0));
+        grid_ui.end_row();
+        grid_ui.label("Handstroke gap (blows):");
+        grid_ui.add(
+            egui::DragValue::new(&mut pace.handstroke_gap)
+                .speed(0.05)
+                .clamp_range(0.0..=3.0),
+        );
+        grid_ui.end_row();
+    });
+    if pace != config.pace {
+        push_action(Action::SetPace(pace));
+    }
+    #[cfg(feature = "audio")]
+    ui.horizontal(|ui| {
+        let mut output = config.sound_output;
+        ui.label("Sound:");
+        ui.radio_value(&mut output, SoundOutput::Bells, "Bells");
+        ui.radio_value(&mut output, SoundOutput::Midi, "MIDI");
+        if output != config.sound_output {
+            push_action(Action::SetSoundOutput(output));
+        }
+    });
+    ui.horizontal(|ui| {
+        if ui.button("Play").clicked() {
+            push_action(Action::SetPlaybackOpen(true));
+        }
+        if ui.button("Export row timings").clicked() {
+            push_action(Action::ExportRowTimings);
+        }
+    });
+}
+
+fn draw_settings_panel(ui: &mut Ui, config: &Config, mut push_action: impl FnMut(Action)) {
+    egui::ComboBox::from_label("Theme")
+        .selected_text(config.theme.name())
+        .show_ui(ui, |combo_ui| {
+            for &theme in Theme::ALL.iter() {
+                if combo_ui
+                    .selectable_label(theme == config.theme, theme.name())
+                    .clicked()
+                {
+                    push_action(Action::SetTheme(theme));
+                }
+            }
+        });
+    egui::ComboBox::from_label("Highlight colours")
+        .selected_text(config.highlight_colors.name())
+        .show_ui(ui, |combo_ui| {
+            for &colors in HighlightColors::ALL.iter() {
+                if combo_ui
+                    .selectable_label(colors == config.highlight_colors, colors.name())
+                    .clicked()
+                {
+                    push_action(Action::SetHighlightColors(colors));
+                }
+            }
+        });
+    let mut hatch_highlights = config.hatch_highlights;
+    ui.checkbox(&mut hatch_highlights, "Draw patterns over highlights")
+        .on_hover_text(
+            "False rows are hatched like '\\', wraps like '/' and anti-music is cross-hatched",
+        );
+    if hatch_highlights != config.hatch_highlights {
+        push_action(Action::SetHatchHighlights(hatch_highlights));
+    }
+
+    // Row sizes
+    let mut density = config.row_density();
+    egui::Grid::new("density_grid").show(ui, |grid_ui| {
+        grid_ui.label("Row height:");
+        grid_ui.add(egui::DragValue::new(&mut density.row_height).clamp_range(6.0..=60.0));
+        grid_ui.end_row();
+        grid_ui.label("Column width:");
+        grid_ui.add(egui::DragValue::new(&mut density.col_width).clamp_range(4.0..=40.0));
+        grid_ui.end_row();
+        grid_ui.label("Font size:");
+        grid_ui.add(egui::DragValue::new(&mut density.font_size).clamp_range(6.0..=40.0));
+        grid_ui.end_row();
+    });
+    ui.horizontal(|ui| {
+        if ui.button("Normal").clicked() {
+            density = RowDensity::DEFAULT;
+        }
+        if ui.button("Compact").clicked() {
+            density = RowDensity::COMPACT;
+        }
+    });
+    if density != config.row_density() {
+        push_action(Action::SetRowDensity(density));
+    }
+}
+
 fn draw_parts_panel(
     ui: &mut Ui,
     full_state: &FullState,
@@ -138,34 +699,43 @@ fn draw_parts_panel(
     }
 }
 
-fn draw_method_panel(ui: &mut Ui, full_state: &FullState) {
-    for (i, method) in full_state.methods.iter().enumerate() {
-        left_then_right(
+fn draw_method_panel(
+    ui: &mut Ui,
+    full_state: &FullState,
+    selected_method: MethodIdx,
+    mut push_action: impl FnMut(Action),
+) {
+    for (i, method) in full_state.methods.iter_enumerated() {
+        let response = left_then_right(
             ui,
-            // The main label sticks to the left
+            // The main label sticks to the left.  Clicking it selects the method used when adding
+            // new leads or courses.
             |left_ui| {
-                left_ui.label(format!(
+                let mut label_text = format!(
                     "(#{}, {}): {}",
-                    i,
+                    i.index(),
                     method.shorthand(),
-                    method.name()
-                ))
+                    method.title()
+                );
+                // Show the lead head code, which is useful when choosing methods for spliced
+                if let Some(code) = method.lead_head_code() {
+                    label_text.push_str(&format!(" ({})", code));
+                }
+                left_ui.selectable_label(i == selected_method, label_text)
             },
             |right_ui| {
+                // Because we're in a right-to-left block, the buttons are added from right to
+                // left (which feels like the reverse order)
+                if right_ui.button("edit").clicked() {
+                    let edit = MethodEdit::new(i, method);
+                    push_action(Action::SetMethodEdit(Some(edit)));
+                }
                 if method.num_rows == 0 {
-                    // Because we're in a right-to-left block, the buttons are added from right
-                    // to left (which feels like the reverse order)
                     if right_ui.button("del").clicked() {
-                        println!(
+                        push_action(Action::ShowError(format!(
                             "Can't delete methods.  Even {}, good though it is!",
                             method.name()
-                        );
-                    }
-                    if right_ui.button("edit").clicked() {
-                        println!(
-                            "Can't edit methods.  Even {}, good though it is!",
-                            method.name()
-                        );
+                        )));
                     }
                 } else {
                     // If the method is used, then display either 'x rows' or 'x/y rows',
@@ -179,47 +749,423 @@ fn draw_method_panel(ui: &mut Ui, full_state: &FullState) {
                 }
             },
         );
+        let (label_response, _) = response.inner;
+        if label_response.clicked() {
+            push_action(Action::SelectMethod(i));
+        }
+        // A small grid of the method, so that the user can check its place notation
+        egui::CollapsingHeader::new("Grid")
+            .id_source(("Method grid", i.index()))
+            .show(ui, |grid_ui| method_grid::draw(grid_ui, method));
+    }
+
+    if ui.button("Add method").clicked() {
+        push_action(Action::SetMethodEdit(Some(MethodEdit::new_method())));
     }
 }
 
-/// Recursively creates the GUI for a set of `MusicGroup`s
+fn draw_call_panel(ui: &mut Ui, full_state: &FullState, mut push_action: impl FnMut(Action)) {
+    if full_state.calls.is_empty() {
+        ui.label("No calls");
+    }
+    for (i, call) in full_state.calls.iter_enumerated() {
+        left_then_right(
+            ui,
+            // The main label sticks to the left
+            |left_ui| {
+                left_ui.label(format!(
+                    "(#{}, {}): {} {}",
+                    i.index(),
+                    call.symbol(),
+                    call.place_notation(),
+                    call.lead_location()
+                ))
+            },
+            |right_ui| {
+                if right_ui.button("edit").clicked() {
+                    push_action(Action::SetCallEdit(Some(CallEdit::new(i, call))));
+                }
+                // Swapping calls replaces every use of one call with the other (e.g. to swap the
+                // bob/single conventions of a composition)
+                if full_state.calls.len() > 1 {
+                    egui::ComboBox::from_id_source(("Swap call", i.index()))
+                        .selected_text("swap with")
+                        .show_ui(right_ui, |combo_ui| {
+                            for (other_i, other_call) in full_state.calls.iter_enumerated() {
+                                if other_i != i
+                                    && combo_ui
+                                        .selectable_label(false, other_call.symbol().to_string())
+                                        .clicked()
+                                {
+                                    push_action(Action::Comp(CompAction::SwapCalls(i, other_i)));
+                                }
+                            }
+                        });
+                }
+                // Display either 'x calls' or 'x/y calls', depending on whether or not all the
+                // call's uses are muted
+                let label_text = if call.num_proved_uses == call.num_uses {
+                    format!("{} calls", call.num_uses)
+                } else {
+                    format!("{}/{} calls", call.num_proved_uses, call.num_uses)
+                };
+                right_ui.label(label_text);
+            },
+        );
+    }
+
+    if ui.button("Add call").clicked() {
+        push_action(Action::SetCallEdit(Some(CallEdit::new_call())));
+    }
+
+    // Calls are labelled by the position of the observation bell after each call
+    let observation_bell = full_state.observation_bell();
+    egui::ComboBox::from_label("Observation bell")
+        .selected_text(observation_bell.name())
+        .show_ui(ui, |combo_ui| {
+            for bell in (0..full_state.stage.num_bells()).map(Bell::from_index) {
+                if combo_ui
+                    .selectable_label(bell == observation_bell, bell.name())
+                    .clicked()
+                    && bell != observation_bell
+                {
+                    push_action(Action::Comp(CompAction::SetObservationBell(bell)));
+                }
+            }
+        });
+}
+
+fn draw_courses_panel(
+    ui: &mut Ui,
+    full_state: &FullState,
+    rows_to_highlight: &mut HashSet<RowSource>,
+) {
+    for (part_idx, lead_heads) in full_state.lead_heads.iter_enumerated() {
+        let part_head = &full_state.part_heads.rows()[part_idx.index()];
+        egui::CollapsingHeader::new(format!("Part #{}: {}", part_idx.index() + 1, part_head))
+            .id_source(("Courses", part_idx.index()))
+            .show(ui, |part_ui| {
+                if lead_heads.is_empty() {
+                    part_ui.label("No lead heads");
+                }
+                // Course ends are drawn more strongly than the other lead heads, so that they
+                // stand out like they would in a printed composition
+                for lead_head in lead_heads {
+                    let label = if lead_head.is_course_end {
+                        egui::Label::new(format!("{} (course end)", lead_head.row)).strong()
+                    } else {
+                        egui::Label::new(lead_head.row.to_string()).weak()
+                    };
+                    // If this is being hovered, then highlight the lead head on the canvas
+                    if part_ui.label(label).hovered() {
+                        rows_to_highlight.insert(lead_head.source);
+                    }
+                }
+            });
+    }
+}
+
+/// Draws the controls for choosing a pair of handbells, along with which places the pair start
+/// each lead in (e.g. `"5-6 of Cambridge"`)
+fn draw_handbells_panel(
+    ui: &mut Ui,
+    full_state: &FullState,
+    handbell_pair: Option<(Bell, Bell)>,
+    rows_to_highlight: &mut HashSet<RowSource>,
+    mut push_action: impl FnMut(Action),
+) {
+    let pair_name = |(bell_a, bell_b): (Bell, Bell)| format!("{}-{}", bell_a, bell_b);
+    // Handbells are rung in pairs starting from the treble (1-2, 3-4, etc.)
+    let pairs = full_state.stage.bells().tuples().collect_vec();
+    egui::ComboBox::from_label("Pair")
+        .selected_text(handbell_pair.map_or_else(|| "None".to_owned(), pair_name))
+        .show_ui(ui, |combo_ui| {
+            if combo_ui
+                .selectable_label(handbell_pair.is_none(), "None")
+                .clicked()
+            {
+                push_action(Action::SetHandbellPair(None));
+            }
+            for &pair in &pairs {
+                if combo_ui
+                    .selectable_label(handbell_pair == Some(pair), pair_name(pair))
+                    .clicked()
+                {
+                    push_action(Action::SetHandbellPair(Some(pair)));
+                }
+            }
+        });
+
+    let (bell_a, bell_b) = match handbell_pair {
+        // The pair can be outside the stage if the composition has been replaced since the pair
+        // was chosen
+        Some(pair) if pairs.contains(&pair) => pair,
+        _ => return,
+    };
+    for (part_idx, lead_heads) in full_state.lead_heads.iter_enumerated() {
+        let part_head = &full_state.part_heads.rows()[part_idx.index()];
+        egui::CollapsingHeader::new(format!("Part #{}: {}", part_idx.index() + 1, part_head))
+            .id_source(("Handbells", part_idx.index()))
+            .show(ui, |part_ui| {
+                // The leftover rows don't start leads, so aren't worth listing
+                for lead_head in lead_heads {
+                    let method = match lead_head.method {
+                        Some(idx) => &full_state.methods[idx],
+                        None => continue,
+                    };
+                    // Unwraps are safe because `bell_a` and `bell_b` are both within the stage
+                    let place_a = lead_head.row.place_of(bell_a).unwrap();
+                    let place_b = lead_head.row.place_of(bell_b).unwrap();
+                    let label = format!(
+                        "{}-{} of {}",
+                        place_a.min(place_b) + 1,
+                        place_a.max(place_b) + 1,
+                        method.name()
+                    );
+                    // If this is being hovered, then highlight the lead head on the canvas
+                    if part_ui.label(label).hovered() {
+                        rows_to_highlight.insert(lead_head.source);
+                    }
+                }
+            });
+    }
+}
+
+fn draw_falseness_panel(
+    ui: &mut Ui,
+    full_state: &FullState,
+    tenors_together_fchs: bool,
+    rows_to_highlight: &mut HashSet<RowSource>,
+    mut push_action: impl FnMut(Action),
+) {
+    // Proving settings
+    let settings = full_state.proof_settings();
+    if let Some(bells) = settings.proved_bells() {
+        ui.label(format!("Only proving bells {}", bells.iter().join("")));
+    }
+    if !settings.allowed_repeats().is_empty() {
+        ui.label(format!(
+            "Allowing repeats of {}",
+            settings.allowed_repeats_string()
+        ));
+    }
+    if ui.button("Proving settings").clicked() {
+        push_action(Action::SetProofEdit(Some(ProofEdit::new(settings))));
+    }
+
+    let falseness = &full_state.falseness;
+    if falseness.is_true() {
+        ui.label("No false rows");
+    }
+    for (i, group) in falseness.groups.iter().enumerate() {
+        let response = ui.label(format!("Group #{}: {} rows", i, group.rows.len()));
+        // If this is being hovered, then highlight every row in the group
+        if response.hovered() {
+            group.add_row_sources(rows_to_highlight);
+        }
+    }
+
+    // Lead-level falseness
+    if !full_state.false_leads.is_empty() {
+        ui.separator();
+        ui.label(format!(
+            "{} pairs of false leads:",
+            full_state.false_leads.len()
+        ));
+    }
+    for (lead_a, lead_b) in &full_state.false_leads {
+        let response = ui.label(format!(
+            "{} is false against {}",
+            lead_description(full_state, lead_a),
+            lead_description(full_state, lead_b)
+        ));
+        // If this is being hovered, then highlight both leads
+        if response.hovered() {
+            lead_a.add_row_sources(rows_to_highlight);
+            lead_b.add_row_sources(rows_to_highlight);
+        }
+    }
+
+    // Method-level falseness
+    ui.separator();
+    ui.label("False course heads:");
+    // Stages with no tenors to keep together would have no course heads to filter out
+    let mut tenors_together = tenors_together_fchs && full_state.has_tenors();
+    if full_state.has_tenors()
+        && ui
+            .checkbox(&mut tenors_together, "Tenors together only")
+            .changed()
+    {
+        push_action(Action::SetTenorsTogetherFchs(tenors_together));
+    }
+    let methods = &full_state.methods;
+    // Falseness is symmetric, so only list each pair of methods once
+    for ((idx_a, method_a), (idx_b, method_b)) in methods
+        .iter_enumerated()
+        .cartesian_product(methods.iter_enumerated())
+        .filter(|((idx_a, _), (idx_b, _))| idx_a <= idx_b)
+    {
+        let course_heads = full_state.false_course_heads(idx_a, idx_b, tenors_together);
+        let fch_str = if course_heads.is_empty() {
+            "clean".to_owned()
+        } else {
+            course_heads.iter().join(" ")
+        };
+        ui.label(format!(
+            "{} against {}: {}",
+            method_a.shorthand(),
+            method_b.shorthand(),
+            fch_str
+        ));
+    }
+}
+
+/// A short description of a [`PlainLead`], e.g. `Y (frag 0, row 32, part 1)`
+fn lead_description(full_state: &FullState, lead: &PlainLead) -> String {
+    format!(
+        "{} (frag {}, row {}, part {})",
+        full_state.methods[lead.method].shorthand(),
+        lead.head.frag_index.index(),
+        lead.head.row_index.index(),
+        lead.head.part_index.index()
+    )
+}
+
+/// Draws every step in the undo history, so that the user can jump to any of them
+fn draw_history_panel(ui: &mut Ui, history: &History, mut push_action: impl FnMut(Action)) {
+    let current_step = history.current_step();
+    for (idx, description) in history.step_descriptions().enumerate() {
+        // Mark the steps which could be redone, since they'll be lost if a new edit is made
+        let label_text = if idx > current_step {
+            format!("{}: {} (undone)", idx, description)
+        } else {
+            format!("{}: {}", idx, description)
+        };
+        if ui
+            .selectable_label(idx == current_step, label_text)
+            .clicked()
+        {
+            push_action(Action::Comp(CompAction::JumpToStep(idx)));
+        }
+    }
+}
+
+/// Recursively creates the GUI for a set of `MusicGroup`s.  `music_defs` are the [`Music`]
+/// definitions which generated `musics`, and `path` is the path of the group containing them.
 fn draw_music_ui(
     ui: &mut Ui,
     musics: &[Rc<full::MusicGroup>],
+    music_defs: &[Music],
+    path: &[usize],
     rows_to_highlight: &mut HashSet<RowSource>,
+    push_action: &mut impl FnMut(Action),
 ) {
-    for m in musics {
-        draw_music_group_ui(m, ui, rows_to_highlight);
+    for (idx, (m, def)) in musics.iter().zip_eq(music_defs).enumerate() {
+        let mut sub_path = path.to_vec();
+        sub_path.push(idx);
+        draw_music_group_ui(m, def, sub_path, ui, rows_to_highlight, push_action);
     }
+
+    // Buttons to add new music to this group
+    ui.horizontal(|button_ui| {
+        if button_ui.button("Add music").clicked() {
+            let edit = MusicEdit::new_regex(path.to_vec());
+            push_action(Action::SetMusicEdit(Some(edit)));
+        }
+        if button_ui.button("Add anti-music").clicked() {
+            let edit = MusicEdit::new_anti_music(path.to_vec());
+            push_action(Action::SetMusicEdit(Some(edit)));
+        }
+        if button_ui.button("Add group").clicked() {
+            let edit = MusicEdit::new_group(path.to_vec());
+            push_action(Action::SetMusicEdit(Some(edit)));
+        }
+    });
 }
 
 /// Recursively creates the GUI for a single `MusicGroup`
 fn draw_music_group_ui(
     group: &full::MusicGroup,
+    def: &Music,
+    path: Vec<usize>,
     ui: &mut Ui,
     rows_to_highlight: &mut HashSet<RowSource>,
+    push_action: &mut impl FnMut(Action),
 ) {
     let full::MusicGroup {
         name,
         max_count,
+        score,
         inner,
     } = group;
 
+    // Show which parts the music is restricted to, if any
+    let name = match def.parts() {
+        Some(parts) => format!("{} (parts {})", name, music_edit::format_parts(Some(parts))),
+        None => name.to_owned(),
+    };
     let response = match inner {
-        MusicGroupInner::Leaf { rows_matched } => {
-            left_then_right(
+        MusicGroupInner::Leaf {
+            rows_matched,
+            wraps_matched,
+        } => {
+            let r = left_then_right(
                 ui,
                 |left_ui| left_ui.label(name),
-                |right_ui| right_ui.label(format!("{}/{}", rows_matched.len(), max_count)),
-            )
-            .response // Get the response from the entire horizontal layout
+                |right_ui| {
+                    let edit_clicked = right_ui.button("edit").clicked();
+                    let mut text = format!("{}/{}", rows_matched.len(), max_count);
+                    if !wraps_matched.is_empty() {
+                        text.push_str(&format!(" +{} wraps", wraps_matched.len()));
+                    }
+                    text.push_str(&format!(" ({})", score));
+                    // Make it obvious when anti-music is lowering the score
+                    let color = if *score < 0.0 {
+                        Color32::RED
+                    } else {
+                        right_ui.visuals().text_color()
+                    };
+                    right_ui.label(egui::Label::new(text).text_color(color));
+                    edit_clicked
+                },
+            );
+            let (_, edit_clicked) = r.inner;
+            if edit_clicked {
+                push_action(Action::SetMusicEdit(Some(MusicEdit::new(path, def))));
+            }
+            r.response // Get the response from the entire horizontal layout
         }
-        MusicGroupInner::Group { sub_groups, count } => {
-            let label = format!("{} ({}/{})", name, count, max_count);
+        MusicGroupInner::Group {
+            sub_groups,
+            count,
+            wrap_count,
+        } => {
+            let wraps = match wrap_count {
+                0 => String::new(),
+                n => format!(" +{} wraps", n),
+            };
+            let label = format!(
+                "{} ({}/{}{}, score {})",
+                name, count, max_count, wraps, score
+            );
             egui::CollapsingHeader::new(label)
-                .id_source(name)
+                .id_source(&path)
                 .show(ui, |sub_ui| {
-                    draw_music_ui(sub_ui, sub_groups, rows_to_highlight)
+                    // Unwrap is safe because `full::MusicGroup`s have the same shape as the
+                    // `Music` which generated them
+                    let sub_defs = def.sub_groups().unwrap();
+                    draw_music_ui(
+                        sub_ui,
+                        sub_groups,
+                        sub_defs,
+                        &path,
+                        rows_to_highlight,
+                        push_action,
+                    );
+                    if sub_ui.button("Edit group").clicked() {
+                        let edit = MusicEdit::new(path.clone(), def);
+                        push_action(Action::SetMusicEdit(Some(edit)));
+                    }
                 })
                 .header_response
         }
@@ -0,0 +1,167 @@
+//! Sound output used by playback, either as synthesised bells or as MIDI notes.  This needs ALSA
+//! on Linux, so is only compiled with the `audio` feature (which the web build, having no access
+//! to audio or MIDI devices, never enables).
+
+use std::{f32::consts::TAU, time::Duration};
+
+use bellframe::{Bell, Stage};
+use midir::{MidiOutput, MidiOutputConnection};
+use rodio::{OutputStream, OutputStreamHandle, Source};
+
+use crate::playback::SoundOutput;
+
+/// The sample rate of the synthesised bells, in Hz
+const SAMPLE_RATE: u32 = 44_100;
+/// How long each synthesised bell sounds for before it's cut off, in seconds
+const STRIKE_LENGTH: f32 = 2.0;
+/// The General MIDI program used for MIDI playback (tubular bells, counting from 0)
+const MIDI_PROGRAM: u8 = 14;
+/// The notes of a major scale, in semitones above the tonic.  Bells are tuned to descending major
+/// scales.
+const MAJOR_SCALE: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+/// The partials of a synthesised bell, as `(frequency relative to the nominal, amplitude, decay
+/// rate)`.  These approximate the hum, prime, tierce, quint, nominal and superquint of a real
+/// church bell.
+const PARTIALS: [(f32, f32, f32); 6] = [
+    (0.25, 0.3, 1.0),
+    (0.5, 0.4, 2.0),
+    (0.6, 0.3, 3.0),
+    (0.75, 0.15, 4.0),
+    (1.0, 0.5, 5.0),
+    (1.5, 0.2, 8.0),
+];
+/// The overall volume of the synthesised bells.  This is low so that overlapping bells don't clip.
+const BELL_VOLUME: f32 = 0.15;
+
+/// A device which can sound bells
+pub(crate) struct Sound {
+    output: Output,
+}
+
+enum Output {
+    Bells {
+        // The stream stops playing when it's dropped, so must be kept alive
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    },
+    Midi(MidiOutputConnection),
+}
+
+impl Sound {
+    /// Open the default audio device or the first MIDI output port
+    pub fn new(output: SoundOutput) -> Result<Self, String> {
+        let output = match output {
+            SoundOutput::Bells => {
+                let (stream, handle) = OutputStream::try_default()
+                    .map_err(|e| format!("Couldn't open audio device: {}", e))?;
+                Output::Bells {
+                    _stream: stream,
+                    handle,
+                }
+            }
+            SoundOutput::Midi => {
+                let midi_out =
+                    MidiOutput::new("Jigsaw").map_err(|e| format!("Couldn't start MIDI: {}", e))?;
+                let ports = midi_out.ports();
+                let port = ports.first().ok_or("There are no MIDI output ports")?;
+                let mut connection = midi_out
+                    .connect(port, "jigsaw-playback")
+                    .map_err(|e| format!("Couldn't connect to MIDI output: {}", e))?;
+                // If the program can't be set, the bells will just sound like another instrument
+                let _ = connection.send(&[0xC0, MIDI_PROGRAM]);
+                Output::Midi(connection)
+            }
+        };
+        Ok(Self { output })
+    }
+
+    /// Which kind of [`SoundOutput`] this is
+    pub fn output(&self) -> SoundOutput {
+        match self.output {
+            Output::Bells { .. } => SoundOutput::Bells,
+            Output::Midi(_) => SoundOutput::Midi,
+        }
+    }
+
+    /// Strike a given [`Bell`], tuned as if it were part of a ring of bells of a given [`Stage`]
+    pub fn strike(&mut self, bell: Bell, stage: Stage) {
+        let note = midi_note(bell, stage);
+        // Failing to sound a bell isn't worth interrupting the user over
+        match &mut self.output {
+            Output::Bells { handle, .. } => {
+                let _ = handle.play_raw(BellStrike::new(note));
+            }
+            Output::Midi(connection) => {
+                let _ = connection.send(&[0x80, note, 0]);
+                let _ = connection.send(&[0x90, note, 100]);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Sound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Sound({:?})", self.output())
+    }
+}
+
+/// The MIDI note of a [`Bell`] in a ring of a given [`Stage`].  The bells are tuned to a major
+/// scale, with the treble around an octave above middle C.
+fn midi_note(bell: Bell, stage: Stage) -> u8 {
+    let scale_note = |n: usize| 12 * (n / 7) as u8 + MAJOR_SCALE[n % 7];
+    let tenor_note = 72 - scale_note(stage.num_bells() - 1);
+    tenor_note + scale_note(stage.num_bells() - 1 - bell.index())
+}
+
+/// The sound of one bell being struck, synthesised as a sum of decaying sine waves
+struct BellStrike {
+    /// The frequency of the bell's nominal, in Hz
+    nominal: f32,
+    sample_idx: u32,
+}
+
+impl BellStrike {
+    fn new(midi_note: u8) -> Self {
+        Self {
+            nominal: 440.0 * 2f32.powf((midi_note as f32 - 69.0) / 12.0),
+            sample_idx: 0,
+        }
+    }
+}
+
+impl Iterator for BellStrike {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let t = self.sample_idx as f32 / SAMPLE_RATE as f32;
+        if t >= STRIKE_LENGTH {
+            return None;
+        }
+        self.sample_idx += 1;
+        let sample = PARTIALS
+            .iter()
+            .map(|&(freq, amplitude, decay)| {
+                amplitude * (-t * decay).exp() * (TAU * freq * self.nominal * t).sin()
+            })
+            .sum::<f32>();
+        Some(sample * BELL_VOLUME)
+    }
+}
+
+impl Source for BellStrike {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(STRIKE_LENGTH))
+    }
+}
@@ -0,0 +1,124 @@
+//! Drawing code for the window used to change where a fragment starts
+
+use bellframe::{RowBuf, Stage};
+use eframe::egui::{self, Color32};
+use jigsaw_comp::spec::FragStart;
+use jigsaw_utils::indexed_vec::FragIdx;
+
+use crate::{Action, CompAction};
+
+/// The state of the window used to change the start of a fragment.  Like
+/// [`CallEdit`](crate::call_edit::CallEdit), the start row can diverge from the undo history
+/// whilst the user is typing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StartEdit {
+    pub frag_idx: FragIdx,
+    pub start_row: String,
+}
+
+impl StartEdit {
+    /// Start editing the start of a fragment, with the start row filled in with its current value
+    pub(crate) fn new(frag_idx: FragIdx, frag_start: &FragStart) -> Self {
+        Self {
+            frag_idx,
+            start_row: frag_start.start_row.to_string(),
+        }
+    }
+}
+
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    edit: &StartEdit,
+    frag_start: &FragStart,
+    stage: Stage,
+    mut push_action: impl FnMut(Action),
+) {
+    let frag_idx = edit.frag_idx;
+    egui::Window::new(format!("Start of fragment {}", frag_idx.index()))
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            // Start row
+            ui.horizontal(|ui| {
+                let mut start_row = edit.start_row.clone();
+                ui.label("Start row:");
+                ui.text_edit_singleline(&mut start_row);
+                if start_row != edit.start_row {
+                    push_action(Action::SetStartEdit(Some(StartEdit {
+                        frag_idx,
+                        start_row,
+                    })));
+                }
+                if ui.button("Rounds").clicked() {
+                    push_action(Action::SetStartEdit(Some(StartEdit {
+                        frag_idx,
+                        start_row: RowBuf::rounds(stage).to_string(),
+                    })));
+                }
+            });
+            match RowBuf::parse_with_stage(edit.start_row.trim(), stage) {
+                Ok(start_row) => {
+                    let is_changed = start_row != frag_start.start_row;
+                    if ui
+                        .add(egui::Button::new("Set start row").enabled(is_changed))
+                        .clicked()
+                    {
+                        push_action(Action::Comp(CompAction::SetStartRow {
+                            frag_idx,
+                            start_row,
+                        }));
+                    }
+                }
+                Err(e) => {
+                    ui.label(egui::Label::new(format!("{}", e)).text_color(Color32::RED));
+                }
+            }
+
+            ui.separator();
+
+            // Position of the start row within the lead
+            ui.label(format!(
+                "Starts at row {} of a {}-row lead",
+                frag_start.sub_lead_index, frag_start.lead_len
+            ));
+            let mut set_sub_lead_index = |sub_lead_index: usize| {
+                push_action(Action::Comp(CompAction::SetStartSubLeadIndex {
+                    frag_idx,
+                    sub_lead_index,
+                }));
+            };
+            ui.horizontal(|ui| {
+                if ui.button("Lead head start").clicked() {
+                    set_sub_lead_index(0);
+                }
+                // A snap start replaces the lead's first handstroke with rounds, so the first
+                // change is rung at backstroke
+                if ui.button("Backstroke snap start").clicked() {
+                    set_sub_lead_index(1);
+                }
+                if ui.button("Half-lead start").clicked() {
+                    set_sub_lead_index(frag_start.lead_len / 2);
+                }
+            });
+            ui.horizontal(|ui| {
+                let idx = frag_start.sub_lead_index;
+                if ui
+                    .add(egui::Button::new("Earlier").enabled(idx > 0))
+                    .clicked()
+                {
+                    set_sub_lead_index(idx - 1);
+                }
+                let is_last = idx + 1 >= frag_start.lead_len;
+                if ui
+                    .add(egui::Button::new("Later").enabled(!is_last))
+                    .clicked()
+                {
+                    set_sub_lead_index(idx + 1);
+                }
+            });
+
+            if ui.button("Close").clicked() {
+                push_action(Action::SetStartEdit(None));
+            }
+        });
+}
@@ -0,0 +1,335 @@
+//! Code to render the canvas as an SVG image, so that compositions can be printed or embedded in
+//! other documents.  This reproduces the layout of the canvas, but writes the SVG directly rather
+//! than going through egui's renderer.
+
+use std::fmt::Write;
+
+use bellframe::Bell;
+use eframe::egui::{Color32, Pos2, Rect, Vec2};
+use itertools::Itertools;
+use jigsaw_comp::full::{Fragment, FullState};
+use jigsaw_utils::indexed_vec::PartIdx;
+
+use crate::{
+    canvas::{music_highlight, place_bell_label, FALSE_ROW_HATCH},
+    config::Config,
+    theme::Hatch,
+};
+
+/// The space left round the edges of the image, in points
+const MARGIN: f32 = 10.0;
+
+/// Renders the canvas as an SVG image.  Only the parts which are drawn on the canvas are rendered,
+/// but the image is cropped to fit the fragments rather than the view of the canvas.
+pub(crate) fn render(
+    full_state: &FullState,
+    config: &Config,
+    part_being_viewed: PartIdx,
+) -> String {
+    let parts_to_draw = if config.show_all_parts {
+        (0..full_state.part_heads.len())
+            .map(PartIdx::new)
+            .collect_vec()
+    } else {
+        vec![part_being_viewed]
+    };
+    let part_col_width = config.part_col_width(full_state.stage);
+
+    let mut svg = Svg::new(config);
+    for frag in &full_state.fragments {
+        let num_lines = if config.course_view {
+            frag.courses.len().max(1)
+        } else {
+            frag.num_rows()
+        };
+        for (col_idx, &part) in parts_to_draw.iter().enumerate() {
+            let rows_bbox = Rect::from_min_size(
+                Pos2::from(frag.position) + Vec2::new(col_idx as f32 * part_col_width, 0.0),
+                Vec2::new(
+                    config.col_width * full_state.stage.num_bells() as f32,
+                    config.row_height * num_lines as f32,
+                ),
+            );
+            // Like the canvas, only annotate the outside edges of the parts
+            let annotate_calls = col_idx == 0;
+            let annotate_methods = col_idx == parts_to_draw.len() - 1;
+            svg.draw_frag(frag, part, rows_bbox, annotate_calls, annotate_methods);
+        }
+    }
+    svg.finish()
+}
+
+/// An SVG image which is being built
+#[derive(Debug)]
+struct Svg<'a> {
+    config: &'a Config,
+    /// The SVG elements drawn so far
+    body: String,
+    /// The smallest [`Rect`] containing everything drawn so far
+    bounds: Option<Rect>,
+}
+
+impl<'a> Svg<'a> {
+    fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            body: String::new(),
+            bounds: None,
+        }
+    }
+
+    /// Wraps the elements drawn so far in an `<svg>` tag which is exactly big enough to fit them
+    fn finish(self) -> String {
+        let bounds = self
+            .bounds
+            .unwrap_or_else(|| Rect::from_min_size(Pos2::ZERO, Vec2::ZERO))
+            .expand(MARGIN);
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             viewBox=\"{} {} {} {}\">\n{}</svg>\n",
+            bounds.width(),
+            bounds.height(),
+            bounds.min.x,
+            bounds.min.y,
+            bounds.width(),
+            bounds.height(),
+            self.body
+        )
+    }
+
+    /// Draws one part of a [`Fragment`], whose rows are contained in `rows_bbox`
+    fn draw_frag(
+        &mut self,
+        frag: &Fragment,
+        part: PartIdx,
+        rows_bbox: Rect,
+        annotate_calls: bool,
+        annotate_methods: bool,
+    ) {
+        let config = self.config;
+        let palette = config.palette();
+        let padded_bbox = rows_bbox.expand2(config.frag_padding_vec());
+        self.rect(padded_bbox, palette.frag_background);
+        let link_markers = [
+            (frag.link_groups.top, padded_bbox.min.y),
+            (frag.link_groups.bottom, padded_bbox.max.y),
+        ];
+        for &(group, y) in link_markers.iter() {
+            if let Some(group) = group {
+                let points = [
+                    Pos2::new(padded_bbox.min.x, y),
+                    Pos2::new(padded_bbox.max.x, y),
+                ];
+                self.line(&points, config.link_line_width, config.link_color(group));
+            }
+        }
+
+        if config.course_view {
+            self.draw_courses(frag, part, rows_bbox, annotate_methods);
+            return;
+        }
+
+        let show_bluelines = config.show_bluelines || frag.show_bluelines;
+        let bell_lines = if show_bluelines {
+            &config.blueline_bells
+        } else {
+            &config.bell_lines
+        };
+        let mut lines: Vec<(Bell, Vec<Pos2>)> = bell_lines
+            .keys()
+            .sorted()
+            .map(|&bell| (bell, Vec::new()))
+            .collect();
+
+        for (row_idx, data) in frag.rows_in_part(part) {
+            let y_coord = rows_bbox.min.y + row_idx.index() as f32 * config.row_height;
+            let text_y_coord = y_coord + config.row_height * config.text_pos_y;
+            // Fade out non-proved rows, like the canvas does
+            let foreground_color = if data.is_proved {
+                palette.foreground
+            } else {
+                Color32::GRAY
+            };
+
+            if data.false_group.is_some() {
+                let rect = Rect::from_min_size(
+                    Pos2::new(rows_bbox.min.x, y_coord),
+                    Vec2::new(rows_bbox.width(), config.row_height),
+                );
+                self.rect(rect, palette.false_row);
+                self.hatch(rect, FALSE_ROW_HATCH);
+            }
+            for (col_idx, bell) in data.row.bell_iter().enumerate() {
+                let rect = Rect::from_min_size(
+                    Pos2::new(rows_bbox.min.x + col_idx as f32 * config.col_width, y_coord),
+                    config.bell_box_size(),
+                );
+                if let Some((fill, hatch)) = music_highlight(&palette, &data.music_counts, col_idx)
+                {
+                    self.rect(rect, fill);
+                    if let Some(hatch) = hatch {
+                        self.hatch(rect, hatch);
+                    }
+                }
+                if let Some((_, points)) = lines.iter_mut().find(|(b, _)| *b == bell) {
+                    points.push(rect.center());
+                } else if !show_bluelines {
+                    let pos = Pos2::new(
+                        rect.min.x + config.col_width * config.text_pos_x,
+                        text_y_coord,
+                    );
+                    self.text(pos, &bell.name(), foreground_color);
+                }
+            }
+            if let (true, Some(place)) = (config.show_place_bells, frag.place_bell(part, row_idx)) {
+                let pos = Pos2::new(rows_bbox.max.x + config.col_width * 0.5, text_y_coord);
+                self.text(pos, &place_bell_label(place), config.place_bell_color);
+            }
+            if let (true, Some(method)) = (annotate_methods, &data.method_annotation) {
+                let pos = Pos2::new(rows_bbox.max.x + config.method_name_offset(), text_y_coord);
+                self.text(pos, &method.name(), foreground_color);
+            }
+            if let (true, Some(label)) = (annotate_calls, frag.call_label(part, row_idx)) {
+                let pos = Pos2::new(rows_bbox.min.x - config.col_width * 2.0, text_y_coord);
+                self.text(pos, &label, foreground_color);
+            }
+            if data.ruleoff_above {
+                let points = [
+                    Pos2::new(rows_bbox.min.x, y_coord),
+                    Pos2::new(rows_bbox.max.x, y_coord),
+                ];
+                self.line(&points, config.ruleoff_line_width, foreground_color);
+            }
+        }
+
+        for (bell, points) in lines {
+            let (width, color) = bell_lines[&bell];
+            self.line(&points, width * config.col_width, color);
+        }
+    }
+
+    /// Draws one part of a [`Fragment`] in the course view, where each course is summarised on
+    /// one line by its course end and calling
+    fn draw_courses(
+        &mut self,
+        frag: &Fragment,
+        part: PartIdx,
+        rows_bbox: Rect,
+        annotate_methods: bool,
+    ) {
+        let config = self.config;
+        let foreground_color = config.palette().foreground;
+        for (line_idx, course) in frag.courses.iter().enumerate() {
+            let y_coord = rows_bbox.min.y + line_idx as f32 * config.row_height;
+            let text_y_coord = y_coord + config.row_height * config.text_pos_y;
+            let course_end = frag.row_in_part(part, course.end);
+            for (col_idx, bell) in course_end.bell_iter().enumerate() {
+                let pos = Pos2::new(
+                    rows_bbox.min.x + config.col_width * (col_idx as f32 + config.text_pos_x),
+                    text_y_coord,
+                );
+                self.text(pos, &bell.name(), foreground_color);
+            }
+            if annotate_methods && !course.calling.is_empty() {
+                let pos = Pos2::new(rows_bbox.max.x + config.col_width, text_y_coord);
+                self.text(pos, &course.calling, foreground_color);
+            }
+            if line_idx > 0 {
+                let points = [
+                    Pos2::new(rows_bbox.min.x, y_coord),
+                    Pos2::new(rows_bbox.max.x, y_coord),
+                ];
+                self.line(&points, config.ruleoff_line_width, foreground_color);
+            }
+        }
+    }
+
+    /* Primitives */
+
+    fn rect(&mut self, rect: Rect, fill: Color32) {
+        writeln!(
+            self.body,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            rect.min.x,
+            rect.min.y,
+            rect.width(),
+            rect.height(),
+            svg_color(fill)
+        )
+        .unwrap();
+        self.extend_bounds(rect);
+    }
+
+    /// Draws a [`Hatch`] pattern over a highlighted [`Rect`], if the user has turned them on
+    fn hatch(&mut self, rect: Rect, hatch: Hatch) {
+        if !self.config.hatch_highlights {
+            return;
+        }
+        let color = self.config.palette().hatch_color();
+        for points in hatch.lines(rect, self.config.hatch_spacing()) {
+            self.line(&points, 1.0, color);
+        }
+    }
+
+    fn line(&mut self, points: &[Pos2], width: f32, color: Color32) {
+        if points.is_empty() {
+            return;
+        }
+        let points_str = points.iter().map(|p| format!("{},{}", p.x, p.y)).join(" ");
+        writeln!(
+            self.body,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            points_str,
+            svg_color(color),
+            width
+        )
+        .unwrap();
+        let mut line_bounds = Rect::NOTHING;
+        for &p in points {
+            line_bounds.extend_with(p);
+        }
+        self.extend_bounds(line_bounds);
+    }
+
+    /// Draws a line of text, where `pos` is the top-left corner of the text
+    fn text(&mut self, pos: Pos2, text: &str, color: Color32) {
+        let font_size = self.config.font_size;
+        writeln!(
+            self.body,
+            "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\" \
+             dominant-baseline=\"hanging\" fill=\"{}\">{}</text>",
+            pos.x,
+            pos.y,
+            font_size,
+            svg_color(color),
+            xml_escape(text)
+        )
+        .unwrap();
+        // We don't know exactly how wide the text will be, so estimate it from the number of
+        // characters (assuming a monospace font which is roughly as wide as a bell's column)
+        let size = Vec2::new(
+            text.chars().count() as f32 * self.config.col_width,
+            font_size,
+        );
+        self.extend_bounds(Rect::from_min_size(pos, size));
+    }
+
+    fn extend_bounds(&mut self, rect: Rect) {
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => bounds.union(rect),
+            None => rect,
+        });
+    }
+}
+
+/// Formats a [`Color32`] as an SVG colour, ignoring its alpha channel
+fn svg_color(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+/// Escapes the characters which can't appear directly in SVG text
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
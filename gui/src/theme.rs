@@ -0,0 +1,220 @@
+//! Colour themes, which control the colours of both egui's widgets and the canvas
+
+use eframe::egui::{Color32, Pos2, Rect, Rgba, Stroke, Visuals};
+use serde::{Deserialize, Serialize};
+
+/// The colour schemes which Jigsaw can be drawn with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Theme {
+    #[default]
+    Dark,
+    Light,
+    /// Black and white text, with strongly saturated highlights
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Dark, Theme::Light, Theme::HighContrast];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+            Theme::HighContrast => "High contrast",
+        }
+    }
+
+    /// The [`Visuals`] used to draw egui's widgets (e.g. the side panel and windows)
+    pub fn visuals(self) -> Visuals {
+        match self {
+            Theme::Dark => Visuals::dark(),
+            Theme::Light => Visuals::light(),
+            Theme::HighContrast => {
+                let mut visuals = Visuals::dark();
+                visuals.override_text_color = Some(Color32::WHITE);
+                visuals.extreme_bg_color = Color32::BLACK;
+                visuals.widgets.noninteractive.bg_fill = Color32::BLACK;
+                visuals.widgets.noninteractive.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+                visuals.widgets.noninteractive.fg_stroke = Stroke::new(1.0, Color32::WHITE);
+                visuals.widgets.inactive.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+                visuals
+            }
+        }
+    }
+
+    /// The colours used to draw [`Fragment`](jigsaw_comp::full::Fragment)s, with falseness and
+    /// music highlighted using a given set of [`HighlightColors`]
+    pub fn palette(self, highlight_colors: HighlightColors) -> Palette {
+        let mut palette = match self {
+            Theme::Dark => Palette {
+                canvas_background: Color32::from_gray(27),
+                frag_background: Color32::BLACK,
+                foreground: Color32::WHITE,
+                false_row: Color32::from_rgb(100, 0, 0),
+                selection: Color32::from_rgb(30, 50, 100),
+                handbell_pair: Color32::from_rgb(90, 70, 20),
+                highlighted_bell: Color32::from_rgb(20, 80, 60),
+                music: Color32::from_rgb(50, 100, 0),
+                anti_music: Color32::from_rgb(110, 30, 30),
+                wraps: Color32::from_rgb(0, 80, 110),
+                comes_round: Color32::from_rgb(80, 200, 80),
+                unfinished: Color32::from_rgb(230, 160, 40),
+            },
+            Theme::Light => Palette {
+                canvas_background: Color32::from_gray(220),
+                frag_background: Color32::WHITE,
+                foreground: Color32::BLACK,
+                false_row: Color32::from_rgb(255, 180, 180),
+                selection: Color32::from_rgb(180, 200, 255),
+                handbell_pair: Color32::from_rgb(250, 225, 150),
+                highlighted_bell: Color32::from_rgb(170, 230, 200),
+                music: Color32::from_rgb(190, 235, 150),
+                anti_music: Color32::from_rgb(240, 170, 170),
+                wraps: Color32::from_rgb(160, 210, 240),
+                comes_round: Color32::from_rgb(0, 140, 0),
+                unfinished: Color32::from_rgb(200, 110, 0),
+            },
+            Theme::HighContrast => Palette {
+                canvas_background: Color32::from_gray(60),
+                frag_background: Color32::BLACK,
+                foreground: Color32::WHITE,
+                false_row: Color32::from_rgb(180, 0, 0),
+                selection: Color32::from_rgb(0, 0, 200),
+                handbell_pair: Color32::from_rgb(150, 100, 0),
+                highlighted_bell: Color32::from_rgb(0, 130, 60),
+                music: Color32::from_rgb(0, 130, 0),
+                anti_music: Color32::from_rgb(180, 0, 0),
+                wraps: Color32::from_rgb(0, 90, 180),
+                comes_round: Color32::from_rgb(0, 255, 0),
+                unfinished: Color32::from_rgb(255, 170, 0),
+            },
+        };
+        if highlight_colors == HighlightColors::ColorBlind {
+            // Colours from the Okabe-Ito palette, toned down so that the bell names drawn over
+            // them are still readable
+            let background = palette.frag_background;
+            let strength = self.highlight_strength();
+            let tone = |r, g, b| mix(Color32::from_rgb(r, g, b), background, strength);
+            palette.false_row = tone(204, 121, 167); // Reddish purple
+            palette.music = tone(0, 114, 178); // Blue
+            palette.anti_music = tone(213, 94, 0); // Vermillion
+            palette.wraps = tone(240, 228, 66); // Yellow
+        }
+        palette
+    }
+
+    /// How much of a full-strength highlight colour is mixed into the fragment background
+    fn highlight_strength(self) -> f32 {
+        match self {
+            Theme::Dark => 0.5,
+            Theme::Light => 0.4,
+            Theme::HighContrast => 0.8,
+        }
+    }
+}
+
+/// The colours used to highlight falseness and music, which can be chosen independently of the
+/// [`Theme`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum HighlightColors {
+    /// Red for falseness and anti-music, green for music and blue for wraps
+    #[default]
+    Standard,
+    /// Colours which can be told apart with any of the common forms of colour-blindness
+    ColorBlind,
+}
+
+impl HighlightColors {
+    pub const ALL: [HighlightColors; 2] = [HighlightColors::Standard, HighlightColors::ColorBlind];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HighlightColors::Standard => "Standard",
+            HighlightColors::ColorBlind => "Colour-blind friendly",
+        }
+    }
+}
+
+/// A pattern of lines drawn over highlighted rows or bells, so that different kinds of highlight
+/// can be told apart without relying on colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Hatch {
+    /// Lines going up to the right (like `/`)
+    Forward,
+    /// Lines going down to the right (like `\`)
+    Backward,
+    /// Both [`Hatch::Forward`] and [`Hatch::Backward`]
+    Cross,
+}
+
+impl Hatch {
+    /// The line segments which cover a [`Rect`] with this pattern.  The lines are aligned to a
+    /// global grid, so the patterns in adjacent [`Rect`]s join up.
+    pub fn lines(self, rect: Rect, spacing: f32) -> Vec<[Pos2; 2]> {
+        let mut lines = Vec::new();
+        if self != Hatch::Backward {
+            // Lines of the form `x + y = c`
+            let mut c = ((rect.min.x + rect.min.y) / spacing).ceil() * spacing;
+            while c < rect.max.x + rect.max.y {
+                let start_x = rect.min.x.max(c - rect.max.y);
+                let end_x = rect.max.x.min(c - rect.min.y);
+                lines.push([Pos2::new(start_x, c - start_x), Pos2::new(end_x, c - end_x)]);
+                c += spacing;
+            }
+        }
+        if self != Hatch::Forward {
+            // Lines of the form `x - y = c`
+            let mut c = ((rect.min.x - rect.max.y) / spacing).ceil() * spacing;
+            while c < rect.max.x - rect.min.y {
+                let start_x = rect.min.x.max(c + rect.min.y);
+                let end_x = rect.max.x.min(c + rect.max.y);
+                lines.push([Pos2::new(start_x, start_x - c), Pos2::new(end_x, end_x - c)]);
+                c += spacing;
+            }
+        }
+        lines
+    }
+}
+
+/// Linearly interpolate (in sRGB space) from `background` to `color`
+fn mix(color: Color32, background: Color32, amount: f32) -> Color32 {
+    let channel = |c: u8, bg: u8| (bg as f32 + (c as f32 - bg as f32) * amount).round() as u8;
+    Color32::from_rgb(
+        channel(color.r(), background.r()),
+        channel(color.g(), background.g()),
+        channel(color.b(), background.b()),
+    )
+}
+
+/// The colours of the canvas in a given [`Theme`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Palette {
+    /// The colour of the canvas between the fragments
+    pub canvas_background: Color32,
+    pub frag_background: Color32,
+    /// The colour of bell names, annotations and rule-offs
+    pub foreground: Color32,
+    /// The background colour of rows which are rung more than once
+    pub false_row: Color32,
+    /// The background colour of rows which the user has selected
+    pub selection: Color32,
+    /// The background colour of the bells in the handbell pair being viewed
+    pub handbell_pair: Color32,
+    /// The background colour of the bell whose path is being highlighted
+    pub highlighted_bell: Color32,
+    /// The background colours of bells which are part of music, anti-music or wraps
+    pub music: Color32,
+    pub anti_music: Color32,
+    pub wraps: Color32,
+    /// The colours of the markers round the leftover rows of fragments which come round, or
+    /// which need to be continued
+    pub comes_round: Color32,
+    pub unfinished: Color32,
+}
+
+impl Palette {
+    /// The colour of the [`Hatch`] patterns drawn over highlights
+    pub fn hatch_color(&self) -> Color32 {
+        Rgba::from(self.foreground).multiply(0.4).into()
+    }
+}
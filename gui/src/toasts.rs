@@ -0,0 +1,80 @@
+//! Short-lived notifications ('toasts') drawn over the bottom-left corner of the screen.  These
+//! tell the user about errors (which would otherwise go unnoticed, especially on the web build)
+//! and the results of large operations like saving or exporting.
+
+use eframe::egui::{self, Align2, Color32, Order, Vec2};
+
+/// How long each toast is shown for, in seconds
+const TOAST_DURATION: f64 = 5.0;
+/// The maximum number of toasts shown at once.  If more are added, the oldest ones are removed.
+const MAX_TOASTS: usize = 5;
+
+/// The toasts which are currently being shown, oldest first
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+#[derive(Debug, Clone)]
+struct Toast {
+    kind: ToastKind,
+    text: String,
+    /// The time (in the same units as [`egui::InputState::time`]) after which this toast is
+    /// removed
+    expiry_time: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToastKind {
+    Success,
+    Error,
+}
+
+impl Toasts {
+    /// Show a toast confirming that something succeeded
+    pub(crate) fn success(&mut self, text: impl Into<String>, time: f64) {
+        self.push(ToastKind::Success, text.into(), time);
+    }
+
+    /// Show a toast saying that something failed
+    pub(crate) fn error(&mut self, text: impl Into<String>, time: f64) {
+        self.push(ToastKind::Error, text.into(), time);
+    }
+
+    fn push(&mut self, kind: ToastKind, text: String, time: f64) {
+        self.toasts.push(Toast {
+            kind,
+            text,
+            expiry_time: time + TOAST_DURATION,
+        });
+        let num_excess_toasts = self.toasts.len().saturating_sub(MAX_TOASTS);
+        self.toasts.drain(..num_excess_toasts);
+    }
+
+    /// Remove every toast which has been shown for long enough
+    pub(crate) fn remove_expired(&mut self, time: f64) {
+        self.toasts.retain(|toast| toast.expiry_time > time);
+    }
+
+    pub(crate) fn draw(&self, ctx: &egui::CtxRef) {
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Area::new("toasts")
+            .order(Order::Foreground)
+            .anchor(Align2::LEFT_BOTTOM, Vec2::new(10.0, -10.0))
+            .show(ctx, |ui| {
+                for toast in &self.toasts {
+                    let color = match toast.kind {
+                        ToastKind::Success => Color32::from_rgb(120, 200, 80),
+                        ToastKind::Error => Color32::from_rgb(230, 80, 80),
+                    };
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.colored_label(color, &toast.text);
+                    });
+                }
+            });
+        // egui only repaints when there's input, so keep repainting until the toasts expire
+        ctx.request_repaint();
+    }
+}
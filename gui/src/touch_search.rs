@@ -0,0 +1,246 @@
+//! Drawing code for the window used to search for touches of the composition's methods and calls
+
+use std::collections::HashSet;
+
+use eframe::egui::{self, Color32, Pos2, Vec2};
+use jigsaw_comp::{
+    full::FullState,
+    search::{SearchGoal, SearchParams, Touch},
+};
+use jigsaw_utils::indexed_vec::{CallIdx, FragIdx, MethodIdx};
+
+use crate::{Action, CompAction};
+
+/// The number of touches kept by a search
+const MAX_RESULTS: usize = 100;
+/// The offset from the top-left corner of the view to where added touches are placed, in points
+const ADDED_TOUCH_OFFSET: Vec2 = Vec2::new(100.0, 100.0);
+
+/// The state of the window used to search for touches.  By default, every method and call is
+/// used in the search.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TouchSearch {
+    /// The methods which the user has chosen not to use
+    pub excluded_methods: HashSet<MethodIdx>,
+    /// The calls which the user has chosen not to use
+    pub excluded_calls: HashSet<CallIdx>,
+    pub min_length: String,
+    pub max_length: String,
+    pub require_truth: bool,
+    /// If `true`, the search tries every touch and keeps the ones with the best music.
+    /// Otherwise, it stops at the first touches it finds.
+    pub optimise_music: bool,
+    /// The touches found by the last search, or `None` if no search has been run yet
+    pub results: Option<Vec<Touch>>,
+    /// The fragments `(frag_a, frag_b)` which the touches should join (by ringing from the end
+    /// of `frag_a` to the start of `frag_b`), or `None` if the touches start and end at rounds
+    pub join: Option<(FragIdx, FragIdx)>,
+}
+
+impl Default for TouchSearch {
+    fn default() -> Self {
+        Self {
+            excluded_methods: HashSet::new(),
+            excluded_calls: HashSet::new(),
+            min_length: "100".to_owned(),
+            max_length: "500".to_owned(),
+            require_truth: true,
+            optimise_music: false,
+            results: None,
+            join: None,
+        }
+    }
+}
+
+impl TouchSearch {
+    /// The state of the window when searching for touches which join `frag_b` onto the end of
+    /// `frag_a`.  These touches are usually short, so there's no minimum length.
+    pub fn joining(frag_a: FragIdx, frag_b: FragIdx) -> Self {
+        Self {
+            min_length: "0".to_owned(),
+            join: Some((frag_a, frag_b)),
+            ..Self::default()
+        }
+    }
+
+    /// Creates the [`SearchParams`] represented by this window, or an error message explaining
+    /// why the search can't be run
+    fn params(&self, full_state: &FullState) -> Result<SearchParams, String> {
+        let parse_length = |s: &str, name: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid {} length '{}'", name, s))
+        };
+        let min_length = parse_length(&self.min_length, "minimum")?;
+        let max_length = parse_length(&self.max_length, "maximum")?;
+        let methods = full_state
+            .methods
+            .indices()
+            .filter(|idx| !self.excluded_methods.contains(idx))
+            .collect::<Vec<_>>();
+        if methods.is_empty() {
+            return Err("No methods to search with".to_owned());
+        }
+        let calls = full_state
+            .calls
+            .indices()
+            .filter(|idx| !self.excluded_calls.contains(idx))
+            .collect();
+        Ok(SearchParams {
+            methods,
+            calls,
+            min_length,
+            max_length,
+            require_truth: self.require_truth,
+            goal: if self.optimise_music {
+                SearchGoal::BestMusic(MAX_RESULTS)
+            } else {
+                SearchGoal::FirstTouches(MAX_RESULTS)
+            },
+        })
+    }
+}
+
+/// Draw the touch search window.  `leads_tried` is the progress of the running search, or `None`
+/// if no search is running.
+pub(crate) fn draw(
+    ctx: &egui::CtxRef,
+    search: &TouchSearch,
+    leads_tried: Option<usize>,
+    full_state: &FullState,
+    camera_pos: Pos2,
+    mut push_action: impl FnMut(Action),
+) {
+    let title = match search.join {
+        Some(_) => "Fill in the gap",
+        None => "Search for touches",
+    };
+    egui::Window::new(title).collapsible(false).show(ctx, |ui| {
+        let mut new_search = search.clone();
+
+        if let Some((frag_a, frag_b)) = search.join {
+            ui.label(format!(
+                "Joining fragment {} onto the end of fragment {}",
+                frag_b.index(),
+                frag_a.index()
+            ));
+        }
+        // Methods and calls
+        ui.label("Methods:");
+        for (idx, method) in full_state.methods.iter_enumerated() {
+            let mut is_used = !search.excluded_methods.contains(&idx);
+            if ui.checkbox(&mut is_used, method.title()).changed() {
+                toggle(&mut new_search.excluded_methods, idx, is_used);
+            }
+        }
+        ui.label("Calls:");
+        for (idx, call) in full_state.calls.iter_enumerated() {
+            let mut is_used = !search.excluded_calls.contains(&idx);
+            let label = format!("{} ({})", call.symbol(), call.place_notation());
+            if ui.checkbox(&mut is_used, label).changed() {
+                toggle(&mut new_search.excluded_calls, idx, is_used);
+            }
+        }
+        // Other constraints
+        egui::Grid::new("touch_search_grid").show(ui, |grid_ui| {
+            grid_ui.label("Min length:");
+            grid_ui.text_edit_singleline(&mut new_search.min_length);
+            grid_ui.end_row();
+
+            grid_ui.label("Max length:");
+            grid_ui.text_edit_singleline(&mut new_search.max_length);
+            grid_ui.end_row();
+        });
+        ui.checkbox(&mut new_search.require_truth, "Only true touches");
+        ui.checkbox(&mut new_search.optimise_music, "Optimise music");
+        if &new_search != search {
+            push_action(Action::SetTouchSearch(Some(new_search)));
+        }
+
+        let params = search.params(full_state);
+        if let Err(e) = &params {
+            ui.label(egui::Label::new(e).text_color(Color32::RED));
+        }
+        ui.horizontal(|button_ui| {
+            if let Some(leads_tried) = leads_tried {
+                if button_ui.button("Cancel").clicked() {
+                    push_action(Action::CancelSearch);
+                }
+                button_ui.label(format!("Searching... ({} leads tried)", leads_tried));
+            } else if button_ui
+                .add(egui::Button::new("Search").enabled(params.is_ok()))
+                .clicked()
+            {
+                // Unwrap is safe because the button is disabled if the params are invalid
+                push_action(Action::SearchTouches {
+                    params: params.unwrap(),
+                    join: search.join,
+                });
+            }
+            if button_ui.button("Close").clicked() {
+                push_action(Action::SetTouchSearch(None));
+            }
+        });
+
+        // Results
+        let touches = match &search.results {
+            Some(touches) => touches,
+            None => return,
+        };
+        ui.separator();
+        if touches.is_empty() && leads_tried.is_none() {
+            ui.label("No touches found");
+        }
+        egui::ScrollArea::auto_sized().show(ui, |results_ui| {
+            for touch in touches {
+                results_ui.horizontal(|touch_ui| {
+                    if let Some((frag_a, frag_b)) = search.join {
+                        // Joining the fragments changes their indices, so the search can
+                        // only be used once
+                        if touch_ui.button("Insert").clicked() {
+                            push_action(Action::Comp(CompAction::JoinWithTouch {
+                                frag_a,
+                                frag_b,
+                                touch: touch.clone(),
+                            }));
+                            push_action(Action::SetTouchSearch(None));
+                        }
+                    } else if touch_ui.button("Add").clicked() {
+                        push_action(Action::Comp(CompAction::AddTouch {
+                            touch: touch.clone(),
+                            position: camera_pos + ADDED_TOUCH_OFFSET,
+                        }));
+                    }
+                    touch_ui.label(format!(
+                        "{} rows, score {}: {}",
+                        touch.length,
+                        touch.music_score,
+                        touch_description(full_state, touch)
+                    ));
+                });
+            }
+        });
+    });
+}
+
+/// A short description of a [`Touch`], giving the method and call of every lead (e.g.
+/// `B B- Y Ys`)
+pub(crate) fn touch_description(full_state: &FullState, touch: &Touch) -> String {
+    let lead_strings = touch.leads.iter().map(|lead| {
+        let shorthand = full_state.methods[lead.method].shorthand();
+        match lead.call {
+            Some(call_idx) => format!("{}{}", shorthand, full_state.calls[call_idx].symbol()),
+            None => shorthand,
+        }
+    });
+    lead_strings.collect::<Vec<_>>().join(" ")
+}
+
+/// Adds `idx` to the excluded set if it's no longer used, or removes it if it's used again
+fn toggle<I: std::hash::Hash + Eq>(excluded: &mut HashSet<I>, idx: I, is_used: bool) {
+    if is_used {
+        excluded.remove(&idx);
+    } else {
+        excluded.insert(idx);
+    }
+}
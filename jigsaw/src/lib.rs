@@ -8,10 +8,43 @@ pub use jigsaw_gui::JigsawApp;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
-/// Start Jigsaw's GUI in a given canvas window
+#[cfg(target_arch = "wasm32")]
+use jigsaw_comp::spec::CompSpec;
+
+/// Start Jigsaw's GUI in a given canvas window.  If the page's URL has a fragment (i.e. the page
+/// was opened from a share link), then the composition stored in the fragment is loaded.
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub fn start(canvas_id: &str) -> Result<(), eframe::wasm_bindgen::JsValue> {
-    let app = gui::JigsawApp::example();
+    let mut app = JigsawApp::example();
+    if let Some(location) = web_sys::window().map(|w| w.location()) {
+        let href = location.href()?;
+        let hash = location.hash()?;
+        // `href` contains the fragment, which shouldn't be included in new share links
+        let page_url = href.split('#').next().unwrap_or(&href);
+        app.set_page_url(page_url.to_owned());
+        // `hash` is either empty or starts with a '#'
+        let fragment = hash.trim_start_matches('#');
+        if !fragment.is_empty() {
+            if let Err(e) = app.load_url_fragment(fragment) {
+                let msg = format!("Couldn't load shared composition: {}", e);
+                web_sys::console::error_1(&msg.into());
+            }
+        }
+    }
     eframe::start_web(canvas_id, Box::new(app))
 }
+
+/// Show a composition (saved as JSON) in a given canvas, without any of the editing UI.  The
+/// composition can be moved around and inspected, but not changed, so this can be used to embed
+/// compositions in other web pages.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn start_readonly(
+    canvas_id: &str,
+    comp_json: &str,
+) -> Result<(), eframe::wasm_bindgen::JsValue> {
+    let spec =
+        CompSpec::from_json(comp_json).map_err(|e| format!("Couldn't load composition: {}", e))?;
+    eframe::start_web(canvas_id, Box::new(JigsawApp::readonly(spec)))
+}
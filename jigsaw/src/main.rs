@@ -1,6 +1,146 @@
-// When compiling natively:
+//! The native Jigsaw binary.  With no arguments (or just the path of a composition), this launches
+//! the GUI.  The other subcommands analyse a composition file and print the results without
+//! launching the GUI, so that compositions can be checked by scripts.
+
+use std::process::exit;
+
+use jigsaw::JigsawApp;
+use jigsaw_comp::{
+    full::FullState,
+    report::{MusicReport, Report},
+    spec::CompSpec,
+};
+
+const USAGE: &str = "Usage:
+    jigsaw                Open the GUI with an example composition
+    jigsaw <file>         Open a composition file in the GUI
+    jigsaw prove <file>   Print whether or not a composition is true (exits with 1 if false)
+    jigsaw stats <file>   Print the length, truth, method stats and music of a composition
+    jigsaw report <file>  Print the same information as 'stats', formatted as JSON
+    jigsaw apply <script> <file> [<output>]
+                          Apply the edits in a JSON script to a composition, saving the result
+                          to <output> (or overwriting <file> if <output> isn't given)";
+
 fn main() {
-    let app = jigsaw::JigsawApp::example();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args.as_slice() {
+        [] => run_gui(JigsawApp::example()),
+        ["-h"] | ["--help"] | ["help"] => println!("{}", USAGE),
+        ["prove", path] => {
+            let report = load_report(path);
+            print_truth(&report);
+            if !report.is_true {
+                exit(1);
+            }
+        }
+        ["stats", path] => print_stats(&load_report(path)),
+        ["report", path] => println!("{}", load_report(path).to_json()),
+        ["apply", script_path, path] => apply_script(script_path, path, path),
+        ["apply", script_path, path, output_path] => apply_script(script_path, path, output_path),
+        [path] => run_gui(JigsawApp::new(load_spec(path), (*path).to_owned())),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    }
+}
+
+fn run_gui(app: JigsawApp) {
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(Box::new(app), native_options);
 }
+
+/// Applies the edits in the script at `script_path` to the composition at `path`, saving the
+/// result to `output_path`.  Exits the process if anything fails.
+fn apply_script(script_path: &str, path: &str, output_path: &str) {
+    let script = read_file(script_path);
+    let spec = jigsaw_gui::apply_script(load_spec(path), &script).unwrap_or_else(|e| {
+        eprintln!("Can't apply '{}': {}", script_path, e);
+        exit(1);
+    });
+    std::fs::write(output_path, spec.to_json()).unwrap_or_else(|e| {
+        eprintln!("Can't write '{}': {}", output_path, e);
+        exit(1);
+    });
+}
+
+/// Loads the [`CompSpec`] saved at `path`, exiting the process if it can't be loaded
+fn load_spec(path: &str) -> CompSpec {
+    let json = read_file(path);
+    CompSpec::from_json(&json).unwrap_or_else(|e| {
+        eprintln!("Can't load '{}': {}", path, e);
+        exit(1);
+    })
+}
+
+/// Reads the contents of the file at `path`, exiting the process if it can't be read
+fn read_file(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Can't read '{}': {}", path, e);
+        exit(1);
+    })
+}
+
+/// Loads the [`CompSpec`] saved at `path` and summarises it, exiting the process if it can't be
+/// loaded
+fn load_report(path: &str) -> Report {
+    Report::new(&FullState::new(&load_spec(path)))
+}
+
+fn print_truth(report: &Report) {
+    if report.is_true {
+        println!("True");
+    } else {
+        println!("False: {} rows are repeated", report.num_false_rows);
+    }
+}
+
+fn print_stats(report: &Report) {
+    print!("{} changes", report.length);
+    if report.num_parts > 1 {
+        print!(" ({} parts of {})", report.num_parts, report.part_len);
+    }
+    println!();
+    print_truth(report);
+
+    // Like the GUI, method and call stats are given per part
+    println!("\n{} changes of method", report.num_coms);
+    match report.is_balanced_spliced {
+        Some(true) => println!("Balanced spliced"),
+        Some(false) => println!("Not balanced spliced"),
+        None => {}
+    }
+    for method in &report.methods {
+        println!(
+            "{}: {} changes, {} leads, {:.0}% ATW",
+            method.title,
+            method.num_changes,
+            method.num_leads,
+            method.atw * 100.0
+        );
+    }
+    for call in &report.calls {
+        println!("'{}': {} calls", call.symbol, call.num_uses);
+    }
+
+    println!("\nMusic (score {}):", report.music_score);
+    for music in &report.music {
+        print_music(music, 1);
+    }
+}
+
+/// Prints the counts of a music group and its sub-groups, indented by `depth` levels
+fn print_music(music: &MusicReport, depth: usize) {
+    println!(
+        "{:indent$}{}: {}/{}",
+        "",
+        music.name,
+        music.count,
+        music.max_count,
+        indent = depth * 2
+    );
+    for sub_group in &music.sub_groups {
+        print_music(sub_group, depth + 1);
+    }
+}
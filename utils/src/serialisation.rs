@@ -1,7 +1,9 @@
 #![allow(dead_code)]
 
+use std::collections::HashMap;
+
 use bellframe::{place_not::PnBlockParseError, Bell, PnBlock, Row, RowBuf, Stage};
-use serde::{ser::SerializeSeq, Serializer};
+use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
 
 /// Required so that folding params default to open
 #[inline]
@@ -59,6 +61,25 @@ pub fn ser_borrowed_rows<S: Serializer>(rows: &[&Row], s: S) -> Result<S::Ok, S:
     seq_ser.end()
 }
 
+/// Custom serialiser to serialise `HashMap<Bell, V>` into `{<bell-index>: V}`
+pub fn ser_bell_map<V: Serialize, S: Serializer>(
+    map: &HashMap<Bell, V>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    s.collect_map(map.iter().map(|(bell, v)| (bell.index(), v)))
+}
+
+/// Custom deserialiser to deserialise `{<bell-index>: V}` into `HashMap<Bell, V>`
+pub fn de_bell_map<'de, V: Deserialize<'de>, D: Deserializer<'de>>(
+    d: D,
+) -> Result<HashMap<Bell, V>, D::Error> {
+    let map = HashMap::<usize, V>::deserialize(d)?;
+    Ok(map
+        .into_iter()
+        .map(|(idx, v)| (Bell::from_index(idx), v))
+        .collect())
+}
+
 /// Custom serialiser to serialise `Stage` as an integer
 pub fn ser_stage<S: Serializer>(stage: &Stage, s: S) -> Result<S::Ok, S::Error> {
     s.serialize_u64(stage.num_bells() as u64)
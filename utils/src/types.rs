@@ -4,6 +4,36 @@ use crate::indexed_vec::{FragIdx, PartIdx, RowIdx};
 #[allow(unused_imports)]
 use bellframe::Row;
 
+/// A position on the canvas, measured in the same units as the GUI's points.  This is used instead
+/// of a GUI library's types so that compositions can be stored and analysed without depending on
+/// any GUI code.  With the `emath` feature enabled, `Position`s can be converted to and from
+/// [`emath::Pos2`]s.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Position {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+#[cfg(feature = "emath")]
+impl From<emath::Pos2> for Position {
+    fn from(pos: emath::Pos2) -> Self {
+        Self::new(pos.x, pos.y)
+    }
+}
+
+#[cfg(feature = "emath")]
+impl From<Position> for emath::Pos2 {
+    fn from(pos: Position) -> Self {
+        emath::Pos2::new(pos.x, pos.y)
+    }
+}
+
 /// The position of a [`Row`] within the source composition (i.e. before parts are expanded).  This
 /// does not specify which part a [`Row`] occurs in - if you want this behaviour, then use
 /// [`RowLocation`]